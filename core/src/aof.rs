@@ -0,0 +1,363 @@
+// Importaciones
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+use crate::config::FsyncPolicy;
+use crate::encryption::{missing_key, Keyring};
+use crate::metrics::Metrics;
+use crate::value::{CollectionOp, Value};
+use crate::wal::{Wal, FLAG_ENCRYPTED};
+
+// Tags de los registros en disco
+const TAG_SET: u8 = 1;
+const TAG_DELETE: u8 = 2;
+const TAG_CLEAR: u8 = 3;
+const TAG_SET_EXPIRING: u8 = 4;
+const TAG_EXPIRE: u8 = 5;
+const TAG_BATCH: u8 = 6;
+const TAG_UPDATE: u8 = 7;
+const TAG_CHECKPOINT: u8 = 8;
+const TAG_RESTORE: u8 = 9;
+
+// Contexto autenticado de los bloques del log
+const LOG_CONTEXT: &[u8] = b"nanodb-aof";
+
+// Registro del log: una escritura exitosa sobre la base de datos.
+// Las expiraciones son absolutas (milisegundos unix) para que sobrevivan al reinicio.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogRecord {
+    Set { key: String, value: Vec<u8>, expires_at: Option<u64> },
+    Delete { key: String },
+    Clear,
+    Expire { key: String, expires_at: Option<u64> },
+    Batch(Vec<LogRecord>),  // Transaccion: se aplica entera o nada
+    Update { key: String, op: CollectionOp },  // Cambio sobre una lista, hash o conjunto
+    Checkpoint { id: u64 },  // Momento en que se tomo un snapshot (no cambia datos)
+    Restore { key: String, value: Value, expires_at: Option<u64> },  // Valor completo de cualquier tipo (sincronizacion de replicas)
+}
+
+impl LogRecord {
+    // Formato: tag (1 byte) | key_len (4 bytes BE) | key | value_len (4 bytes BE) | value
+    // Para SET con expiracion y EXPIRE, el value empieza con la expiracion (8 bytes BE)
+    // RESTORE: expiracion (8 bytes BE, 0 = sin expiracion) | tipo (1) | valor (ver Value::encode)
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            LogRecord::Set { key, value, expires_at: None } => frame(out, TAG_SET, key, &[value]),
+            LogRecord::Set { key, value, expires_at: Some(at) } => {
+                frame(out, TAG_SET_EXPIRING, key, &[&at.to_be_bytes(), value])
+            }
+            LogRecord::Delete { key } => frame(out, TAG_DELETE, key, &[]),
+            LogRecord::Clear => frame(out, TAG_CLEAR, "", &[]),
+            LogRecord::Expire { key, expires_at: Some(at) } => frame(out, TAG_EXPIRE, key, &[&at.to_be_bytes()]),
+            LogRecord::Expire { key, expires_at: None } => frame(out, TAG_EXPIRE, key, &[]),
+            LogRecord::Batch(records) => {
+                // El value contiene los registros codificados uno tras otro
+                let mut body = Vec::new();
+                for record in records {
+                    record.encode(&mut body);
+                }
+                frame(out, TAG_BATCH, "", &[&body])
+            }
+            LogRecord::Update { key, op } => {
+                let mut body = Vec::new();
+                op.encode(&mut body);
+                frame(out, TAG_UPDATE, key, &[&body])
+            }
+            LogRecord::Checkpoint { id } => frame(out, TAG_CHECKPOINT, "", &[&id.to_be_bytes()]),
+            LogRecord::Restore { key, value, expires_at } => {
+                let (tag, body) = value.encode();
+                frame(out, TAG_RESTORE, key, &[&expires_at.unwrap_or(0).to_be_bytes(), &[tag], &body])
+            }
+        }
+    }
+
+    // Decodifica un registro; Ok(None) si el buffer esta incompleto
+    pub fn decode(buf: &[u8]) -> io::Result<Option<(LogRecord, usize)>> {
+        let mut pos = 0;
+        let Some(&tag) = buf.first() else { return Ok(None) };
+        pos += 1;
+        let Some(key_len) = read_u32(buf, pos) else { return Ok(None) };
+        pos += 4;
+        let Some(key_bytes) = buf.get(pos..pos + key_len as usize) else { return Ok(None) };
+        pos += key_len as usize;
+        let Some(value_len) = read_u32(buf, pos) else { return Ok(None) };
+        pos += 4;
+        let Some(value) = buf.get(pos..pos + value_len as usize) else { return Ok(None) };
+        pos += value_len as usize;
+
+        let key = String::from_utf8(key_bytes.to_vec())
+            .map_err(|_| invalid("invalid UTF-8 key in log"))?;
+        let record = match tag {
+            TAG_SET => LogRecord::Set { key, value: value.to_vec(), expires_at: None },
+            TAG_SET_EXPIRING => {
+                let at = read_u64(value, 0).ok_or_else(|| invalid("truncated expiration in log"))?;
+                LogRecord::Set { key, value: value[8..].to_vec(), expires_at: Some(at) }
+            }
+            TAG_DELETE => LogRecord::Delete { key },
+            TAG_CLEAR => LogRecord::Clear,
+            TAG_EXPIRE => match value.len() {
+                0 => LogRecord::Expire { key, expires_at: None },
+                8 => LogRecord::Expire { key, expires_at: read_u64(value, 0) },
+                _ => return Err(invalid("invalid expiration in log")),
+            },
+            TAG_BATCH => {
+                // Un registro incompleto dentro de un lote completo es corrupcion, no un corte
+                let mut records = Vec::new();
+                let mut offset = 0;
+                while offset < value.len() {
+                    let Some((record, used)) = LogRecord::decode(&value[offset..])? else {
+                        return Err(invalid("truncated record inside batch"));
+                    };
+                    records.push(record);
+                    offset += used;
+                }
+                LogRecord::Batch(records)
+            }
+            TAG_UPDATE => LogRecord::Update { key, op: CollectionOp::decode(value)? },
+            TAG_CHECKPOINT => LogRecord::Checkpoint {
+                id: read_u64(value, 0).ok_or_else(|| invalid("invalid checkpoint in log"))?,
+            },
+            TAG_RESTORE => {
+                let at = read_u64(value, 0).ok_or_else(|| invalid("truncated expiration in log"))?;
+                let tag = *value.get(8).ok_or_else(|| invalid("truncated value in log"))?;
+                LogRecord::Restore { key, value: Value::decode(tag, &value[9..])?, expires_at: Some(at).filter(|&at| at != 0) }
+            }
+            other => return Err(invalid(&format!("unknown log record tag {}", other))),
+        };
+        Ok(Some((record, pos)))
+    }
+}
+
+fn frame(out: &mut Vec<u8>, tag: u8, key: &str, value_parts: &[&[u8]]) {
+    let value_len: usize = value_parts.iter().map(|part| part.len()).sum();
+    out.push(tag);
+    out.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    out.extend_from_slice(key.as_bytes());
+    out.extend_from_slice(&(value_len as u32).to_be_bytes());
+    for part in value_parts {
+        out.extend_from_slice(part);
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u64(buf: &[u8], pos: usize) -> Option<u64> {
+    let bytes = buf.get(pos..pos + 8)?;
+    let mut arr = [0u8; 8];
+    arr.copy_from_slice(bytes);
+    Some(u64::from_be_bytes(arr))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    let bytes = buf.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Log append-only: registros de LogRecord (sellados si hay clave) dentro de un WAL
+pub struct AppendLog {
+    wal: Wal,
+    keyring: Option<Keyring>,       // Some = registros cifrados
+}
+
+impl AppendLog {
+    // Abre (o crea) el log y devuelve los registros existentes para reproducirlos
+    pub fn open(path: impl AsRef<Path>, policy: FsyncPolicy) -> io::Result<(Self, Vec<LogRecord>)> {
+        Self::open_encrypted(path, policy, None, Metrics::new())
+    }
+
+    // Igual que open(), cifrando los registros con la clave actual del keyring y reportando
+    // los commits en `metrics`. Un log en claro existente se reescribe cifrado; uno cifrado
+    // no se puede abrir sin clave.
+    pub fn open_encrypted(path: impl AsRef<Path>, policy: FsyncPolicy, keyring: Option<Keyring>, metrics: Arc<Metrics>) -> io::Result<(Self, Vec<LogRecord>)> {
+        let path = path.as_ref();
+        let flags = if keyring.is_some() { FLAG_ENCRYPTED } else { 0 };
+        let (wal, recovered) = Wal::open(path, policy, flags, metrics)?;
+        let encrypted = recovered.flags & FLAG_ENCRYPTED != 0;
+        if encrypted && keyring.is_none() {
+            return Err(missing_key(path));
+        }
+        let records = recovered
+            .records
+            .iter()
+            .map(|payload| decode_payload(payload, keyring.as_ref().filter(|_| encrypted)))
+            .collect::<io::Result<Vec<_>>>()?;
+        info!(path = %path.display(), records = records.len(), encrypted = encrypted, "Append-only log opened");
+
+        // Con clave, un log en claro (o vacio) pasa a estar cifrado
+        let log = AppendLog { wal, keyring };
+        if let (Some(keyring), false) = (&log.keyring, encrypted) {
+            log.wal.rewrite(FLAG_ENCRYPTED, |_| Ok(Some(records.iter().map(|record| log.encode(record)).collect())))?;
+            info!(path = %path.display(), key = format!("{:08x}", keyring.current_id()), "Append-only log encrypted");
+        }
+        Ok((log, records))
+    }
+
+    pub fn path(&self) -> &Path {
+        self.wal.path()
+    }
+
+    // Escribe el registro y aplica el cambio en memoria bajo el lock del log.
+    // Con fsync always el cambio se aplica recien cuando el registro esta en disco.
+    pub fn record<F: FnOnce(LogRecord)>(&self, record: LogRecord, apply: F) -> io::Result<()> {
+        let bytes = self.encode(&record);
+        self.wal.append(&bytes, || apply(record))?;
+        debug!(bytes = bytes.len(), "Record appended to log");
+        Ok(())
+    }
+
+    // Fuerza la sincronizacion a disco
+    pub fn sync(&self) -> io::Result<()> {
+        self.wal.sync()
+    }
+
+    // Compactacion: descarta lo anterior al checkpoint (ya incluido en el snapshot) y
+    // reescribe el resto con la clave actual, asi una clave rotada deja de usarse.
+    // Devuelve false si el checkpoint no esta en el log (no se toca nada).
+    pub fn compact(&self, checkpoint: u64) -> io::Result<bool> {
+        let flags = if self.keyring.is_some() { FLAG_ENCRYPTED } else { 0 };
+        let mut dropped = 0;
+        let compacted = self.wal.rewrite(flags, |payloads| {
+            let records = payloads
+                .iter()
+                .map(|payload| decode_payload(payload, self.keyring.as_ref()))
+                .collect::<io::Result<Vec<_>>>()?;
+            let Some(start) = records.iter().rposition(|record| *record == LogRecord::Checkpoint { id: checkpoint }) else {
+                return Ok(None);
+            };
+            dropped = start;
+            Ok(Some(records[start..].iter().map(|record| self.encode(record)).collect()))
+        })?;
+        if compacted {
+            info!(path = %self.path().display(), dropped = dropped, "Append-only log compacted");
+        }
+        Ok(compacted)
+    }
+
+    fn encode(&self, record: &LogRecord) -> Vec<u8> {
+        encode_payload(record, self.keyring.as_ref())
+    }
+}
+
+fn encode_payload(record: &LogRecord, keyring: Option<&Keyring>) -> Vec<u8> {
+    let mut plain = Vec::new();
+    record.encode(&mut plain);
+    match keyring {
+        Some(keyring) => keyring.seal(LOG_CONTEXT, &plain),
+        None => plain,
+    }
+}
+
+// Un registro del WAL contiene exactamente un LogRecord (sellado si hay clave)
+fn decode_payload(payload: &[u8], keyring: Option<&Keyring>) -> io::Result<LogRecord> {
+    let opened;
+    let plain = match keyring {
+        Some(keyring) => {
+            opened = keyring.open(LOG_CONTEXT, payload)?;
+            &opened[..]
+        }
+        None => payload,
+    };
+    match LogRecord::decode(plain)? {
+        Some((record, used)) if used == plain.len() => Ok(record),
+        _ => Err(invalid("malformed record in append-only log")),
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use crate::{DbResult, NanoDb};
+
+    #[test]
+    fn test_record_roundtrip() {
+        let records = vec![
+            LogRecord::Set { key: "k".to_string(), value: b"v".to_vec(), expires_at: None },
+            LogRecord::Set { key: "t".to_string(), value: b"v".to_vec(), expires_at: Some(42) },
+            LogRecord::Expire { key: "t".to_string(), expires_at: Some(7) },
+            LogRecord::Expire { key: "t".to_string(), expires_at: None },
+            LogRecord::Delete { key: "k".to_string() },
+            LogRecord::Clear,
+            LogRecord::Batch(vec![
+                LogRecord::Set { key: "from".to_string(), value: Vec::new(), expires_at: None },
+                LogRecord::Delete { key: "to".to_string() },
+            ]),
+            LogRecord::Update {
+                key: "list".to_string(),
+                op: CollectionOp::Push { values: vec![b"a".to_vec(), Vec::new()], front: true },
+            },
+            LogRecord::Checkpoint { id: 99 },
+            LogRecord::Restore { key: "set".to_string(), value: Value::Set([b"m".to_vec()].into_iter().collect()), expires_at: Some(5) },
+        ];
+        let mut buf = Vec::new();
+        for record in &records {
+            record.encode(&mut buf);
+        }
+        let mut decoded = Vec::new();
+        let mut offset = 0;
+        while let Some((record, used)) = LogRecord::decode(&buf[offset..]).unwrap() {
+            decoded.push(record);
+            offset += used;
+        }
+        assert_eq!(decoded, records);
+    }
+
+    #[tokio::test]
+    async fn test_replay_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nanodb.aof");
+
+        {
+            let db = NanoDb::open(&path).unwrap();
+            db.set("a".to_string(), b"1".to_vec()).await;
+            db.set("b".to_string(), b"2".to_vec()).await;
+            db.set("c".to_string(), b"3".to_vec()).await;
+            db.delete("b").await;
+        }
+
+        let db = NanoDb::open(&path).unwrap();
+        assert!(matches!(db.get("a").await, DbResult::Ok(ref v) if v == b"1"));
+        assert!(matches!(db.get("b").await, DbResult::NotFound));
+        assert!(matches!(db.get("c").await, DbResult::Ok(ref v) if v == b"3"));
+
+        // Clear tambien se reproduce
+        db.clear().await;
+        db.set("d".to_string(), b"4".to_vec()).await;
+        drop(db);
+        let db = NanoDb::open(&path).unwrap();
+        if let DbResult::Ok(keys) = db.keys().await {
+            assert_eq!(keys, vec!["d".to_string()]);
+        } else {
+            panic!("Expected Ok with keys");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncates_incomplete_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nanodb.aof");
+
+        {
+            let db = NanoDb::open(&path).unwrap();
+            db.set("ok".to_string(), b"value".to_vec()).await;
+        }
+        let good_len = std::fs::metadata(&path).unwrap().len();
+
+        // Simular un registro cortado a la mitad
+        let mut partial = Vec::new();
+        LogRecord::Set { key: "torn".to_string(), value: b"xxxx".to_vec(), expires_at: None }.encode(&mut partial);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&partial[..partial.len() - 2]).unwrap();
+        drop(file);
+
+        let db = NanoDb::open(&path).unwrap();
+        assert!(matches!(db.get("ok").await, DbResult::Ok(ref v) if v == b"value"));
+        assert!(matches!(db.get("torn").await, DbResult::NotFound));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), good_len);
+    }
+}
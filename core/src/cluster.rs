@@ -0,0 +1,620 @@
+// Cluster con consenso Raft alrededor de un NanoDb: las escrituras se agregan a un log
+// replicado de DbOperation y cada nodo las aplica a su base en el mismo orden. Solo el lider
+// atiende clientes; los demas nodos responden DbError::NotLeader con la direccion del lider.
+//   - Eleccion de lider con terminos y votos (timeouts aleatorios)
+//   - Cambios de configuracion de a un nodo (add_member / remove_member)
+//   - Compactacion: cada snapshot_threshold entradas aplicadas el log se reemplaza por un
+//     snapshot de la base; un seguidor atrasado recibe el snapshot entero
+//   - Lecturas linealizables (ReadIndex): el lider confirma con una mayoria que sigue
+//     siendolo y espera a haber aplicado su commit antes de leer la base local
+// Solo se replica la base principal (los namespaces no estan soportados). La base tiene que
+// estar vacia y sin log propio: al reiniciar se reconstruye con el snapshot y el log de Raft.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use crate::{DbError, DbOperation, DbResponse, DbResult, Metrics, NanoDb, SnapshotInfo, StoragePort, Subscription, WatchFilter, Watcher};
+
+mod log;
+mod raft;
+mod rpc;
+
+use log::{Entry, HardState, Payload, RaftLog, Snapshot};
+use rpc::{Message, Peers};
+
+// Tiempo maximo que un cliente espera el commit de una escritura o la confirmacion de una lectura
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Configuracion del cluster: id del nodo -> direccion entre nodos
+pub type Members = BTreeMap<u64, String>;
+
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    pub id: u64,
+    pub addr: String,               // Direccion entre nodos donde escucha este nodo
+    pub members: Members,           // Configuracion inicial (vacia = se une con add_member desde el lider)
+    pub advertise: Option<String>,  // Direccion para clientes, que los seguidores informan como lider
+    pub dir: Option<PathBuf>,       // Estado persistente de Raft (None = solo en memoria)
+    pub heartbeat: Duration,
+    pub election_timeout: Duration, // Minimo; cada eleccion espera entre 1x y 2x
+    pub snapshot_threshold: u64,    // Entradas aplicadas entre snapshots
+}
+
+impl ClusterConfig {
+    pub fn new(id: u64, addr: impl Into<String>, members: Members) -> Self {
+        ClusterConfig {
+            id,
+            addr: addr.into(),
+            members,
+            advertise: None,
+            dir: None,
+            heartbeat: Duration::from_millis(50),
+            election_timeout: Duration::from_millis(300),
+            snapshot_threshold: 10_000,
+        }
+    }
+
+    // Configuracion desde variables de entorno (None si NANODB_CLUSTER_ID no esta definido)
+    //   NANODB_CLUSTER_ID      -> id de este nodo
+    //   NANODB_CLUSTER_MEMBERS -> configuracion inicial, ej. 1=10.0.0.1:7000,2=10.0.0.2:7000
+    //   NANODB_CLUSTER_ADDR    -> host:puerto entre nodos (por defecto el de este id en MEMBERS)
+    //   NANODB_CLUSTER_ADVERTISE -> host:puerto para clientes, informado como lider
+    //   NANODB_CLUSTER_DIR     -> directorio del log de Raft
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let Some(id) = std::env::var("NANODB_CLUSTER_ID").ok().filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        let id = id.parse().map_err(|_| invalid_config(format!("invalid NANODB_CLUSTER_ID: {}", id)))?;
+        let members = match std::env::var("NANODB_CLUSTER_MEMBERS") {
+            Ok(value) => parse_members(&value)?,
+            Err(_) => Members::new(),
+        };
+        let addr = match std::env::var("NANODB_CLUSTER_ADDR").ok().filter(|v| !v.is_empty()) {
+            Some(addr) => addr,
+            None => members.get(&id).cloned().ok_or_else(|| invalid_config("NANODB_CLUSTER_ADDR is required".to_string()))?,
+        };
+        let mut config = ClusterConfig::new(id, addr, members);
+        config.advertise = std::env::var("NANODB_CLUSTER_ADVERTISE").ok().filter(|v| !v.is_empty());
+        config.dir = std::env::var("NANODB_CLUSTER_DIR").ok().filter(|v| !v.is_empty()).map(PathBuf::from);
+        Ok(Some(config))
+    }
+}
+
+// "1=host:port,2=host:port"
+fn parse_members(value: &str) -> io::Result<Members> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|member| !member.is_empty())
+        .map(|member| {
+            let (id, addr) = member.split_once('=').ok_or_else(|| invalid_config(format!("invalid cluster member: {}", member)))?;
+            let id = id.trim().parse().map_err(|_| invalid_config(format!("invalid cluster member id: {}", id)))?;
+            Ok((id, addr.trim().to_string()))
+        })
+        .collect()
+}
+
+fn invalid_config(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+// Estado de un nodo, para monitoreo y tests
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClusterStatus {
+    pub id: u64,
+    pub role: Role,
+    pub term: u64,
+    pub leader: Option<u64>,
+    pub commit: u64,
+    pub applied: u64,
+    pub last_index: u64,
+    pub snapshot_index: u64,
+    pub members: Members,
+}
+
+// Nodo del cluster. Vive hasta shutdown(): sus tareas en segundo plano mantienen una referencia.
+pub struct RaftNode {
+    config: ClusterConfig,
+    db: Arc<NanoDb>,
+    state: Mutex<State>,
+    peers: Peers,
+    scripts: Mutex<HashMap<String, String>>,  // Hash -> fuente de los scripts aplicados (EVALSHA)
+    appended: watch::Sender<u64>,   // Ultimo indice del log; despierta a los replicadores
+    committed: watch::Sender<u64>,  // Despierta al aplicador
+    applied: watch::Sender<u64>,
+    acked: watch::Sender<()>,       // Cada respuesta de un seguidor (lecturas pendientes)
+    apply_lock: tokio::sync::Mutex<()>,  // Aplicar entradas e instalar un snapshot se excluyen
+    stopped: AtomicBool,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+struct State {
+    role: Role,
+    term: u64,
+    voted_for: Option<u64>,
+    leader: Option<u64>,
+    leader_hint: Option<String>,    // Direccion para clientes del lider conocido
+    log: RaftLog,
+    members: Members,               // Ultima configuracion del log (comprometida o no)
+    commit: u64,
+    applied: u64,
+    deadline: Instant,              // Sin noticias del lider hasta entonces: nueva eleccion
+    votes: BTreeSet<u64>,           // Candidato: votos recibidos en este termino
+    progress: HashMap<u64, Progress>,   // Lider: estado de cada seguidor
+    replicators: BTreeSet<u64>,     // Lider: seguidores con tarea de replicacion
+    waiters: HashMap<u64, (u64, oneshot::Sender<DbResponse>)>,  // Indice -> (termino, cliente)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    next: u64,                      // Proxima entrada a enviar
+    matched: u64,                   // Ultima entrada que se sabe replicada
+    acked: Option<Instant>,         // Envio del ultimo pedido que respondio (lecturas)
+}
+
+// Como se atiende cada operacion
+enum Route {
+    Local,      // No toca datos replicados
+    Read,       // ReadIndex en el lider
+    Write,      // Pasa por el log
+}
+
+fn route(op: &DbOperation) -> Result<Route, DbError> {
+    Ok(match op {
+        DbOperation::Save
+        | DbOperation::Watch { .. }
+        | DbOperation::Subscribe { .. }
+        | DbOperation::Unsubscribe { .. }
+        | DbOperation::ReplicationInfo
+        | DbOperation::Select { namespace: None } => Route::Local,
+        DbOperation::Select { namespace: Some(_) } | DbOperation::CreateNamespace { .. } | DbOperation::DropNamespace { .. } => {
+            return Err(DbError::NotImplemented("namespaces in cluster mode".to_string()));
+        }
+        DbOperation::Get { .. }
+        | DbOperation::Exists { .. }
+        | DbOperation::Keys
+        | DbOperation::KeysCursor { .. }
+        | DbOperation::KeysPrefix { .. }
+        | DbOperation::Range { .. }
+        | DbOperation::Values
+        | DbOperation::ValuesPrefix { .. }
+        | DbOperation::GetPrefix { .. }
+        | DbOperation::Size
+        | DbOperation::Ttl { .. }
+        | DbOperation::Version { .. }
+        | DbOperation::Namespaces
+        | DbOperation::ListRange { .. }
+        | DbOperation::HashGet { .. }
+        | DbOperation::SetMembers { .. }
+        | DbOperation::SetIntersection { .. }
+        | DbOperation::ZSetRange { .. }
+        | DbOperation::ZSetRangeByScore { .. }
+        | DbOperation::MultiGet { .. }
+        | DbOperation::JsonGet { .. }
+        | DbOperation::Indexes
+        | DbOperation::QueryIndex { .. } => Route::Read,
+        _ => Route::Write,
+    })
+}
+
+impl RaftNode {
+    // Arranca un nodo escuchando en config.addr
+    pub async fn start(db: Arc<NanoDb>, config: ClusterConfig) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(&config.addr).await?;
+        Self::with_listener(db, config, listener)
+    }
+
+    // Igual que start() con un listener ya abierto (ej. puerto 0 en tests)
+    pub fn with_listener(db: Arc<NanoDb>, config: ClusterConfig, listener: TcpListener) -> io::Result<Arc<Self>> {
+        let (log, hard) = RaftLog::open(config.dir.as_deref(), config.members.clone())?;
+        let data = log.snapshot().data.clone();
+        if !data.is_empty() {
+            db.restore_snapshot(&data)?;
+        }
+        let applied = log.snapshot().index;
+        let state = State {
+            role: Role::Follower,
+            term: hard.term,
+            voted_for: hard.voted_for,
+            leader: None,
+            leader_hint: None,
+            members: log.members(),
+            commit: applied,
+            applied,
+            deadline: raft::election_deadline(config.election_timeout),
+            votes: BTreeSet::new(),
+            progress: HashMap::new(),
+            replicators: BTreeSet::new(),
+            waiters: HashMap::new(),
+            log,
+        };
+        info!(id = config.id, addr = %config.addr, term = state.term, members = ?state.members, "Cluster node started");
+        let node = Arc::new(RaftNode {
+            appended: watch::channel(state.log.last_index()).0,
+            committed: watch::channel(applied).0,
+            applied: watch::channel(applied).0,
+            acked: watch::channel(()).0,
+            state: Mutex::new(state),
+            config,
+            db,
+            peers: Peers::default(),
+            scripts: Mutex::new(HashMap::new()),
+            apply_lock: tokio::sync::Mutex::new(()),
+            stopped: AtomicBool::new(false),
+            tasks: Mutex::new(Vec::new()),
+        });
+        let tasks = vec![
+            tokio::spawn(node.clone().serve_peers(listener)),
+            tokio::spawn(node.clone().run_ticker()),
+            tokio::spawn(node.clone().run_applier()),
+        ];
+        node.tasks.lock().unwrap_or_else(|e| e.into_inner()).extend(tasks);
+        Ok(node)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.config.id
+    }
+
+    // Base local; fuera del lider puede estar atrasada
+    pub fn db(&self) -> &Arc<NanoDb> {
+        &self.db
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        let state = self.lock();
+        ClusterStatus {
+            id: self.config.id,
+            role: state.role,
+            term: state.term,
+            leader: state.leader,
+            commit: state.commit,
+            applied: state.applied,
+            last_index: state.log.last_index(),
+            snapshot_index: state.log.snapshot().index,
+            members: state.members.clone(),
+        }
+    }
+
+    // Ejecuta una operacion: escrituras por el log, lecturas linealizables en el lider
+    pub async fn execute(&self, op: DbOperation) -> DbResponse {
+        let route = match route(&op) {
+            Ok(route) => route,
+            Err(e) => return DbResponse::Error(e),
+        };
+        match route {
+            Route::Local => self.db.execute(op).await,
+            Route::Read => match self.read_barrier().await {
+                Ok(()) => self.db.execute(op).await,
+                Err(e) => DbResponse::Error(e),
+            },
+            Route::Write => match self.resolve_script(op) {
+                Ok(op) => self.propose(Payload::Op(op)).await,
+                Err(e) => DbResponse::Error(e),
+            },
+        }
+    }
+
+    // Agrega un nodo (que debe estar arrancado con una configuracion vacia); solo en el lider
+    pub async fn add_member(&self, id: u64, addr: impl Into<String>) -> DbResult<()> {
+        let addr = addr.into();
+        self.change_members(|members| members.insert(id, addr).is_none()).await
+    }
+
+    pub async fn remove_member(&self, id: u64) -> DbResult<()> {
+        self.change_members(|members| members.remove(&id).is_some()).await
+    }
+
+    // Detiene las tareas del nodo; las operaciones pendientes fallan con NotLeader
+    pub fn shutdown(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        for task in self.tasks.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            task.abort();
+        }
+        let mut state = self.lock();
+        state.role = Role::Follower;
+        state.leader = None;
+        state.leader_hint = None;
+        state.waiters.clear();
+        info!(id = self.config.id, "Cluster node stopped");
+    }
+
+    // Un cambio de configuracion a la vez, y solo con un commit del termino actual
+    // (asi la configuracion anterior ya esta comprometida)
+    async fn change_members(&self, change: impl FnOnce(&mut Members) -> bool) -> DbResult<()> {
+        let mut members = {
+            let state = self.lock();
+            if state.role != Role::Leader {
+                return DbResult::Err(self.not_leader(&state));
+            }
+            let pending = state.log.members() != state.log.members_at(state.commit);
+            if pending || state.log.term_at(state.commit) != Some(state.term) {
+                return DbResult::Err(DbError::Busy("cluster membership change in progress".to_string()));
+            }
+            state.members.clone()
+        };
+        if !change(&mut members) {
+            return DbResult::Ok(());
+        }
+        match self.propose(Payload::Members(members)).await {
+            DbResponse::Error(e) => DbResult::Err(e),
+            _ => DbResult::Ok(()),
+        }
+    }
+
+    // EVALSHA se replica como EVAL: un nodo que recibio un snapshot no tiene el cache de scripts
+    fn resolve_script(&self, op: DbOperation) -> Result<DbOperation, DbError> {
+        match op {
+            DbOperation::EvalSha { sha, keys, args } => {
+                let script = self.scripts.lock().unwrap_or_else(|e| e.into_inner()).get(&sha.to_ascii_lowercase()).cloned();
+                match script {
+                    Some(script) => Ok(DbOperation::Eval { script, keys, args }),
+                    None => Err(DbError::NoScript(sha)),
+                }
+            }
+            op => Ok(op),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn not_leader(&self, state: &State) -> DbError {
+        DbError::NotLeader { leader: state.leader_hint.clone() }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+}
+
+// Los adaptadores sirven un nodo del cluster igual que una base local
+impl StoragePort for RaftNode {
+    async fn get(&self, key: &str) -> DbResult<Vec<u8>> {
+        match self.execute(DbOperation::Get { key: key.to_string(), default: None }).await {
+            DbResponse::Value(value) => DbResult::Ok(value),
+            other => unexpected(other),
+        }
+    }
+
+    async fn set(&self, key: String, value: Vec<u8>) -> DbResult<()> {
+        self.set_with_ttl(key, value, None).await
+    }
+
+    async fn set_with_ttl(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> DbResult<()> {
+        match self.execute(DbOperation::Set { key, value, ttl }).await {
+            DbResponse::Ok => DbResult::Ok(()),
+            other => unexpected(other),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> DbResult<()> {
+        match self.execute(DbOperation::Delete { key: key.to_string() }).await {
+            DbResponse::Ok => DbResult::Ok(()),
+            other => unexpected(other),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> DbResult<bool> {
+        match self.execute(DbOperation::Exists { key: key.to_string() }).await {
+            DbResponse::Bool(exists) => DbResult::Ok(exists),
+            other => unexpected(other),
+        }
+    }
+
+    async fn keys(&self) -> DbResult<Vec<String>> {
+        match self.execute(DbOperation::Keys).await {
+            DbResponse::Keys(keys) => DbResult::Ok(keys),
+            other => unexpected(other),
+        }
+    }
+
+    async fn clear(&self) -> DbResult<()> {
+        match self.execute(DbOperation::Flush).await {
+            DbResponse::Ok => DbResult::Ok(()),
+            other => unexpected(other),
+        }
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> DbResult<bool> {
+        match self.execute(DbOperation::Expire { key: key.to_string(), ttl }).await {
+            DbResponse::Bool(found) => DbResult::Ok(found),
+            other => unexpected(other),
+        }
+    }
+
+    async fn persist(&self, key: &str) -> DbResult<bool> {
+        match self.execute(DbOperation::Persist { key: key.to_string() }).await {
+            DbResponse::Bool(found) => DbResult::Ok(found),
+            other => unexpected(other),
+        }
+    }
+
+    async fn ttl(&self, key: &str) -> DbResult<Option<Duration>> {
+        match self.execute(DbOperation::Ttl { key: key.to_string() }).await {
+            DbResponse::Ttl(ttl) => DbResult::Ok(ttl),
+            other => unexpected(other),
+        }
+    }
+
+    async fn save(&self) -> DbResult<SnapshotInfo> {
+        self.db.save().await
+    }
+
+    async fn execute(&self, op: DbOperation) -> DbResponse {
+        RaftNode::execute(self, op).await
+    }
+
+    fn watch(&self, filter: WatchFilter) -> Watcher {
+        self.db.watch(filter)
+    }
+
+    fn subscriber(&self) -> Subscription {
+        self.db.subscriber()
+    }
+
+    fn namespace(&self, _name: &str) -> Option<Arc<Self>> {
+        None
+    }
+
+    fn metrics(&self) -> Arc<Metrics> {
+        self.db.metrics()
+    }
+
+    fn used_memory(&self) -> u64 {
+        self.db.used_memory()
+    }
+}
+
+fn unexpected<T>(response: DbResponse) -> DbResult<T> {
+    match response {
+        DbResponse::NotFound => DbResult::NotFound,
+        DbResponse::Error(e) => DbResult::Err(e),
+        other => DbResult::Err(DbError::Internal(format!("unexpected cluster response: {:?}", other))),
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nodos en loopback con tiempos cortos
+    async fn start_cluster(size: u64) -> Vec<Arc<RaftNode>> {
+        let mut listeners = Vec::new();
+        for _ in 0..size {
+            listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        }
+        let members: Members = listeners.iter().enumerate().map(|(i, l)| (i as u64 + 1, l.local_addr().unwrap().to_string())).collect();
+        listeners
+            .into_iter()
+            .enumerate()
+            .map(|(i, listener)| start_node(i as u64 + 1, members.clone(), listener, 10_000))
+            .collect()
+    }
+
+    fn start_node(id: u64, members: Members, listener: TcpListener, snapshot_threshold: u64) -> Arc<RaftNode> {
+        let mut config = ClusterConfig::new(id, listener.local_addr().unwrap().to_string(), members);
+        config.advertise = Some(format!("client-{}", id));
+        config.heartbeat = Duration::from_millis(20);
+        config.election_timeout = Duration::from_millis(150);
+        config.snapshot_threshold = snapshot_threshold;
+        RaftNode::with_listener(Arc::new(NanoDb::new()), config, listener).unwrap()
+    }
+
+    async fn wait_for<T>(mut check: impl FnMut() -> Option<T>) -> T {
+        for _ in 0..500 {
+            if let Some(value) = check() {
+                return value;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("cluster condition not reached");
+    }
+
+    async fn leader_of(nodes: &[Arc<RaftNode>]) -> Arc<RaftNode> {
+        wait_for(|| nodes.iter().find(|node| node.status().role == Role::Leader).cloned()).await
+    }
+
+    fn set(key: &str, value: &str) -> DbOperation {
+        DbOperation::Set { key: key.to_string(), value: value.as_bytes().to_vec(), ttl: None }
+    }
+
+    fn get(key: &str) -> DbOperation {
+        DbOperation::Get { key: key.to_string(), default: None }
+    }
+
+    #[tokio::test]
+    async fn test_replicated_writes_and_redirect() {
+        let nodes = start_cluster(3).await;
+        let leader = leader_of(&nodes).await;
+        assert_eq!(leader.execute(set("a", "1")).await, DbResponse::Ok);
+        assert_eq!(leader.execute(get("a")).await, DbResponse::Value(b"1".to_vec()));
+
+        // Los seguidores aplican la escritura y redirigen a los clientes
+        let follower = nodes.iter().find(|node| node.id() != leader.id()).unwrap();
+        let hint = format!("client-{}", leader.id());
+        wait_for(|| (follower.status().leader == Some(leader.id())).then_some(())).await;
+        assert_eq!(follower.execute(set("b", "2")).await, DbResponse::Error(DbError::NotLeader { leader: Some(hint.clone()) }));
+        assert!(matches!(StoragePort::get(follower.as_ref(), "a").await, DbResult::Err(DbError::NotLeader { .. })));
+        for node in &nodes {
+            wait_for(|| (node.status().applied >= leader.status().commit).then_some(())).await;
+            assert!(matches!(node.db().get("a").await, DbResult::Ok(ref v) if v == b"1"));
+        }
+
+        // EVALSHA con un script cargado a traves del log
+        let DbResponse::Value(sha) = leader.execute(DbOperation::ScriptLoad { script: "40 + 2".to_string() }).await else { panic!("Expected sha") };
+        let eval = DbOperation::EvalSha { sha: String::from_utf8(sha).unwrap(), keys: vec![], args: vec![] };
+        assert_eq!(leader.execute(eval).await, DbResponse::Json(serde_json::json!(42)));
+        nodes.iter().for_each(|node| node.shutdown());
+    }
+
+    #[tokio::test]
+    async fn test_failover_keeps_committed_writes() {
+        let nodes = start_cluster(3).await;
+        let old = leader_of(&nodes).await;
+        assert_eq!(old.execute(set("k", "v")).await, DbResponse::Ok);
+        old.shutdown();
+
+        let rest: Vec<_> = nodes.iter().filter(|node| node.id() != old.id()).cloned().collect();
+        let leader = leader_of(&rest).await;
+        assert!(leader.status().term > old.status().term);
+        assert_eq!(leader.execute(get("k")).await, DbResponse::Value(b"v".to_vec()));
+        assert_eq!(leader.execute(set("k", "w")).await, DbResponse::Ok);
+        // Un lider depuesto no puede confirmar lecturas
+        assert!(matches!(old.execute(get("k")).await, DbResponse::Error(DbError::NotLeader { .. })));
+        rest.iter().for_each(|node| node.shutdown());
+    }
+
+    #[tokio::test]
+    async fn test_membership_change_with_snapshot() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let first = start_node(1, Members::from([(1, addr)]), listener, 5);
+        let leader = leader_of(std::slice::from_ref(&first)).await;
+        for i in 0..20 {
+            assert_eq!(leader.execute(set(&format!("k{}", i), "x")).await, DbResponse::Ok);
+        }
+        wait_for(|| (leader.status().snapshot_index > 0).then_some(())).await;
+
+        // El nodo nuevo arranca sin configuracion y se pone al dia con el snapshot
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let second = start_node(2, Members::new(), listener, 5);
+        assert!(matches!(leader.add_member(2, addr).await, DbResult::Ok(())));
+        assert_eq!(leader.execute(set("after", "y")).await, DbResponse::Ok);
+        wait_for(|| (second.status().applied == leader.status().commit).then_some(())).await;
+        assert!(matches!(second.db().size().await, DbResult::Ok(21)));
+        assert_eq!(second.status().members.len(), 2);
+
+        // Al quitarse a si mismo el lider deja el cluster al otro nodo
+        assert!(matches!(leader.remove_member(1).await, DbResult::Ok(())));
+        let new_leader = leader_of(std::slice::from_ref(&second)).await;
+        assert_eq!(new_leader.execute(get("after")).await, DbResponse::Value(b"y".to_vec()));
+        assert_ne!(first.status().role, Role::Leader);
+        first.shutdown();
+        second.shutdown();
+    }
+
+    #[test]
+    fn test_parse_members() {
+        let members = parse_members("1=127.0.0.1:7001, 2=127.0.0.1:7002").unwrap();
+        assert_eq!(members, Members::from([(1, "127.0.0.1:7001".to_string()), (2, "127.0.0.1:7002".to_string())]));
+        assert!(parse_members("one=127.0.0.1:7001").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+// Log replicado de un nodo: el ultimo snapshot (prefijo ya compactado) y las entradas
+// posteriores. Con directorio se guarda en disco:
+//   raft.state    -> termino actual y voto (se reemplaza entero)
+//   raft.log      -> WAL con lotes de entradas (primer indice, entradas) en MessagePack
+//   raft.snapshot -> ultimo snapshot en MessagePack
+use std::fs::{self, File};
+use std::io::Write;
+use crate::config::FsyncPolicy;
+use crate::wal::Wal;
+use super::*;
+
+const STATE_FILE: &str = "raft.state";
+const LOG_FILE: &str = "raft.log";
+const SNAPSHOT_FILE: &str = "raft.snapshot";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    pub(crate) term: u64,
+    pub(crate) payload: Payload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Payload {
+    Noop,                   // Primera entrada de cada lider, para confirmar las anteriores
+    Op(DbOperation),
+    Members(Members),       // Nueva configuracion; rige desde que se agrega al log
+}
+
+// Termino y voto: se guardan antes de responder a otro nodo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HardState {
+    pub(crate) term: u64,
+    pub(crate) voted_for: Option<u64>,
+}
+
+// Estado de la base hasta `index` inclusive (ver NanoDb::snapshot_bytes)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    pub(crate) index: u64,
+    pub(crate) term: u64,
+    pub(crate) members: Members,
+    #[serde(with = "serde_bytes")]
+    pub(crate) data: Vec<u8>,
+}
+
+pub(crate) struct RaftLog {
+    dir: Option<PathBuf>,
+    wal: Option<Wal>,
+    snapshot: Snapshot,
+    entries: Vec<Entry>,    // entries[i] tiene el indice snapshot.index + 1 + i
+}
+
+impl RaftLog {
+    // Abre (o crea) el log. Sin estado previo se arranca con la configuracion `members`
+    // (vacia para un nodo que se va a unir a un cluster existente).
+    pub(crate) fn open(dir: Option<&Path>, members: Members) -> io::Result<(RaftLog, HardState)> {
+        let mut log = RaftLog {
+            dir: dir.map(Path::to_path_buf),
+            wal: None,
+            snapshot: Snapshot { members, ..Snapshot::default() },
+            entries: Vec::new(),
+        };
+        let Some(dir) = dir else { return Ok((log, HardState::default())) };
+        fs::create_dir_all(dir)?;
+        let state = read_optional(&dir.join(STATE_FILE))?.unwrap_or_default();
+        if let Some(snapshot) = read_optional(&dir.join(SNAPSHOT_FILE))? {
+            log.snapshot = snapshot;
+        }
+        let (wal, recovered) = Wal::open(dir.join(LOG_FILE), FsyncPolicy::Always, 0, Metrics::new())?;
+        for payload in recovered.records {
+            let (first, entries): (u64, Vec<Entry>) = decode(&payload)?;
+            log.restore_batch(first, entries)?;
+        }
+        log.wal = Some(wal);
+        debug!(dir = %dir.display(), snapshot = log.snapshot.index, last = log.last_index(), "Raft log opened");
+        Ok((log, state))
+    }
+
+    pub(crate) fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    pub(crate) fn last_index(&self) -> u64 {
+        self.snapshot.index + self.entries.len() as u64
+    }
+
+    pub(crate) fn last_term(&self) -> u64 {
+        self.entries.last().map_or(self.snapshot.term, |entry| entry.term)
+    }
+
+    // None = compactado (anterior al snapshot) o todavia no existe
+    pub(crate) fn term_at(&self, index: u64) -> Option<u64> {
+        match index.checked_sub(self.snapshot.index) {
+            Some(0) => Some(self.snapshot.term),
+            Some(offset) => self.entries.get(offset as usize - 1).map(|entry| entry.term),
+            None => None,
+        }
+    }
+
+    // Hasta `max` entradas desde `index` (que tiene que ser posterior al snapshot)
+    pub(crate) fn entries_from(&self, index: u64, max: usize) -> Vec<Entry> {
+        let start = (index.saturating_sub(self.snapshot.index + 1) as usize).min(self.entries.len());
+        self.entries[start..].iter().take(max).cloned().collect()
+    }
+
+    // Configuracion vigente: la ultima del log, o la del snapshot
+    pub(crate) fn members(&self) -> Members {
+        self.members_at(self.last_index())
+    }
+
+    pub(crate) fn members_at(&self, index: u64) -> Members {
+        let end = (index.saturating_sub(self.snapshot.index) as usize).min(self.entries.len());
+        self.entries[..end]
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.payload {
+                Payload::Members(members) => Some(members.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.snapshot.members.clone())
+    }
+
+    // Indice de la ultima configuracion (la del snapshot si el log no tiene ninguna)
+    pub(crate) fn members_index(&self) -> u64 {
+        let position = self.entries.iter().rposition(|entry| matches!(entry.payload, Payload::Members(_)));
+        position.map_or(self.snapshot.index, |i| self.snapshot.index + 1 + i as u64)
+    }
+
+    // Agrega entradas al final; vuelve cuando estan en disco
+    pub(crate) fn append(&mut self, entries: Vec<Entry>) -> io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        if let Some(wal) = &self.wal {
+            wal.append(&encode(&(self.last_index() + 1, &entries)), || {})?;
+        }
+        self.entries.extend(entries);
+        Ok(())
+    }
+
+    // Descarta `index` y todas las entradas posteriores (conflicto con el lider)
+    pub(crate) fn truncate_from(&mut self, index: u64) -> io::Result<()> {
+        let keep = index.saturating_sub(self.snapshot.index + 1) as usize;
+        if keep >= self.entries.len() {
+            return Ok(());
+        }
+        self.entries.truncate(keep);
+        self.rewrite()
+    }
+
+    // Reemplaza el prefijo hasta `index` (ya aplicado) por el snapshot de la base
+    pub(crate) fn compact(&mut self, index: u64, data: Vec<u8>) -> io::Result<()> {
+        let Some(term) = self.term_at(index).filter(|_| index > self.snapshot.index) else {
+            return Ok(());
+        };
+        let members = self.members_at(index);
+        let dropped = (index - self.snapshot.index) as usize;
+        self.save_snapshot(Snapshot { index, term, members, data })?;
+        self.entries.drain(..dropped);
+        self.rewrite()?;
+        info!(index, term, dropped, "Raft log compacted");
+        Ok(())
+    }
+
+    // Snapshot recibido del lider: las entradas que lo siguen se conservan solo si el
+    // log coincide con el en ese punto
+    pub(crate) fn install(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        match self.term_at(snapshot.index) {
+            Some(term) if term == snapshot.term && snapshot.index <= self.last_index() => {
+                let dropped = (snapshot.index - self.snapshot.index) as usize;
+                self.entries.drain(..dropped);
+            }
+            _ => self.entries.clear(),
+        }
+        self.save_snapshot(snapshot)?;
+        self.rewrite()
+    }
+
+    pub(crate) fn save_state(&self, state: &HardState) -> io::Result<()> {
+        match &self.dir {
+            Some(dir) => write_atomic(&dir.join(STATE_FILE), &encode(state)),
+            None => Ok(()),
+        }
+    }
+
+    fn save_snapshot(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        if let Some(dir) = &self.dir {
+            write_atomic(&dir.join(SNAPSHOT_FILE), &encode(&snapshot))?;
+        }
+        self.snapshot = snapshot;
+        Ok(())
+    }
+
+    // El WAL pasa a tener un solo lote con las entradas actuales
+    fn rewrite(&self) -> io::Result<()> {
+        if let Some(wal) = &self.wal {
+            let batch = match self.entries.is_empty() {
+                true => Vec::new(),
+                false => vec![encode(&(self.snapshot.index + 1, &self.entries))],
+            };
+            wal.rewrite(0, |_| Ok(Some(batch)))?;
+        }
+        Ok(())
+    }
+
+    // Un lote leido del WAL reemplaza lo que hubiera desde su primer indice. Las entradas ya
+    // incluidas en el snapshot (crash entre guardar el snapshot y reescribir el log) se saltean.
+    fn restore_batch(&mut self, first: u64, entries: Vec<Entry>) -> io::Result<()> {
+        if first > self.last_index() + 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("gap in raft log at index {}", first)));
+        }
+        let skip = (self.snapshot.index + 1).saturating_sub(first) as usize;
+        let first = first.max(self.snapshot.index + 1);
+        self.entries.truncate((first - self.snapshot.index - 1) as usize);
+        self.entries.extend(entries.into_iter().skip(skip));
+        Ok(())
+    }
+}
+
+pub(crate) fn encode<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    rmp_serde::to_vec(value).expect("cluster types are always serializable")
+}
+
+pub(crate) fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    rmp_serde::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+fn read_optional<T: serde::de::DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    match fs::read(path) {
+        Ok(bytes) => decode(&bytes).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Archivo temporal + rename: nunca queda un archivo a medias
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(key: &str, term: u64) -> Entry {
+        let op = DbOperation::Set { key: key.to_string(), value: vec![1], ttl: None };
+        Entry { term, payload: Payload::Op(op) }
+    }
+
+    #[test]
+    fn test_log_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let members = Members::from([(1, "127.0.0.1:1".to_string())]);
+        {
+            let (mut log, state) = RaftLog::open(Some(dir.path()), members.clone()).unwrap();
+            assert_eq!(state, HardState::default());
+            log.save_state(&HardState { term: 3, voted_for: Some(1) }).unwrap();
+            log.append(vec![op("a", 1), op("b", 1), op("c", 2)]).unwrap();
+            log.append(vec![op("d", 2)]).unwrap();
+            // Conflicto: se reemplazan las entradas desde la 3
+            log.truncate_from(3).unwrap();
+            log.append(vec![op("x", 3)]).unwrap();
+            log.compact(1, b"data".to_vec()).unwrap();
+        }
+        let (log, state) = RaftLog::open(Some(dir.path()), Members::new()).unwrap();
+        assert_eq!(state, HardState { term: 3, voted_for: Some(1) });
+        assert_eq!((log.snapshot().index, log.snapshot().term, &log.snapshot().data[..]), (1, 1, &b"data"[..]));
+        assert_eq!(log.members(), members);
+        assert_eq!((log.last_index(), log.last_term()), (3, 3));
+        assert_eq!(log.entries_from(2, 1), vec![op("b", 1)]);
+        assert_eq!(log.term_at(1), Some(1));
+        assert_eq!(log.term_at(0), None);
+    }
+
+    #[test]
+    fn test_install_keeps_matching_suffix() {
+        let (mut log, _) = RaftLog::open(None, Members::new()).unwrap();
+        log.append(vec![op("a", 1), op("b", 1), op("c", 1)]).unwrap();
+        let snapshot = |index, term| Snapshot { index, term, members: Members::new(), data: Vec::new() };
+        log.install(snapshot(2, 1)).unwrap();
+        assert_eq!((log.last_index(), log.entries_from(3, 10)), (3, vec![op("c", 1)]));
+        // Termino distinto: el log local no sirve
+        log.install(snapshot(3, 2)).unwrap();
+        assert_eq!((log.last_index(), log.last_term()), (3, 2));
+        assert!(log.entries_from(4, 10).is_empty());
+    }
+}
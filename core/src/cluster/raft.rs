@@ -0,0 +1,532 @@
+// Algoritmo de Raft: elecciones, replicacion del log, commit y aplicacion a la base.
+// El estado vive bajo un Mutex de std que nunca se retiene durante un await.
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, timeout_at};
+use crate::script::script_hash;
+use super::rpc::{read_message, write_message};
+use super::*;
+
+const TICK: Duration = Duration::from_millis(10);
+const MAX_BATCH: usize = 512;   // Entradas por pedido Append y por lote aplicado
+
+// Instante aleatorio entre 1x y 2x el timeout, para que las elecciones no coincidan
+pub(super) fn election_deadline(election_timeout: Duration) -> Instant {
+    let jitter = RandomState::new().hash_one(Instant::now()) % (election_timeout.as_millis() as u64 + 1);
+    Instant::now() + election_timeout + Duration::from_millis(jitter)
+}
+
+// Mayoria de la configuracion
+fn has_quorum(members: &Members, votes: &BTreeSet<u64>) -> bool {
+    votes.iter().filter(|id| members.contains_key(id)).count() * 2 > members.len()
+}
+
+impl RaftNode {
+    // Pedidos de los demas nodos; un nodo detenido cierra las conexiones
+    pub(super) async fn serve_peers(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!(error = %e, "Cluster accept failed");
+                    sleep(TICK).await;
+                    continue;
+                }
+            };
+            let node = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = node.serve_peer(stream).await {
+                    debug!(error = %e, "Cluster connection closed");
+                }
+            });
+        }
+    }
+
+    async fn serve_peer(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        loop {
+            let request = read_message(&mut stream).await?;
+            if self.is_stopped() {
+                return Ok(());
+            }
+            let reply = match request {
+                Message::Vote { term, candidate, last_index, last_term } => self.handle_vote(term, candidate, last_index, last_term)?,
+                Message::Append { term, leader, hint, prev_index, prev_term, entries, commit } => {
+                    self.handle_append(term, leader, hint, prev_index, prev_term, entries, commit)?
+                }
+                Message::Install { term, leader, hint, snapshot } => self.handle_install(term, leader, hint, snapshot).await?,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected cluster message")),
+            };
+            write_message(&mut stream, &reply).await?;
+        }
+    }
+
+    // Elecciones cuando no hay noticias del lider; el lider mantiene un replicador por seguidor
+    pub(super) async fn run_ticker(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(TICK);
+        while !self.is_stopped() {
+            ticker.tick().await;
+            let mut state = self.lock();
+            match state.role {
+                Role::Leader => self.spawn_replicators(&mut state),
+                _ if Instant::now() >= state.deadline && state.members.contains_key(&self.config.id) => self.campaign(&mut state),
+                _ => {}
+            }
+        }
+    }
+
+    fn campaign(self: &Arc<Self>, state: &mut State) {
+        state.term += 1;
+        state.role = Role::Candidate;
+        state.voted_for = Some(self.config.id);
+        state.leader = None;
+        state.leader_hint = None;
+        state.deadline = election_deadline(self.config.election_timeout);
+        state.votes = BTreeSet::from([self.config.id]);
+        if let Err(e) = self.persist(state) {
+            warn!(error = %e, "Could not persist cluster term; election abandoned");
+            return;
+        }
+        let term = state.term;
+        info!(id = self.config.id, term, "Starting cluster election");
+        if has_quorum(&state.members, &state.votes) {
+            self.become_leader(state);
+            return;
+        }
+        let request = Message::Vote { term, candidate: self.config.id, last_index: state.log.last_index(), last_term: state.log.last_term() };
+        for (&peer, addr) in state.members.iter().filter(|(&id, _)| id != self.config.id) {
+            let (node, addr, request) = (self.clone(), addr.clone(), request.clone());
+            tokio::spawn(async move {
+                match node.peers.call(&addr, &request).await {
+                    Ok(Message::VoteReply { term: reply_term, granted }) => node.handle_vote_reply(peer, term, reply_term, granted),
+                    Ok(_) => warn!(peer, "Unexpected reply to cluster vote request"),
+                    Err(e) => debug!(peer, error = %e, "Cluster vote request failed"),
+                }
+            });
+        }
+    }
+
+    fn handle_vote_reply(self: &Arc<Self>, peer: u64, term: u64, reply_term: u64, granted: bool) {
+        let mut state = self.lock();
+        if reply_term > state.term {
+            self.step_down(&mut state, reply_term);
+            return;
+        }
+        if state.role != Role::Candidate || state.term != term || !granted {
+            return;
+        }
+        state.votes.insert(peer);
+        if has_quorum(&state.members, &state.votes) {
+            self.become_leader(&mut state);
+        }
+    }
+
+    fn become_leader(self: &Arc<Self>, state: &mut State) {
+        state.role = Role::Leader;
+        state.leader = Some(self.config.id);
+        state.leader_hint = self.config.advertise.clone();
+        state.votes.clear();
+        state.progress.clear();
+        state.replicators.clear();
+        info!(id = self.config.id, term = state.term, "Elected cluster leader");
+        // Las entradas de terminos anteriores se confirman junto con la primera del termino
+        if let Err(e) = self.append_entry(state, Payload::Noop) {
+            warn!(error = %e, "Could not append to cluster log; stepping down");
+            let term = state.term;
+            self.step_down(state, term);
+            return;
+        }
+        self.spawn_replicators(state);
+    }
+
+    // Pasa a seguidor (de un termino mayor o del mismo); los clientes en espera reciben NotLeader
+    fn step_down(&self, state: &mut State, term: u64) {
+        if term > state.term {
+            state.term = term;
+            state.voted_for = None;
+            if let Err(e) = self.persist(state) {
+                warn!(error = %e, "Could not persist cluster term");
+            }
+        }
+        if state.role == Role::Leader {
+            info!(id = self.config.id, term, "Stepping down as cluster leader");
+            state.leader = None;
+            state.leader_hint = None;
+        }
+        state.role = Role::Follower;
+        state.votes.clear();
+        state.progress.clear();
+        state.replicators.clear();
+        state.waiters.clear();
+        state.deadline = election_deadline(self.config.election_timeout);
+    }
+
+    // Un lider o candidato que recibe noticias del lider actual pasa a seguirlo
+    fn follow(&self, state: &mut State, term: u64, leader: u64, hint: Option<String>) {
+        if term > state.term || state.role != Role::Follower {
+            self.step_down(state, term);
+        }
+        state.leader = Some(leader);
+        state.leader_hint = hint;
+        state.deadline = election_deadline(self.config.election_timeout);
+    }
+
+    fn persist(&self, state: &State) -> io::Result<()> {
+        state.log.save_state(&HardState { term: state.term, voted_for: state.voted_for })
+    }
+
+    // Lider: agrega una entrada de su termino y devuelve su indice
+    fn append_entry(&self, state: &mut State, payload: Payload) -> io::Result<u64> {
+        let index = state.log.last_index() + 1;
+        let reconfigure = matches!(payload, Payload::Members(_));
+        state.log.append(vec![Entry { term: state.term, payload }])?;
+        if reconfigure {
+            state.members = state.log.members();
+        }
+        self.appended.send_replace(index);
+        self.advance_commit(state);
+        Ok(index)
+    }
+
+    // Lider: propone una entrada y espera a que se aplique
+    pub(super) async fn propose(&self, payload: Payload) -> DbResponse {
+        let (index, receiver) = {
+            let mut state = self.lock();
+            if state.role != Role::Leader {
+                return DbResponse::Error(self.not_leader(&state));
+            }
+            let index = match self.append_entry(&mut state, payload) {
+                Ok(index) => index,
+                Err(e) => return DbResponse::Error(e.into()),
+            };
+            let (sender, receiver) = oneshot::channel();
+            let term = state.term;
+            state.waiters.insert(index, (term, sender));
+            (index, receiver)
+        };
+        match timeout(REQUEST_TIMEOUT, receiver).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => DbResponse::Error(self.not_leader(&self.lock())),
+            Err(_) => {
+                self.lock().waiters.remove(&index);
+                DbResponse::Error(DbError::Busy("timed out waiting for the cluster to commit".to_string()))
+            }
+        }
+    }
+
+    // Lider: la entrada mas alta replicada en una mayoria se compromete si es de su termino
+    fn advance_commit(&self, state: &mut State) {
+        if state.role != Role::Leader || state.members.is_empty() {
+            return;
+        }
+        let mut matched: Vec<u64> = state
+            .members
+            .keys()
+            .map(|id| match *id == self.config.id {
+                true => state.log.last_index(),
+                false => state.progress.get(id).map_or(0, |progress| progress.matched),
+            })
+            .collect();
+        matched.sort_unstable_by(|a, b| b.cmp(a));
+        let index = matched[state.members.len() / 2];
+        if index > state.commit && state.log.term_at(index) == Some(state.term) {
+            state.commit = index;
+            self.committed.send_replace(index);
+        }
+    }
+
+    // Lider: una tarea por seguidor de la configuracion actual
+    fn spawn_replicators(self: &Arc<Self>, state: &mut State) {
+        let next = state.log.last_index() + 1;
+        let term = state.term;
+        for (&peer, addr) in state.members.iter().filter(|(&id, _)| id != self.config.id) {
+            state.progress.entry(peer).or_insert(Progress { next, matched: 0, acked: None });
+            if state.replicators.insert(peer) {
+                tokio::spawn(self.clone().replicate(peer, addr.clone(), term));
+            }
+        }
+    }
+
+    // Envia entradas (o el snapshot) al seguidor; sin nada nuevo, un heartbeat por intervalo
+    async fn replicate(self: Arc<Self>, peer: u64, addr: String, term: u64) {
+        let mut appended = self.appended.subscribe();
+        loop {
+            appended.borrow_and_update();
+            let Some((request, last)) = self.next_request(peer, term) else { break };
+            let sent_at = Instant::now();
+            match self.peers.call(&addr, &request).await {
+                Ok(reply) => self.handle_replication_reply(peer, term, last, sent_at, reply),
+                Err(e) => {
+                    debug!(peer, error = %e, "Cluster replication request failed");
+                    sleep(self.config.heartbeat).await;
+                    continue;
+                }
+            }
+            if !self.has_pending(peer) {
+                let _ = timeout(self.config.heartbeat, appended.changed()).await;
+            }
+        }
+        let mut state = self.lock();
+        if state.term == term {
+            state.replicators.remove(&peer);
+        }
+        debug!(peer, term, "Cluster replicator stopped");
+    }
+
+    // Pedido para el seguidor y ultimo indice que incluye. Un nodo quitado de la configuracion
+    // sigue recibiendo entradas hasta tener la que lo quita (asi no inicia elecciones).
+    fn next_request(&self, peer: u64, term: u64) -> Option<(Message, u64)> {
+        let state = self.lock();
+        if self.is_stopped() || state.role != Role::Leader || state.term != term {
+            return None;
+        }
+        let progress = state.progress.get(&peer)?;
+        if !state.members.contains_key(&peer) && progress.matched >= state.log.members_index() {
+            return None;
+        }
+        let (leader, hint) = (self.config.id, self.config.advertise.clone());
+        let snapshot = state.log.snapshot();
+        if progress.next <= snapshot.index {
+            let install = Message::Install { term, leader, hint, snapshot: snapshot.clone() };
+            return Some((install, snapshot.index));
+        }
+        let prev_index = progress.next - 1;
+        let prev_term = state.log.term_at(prev_index).unwrap_or_default();
+        let entries = state.log.entries_from(progress.next, MAX_BATCH);
+        let last = prev_index + entries.len() as u64;
+        Some((Message::Append { term, leader, hint, prev_index, prev_term, entries, commit: state.commit }, last))
+    }
+
+    fn has_pending(&self, peer: u64) -> bool {
+        let state = self.lock();
+        state.progress.get(&peer).is_some_and(|progress| progress.next <= state.log.last_index())
+    }
+
+    fn handle_replication_reply(&self, peer: u64, term: u64, last: u64, sent_at: Instant, reply: Message) {
+        let (reply_term, success, last_index) = match reply {
+            Message::AppendReply { term, success, last_index } => (term, success, last_index),
+            Message::InstallReply { term } => (term, true, last),
+            _ => {
+                warn!(peer, "Unexpected reply to cluster replication request");
+                return;
+            }
+        };
+        let mut state = self.lock();
+        if reply_term > state.term {
+            self.step_down(&mut state, reply_term);
+            return;
+        }
+        if state.role != Role::Leader || state.term != term {
+            return;
+        }
+        let Some(progress) = state.progress.get_mut(&peer) else { return };
+        progress.acked = Some(sent_at);
+        if success {
+            progress.matched = progress.matched.max(last);
+            progress.next = progress.next.max(last + 1);
+            self.advance_commit(&mut state);
+        } else {
+            // El seguidor indica hasta donde llega su log; se retrocede para buscar la coincidencia
+            progress.next = (progress.next - 1).min(last_index + 1).max(1);
+        }
+        self.acked.send_replace(());
+    }
+
+    fn handle_vote(&self, term: u64, candidate: u64, last_index: u64, last_term: u64) -> io::Result<Message> {
+        let mut state = self.lock();
+        if term > state.term {
+            self.step_down(&mut state, term);
+        }
+        let up_to_date = (last_term, last_index) >= (state.log.last_term(), state.log.last_index());
+        let free = state.voted_for.is_none() || state.voted_for == Some(candidate);
+        let granted = term == state.term && up_to_date && free;
+        if granted {
+            state.voted_for = Some(candidate);
+            self.persist(&state)?;
+            state.deadline = election_deadline(self.config.election_timeout);
+        }
+        debug!(candidate, term, granted, "Cluster vote requested");
+        Ok(Message::VoteReply { term: state.term, granted })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_append(&self, term: u64, leader: u64, hint: Option<String>, prev_index: u64, prev_term: u64, mut entries: Vec<Entry>, commit: u64) -> io::Result<Message> {
+        let mut state = self.lock();
+        if term < state.term {
+            return Ok(Message::AppendReply { term: state.term, success: false, last_index: state.log.last_index() });
+        }
+        self.follow(&mut state, term, leader, hint);
+
+        // Lo anterior al snapshot ya esta comprometido y coincide con el lider
+        let (mut prev_index, mut prev_term) = (prev_index, prev_term);
+        let snapshot = state.log.snapshot();
+        if prev_index < snapshot.index {
+            entries.drain(..((snapshot.index - prev_index) as usize).min(entries.len()));
+            (prev_index, prev_term) = (snapshot.index, snapshot.term);
+        }
+        if state.log.term_at(prev_index) != Some(prev_term) {
+            let last_index = state.log.last_index().min(prev_index.saturating_sub(1));
+            return Ok(Message::AppendReply { term, success: false, last_index });
+        }
+
+        // Se saltean las entradas que ya estan; desde el primer conflicto se reemplaza el log
+        let last_new = prev_index + entries.len() as u64;
+        let mut skip = 0;
+        for (index, entry) in (prev_index + 1..).zip(&entries) {
+            match state.log.term_at(index) {
+                Some(existing) if existing == entry.term => skip += 1,
+                Some(_) => {
+                    state.log.truncate_from(index)?;
+                    state.waiters.retain(|&waiting, _| waiting < index);
+                    break;
+                }
+                None => break,
+            }
+        }
+        if skip < entries.len() {
+            state.log.append(entries.split_off(skip))?;
+            state.members = state.log.members();
+        }
+        let commit = commit.min(last_new);
+        if commit > state.commit {
+            state.commit = commit;
+            self.committed.send_replace(commit);
+        }
+        Ok(Message::AppendReply { term, success: true, last_index: last_new })
+    }
+
+    async fn handle_install(&self, term: u64, leader: u64, hint: Option<String>, snapshot: Snapshot) -> io::Result<Message> {
+        let _apply = self.apply_lock.lock().await;
+        {
+            let mut state = self.lock();
+            if term < state.term {
+                return Ok(Message::InstallReply { term: state.term });
+            }
+            self.follow(&mut state, term, leader, hint);
+            if snapshot.index <= state.applied {
+                return Ok(Message::InstallReply { term });
+            }
+        }
+        let keys = self.db.restore_snapshot(&snapshot.data)?;
+        let index = snapshot.index;
+        let mut state = self.lock();
+        state.log.install(snapshot)?;
+        state.members = state.log.members();
+        state.applied = index;
+        state.commit = state.commit.max(index);
+        state.deadline = election_deadline(self.config.election_timeout);
+        self.applied.send_replace(index);
+        info!(id = self.config.id, index, keys, "Cluster snapshot installed from leader");
+        Ok(Message::InstallReply { term: state.term })
+    }
+
+    // Aplica a la base las entradas comprometidas, en orden
+    pub(super) async fn run_applier(self: Arc<Self>) {
+        let mut committed = self.committed.subscribe();
+        while !self.is_stopped() {
+            committed.borrow_and_update();
+            if !self.apply_committed().await {
+                let _ = committed.changed().await;
+            }
+        }
+    }
+
+    // Un lote de entradas; false si no habia ninguna pendiente
+    async fn apply_committed(&self) -> bool {
+        let _apply = self.apply_lock.lock().await;
+        let (first, entries) = {
+            let state = self.lock();
+            let count = (state.commit.saturating_sub(state.applied) as usize).min(MAX_BATCH);
+            (state.applied + 1, state.log.entries_from(state.applied + 1, count))
+        };
+        if entries.is_empty() {
+            return false;
+        }
+        let mut applied = first;
+        for (index, entry) in (first..).zip(entries) {
+            let removed = matches!(&entry.payload, Payload::Members(members) if !members.contains_key(&self.config.id));
+            let response = match entry.payload {
+                Payload::Op(op) => {
+                    self.remember_script(&op);
+                    self.db.execute(op).await
+                }
+                Payload::Noop | Payload::Members(_) => DbResponse::Ok,
+            };
+            let mut state = self.lock();
+            state.applied = index;
+            applied = index;
+            if let Some((term, waiter)) = state.waiters.remove(&index) {
+                // Otro termino: la entrada del cliente fue reemplazada
+                if term == entry.term {
+                    let _ = waiter.send(response);
+                }
+            }
+            // Un lider quitado de la configuracion se retira cuando el cambio esta comprometido
+            if removed && state.role == Role::Leader && !state.members.contains_key(&self.config.id) {
+                let term = state.term;
+                self.step_down(&mut state, term);
+            }
+        }
+        self.applied.send_replace(applied);
+
+        let compact = {
+            let state = self.lock();
+            applied - state.log.snapshot().index >= self.config.snapshot_threshold
+        };
+        if compact {
+            if let Err(e) = self.db.snapshot_bytes().and_then(|data| self.lock().log.compact(applied, data)) {
+                warn!(error = %e, "Cluster log compaction failed");
+            }
+        }
+        true
+    }
+
+    fn remember_script(&self, op: &DbOperation) {
+        if let DbOperation::ScriptLoad { script } | DbOperation::Eval { script, .. } = op {
+            self.scripts.lock().unwrap_or_else(|e| e.into_inner()).insert(script_hash(script), script.clone());
+        }
+    }
+
+    // ReadIndex: el lider confirma con una mayoria que sigue siendolo despues de recibir la
+    // lectura, y espera a haber aplicado todo lo comprometido hasta ese momento
+    pub(super) async fn read_barrier(&self) -> Result<(), DbError> {
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + REQUEST_TIMEOUT;
+        let timed_out = || DbError::Busy("timed out confirming cluster leadership".to_string());
+        let mut acked = self.acked.subscribe();
+        let mut committed = self.committed.subscribe();
+        // Heartbeat inmediato en vez de esperar al proximo
+        self.appended.send_modify(|_| {});
+        let index = loop {
+            acked.borrow_and_update();
+            committed.borrow_and_update();
+            {
+                let state = self.lock();
+                if state.role != Role::Leader {
+                    return Err(self.not_leader(&state));
+                }
+                let confirmed: BTreeSet<u64> = state
+                    .members
+                    .keys()
+                    .copied()
+                    .filter(|id| *id == self.config.id || state.progress.get(id).and_then(|p| p.acked).is_some_and(|at| at >= started))
+                    .collect();
+                if state.log.term_at(state.commit) == Some(state.term) && has_quorum(&state.members, &confirmed) {
+                    break state.commit;
+                }
+            }
+            tokio::select! {
+                _ = acked.changed() => {}
+                _ = committed.changed() => {}
+                _ = tokio::time::sleep_until(deadline) => return Err(timed_out()),
+            }
+        };
+        let mut applied = self.applied.subscribe();
+        let reached = matches!(timeout_at(deadline, applied.wait_for(|applied| *applied >= index)).await, Ok(Ok(_)));
+        if !reached {
+            return Err(timed_out());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+// Mensajes entre nodos: len (4 bytes BE) | mensaje (MessagePack). Cada pedido se responde
+// sobre la misma conexion, que queda abierta para los siguientes.
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use super::log::{decode, encode};
+use super::*;
+
+const MAX_MESSAGE: usize = 1 << 30;
+const RPC_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Message {
+    Vote { term: u64, candidate: u64, last_index: u64, last_term: u64 },
+    VoteReply { term: u64, granted: bool },
+    // `hint` es la direccion del lider para los clientes
+    Append { term: u64, leader: u64, hint: Option<String>, prev_index: u64, prev_term: u64, entries: Vec<Entry>, commit: u64 },
+    AppendReply { term: u64, success: bool, last_index: u64 },
+    Install { term: u64, leader: u64, hint: Option<String>, snapshot: Snapshot },
+    InstallReply { term: u64 },
+}
+
+pub(crate) async fn read_message(stream: &mut TcpStream) -> io::Result<Message> {
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("cluster message too large: {} bytes", len)));
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).await?;
+    decode(&body)
+}
+
+pub(crate) async fn write_message(stream: &mut TcpStream, message: &Message) -> io::Result<()> {
+    let body = encode(message);
+    let mut frame = Vec::with_capacity(4 + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&body);
+    stream.write_all(&frame).await
+}
+
+// Conexiones salientes, una por direccion; un error o timeout la descarta
+#[derive(Default)]
+pub(crate) struct Peers {
+    connections: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<TcpStream>>>>>,
+}
+
+impl Peers {
+    pub(crate) async fn call(&self, addr: &str, message: &Message) -> io::Result<Message> {
+        let connection = self.connections.lock().unwrap_or_else(|e| e.into_inner()).entry(addr.to_string()).or_default().clone();
+        let mut connection = connection.lock().await;
+        let result = match timeout(RPC_TIMEOUT, roundtrip(&mut connection, addr, message)).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, format!("cluster request to {} timed out", addr))),
+        };
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+}
+
+async fn roundtrip(connection: &mut Option<TcpStream>, addr: &str, message: &Message) -> io::Result<Message> {
+    if connection.is_none() {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        *connection = Some(stream);
+    }
+    let stream = connection.as_mut().expect("connected above");
+    write_message(stream, message).await?;
+    read_message(stream).await
+}
@@ -0,0 +1,185 @@
+// Compresion de valores en memoria (el log y el snapshot guardan los bytes originales)
+use crate::errors::MAX_VALUE_SIZE;
+
+// Valores mas chicos no se comprimen: la ganancia no compensa el costo
+pub const DEFAULT_COMPRESSION_MIN_SIZE: usize = 1024;
+// Nivel de zstd: buen equilibrio entre velocidad y tamaño
+const ZSTD_LEVEL: i32 = 3;
+
+// Algoritmo de compresion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Lz4,            // Muy rapido, compresion moderada
+    Zstd,           // Mas lento, mejor compresion
+}
+
+impl Codec {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "lz4" => Some(Codec::Lz4),
+            "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    // Comprime `raw`; None si el resultado no es mas chico
+    pub fn compress(self, raw: &[u8]) -> Option<Compressed> {
+        let data = match self {
+            Codec::Lz4 => lz4_flex::compress(raw),
+            Codec::Zstd => zstd::bulk::compress(raw, ZSTD_LEVEL).ok()?,
+        };
+        (data.len() < raw.len()).then_some(Compressed { codec: self, raw_len: raw.len(), data })
+    }
+}
+
+// Configuracion de la compresion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub codec: Codec,
+    pub min_size: usize,            // Solo se comprimen valores de al menos este tamaño
+}
+
+impl Compression {
+    pub fn new(codec: Codec) -> Self {
+        Compression { codec, min_size: DEFAULT_COMPRESSION_MIN_SIZE }
+    }
+
+    // Comprime el valor si supera el umbral y el codec lo achica
+    pub fn apply(&self, raw: &[u8]) -> Option<Compressed> {
+        if raw.len() < self.min_size {
+            return None;
+        }
+        self.codec.compress(raw)
+    }
+}
+
+// Valor comprimido junto con lo necesario para recuperarlo
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compressed {
+    codec: Codec,
+    raw_len: usize,
+    data: Vec<u8>,
+}
+
+impl Compressed {
+    // Reconstruye un valor guardado en disco por el motor LSM. Los bytes vienen de un
+    // archivo, asi que se comprueba que descompriman al tamaño indicado; None si no.
+    pub(crate) fn from_parts(codec: Codec, raw_len: usize, data: Vec<u8>) -> Option<Self> {
+        if raw_len > MAX_VALUE_SIZE {
+            return None;
+        }
+        let compressed = Compressed { codec, raw_len, data };
+        compressed.try_decompress()?;
+        Some(compressed)
+    }
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+    // Tamaño original
+    pub fn raw_len(&self) -> usize {
+        self.raw_len
+    }
+    // Tamaño en memoria
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    // Bytes originales. Los datos salen de compress() o de from_parts(), que ya los
+    // verifico, asi que un fallo aca es un error de programacion.
+    pub fn decompress(&self) -> Vec<u8> {
+        self.try_decompress().expect("compressed value was checked when it was built")
+    }
+
+    fn try_decompress(&self) -> Option<Vec<u8>> {
+        let raw = match self.codec {
+            Codec::Lz4 => lz4_flex::decompress(&self.data, self.raw_len).ok()?,
+            Codec::Zstd => zstd::bulk::decompress(&self.data, self.raw_len).ok()?,
+        };
+        (raw.len() == self.raw_len).then_some(raw)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbConfig, DbResult, NanoDb};
+
+    #[test]
+    fn test_codecs_roundtrip() {
+        let raw = br#"{"name":"nanodb","tags":["kv","kv","kv","kv"]}"#.repeat(64);
+        for codec in [Codec::Lz4, Codec::Zstd] {
+            let compressed = codec.compress(&raw).unwrap();
+            assert!(compressed.len() < raw.len());
+            assert_eq!(compressed.raw_len(), raw.len());
+            assert_eq!(compressed.decompress(), raw);
+        }
+    }
+
+    #[test]
+    fn test_from_parts_rejects_corrupt_data() {
+        let raw = b"nanodb ".repeat(64);
+        for codec in [Codec::Lz4, Codec::Zstd] {
+            let compressed = codec.compress(&raw).unwrap();
+            let rebuilt = Compressed::from_parts(codec, raw.len(), compressed.data().to_vec()).unwrap();
+            assert_eq!(rebuilt.decompress(), raw);
+            assert!(Compressed::from_parts(codec, raw.len() + 1, compressed.data().to_vec()).is_none());
+            assert!(Compressed::from_parts(codec, raw.len(), vec![0xff; 16]).is_none());
+            assert!(Compressed::from_parts(codec, usize::MAX, compressed.data().to_vec()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_threshold_and_incompressible_values() {
+        let compression = Compression::new(Codec::Lz4);
+        assert!(compression.apply(&[b'a'; 100]).is_none());
+        assert!(compression.apply(&[b'a'; 4096]).is_some());
+        // Bytes sin repeticiones: comprimidos ocupan mas, se guardan tal cual
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect();
+        assert!(Codec::Lz4.compress(&noise).is_none());
+        assert_eq!(Codec::parse("ZSTD"), Some(Codec::Zstd));
+    }
+
+    #[tokio::test]
+    async fn test_values_are_stored_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            snapshot_path: Some(dir.path().join("db.snap")),
+            compression: Some(Compression { codec: Codec::Zstd, min_size: 64 }),
+            ..DbConfig::default()
+        };
+        let blob = br#"{"user":"ana","roles":["admin","dev"],"active":true}"#.repeat(100);
+        {
+            let db = NanoDb::with_config(config.clone()).unwrap();
+            db.set("blob".to_string(), blob.clone()).await;
+            db.set("small".to_string(), b"tiny".to_vec()).await;
+            assert!(db.used_memory() < blob.len() as u64 / 4);
+            assert!(matches!(db.get("blob").await, DbResult::Ok(ref v) if *v == blob));
+
+            let stats = db.metrics().get_stats();
+            assert_eq!(stats.compressed_values, 1);
+            assert_eq!(stats.compression_input_bytes, blob.len() as u64);
+            assert!(stats.compression_ratio > 4.0);
+            assert_eq!(stats.compression_saved_bytes, stats.compression_input_bytes - stats.compression_output_bytes);
+            db.save().await;
+        }
+        // El snapshot guarda los bytes originales y se recomprimen al cargarlo
+        let db = NanoDb::with_config(config).unwrap();
+        assert!(matches!(db.get("blob").await, DbResult::Ok(ref v) if *v == blob));
+        assert_eq!(db.metrics().get_stats().compressed_values, 1);
+    }
+}
@@ -0,0 +1,186 @@
+// Importaciones
+use std::path::PathBuf;
+use std::io;
+use std::time::Duration;
+use crate::compression::{Codec, Compression};
+use crate::encryption::Keyring;
+
+// Politica de sincronizacion del log en disco
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    Always,         // cada escritura espera su fsync (compartido con las escrituras concurrentes)
+    #[default]
+    EverySecond,    // fsync en segundo plano cada segundo
+    Never,          // el sistema operativo decide cuando escribir
+}
+
+impl FsyncPolicy {
+    // Parsear desde texto (variables de entorno, flags)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "always" => Some(FsyncPolicy::Always),
+            "everysec" | "every_second" => Some(FsyncPolicy::EverySecond),
+            "never" | "no" => Some(FsyncPolicy::Never),
+            _ => None,
+        }
+    }
+}
+
+// Politica de desalojo cuando se alcanza max_memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    #[default]
+    NoEviction,     // rechazar escrituras con error
+    AllKeysLru,     // desalojar la clave usada hace mas tiempo
+    AllKeysLfu,     // desalojar la clave menos usada
+    VolatileTtl,    // desalojar la clave con TTL mas cercano a expirar
+}
+
+impl EvictionPolicy {
+    // Parsear desde texto (mismos nombres que Redis)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "noeviction" | "no-eviction" => Some(EvictionPolicy::NoEviction),
+            "allkeys-lru" => Some(EvictionPolicy::AllKeysLru),
+            "allkeys-lfu" => Some(EvictionPolicy::AllKeysLfu),
+            "volatile-ttl" => Some(EvictionPolicy::VolatileTtl),
+            _ => None,
+        }
+    }
+}
+
+// Estructura en memoria del keyspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageEngine {
+    #[default]
+    Hash,           // DashMap: acceso por clave mas rapido, sin orden
+    Ordered,        // Skiplist concurrente: rangos, orden inverso y cursores estables
+    Lsm,            // Arbol LSM en disco: datos mas grandes que la RAM, ordenado como Ordered
+}
+
+impl StorageEngine {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "hash" => Some(StorageEngine::Hash),
+            "ordered" | "skiplist" => Some(StorageEngine::Ordered),
+            "lsm" | "disk" => Some(StorageEngine::Lsm),
+            _ => None,
+        }
+    }
+}
+
+// Parsear tamaños como "512", "64kb", "100mb", "1gb"
+pub fn parse_bytes(value: &str) -> Option<u64> {
+    let value = value.trim().to_ascii_lowercase();
+    let (number, multiplier) = if let Some(n) = value.strip_suffix("gb") {
+        (n, 1024 * 1024 * 1024)
+    } else if let Some(n) = value.strip_suffix("mb") {
+        (n, 1024 * 1024)
+    } else if let Some(n) = value.strip_suffix("kb") {
+        (n, 1024)
+    } else {
+        (value.strip_suffix('b').unwrap_or(&value), 1)
+    };
+    number.trim().parse::<u64>().ok().map(|n| n.saturating_mul(multiplier))
+}
+
+// Configuracion de la base de datos
+#[derive(Debug, Clone, Default)]
+pub struct DbConfig {
+    pub aof_path: Option<PathBuf>,  // Log append-only (None = solo memoria)
+    pub fsync: FsyncPolicy,
+    pub snapshot_path: Option<PathBuf>,  // Snapshot cargado al iniciar y escrito por save()
+    pub max_memory: Option<u64>,    // Limite en bytes de claves + valores, por namespace (None = sin limite)
+    pub eviction: EvictionPolicy,
+    pub engine: StorageEngine,
+    pub lsm_dir: Option<PathBuf>,   // Directorio del motor LSM (None = temporal, se borra al cerrar)
+    pub read_only: bool,            // Rechazar escrituras (DbError::ReadOnly)
+    pub compression: Option<Compression>,  // Compresion de cadenas en memoria (None = desactivada)
+    pub encryption: Option<Keyring>,       // Cifrado del log y del snapshot (None = en claro)
+    pub script_timeout: Option<Duration>,  // Tiempo maximo de un script (None = script::DEFAULT_TIMEOUT)
+    pub replica_of: Option<String>,        // Direccion de replicacion del lider (Some = seguidor, solo lectura)
+    pub replication_addr: Option<String>,  // Direccion donde el lider atiende a sus seguidores
+}
+
+impl DbConfig {
+    // Configuracion desde variables de entorno, usada por los adaptadores
+    //   NANODB_AOF_PATH  -> ruta del log append-only
+    //   NANODB_FSYNC     -> always | everysec | never
+    //   NANODB_SNAPSHOT_PATH -> ruta del archivo de snapshot
+    //   NANODB_MAX_MEMORY -> limite de memoria (ej. 100mb)
+    //   NANODB_EVICTION  -> noeviction | allkeys-lru | allkeys-lfu | volatile-ttl
+    //   NANODB_ENGINE    -> hash | ordered | lsm
+    //   NANODB_LSM_DIR   -> directorio de datos del motor LSM
+    //   NANODB_READ_ONLY -> 1 | true
+    //   NANODB_COMPRESSION -> lz4 | zstd
+    //   NANODB_COMPRESSION_MIN_SIZE -> tamaño minimo a comprimir (ej. 4kb)
+    //   NANODB_ENCRYPTION_KEY, NANODB_ENCRYPTION_KEY_FILE, NANODB_ENCRYPTION_PREVIOUS_KEYS -> ver Keyring::from_env
+    //   NANODB_SCRIPT_TIMEOUT_MS -> tiempo maximo de un script en milisegundos
+    //   NANODB_REPLICA_OF -> host:puerto de replicacion del lider (esta base pasa a ser seguidor)
+    //   NANODB_REPLICATION_ADDR -> host:puerto donde atender seguidores
+    // Falla solo si hay una clave de cifrado invalida: seguir sin cifrar no es una opcion segura.
+    pub fn from_env() -> io::Result<Self> {
+        let mut config = DbConfig::default();
+        if let Ok(path) = std::env::var("NANODB_AOF_PATH") {
+            if !path.is_empty() {
+                config.aof_path = Some(PathBuf::from(path));
+            }
+        }
+        if let Ok(path) = std::env::var("NANODB_SNAPSHOT_PATH") {
+            if !path.is_empty() {
+                config.snapshot_path = Some(PathBuf::from(path));
+            }
+        }
+        if let Some(policy) = std::env::var("NANODB_FSYNC").ok().and_then(|v| FsyncPolicy::parse(&v)) {
+            config.fsync = policy;
+        }
+        config.max_memory = std::env::var("NANODB_MAX_MEMORY").ok().and_then(|v| parse_bytes(&v));
+        if let Some(policy) = std::env::var("NANODB_EVICTION").ok().and_then(|v| EvictionPolicy::parse(&v)) {
+            config.eviction = policy;
+        }
+        if let Some(engine) = std::env::var("NANODB_ENGINE").ok().and_then(|v| StorageEngine::parse(&v)) {
+            config.engine = engine;
+        }
+        if let Ok(dir) = std::env::var("NANODB_LSM_DIR") {
+            if !dir.is_empty() {
+                config.lsm_dir = Some(PathBuf::from(dir));
+            }
+        }
+        config.read_only = std::env::var("NANODB_READ_ONLY").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        config.compression = std::env::var("NANODB_COMPRESSION").ok().and_then(|v| Codec::parse(&v)).map(|codec| {
+            let mut compression = Compression::new(codec);
+            if let Some(min_size) = std::env::var("NANODB_COMPRESSION_MIN_SIZE").ok().and_then(|v| parse_bytes(&v)) {
+                compression.min_size = min_size as usize;
+            }
+            compression
+        });
+        config.replica_of = std::env::var("NANODB_REPLICA_OF").ok().filter(|v| !v.is_empty());
+        config.replication_addr = std::env::var("NANODB_REPLICATION_ADDR").ok().filter(|v| !v.is_empty());
+        config.script_timeout = std::env::var("NANODB_SCRIPT_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()).map(Duration::from_millis);
+        config.encryption = Keyring::from_env()?;
+        Ok(config)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("512"), Some(512));
+        assert_eq!(parse_bytes("64kb"), Some(64 * 1024));
+        assert_eq!(parse_bytes("100MB"), Some(100 * 1024 * 1024));
+        assert_eq!(parse_bytes("lots"), None);
+    }
+
+    #[test]
+    fn test_parse_policies() {
+        assert_eq!(FsyncPolicy::parse("everysec"), Some(FsyncPolicy::EverySecond));
+        assert_eq!(EvictionPolicy::parse("allkeys-lru"), Some(EvictionPolicy::AllKeysLru));
+        assert_eq!(EvictionPolicy::parse("random"), None);
+        assert_eq!(StorageEngine::parse("skiplist"), Some(StorageEngine::Ordered));
+        assert_eq!(StorageEngine::parse("LSM"), Some(StorageEngine::Lsm));
+    }
+}
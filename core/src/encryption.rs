@@ -0,0 +1,272 @@
+// Cifrado en reposo del log y del snapshot (AES-256-GCM)
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::{Digest, Sha256};
+
+pub const KEY_LEN: usize = 32;
+const KEY_ID_LEN: usize = 4;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// Bytes que el cifrado agrega a cada bloque: id de clave + nonce + tag
+pub const SEAL_OVERHEAD: usize = KEY_ID_LEN + NONCE_LEN + TAG_LEN;
+
+// Clave de 256 bits con su identificador (los primeros bytes de su SHA-256).
+// El id viaja con cada bloque cifrado para saber con que clave abrirlo.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: u32,
+    cipher: Aes256Gcm,
+}
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; KEY_LEN]) -> Self {
+        let digest = Sha256::new().chain_update(b"nanodb-key-id").chain_update(bytes).finalize();
+        EncryptionKey {
+            id: u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+        }
+    }
+
+    // 64 caracteres hexadecimales
+    pub fn from_hex(text: &str) -> io::Result<Self> {
+        let text = text.trim();
+        if text.len() != KEY_LEN * 2 || !text.is_ascii() {
+            return Err(invalid_key("expected 64 hexadecimal characters"));
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).map_err(|_| invalid_key("expected 64 hexadecimal characters"))?;
+        }
+        Ok(Self::new(bytes))
+    }
+
+    // Archivo con 32 bytes crudos o con la clave en hexadecimal
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let content = fs::read(path)?;
+        match <[u8; KEY_LEN]>::try_from(content.as_slice()) {
+            Ok(bytes) => Ok(Self::new(bytes)),
+            Err(_) => Self::from_hex(&String::from_utf8_lossy(&content)),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EncryptionKey({:08x})", self.id)
+    }
+}
+
+// Clave actual (cifra todo lo nuevo) y claves anteriores (solo para leer datos viejos).
+// Rotar = poner la nueva como actual y pasar la vieja a `previous`; los archivos se
+// vuelven a cifrar con la clave actual en el siguiente save() (snapshot + compactacion del log).
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    current: EncryptionKey,
+    previous: Vec<EncryptionKey>,
+}
+
+impl Keyring {
+    pub fn new(current: EncryptionKey) -> Self {
+        Keyring { current, previous: Vec::new() }
+    }
+
+    pub fn with_previous(mut self, key: EncryptionKey) -> Self {
+        self.previous.push(key);
+        self
+    }
+
+    pub fn current_id(&self) -> u32 {
+        self.current.id
+    }
+
+    // Claves desde variables de entorno:
+    //   NANODB_ENCRYPTION_KEY      -> clave actual en hexadecimal
+    //   NANODB_ENCRYPTION_KEY_FILE -> archivo con la clave actual (alternativa a la anterior)
+    //   NANODB_ENCRYPTION_PREVIOUS_KEYS -> claves anteriores en hexadecimal, separadas por comas
+    // Ok(None) si no hay clave configurada.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let current = match (std::env::var("NANODB_ENCRYPTION_KEY"), std::env::var("NANODB_ENCRYPTION_KEY_FILE")) {
+            (Ok(hex), _) if !hex.is_empty() => EncryptionKey::from_hex(&hex)?,
+            (_, Ok(path)) if !path.is_empty() => EncryptionKey::from_file(Path::new(&path))?,
+            _ => return Ok(None),
+        };
+        let mut keyring = Keyring::new(current);
+        if let Ok(previous) = std::env::var("NANODB_ENCRYPTION_PREVIOUS_KEYS") {
+            for hex in previous.split(',').filter(|hex| !hex.trim().is_empty()) {
+                keyring = keyring.with_previous(EncryptionKey::from_hex(hex)?);
+            }
+        }
+        Ok(Some(keyring))
+    }
+
+    // Cifra con la clave actual: key_id (4) | nonce (12) | datos cifrados + tag (16).
+    // `context` se autentica pero no se guarda: un bloque no se puede mover a otro tipo de archivo.
+    pub fn seal(&self, context: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .current
+            .cipher
+            .encrypt(&nonce, Payload { msg: plaintext, aad: context })
+            .expect("AES-GCM encryption does not fail for in-memory buffers");
+        let mut out = Vec::with_capacity(SEAL_OVERHEAD + plaintext.len());
+        out.extend_from_slice(&self.current.id.to_be_bytes());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        out
+    }
+
+    // Descifra un bloque de seal(); el error explica si falta la clave o si no coincide
+    pub fn open(&self, context: &[u8], sealed: &[u8]) -> io::Result<Vec<u8>> {
+        if sealed.len() < SEAL_OVERHEAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated encrypted block"));
+        }
+        let id = u32::from_be_bytes([sealed[0], sealed[1], sealed[2], sealed[3]]);
+        let Some(key) = std::iter::once(&self.current).chain(&self.previous).find(|key| key.id == id) else {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "data was encrypted with key {:08x}, which is not configured (current key is {:08x}); wrong encryption key?",
+                    id, self.current.id
+                ),
+            ));
+        };
+        let nonce = Nonce::from_slice(&sealed[KEY_ID_LEN..KEY_ID_LEN + NONCE_LEN]);
+        key.cipher
+            .decrypt(nonce, Payload { msg: &sealed[KEY_ID_LEN + NONCE_LEN..], aad: context })
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("decryption with key {:08x} failed: data is corrupted", id)))
+    }
+}
+
+// Error comun para archivos cifrados que se abren sin clave
+pub(crate) fn missing_key(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{} is encrypted but no encryption key is configured (NANODB_ENCRYPTION_KEY)", path.display()),
+    )
+}
+
+fn invalid_key(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid encryption key: {}", msg))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbConfig, DbResult, NanoDb};
+
+    fn config(dir: &Path, keyring: Option<Keyring>) -> DbConfig {
+        DbConfig {
+            aof_path: Some(dir.join("db.aof")),
+            snapshot_path: Some(dir.join("db.snap")),
+            encryption: keyring,
+            ..DbConfig::default()
+        }
+    }
+
+    fn contains(path: &Path, needle: &[u8]) -> bool {
+        fs::read(path).unwrap().windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let keyring = Keyring::new(EncryptionKey::new([7; KEY_LEN]));
+        let sealed = keyring.seal(b"log", b"secret value");
+        assert_eq!(sealed.len(), SEAL_OVERHEAD + b"secret value".len());
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(keyring.open(b"log", &sealed).unwrap(), b"secret value");
+        // Otro contexto o un byte alterado no pasan la autenticacion
+        assert!(keyring.open(b"snapshot", &sealed).is_err());
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(keyring.open(b"log", &tampered).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_rotation_and_wrong_key() {
+        let old = EncryptionKey::new([1; KEY_LEN]);
+        let new = EncryptionKey::new([2; KEY_LEN]);
+        let sealed = Keyring::new(old.clone()).seal(b"log", b"v");
+
+        let rotated = Keyring::new(new.clone()).with_previous(old);
+        assert_eq!(rotated.open(b"log", &sealed).unwrap(), b"v");
+        let err = Keyring::new(new).open(b"log", &sealed).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("wrong encryption key"));
+    }
+
+    #[test]
+    fn test_key_parsing() {
+        let hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+        let key = EncryptionKey::from_hex(hex).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        fs::write(&path, format!("{}\n", hex)).unwrap();
+        assert_eq!(EncryptionKey::from_file(&path).unwrap().id(), key.id());
+        assert!(EncryptionKey::from_hex("abc").is_err());
+        assert!(EncryptionKey::from_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_files_are_encrypted_at_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = Keyring::new(EncryptionKey::new([3; KEY_LEN]));
+        {
+            // Un log en claro existente se cifra al abrirlo con clave
+            let db = NanoDb::with_config(config(dir.path(), None)).unwrap();
+            db.set("plain".to_string(), b"old-secret".to_vec()).await;
+        }
+        {
+            let db = NanoDb::with_config(config(dir.path(), Some(key.clone()))).unwrap();
+            db.set("card".to_string(), b"4111-1111".to_vec()).await;
+            db.save().await;
+            db.set("after".to_string(), b"snapshot".to_vec()).await;
+        }
+        for file in ["db.aof", "db.snap"] {
+            let path = dir.path().join(file);
+            assert!(!contains(&path, b"4111-1111") && !contains(&path, b"old-secret"), "{} is not encrypted", file);
+        }
+
+        let db = NanoDb::with_config(config(dir.path(), Some(key))).unwrap();
+        assert!(matches!(db.get("plain").await, DbResult::Ok(ref v) if v == b"old-secret"));
+        assert!(matches!(db.get("card").await, DbResult::Ok(ref v) if v == b"4111-1111"));
+        assert!(matches!(db.get("after").await, DbResult::Ok(ref v) if v == b"snapshot"));
+        drop(db);
+
+        let Err(err) = NanoDb::with_config(config(dir.path(), None)) else { panic!("opened without key") };
+        assert!(err.to_string().contains("no encryption key is configured"));
+        let wrong = Keyring::new(EncryptionKey::new([4; KEY_LEN]));
+        let Err(err) = NanoDb::with_config(config(dir.path(), Some(wrong))) else { panic!("opened with wrong key") };
+        assert!(err.to_string().contains("wrong encryption key"));
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation_on_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = EncryptionKey::new([5; KEY_LEN]);
+        let new = EncryptionKey::new([6; KEY_LEN]);
+        {
+            let db = NanoDb::with_config(config(dir.path(), Some(Keyring::new(old.clone())))).unwrap();
+            db.set("a".to_string(), b"1".to_vec()).await;
+            db.save().await;
+            db.set("b".to_string(), b"2".to_vec()).await;
+        }
+        {
+            // Rotacion: la clave vieja solo se usa para leer hasta el siguiente save()
+            let db = NanoDb::with_config(config(dir.path(), Some(Keyring::new(new.clone()).with_previous(old)))).unwrap();
+            db.set("c".to_string(), b"3".to_vec()).await;
+            db.save().await;
+        }
+        let db = NanoDb::with_config(config(dir.path(), Some(Keyring::new(new)))).unwrap();
+        let DbResult::Ok(values) = db.mget(&["a".to_string(), "b".to_string(), "c".to_string()]).await else { panic!() };
+        assert_eq!(values, vec![Some(b"1".to_vec()), Some(b"2".to_vec()), Some(b"3".to_vec())]);
+    }
+}
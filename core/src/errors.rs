@@ -0,0 +1,140 @@
+// Importaciones
+use std::fmt;
+use std::io;
+
+// Limites de tamaño (la clave cabe en el frame TCP, key_len de 2 bytes)
+pub const MAX_KEY_SIZE: usize = u16::MAX as usize;
+pub const MAX_VALUE_SIZE: usize = 512 * 1024 * 1024;
+
+// Errores del nucleo. Los codigos numericos son estables: los adaptadores los
+// envian tal cual a los clientes, asi que no se deben reutilizar ni renumerar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbError {
+    KeyTooLarge { size: usize, max: usize },
+    ValueTooLarge { size: usize, max: usize },
+    TypeMismatch { key: String, expected: &'static str },
+    CasConflict { key: String },
+    OutOfMemory { requested: u64, max: u64 },
+    ReadOnly,
+    Unauthorized,
+    NotImplemented(String),
+    InvalidArgument(String),
+    Busy(String),           // Operacion en curso (ej. snapshot)
+    Storage(String),        // Fallo de disco (log, snapshot)
+    Internal(String),
+    NotANumber { key: String },   // El valor no es un numero (INCR y compania)
+    Overflow { key: String },     // El resultado no cabe en i64 o no es finito
+    JsonPath { path: String, reason: String },  // La ruta no existe o no tiene el tipo esperado
+    Script(String),               // El script no compila o fallo al ejecutarse
+    ScriptTimeout { limit_ms: u64 },  // El script supero el tiempo maximo de ejecucion
+    NoScript(String),             // Ningun script cargado con ese hash (EVALSHA)
+    NotLeader { leader: Option<String> },  // Nodo del cluster que no es lider; direccion del lider si se conoce
+}
+
+impl DbError {
+    pub fn code(&self) -> u16 {
+        match self {
+            DbError::KeyTooLarge { .. } => 1,
+            DbError::ValueTooLarge { .. } => 2,
+            DbError::TypeMismatch { .. } => 3,
+            DbError::CasConflict { .. } => 4,
+            DbError::OutOfMemory { .. } => 5,
+            DbError::ReadOnly => 6,
+            DbError::Unauthorized => 7,
+            DbError::NotImplemented(_) => 8,
+            DbError::InvalidArgument(_) => 9,
+            DbError::Busy(_) => 10,
+            DbError::Storage(_) => 11,
+            DbError::Internal(_) => 12,
+            DbError::NotANumber { .. } => 13,
+            DbError::Overflow { .. } => 14,
+            DbError::JsonPath { .. } => 15,
+            DbError::Script(_) => 16,
+            DbError::ScriptTimeout { .. } => 17,
+            DbError::NoScript(_) => 18,
+            DbError::NotLeader { .. } => 19,
+        }
+    }
+
+    // Verifica los limites de tamaño de una escritura
+    pub fn check_sizes(key: &str, value_len: usize) -> Result<(), DbError> {
+        if key.len() > MAX_KEY_SIZE {
+            return Err(DbError::KeyTooLarge { size: key.len(), max: MAX_KEY_SIZE });
+        }
+        if value_len > MAX_VALUE_SIZE {
+            return Err(DbError::ValueTooLarge { size: value_len, max: MAX_VALUE_SIZE });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::KeyTooLarge { size, max } => write!(f, "key of {} bytes exceeds limit of {} bytes", size, max),
+            DbError::ValueTooLarge { size, max } => write!(f, "value of {} bytes exceeds limit of {} bytes", size, max),
+            DbError::TypeMismatch { key, expected } => write!(f, "key {} does not hold a {} value", key, expected),
+            DbError::CasConflict { key } => write!(f, "compare-and-swap conflict on key {}", key),
+            DbError::OutOfMemory { requested, max } => {
+                write!(f, "out of memory: {} bytes requested with max_memory of {} bytes", requested, max)
+            }
+            DbError::ReadOnly => write!(f, "database is read-only"),
+            DbError::Unauthorized => write!(f, "unauthorized"),
+            DbError::NotImplemented(what) => write!(f, "not implemented: {}", what),
+            DbError::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+            DbError::Busy(msg) => write!(f, "busy: {}", msg),
+            DbError::Storage(msg) => write!(f, "storage error: {}", msg),
+            DbError::Internal(msg) => write!(f, "internal error: {}", msg),
+            DbError::NotANumber { key } => write!(f, "value of key {} is not a number", key),
+            DbError::Overflow { key } => write!(f, "increment would overflow the value of key {}", key),
+            DbError::JsonPath { path, reason } => write!(f, "json path {}: {}", path, reason),
+            DbError::Script(msg) => write!(f, "script error: {}", msg),
+            DbError::ScriptTimeout { limit_ms } => write!(f, "script exceeded the time limit of {} ms", limit_ms),
+            DbError::NoScript(sha) => write!(f, "no script loaded with hash {}", sha),
+            DbError::NotLeader { leader: Some(leader) } => write!(f, "not the cluster leader; leader is at {}", leader),
+            DbError::NotLeader { leader: None } => write!(f, "not the cluster leader; no leader is known"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<io::Error> for DbError {
+    fn from(e: io::Error) -> Self {
+        DbError::Storage(e.to_string())
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_stable() {
+        assert_eq!(DbError::KeyTooLarge { size: 1, max: 0 }.code(), 1);
+        assert_eq!(DbError::CasConflict { key: String::new() }.code(), 4);
+        assert_eq!(DbError::OutOfMemory { requested: 1, max: 0 }.code(), 5);
+        assert_eq!(DbError::Unauthorized.code(), 7);
+        assert_eq!(DbError::Internal(String::new()).code(), 12);
+        assert_eq!(DbError::Overflow { key: String::new() }.code(), 14);
+        assert_eq!(DbError::JsonPath { path: String::new(), reason: String::new() }.code(), 15);
+        assert_eq!(DbError::NoScript(String::new()).code(), 18);
+        assert_eq!(DbError::NotLeader { leader: None }.code(), 19);
+    }
+
+    #[test]
+    fn test_cas_conflict_and_unauthorized_display() {
+        let err = DbError::CasConflict { key: "k".to_string() };
+        assert_eq!(err.to_string(), "compare-and-swap conflict on key k");
+        assert_eq!(DbError::Unauthorized.to_string(), "unauthorized");
+    }
+
+    #[test]
+    fn test_check_sizes() {
+        assert!(DbError::check_sizes("key", 10).is_ok());
+        let long_key = "k".repeat(MAX_KEY_SIZE + 1);
+        assert!(matches!(DbError::check_sizes(&long_key, 0), Err(DbError::KeyTooLarge { .. })));
+        assert!(matches!(DbError::check_sizes("key", MAX_VALUE_SIZE + 1), Err(DbError::ValueTooLarge { .. })));
+    }
+}
@@ -0,0 +1,356 @@
+// Indices secundarios sobre campos de documentos JSON
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use crate::json::{self, JsonPath};
+use crate::value::Value;
+
+const MAX_INDEX_NAME_LEN: usize = 64;
+
+// Definicion de un indice: las claves que empiezan con `prefix` y guardan un documento JSON
+// se indexan por el valor en `path`. Solo se indexan valores escalares (null, booleanos,
+// numeros y cadenas); un documento sin el campo, o con un arreglo u objeto ahi, no aparece.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexDef {
+    pub name: String,
+    pub prefix: String,
+    pub path: String,
+}
+
+impl IndexDef {
+    pub(crate) fn validate(&self) -> Result<JsonPath, String> {
+        if self.name.is_empty() || self.name.len() > MAX_INDEX_NAME_LEN {
+            return Err(format!("index name must be between 1 and {} characters", MAX_INDEX_NAME_LEN));
+        }
+        if !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("invalid index name {}: use letters, digits, '_' or '-'", self.name));
+        }
+        JsonPath::parse(&self.path)
+    }
+}
+
+// Consulta sobre un indice. Los limites del rango son inclusivos y tienen que ser del mismo
+// tipo; un rango solo incluye valores de ese tipo (sin limites recorre todo el indice).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum IndexQuery {
+    Equals(Json),
+    Range { min: Option<Json>, max: Option<Json> },
+}
+
+// Valor indexado. Orden: null < booleanos < numeros < cadenas.
+// Los numeros se comparan como f64, asi que 1 y 1.0 son iguales.
+#[derive(Debug, Clone)]
+pub(crate) enum Scalar {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Scalar {
+    fn from_json(value: &Json) -> Option<Self> {
+        match value {
+            Json::Null => Some(Scalar::Null),
+            Json::Bool(flag) => Some(Scalar::Bool(*flag)),
+            // + 0.0 normaliza -0.0
+            Json::Number(number) => number.as_f64().map(|n| Scalar::Number(n + 0.0)),
+            Json::String(text) => Some(Scalar::String(text.clone())),
+            Json::Array(_) | Json::Object(_) => None,
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        match self {
+            Scalar::Null => Json::Null,
+            Scalar::Bool(flag) => Json::Bool(*flag),
+            Scalar::Number(n) => serde_json::Number::from_f64(*n).map_or(Json::Null, Json::Number),
+            Scalar::String(text) => Json::String(text.clone()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Scalar::Null => 0,
+            Scalar::Bool(_) => 1,
+            Scalar::Number(_) => 2,
+            Scalar::String(_) => 3,
+        }
+    }
+
+    // Menor valor posible del mismo tipo
+    fn first_of_rank(rank: u8) -> Self {
+        match rank {
+            0 => Scalar::Null,
+            1 => Scalar::Bool(false),
+            2 => Scalar::Number(f64::NEG_INFINITY),
+            _ => Scalar::String(String::new()),
+        }
+    }
+}
+
+impl Ord for Scalar {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Scalar::Bool(a), Scalar::Bool(b)) => a.cmp(b),
+            (Scalar::Number(a), Scalar::Number(b)) => a.total_cmp(b),
+            (Scalar::String(a), Scalar::String(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Scalar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Scalar {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scalar {}
+
+// Valor escalar de un documento en la ruta (None si no es JSON o no hay un escalar ahi)
+pub(crate) fn extract(value: &Value, path: &JsonPath) -> Option<Scalar> {
+    match value {
+        Value::Json(doc) => json::get(doc, path).and_then(Scalar::from_json),
+        _ => None,
+    }
+}
+
+// Posicion dentro de un indice: valor y clave (las claves con el mismo valor van en orden)
+pub(crate) type Position = (Scalar, String);
+
+// Cursor opaco para paginar: `[valor, clave]` en JSON
+pub(crate) fn encode_cursor((value, key): &Position) -> String {
+    Json::Array(vec![value.to_json(), Json::String(key.clone())]).to_string()
+}
+
+pub(crate) fn decode_cursor(cursor: &str) -> Option<Position> {
+    match serde_json::from_str(cursor).ok()? {
+        Json::Array(items) => match items.as_slice() {
+            [value, Json::String(key)] => Some((Scalar::from_json(value)?, key.clone())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Valores que acepta una consulta: [lower, upper] dentro de un tipo
+pub(crate) struct ValueRange {
+    lower: Option<Scalar>,
+    upper: Option<Scalar>,
+    rank: Option<u8>,
+}
+
+impl ValueRange {
+    pub(crate) fn new(query: &IndexQuery) -> Result<Self, String> {
+        let scalar = |value: &Json| Scalar::from_json(value).ok_or_else(|| format!("index queries only match scalar values, got {}", value));
+        let (lower, upper) = match query {
+            IndexQuery::Equals(value) => {
+                let value = scalar(value)?;
+                (Some(value.clone()), Some(value))
+            }
+            IndexQuery::Range { min, max } => (min.as_ref().map(scalar).transpose()?, max.as_ref().map(scalar).transpose()?),
+        };
+        let rank = match (&lower, &upper) {
+            (Some(lower), Some(upper)) if lower.rank() != upper.rank() => {
+                return Err("range bounds must have the same type".to_string());
+            }
+            (Some(bound), _) | (None, Some(bound)) => Some(bound.rank()),
+            (None, None) => None,
+        };
+        Ok(ValueRange { lower, upper, rank })
+    }
+
+    fn start(&self) -> Option<Scalar> {
+        self.lower.clone().or_else(|| self.rank.map(Scalar::first_of_rank))
+    }
+
+    fn contains(&self, value: &Scalar) -> bool {
+        self.rank.is_none_or(|rank| value.rank() == rank)
+            && self.lower.as_ref().is_none_or(|lower| value >= lower)
+            && self.upper.as_ref().is_none_or(|upper| value <= upper)
+    }
+
+    // Ya no puede haber valores aceptados despues de este
+    fn passed(&self, value: &Scalar) -> bool {
+        self.rank.is_some_and(|rank| value.rank() > rank) || self.upper.as_ref().is_some_and(|upper| value > upper)
+    }
+}
+
+struct Index {
+    def: IndexDef,
+    path: JsonPath,
+    entries: BTreeSet<Position>,
+    by_key: HashMap<String, Scalar>,
+}
+
+impl Index {
+    fn set(&mut self, key: &str, value: Option<Scalar>) {
+        if let Some(old) = self.by_key.remove(key) {
+            self.entries.remove(&(old, key.to_string()));
+        }
+        if let Some(value) = value {
+            self.entries.insert((value.clone(), key.to_string()));
+            self.by_key.insert(key.to_string(), value);
+        }
+    }
+}
+
+// Indices de una base. Nunca se toma el lock del keyspace con este lock tomado:
+// quien escribe lee los valores primero (matching + extract) y despues los aplica.
+#[derive(Default)]
+pub(crate) struct Indexes {
+    inner: RwLock<BTreeMap<String, Index>>,
+}
+
+impl Indexes {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub(crate) fn definitions(&self) -> Vec<IndexDef> {
+        self.read().values().map(|index| index.def.clone()).collect()
+    }
+
+    pub(crate) fn definition(&self, name: &str) -> Option<IndexDef> {
+        self.read().get(name).map(|index| index.def.clone())
+    }
+
+    // Indice nuevo con sus entradas iniciales; false si ya existia uno con ese nombre
+    pub(crate) fn create(&self, def: IndexDef, path: JsonPath, entries: Vec<(String, Scalar)>) -> bool {
+        let mut indexes = self.write();
+        if indexes.contains_key(&def.name) {
+            return false;
+        }
+        let mut index = Index { def, path, entries: BTreeSet::new(), by_key: HashMap::new() };
+        for (key, value) in entries {
+            index.set(&key, Some(value));
+        }
+        indexes.insert(index.def.name.clone(), index);
+        true
+    }
+
+    pub(crate) fn drop_index(&self, name: &str) -> bool {
+        self.write().remove(name).is_some()
+    }
+
+    // Indices que cubren la clave y la ruta que usan
+    pub(crate) fn matching(&self, key: &str) -> Vec<(String, JsonPath)> {
+        self.read()
+            .values()
+            .filter(|index| key.starts_with(&index.def.prefix))
+            .map(|index| (index.def.name.clone(), index.path.clone()))
+            .collect()
+    }
+
+    // Nuevos valores de la clave (None = ya no aparece en ese indice)
+    pub(crate) fn apply(&self, key: &str, values: Vec<(String, Option<Scalar>)>) {
+        let mut indexes = self.write();
+        for (name, value) in values {
+            // El indice pudo borrarse entre matching() y apply()
+            if let Some(index) = indexes.get_mut(&name) {
+                index.set(key, value);
+            }
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        if self.is_empty() {
+            return;
+        }
+        for index in self.write().values_mut() {
+            index.set(key, None);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        for index in self.write().values_mut() {
+            index.entries.clear();
+            index.by_key.clear();
+        }
+    }
+
+    // Hasta `limit` posiciones aceptadas por el rango despues de `after` (0 = sin limite).
+    // None si el indice no existe.
+    pub(crate) fn scan(&self, name: &str, range: &ValueRange, after: Option<&Position>, limit: usize) -> Option<Vec<Position>> {
+        let indexes = self.read();
+        let index = indexes.get(name)?;
+        let start = match (after, range.start()) {
+            (Some(after), Some(start)) if *after < (start.clone(), String::new()) => Bound::Included((start, String::new())),
+            (Some(after), _) => Bound::Excluded(after.clone()),
+            (None, Some(start)) => Bound::Included((start, String::new())),
+            (None, None) => Bound::Unbounded,
+        };
+        let mut found = Vec::new();
+        for (value, key) in index.entries.range((start, Bound::Unbounded)) {
+            if range.passed(value) || (limit > 0 && found.len() == limit) {
+                break;
+            }
+            if range.contains(value) {
+                found.push((value.clone(), key.clone()));
+            }
+        }
+        Some(found)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<String, Index>> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, Index>> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn index_with(values: &[(&str, Json)]) -> Indexes {
+        let indexes = Indexes::default();
+        let def = IndexDef { name: "age".to_string(), prefix: String::new(), path: "$.age".to_string() };
+        let path = def.validate().unwrap();
+        let entries = values.iter().filter_map(|(key, value)| Some((key.to_string(), Scalar::from_json(value)?))).collect();
+        assert!(indexes.create(def, path, entries));
+        indexes
+    }
+
+    fn keys(indexes: &Indexes, query: IndexQuery) -> Vec<String> {
+        let range = ValueRange::new(&query).unwrap();
+        indexes.scan("age", &range, None, 0).unwrap().into_iter().map(|(_, key)| key).collect()
+    }
+
+    #[test]
+    fn test_scalar_order_and_queries() {
+        let indexes = index_with(&[("a", json!(30)), ("b", json!(25.5)), ("c", json!("30")), ("d", json!(30.0)), ("e", json!(null)), ("f", json!([1]))]);
+        assert_eq!(keys(&indexes, IndexQuery::Equals(json!(30))), vec!["a", "d"]);
+        assert_eq!(keys(&indexes, IndexQuery::Range { min: Some(json!(20)), max: Some(json!(30)) }), vec!["b", "a", "d"]);
+        // Un rango con un solo limite no cruza a otros tipos
+        assert_eq!(keys(&indexes, IndexQuery::Range { min: Some(json!(26)), max: None }), vec!["a", "d"]);
+        assert_eq!(keys(&indexes, IndexQuery::Range { min: None, max: None }), vec!["e", "b", "a", "d", "c"]);
+        assert!(ValueRange::new(&IndexQuery::Range { min: Some(json!(1)), max: Some(json!("z")) }).is_err());
+        assert!(ValueRange::new(&IndexQuery::Equals(json!({"a": 1}))).is_err());
+
+        indexes.apply("a", vec![("age".to_string(), None)]);
+        indexes.remove("d");
+        assert!(keys(&indexes, IndexQuery::Equals(json!(30))).is_empty());
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let position = (Scalar::String("x\"y".to_string()), "user:1".to_string());
+        assert_eq!(decode_cursor(&encode_cursor(&position)), Some(position));
+        assert_eq!(decode_cursor("[1, 2]"), None);
+        assert_eq!(decode_cursor("nope"), None);
+    }
+}
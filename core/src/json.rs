@@ -0,0 +1,269 @@
+// Documentos JSON: rutas y operaciones sobre serde_json::Value
+use serde_json::{Number, Value as Json};
+
+// Bytes extra contabilizados por elemento de un arreglo u objeto (igual que en las colecciones)
+const ELEMENT_OVERHEAD: usize = 16;
+
+// Ruta dentro de un documento: `$` (o vacia) es la raiz; `$.a.b`, `$.items[0]`,
+// `$.items[-1]` (desde el final) y `$["clave con puntos"]`. El `$` inicial es opcional.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JsonPath(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+}
+
+impl JsonPath {
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let mut rest = text.strip_prefix('$').unwrap_or(text);
+        let mut segments = Vec::new();
+        // Sin `$` la ruta puede empezar directamente con un nombre ("a.b")
+        if !text.starts_with('$') && !rest.is_empty() && !rest.starts_with(['.', '[']) {
+            let (name, tail) = split_name(rest);
+            segments.push(Segment::Key(name.to_string()));
+            rest = tail;
+        }
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('.') {
+                let (name, tail) = split_name(tail);
+                if name.is_empty() {
+                    return Err(format!("empty field name in json path {}", text));
+                }
+                segments.push(Segment::Key(name.to_string()));
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                let end = bracket_end(tail).ok_or_else(|| format!("unclosed bracket in json path {}", text))?;
+                let inner = tail[..end].trim();
+                segments.push(match inner.chars().next() {
+                    Some(quote @ ('"' | '\'')) if inner.len() >= 2 && inner.ends_with(quote) => {
+                        Segment::Key(inner[1..inner.len() - 1].replace(&format!("\\{}", quote), &quote.to_string()))
+                    }
+                    _ => Segment::Index(inner.parse().map_err(|_| format!("invalid index {} in json path {}", inner, text))?),
+                });
+                rest = &tail[end + 1..];
+            } else {
+                return Err(format!("unexpected character in json path {}", text));
+            }
+        }
+        Ok(JsonPath(segments))
+    }
+
+    pub(crate) fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Ruta del contenedor y ultimo segmento (None en la raiz)
+    fn split_last(&self) -> Option<(&[Segment], &Segment)> {
+        self.0.split_last().map(|(last, parent)| (parent, last))
+    }
+}
+
+// Nombre hasta el proximo `.` o `[`
+fn split_name(text: &str) -> (&str, &str) {
+    let end = text.find(['.', '[']).unwrap_or(text.len());
+    text.split_at(end)
+}
+
+// Posicion del `]` que cierra el segmento (los corchetes entre comillas no cuentan)
+fn bracket_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(_), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ']') => return Some(i),
+            _ => {}
+        }
+        escaped = false;
+    }
+    None
+}
+
+// Posicion real de un indice (negativos desde el final); None si esta fuera de rango
+fn resolve(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+fn step<'a>(value: &'a Json, segment: &Segment) -> Option<&'a Json> {
+    match (value, segment) {
+        (Json::Object(map), Segment::Key(key)) => map.get(key),
+        (Json::Array(items), Segment::Index(index)) => items.get(resolve(items.len(), *index)?),
+        _ => None,
+    }
+}
+
+fn step_mut<'a>(value: &'a mut Json, segment: &Segment) -> Option<&'a mut Json> {
+    match (value, segment) {
+        (Json::Object(map), Segment::Key(key)) => map.get_mut(key),
+        (Json::Array(items), Segment::Index(index)) => {
+            let index = resolve(items.len(), *index)?;
+            items.get_mut(index)
+        }
+        _ => None,
+    }
+}
+
+fn walk<'a>(doc: &'a Json, segments: &[Segment]) -> Option<&'a Json> {
+    segments.iter().try_fold(doc, step)
+}
+
+fn walk_mut<'a>(doc: &'a mut Json, segments: &[Segment]) -> Option<&'a mut Json> {
+    segments.iter().try_fold(doc, step_mut)
+}
+
+pub(crate) fn get<'a>(doc: &'a Json, path: &JsonPath) -> Option<&'a Json> {
+    walk(doc, &path.0)
+}
+
+// Verifica que `set` pueda escribir en la ruta: el contenedor tiene que existir; en un objeto
+// se agrega o reemplaza el campo, en un arreglo solo se reemplaza una posicion existente
+pub(crate) fn check_set(doc: &Json, path: &JsonPath) -> Result<(), String> {
+    let Some((parent, last)) = path.split_last() else { return Ok(()) };
+    match (walk(doc, parent), last) {
+        (None, _) => Err("parent does not exist".to_string()),
+        (Some(Json::Object(_)), Segment::Key(_)) => Ok(()),
+        (Some(Json::Array(items)), Segment::Index(index)) => match resolve(items.len(), *index) {
+            Some(_) => Ok(()),
+            None => Err("array index out of range".to_string()),
+        },
+        (Some(Json::Array(_)), Segment::Key(_)) => Err("parent is an array, expected an index".to_string()),
+        (Some(Json::Object(_)), Segment::Index(_)) => Err("parent is an object, expected a field name".to_string()),
+        (Some(_), _) => Err("parent is not an object or array".to_string()),
+    }
+}
+
+pub(crate) fn set(doc: &mut Json, path: &JsonPath, value: Json) -> Result<(), String> {
+    check_set(doc, path)?;
+    let Some((parent, last)) = path.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match (walk_mut(doc, parent), last) {
+        (Some(Json::Object(map)), Segment::Key(key)) => {
+            map.insert(key.clone(), value);
+        }
+        (Some(Json::Array(items)), Segment::Index(index)) => {
+            if let Some(index) = resolve(items.len(), *index) {
+                items[index] = value;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Quita el valor en la ruta (no la raiz); false si no existia
+pub(crate) fn delete(doc: &mut Json, path: &JsonPath) -> bool {
+    let Some((parent, last)) = path.split_last() else { return false };
+    match (walk_mut(doc, parent), last) {
+        (Some(Json::Object(map)), Segment::Key(key)) => map.remove(key).is_some(),
+        (Some(Json::Array(items)), Segment::Index(index)) => match resolve(items.len(), *index) {
+            Some(index) => {
+                items.remove(index);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+// Agrega al final del arreglo de la ruta; devuelve la nueva longitud
+pub(crate) fn append(doc: &mut Json, path: &JsonPath, values: &[Json]) -> Result<usize, String> {
+    match walk_mut(doc, &path.0) {
+        Some(Json::Array(items)) => {
+            items.extend(values.iter().cloned());
+            Ok(items.len())
+        }
+        Some(_) => Err("value is not an array".to_string()),
+        None => Err("path does not exist".to_string()),
+    }
+}
+
+// Suma `delta` al numero de la ruta: entero si ambos lo son y el resultado cabe en i64,
+// si no punto flotante. None si el resultado desborda o no es finito.
+pub(crate) fn add(current: &Number, delta: f64) -> Option<Number> {
+    if let Some(current) = current.as_i64() {
+        if delta.fract() == 0.0 && delta.abs() < i64::MAX as f64 {
+            return current.checked_add(delta as i64).map(Number::from);
+        }
+    }
+    Number::from_f64(current.as_f64()? + delta)
+}
+
+pub(crate) fn increment(doc: &mut Json, path: &JsonPath, delta: f64) -> Result<Option<Number>, String> {
+    match walk_mut(doc, &path.0) {
+        Some(Json::Number(number)) => {
+            let next = add(number, delta);
+            if let Some(next) = &next {
+                *number = next.clone();
+            }
+            Ok(next)
+        }
+        Some(_) => Err("value is not a number".to_string()),
+        None => Err("path does not exist".to_string()),
+    }
+}
+
+// Bytes contabilizados para el limite de memoria (aproximado, sin serializar)
+pub(crate) fn size(value: &Json) -> usize {
+    match value {
+        Json::Null | Json::Bool(_) => 4,
+        Json::Number(_) => 8,
+        Json::String(text) => text.len(),
+        Json::Array(items) => items.iter().map(|item| size(item) + ELEMENT_OVERHEAD).sum(),
+        Json::Object(map) => map.iter().map(|(key, item)| key.len() + size(item) + ELEMENT_OVERHEAD).sum(),
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(text: &str) -> JsonPath {
+        JsonPath::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        assert!(path("$").is_root());
+        assert!(path("").is_root());
+        assert_eq!(path("$.a.b[2]"), path("a.b[2]"));
+        assert_eq!(path("$[\"a.b\"]['c']"), JsonPath(vec![Segment::Key("a.b".to_string()), Segment::Key("c".to_string())]));
+        assert_eq!(path("$.items[-1]"), JsonPath(vec![Segment::Key("items".to_string()), Segment::Index(-1)]));
+        for invalid in ["$.", "$[1", "$[x]", "$a", "$..a"] {
+            assert!(JsonPath::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_document_operations() {
+        let mut doc = json!({"user": {"name": "ana", "tags": ["a"]}, "n": 1});
+        assert_eq!(get(&doc, &path("$.user.tags[-1]")), Some(&json!("a")));
+        set(&mut doc, &path("$.user.age"), json!(30)).unwrap();
+        set(&mut doc, &path("$.user.tags[0]"), json!("b")).unwrap();
+        assert!(set(&mut doc, &path("$.user.tags[5]"), json!("x")).is_err());
+        assert!(set(&mut doc, &path("$.missing.field"), json!(1)).is_err());
+        assert_eq!(append(&mut doc, &path("$.user.tags"), &[json!("c"), json!(null)]), Ok(3));
+        assert!(append(&mut doc, &path("$.n"), &[json!(1)]).is_err());
+        assert_eq!(increment(&mut doc, &path("$.n"), 2.0), Ok(Some(Number::from(3))));
+        assert_eq!(increment(&mut doc, &path("$.n"), 0.5), Ok(Number::from_f64(3.5)));
+        assert!(delete(&mut doc, &path("$.user.tags[1]")));
+        assert!(!delete(&mut doc, &path("$.user.zip")));
+        assert_eq!(doc, json!({"user": {"name": "ana", "age": 30, "tags": ["b", null]}, "n": 3.5}));
+
+        let mut counter = json!(i64::MAX);
+        assert_eq!(increment(&mut counter, &path("$"), 1.0), Ok(None));
+    }
+}
@@ -0,0 +1,432 @@
+// Importaciones
+use std::io;
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crossbeam_skiplist::SkipMap;
+use dashmap::DashMap;
+use crate::config::{DbConfig, StorageEngine};
+use crate::lsm::{Lsm, LsmOptions};
+use crate::value::Value;
+
+// Valor almacenado con su expiracion (milisegundos unix) y estadisticas de acceso
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) value: Value,
+    pub(crate) expires_at: Option<u64>,
+    pub(crate) version: u64,               // revision de la ultima escritura
+    pub(crate) last_access: AtomicU64,     // reloj logico del ultimo acceso (LRU)
+    pub(crate) hits: AtomicU64,            // numero de accesos (LFU)
+}
+
+impl Entry {
+    pub(crate) fn new(value: Value, expires_at: Option<u64>, version: u64, clock: u64) -> Self {
+        Entry {
+            value,
+            expires_at,
+            version,
+            last_access: AtomicU64::new(clock),
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expires_at, Some(at) if at <= now)
+    }
+
+    pub(crate) fn touch(&self, clock: u64) {
+        self.last_access.store(clock, Ordering::Relaxed);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Copia con otra expiracion, conservando las estadisticas de acceso
+    pub(crate) fn with_expiry(&self, expires_at: Option<u64>, version: u64) -> Self {
+        self.with_value(self.value.clone(), expires_at, version)
+    }
+
+    pub(crate) fn with_value(&self, value: Value, expires_at: Option<u64>, version: u64) -> Self {
+        Entry {
+            value,
+            expires_at,
+            version,
+            last_access: AtomicU64::new(self.last_access.load(Ordering::Relaxed)),
+            hits: AtomicU64::new(self.hits.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+// Totales de un keyspace: claves, bytes de claves y valores, y version mas alta
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Totals {
+    pub(crate) keys: usize,
+    pub(crate) bytes: u64,
+    pub(crate) version: u64,
+}
+
+// Contenedor de claves segun el motor elegido:
+//   Hash    -> DashMap, acceso O(1) sin orden
+//   Ordered -> skiplist concurrente, orden lexicografico para rangos y cursores
+//   Lsm     -> arbol LSM en disco (ver lsm.rs), ordenado
+// Las escrituras sobre una misma clave ya llegan serializadas por los locks de NanoDb.
+pub(crate) enum Keyspace {
+    Hash(DashMap<String, Entry>),
+    // Box: la skiplist es mucho mas grande que DashMap. Sus valores no se pueden reemplazar
+    // en su lugar: el RwLock permite modificar una entrada sin copiarla.
+    Ordered(Box<SkipMap<String, RwLock<Entry>>>),
+    Lsm(Lsm),
+}
+
+fn read(entry: &RwLock<Entry>) -> RwLockReadGuard<'_, Entry> {
+    entry.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write(entry: &RwLock<Entry>) -> RwLockWriteGuard<'_, Entry> {
+    entry.write().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Keyspace {
+    // Motor vacio; Lsm usa un directorio temporal
+    pub(crate) fn new(engine: StorageEngine) -> Self {
+        match engine {
+            StorageEngine::Hash => Keyspace::Hash(DashMap::new()),
+            StorageEngine::Ordered => Keyspace::Ordered(Box::new(SkipMap::new())),
+            StorageEngine::Lsm => Keyspace::Lsm(
+                Lsm::temporary(LsmOptions::default()).expect("cannot create a temporary directory for the lsm engine"),
+            ),
+        }
+    }
+
+    // Motor segun la configuracion; Lsm con lsm_dir recupera los datos del directorio
+    pub(crate) fn open(config: &DbConfig) -> io::Result<Self> {
+        let options = LsmOptions { fsync: config.fsync, ..LsmOptions::default() };
+        match (config.engine, &config.lsm_dir) {
+            (StorageEngine::Lsm, Some(dir)) => Ok(Keyspace::Lsm(Lsm::open(dir, options)?)),
+            (StorageEngine::Lsm, None) => Ok(Keyspace::Lsm(Lsm::temporary(options)?)),
+            (engine, _) => Ok(Self::new(engine)),
+        }
+    }
+
+    pub(crate) fn engine(&self) -> StorageEngine {
+        match self {
+            Keyspace::Hash(_) => StorageEngine::Hash,
+            Keyspace::Ordered(_) => StorageEngine::Ordered,
+            Keyspace::Lsm(_) => StorageEngine::Lsm,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Keyspace::Hash(map) => map.len(),
+            Keyspace::Ordered(map) => map.len(),
+            Keyspace::Lsm(lsm) => lsm.len(),
+        }
+    }
+
+    // Los errores de disco (solo Lsm) se devuelven a quien hizo la operacion; si la
+    // operacion falla no se aplico nada.
+    pub(crate) fn get<R>(&self, key: &str, read: impl FnOnce(&Entry) -> R) -> io::Result<Option<R>> {
+        match self {
+            Keyspace::Hash(map) => Ok(map.get(key).map(|entry| read(&entry))),
+            Keyspace::Ordered(map) => Ok(map.get(key).map(|entry| read(&self::read(entry.value())))),
+            Keyspace::Lsm(lsm) => lsm.get(key, read),
+        }
+    }
+
+    pub(crate) fn contains_key(&self, key: &str) -> io::Result<bool> {
+        match self {
+            Keyspace::Hash(map) => Ok(map.contains_key(key)),
+            Keyspace::Ordered(map) => Ok(map.contains_key(key)),
+            Keyspace::Lsm(lsm) => Ok(lsm.get(key, |_| ())?.is_some()),
+        }
+    }
+
+    // Inserta o reemplaza; devuelve el tamaño del valor anterior
+    pub(crate) fn insert(&self, key: String, entry: Entry) -> io::Result<Option<usize>> {
+        match self {
+            Keyspace::Hash(map) => Ok(map.insert(key, entry).map(|old| old.value.size())),
+            Keyspace::Ordered(map) => {
+                let old = map.get(&key).map(|old| read(old.value()).value.size());
+                map.insert(key, RwLock::new(entry));
+                Ok(old)
+            }
+            Keyspace::Lsm(lsm) => lsm.insert(key, entry),
+        }
+    }
+
+    // Borra la clave; devuelve el tamaño del valor borrado
+    pub(crate) fn remove(&self, key: &str) -> io::Result<Option<usize>> {
+        match self {
+            Keyspace::Hash(map) => Ok(map.remove(key).map(|(_, old)| old.value.size())),
+            Keyspace::Ordered(map) => Ok(map.remove(key).map(|old| read(old.value()).value.size())),
+            Keyspace::Lsm(lsm) => lsm.remove(key),
+        }
+    }
+
+    // Borra la clave solo si cumple la condicion
+    pub(crate) fn remove_if(&self, key: &str, condition: impl FnOnce(&Entry) -> bool) -> io::Result<Option<usize>> {
+        match self {
+            Keyspace::Hash(map) => Ok(map
+                .remove_if(key, |_, entry| condition(entry))
+                .map(|(_, old)| old.value.size())),
+            Keyspace::Ordered(map) => {
+                let Some(entry) = map.get(key) else { return Ok(None) };
+                // remove() falla si otro hilo ya reemplazo o borro esta entrada
+                Ok((condition(&read(entry.value())) && entry.remove()).then(|| read(entry.value()).value.size()))
+            }
+            Keyspace::Lsm(lsm) => lsm.remove_if(key, condition),
+        }
+    }
+
+    // Cambia la expiracion de una clave existente
+    pub(crate) fn set_expiry(&self, key: &str, expires_at: Option<u64>, version: u64) -> io::Result<()> {
+        match self {
+            Keyspace::Hash(map) => {
+                if let Some(mut entry) = map.get_mut(key) {
+                    entry.expires_at = expires_at;
+                    entry.version = version;
+                }
+            }
+            Keyspace::Ordered(map) => {
+                if let Some(entry) = map.get(key) {
+                    let mut entry = write(entry.value());
+                    entry.expires_at = expires_at;
+                    entry.version = version;
+                }
+            }
+            Keyspace::Lsm(lsm) => return lsm.set_expiry(key, expires_at, version),
+        }
+        Ok(())
+    }
+
+    // Modifica el valor de la clave (creandola con `init` si no existe) y le asigna `version`.
+    // En memoria se modifica en su lugar; con Lsm se escribe una copia (las tablas en disco
+    // no se modifican).
+    // Devuelve el tamaño anterior (None si la clave no existia) y el nuevo.
+    pub(crate) fn update(
+        &self,
+        key: &str,
+        version: u64,
+        clock: u64,
+        init: impl FnOnce() -> Value,
+        change: impl FnOnce(&mut Value),
+    ) -> io::Result<(Option<usize>, usize)> {
+        match self {
+            Keyspace::Hash(map) => {
+                let mut old = None;
+                let mut entry = map
+                    .entry(key.to_string())
+                    .and_modify(|entry| old = Some(entry.value.size()))
+                    .or_insert_with(|| Entry::new(init(), None, version, clock));
+                change(&mut entry.value);
+                entry.version = version;
+                Ok((old, entry.value.size()))
+            }
+            Keyspace::Ordered(map) => match map.get(key) {
+                Some(current) => {
+                    let mut entry = write(current.value());
+                    let old = entry.value.size();
+                    change(&mut entry.value);
+                    entry.version = version;
+                    Ok((Some(old), entry.value.size()))
+                }
+                None => {
+                    let mut value = init();
+                    change(&mut value);
+                    let size = value.size();
+                    map.insert(key.to_string(), RwLock::new(Entry::new(value, None, version, clock)));
+                    Ok((None, size))
+                }
+            },
+            Keyspace::Lsm(lsm) => lsm.update(key, version, clock, init, change),
+        }
+    }
+
+    // Borra las entradas expiradas; `removed` recibe la clave y el tamaño de cada valor borrado
+    pub(crate) fn purge_expired(&self, now: u64, mut removed: impl FnMut(&str, usize)) -> io::Result<()> {
+        match self {
+            Keyspace::Hash(map) => map.retain(|key, entry| {
+                if entry.is_expired(now) {
+                    removed(key, entry.value.size());
+                    return false;
+                }
+                true
+            }),
+            Keyspace::Ordered(map) => {
+                for entry in map.iter() {
+                    let size = {
+                        let current = read(entry.value());
+                        current.is_expired(now).then(|| current.value.size())
+                    };
+                    // remove() falla si otro hilo ya reemplazo o borro esta entrada
+                    if let Some(size) = size.filter(|_| entry.remove()) {
+                        removed(entry.key(), size);
+                    }
+                }
+            }
+            Keyspace::Lsm(lsm) => return lsm.purge_expired(now, removed),
+        }
+        Ok(())
+    }
+
+    // Totales para reconstruir la contabilidad al abrir (Lsm los guarda en su manifiesto)
+    pub(crate) fn totals(&self) -> Totals {
+        let mut totals = Totals::default();
+        let mut add = |key: &str, entry: &Entry| {
+            totals.keys += 1;
+            totals.bytes += key.len() as u64 + entry.value.size() as u64;
+            totals.version = totals.version.max(entry.version);
+        };
+        match self {
+            Keyspace::Hash(map) => map.iter().for_each(|kv| add(kv.key(), kv.value())),
+            Keyspace::Ordered(map) => map.iter().for_each(|entry| add(entry.key(), &read(entry.value()))),
+            Keyspace::Lsm(lsm) => return lsm.totals(),
+        }
+        totals
+    }
+
+    // Borra todas las entradas
+    pub(crate) fn clear(&self) -> io::Result<()> {
+        match self {
+            Keyspace::Hash(map) => map.clear(),
+            Keyspace::Ordered(map) => map.clear(),
+            Keyspace::Lsm(lsm) => return lsm.clear(),
+        }
+        Ok(())
+    }
+
+    // Error del volcado que disparo la ultima escritura (solo Lsm: el registro ya se aplico
+    // y esta en el WAL, pero no se pudo congelar el memtable)
+    pub(crate) fn take_error(&self) -> Option<io::Error> {
+        match self {
+            Keyspace::Lsm(lsm) => lsm.take_error(),
+            _ => None,
+        }
+    }
+
+    // Recorre todas las entradas (en orden con los motores Ordered y Lsm)
+    pub(crate) fn for_each(&self, mut visit: impl FnMut(&str, &Entry)) -> io::Result<()> {
+        match self {
+            Keyspace::Hash(map) => map.iter().for_each(|kv| visit(kv.key(), kv.value())),
+            Keyspace::Ordered(map) => map.iter().for_each(|entry| visit(entry.key(), &read(entry.value()))),
+            Keyspace::Lsm(lsm) => return lsm.for_each(visit),
+        }
+        Ok(())
+    }
+
+    // Entradas dentro de los limites, en orden lexicografico (o inverso).
+    // `visit` devuelve None para saltar una entrada (ej. expirada), que no cuenta para el limite.
+    // limit = 0 devuelve todas.
+    pub(crate) fn range<R>(
+        &self,
+        lower: Bound<&str>,
+        upper: Bound<&str>,
+        reverse: bool,
+        limit: usize,
+        mut visit: impl FnMut(&str, &Entry) -> Option<R>,
+    ) -> io::Result<Vec<(String, R)>> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        match self {
+            Keyspace::Hash(map) => {
+                // Sin orden: filtrar todo y ordenar
+                let mut out: Vec<(String, R)> = map
+                    .iter()
+                    .filter(|kv| RangeBounds::<str>::contains(&(lower, upper), kv.key().as_str()))
+                    .filter_map(|kv| visit(kv.key(), kv.value()).map(|r| (kv.key().clone(), r)))
+                    .collect();
+                out.sort_by(|a, b| a.0.cmp(&b.0));
+                if reverse {
+                    out.reverse();
+                }
+                out.truncate(limit);
+                Ok(out)
+            }
+            Keyspace::Ordered(map) => {
+                let range = map.range::<str, _>((lower, upper));
+                let visit = |entry: crossbeam_skiplist::map::Entry<'_, String, RwLock<Entry>>| {
+                    visit(entry.key(), &read(entry.value())).map(|r| (entry.key().clone(), r))
+                };
+                if reverse {
+                    Ok(range.rev().filter_map(visit).take(limit).collect())
+                } else {
+                    Ok(range.filter_map(visit).take(limit).collect())
+                }
+            }
+            Keyspace::Lsm(lsm) => lsm.range(lower, upper, reverse, limit, visit),
+        }
+    }
+}
+
+// Menor cadena mayor que todas las que empiezan con `prefix` (None = no existe)
+pub(crate) fn prefix_end(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        let next = match last as u32 + 1 {
+            0xD800 => Some('\u{E000}'),  // Saltar el rango de surrogates
+            code => char::from_u32(code),
+        };
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(prefix_end("user:"), Some("user;".to_string()));
+        assert_eq!(prefix_end("a\u{10FFFF}"), Some("b".to_string()));
+        assert_eq!(prefix_end(""), None);
+    }
+
+    #[test]
+    fn test_engines_agree_on_range() {
+        for engine in [StorageEngine::Hash, StorageEngine::Ordered, StorageEngine::Lsm] {
+            let keyspace = Keyspace::new(engine);
+            for key in ["b", "a", "d", "c"] {
+                keyspace.insert(key.to_string(), Entry::new(Value::Bytes(key.as_bytes().to_vec()), None, 1, 0)).unwrap();
+            }
+            let keys = |reverse| -> Vec<String> {
+                keyspace
+                    .range(Bound::Included("b"), Bound::Excluded("d"), reverse, 0, |_, _| Some(()))
+                    .unwrap()
+                    .into_iter()
+                    .map(|(key, _)| key)
+                    .collect()
+            };
+            assert_eq!(keys(false), vec!["b", "c"], "{:?}", engine);
+            assert_eq!(keys(true), vec!["c", "b"], "{:?}", engine);
+            assert_eq!(keyspace.remove_if("a", |entry| entry.value.to_bytes() == Some(b"a".to_vec())).unwrap(), Some(1));
+            assert_eq!(keyspace.len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_update_keeps_entry_metadata() {
+        for engine in [StorageEngine::Hash, StorageEngine::Ordered, StorageEngine::Lsm] {
+            let keyspace = Keyspace::new(engine);
+            let push = |item: &[u8], version| {
+                keyspace.update("list", version, 0, || Value::List(Default::default()), |value| {
+                    if let Value::List(list) = value {
+                        list.push_back(item.to_vec());
+                    }
+                })
+                .unwrap()
+            };
+            let (old, size) = push(b"a", 1);
+            assert_eq!(old, None);
+            keyspace.set_expiry("list", Some(u64::MAX), 2).unwrap();
+            let (old, grown) = push(b"bc", 3);
+            assert_eq!(old, Some(size));
+            assert!(grown > size);
+            // La expiracion y la version se conservan al modificar el valor
+            let (expires_at, version) = keyspace.get("list", |entry| (entry.expires_at, entry.version)).unwrap().unwrap();
+            assert_eq!((expires_at, version), (Some(u64::MAX), 3), "{:?}", engine);
+        }
+    }
+}
@@ -1,144 +1,585 @@
 // Exports públicos
 pub use storage::NanoDb;
-pub use operations::{DbOperation, DbResult};
-pub use metrics::{Metrics, MetricsSnapshot};
+pub use operations::{CasOutcome, DbOperation, DbResponse, DbResult, KeysPage, Precondition, TxnOutcome};
+pub use metrics::{ChannelSnapshot, Metrics, MetricsSnapshot};
+pub use config::{DbConfig, EvictionPolicy, FsyncPolicy, StorageEngine};
+pub use aof::{AppendLog, LogRecord};
+pub use snapshot::SnapshotInfo;
+pub use port::StoragePort;
+pub use errors::DbError;
+pub use watch::{ChangeEvent, ChangeKind, WatchFilter, Watcher};
+pub use pubsub::{Message, Subscription};
+pub use value::{CollectionOp, SortedSet, Value};
+pub use compression::{Codec, Compression};
+pub use encryption::{EncryptionKey, Keyring};
+pub use index::{IndexDef, IndexQuery};
+pub use script::script_hash;
+pub use replication::{FollowerInfo, ReplicationInfo};
+pub use cluster::{ClusterConfig, ClusterStatus, Members, RaftNode, Role};
 
 // Módulos
 pub mod storage;
 pub mod operations;
 pub mod metrics;
+pub mod config;
+pub mod aof;
+pub mod snapshot;
+pub mod port;
+pub mod errors;
+pub mod watch;
+pub mod pubsub;
+pub mod value;
+pub mod compression;
+pub mod encryption;
+pub mod index;
+pub mod script;
+pub mod replication;
+pub mod cluster;
+mod json;
+mod wal;
+mod keyspace;
+mod lsm;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_set_and_get() {
-        let db = NanoDb::new();
-        
-        // Test SET
-        let result = db.set("test_key".to_string(), b"test_value".to_vec()).await;
-        assert!(matches!(result, DbResult::Ok(())));
-        
-        // Test GET
-        let result = db.get("test_key").await;
-        assert!(matches!(result, DbResult::Ok(ref data) if data == b"test_value"));
+    // La misma bateria corre sobre cada motor de almacenamiento
+    macro_rules! engine_suite {
+        ($engine:expr) => {
+            fn new_db() -> NanoDb {
+                with_engine(DbConfig::default())
+            }
+
+            fn with_engine(config: DbConfig) -> NanoDb {
+                NanoDb::with_config(DbConfig { engine: $engine, ..config }).unwrap()
+            }
+
+            #[tokio::test]
+            async fn test_set_and_get() {
+                let db = new_db();
+
+                // Test SET
+                let result = db.set("test_key".to_string(), b"test_value".to_vec()).await;
+                assert!(matches!(result, DbResult::Ok(())));
+
+                // Test GET
+                let result = db.get("test_key").await;
+                assert!(matches!(result, DbResult::Ok(ref data) if data == b"test_value"));
+            }
+
+            #[tokio::test]
+            async fn test_get_nonexistent() {
+                let db = new_db();
+
+                let result = db.get("nonexistent").await;
+                assert!(matches!(result, DbResult::NotFound));
+            }
+
+            #[tokio::test]
+            async fn test_delete() {
+                let db = new_db();
+
+                // Set a value
+                db.set("delete_me".to_string(), b"value".to_vec()).await;
+
+                // Delete it
+                let result = db.delete("delete_me").await;
+                assert!(matches!(result, DbResult::Ok(())));
+
+                // Verify it's gone
+                let result = db.get("delete_me").await;
+                assert!(matches!(result, DbResult::NotFound));
+            }
+
+            #[tokio::test]
+            async fn test_keys() {
+                let db = new_db();
+
+                // Add some keys
+                db.set("key1".to_string(), b"value1".to_vec()).await;
+                db.set("key2".to_string(), b"value2".to_vec()).await;
+
+                // Get keys
+                let result = db.keys().await;
+                if let DbResult::Ok(keys) = result {
+                    assert_eq!(keys.len(), 2);
+                    assert!(keys.contains(&"key1".to_string()));
+                    assert!(keys.contains(&"key2".to_string()));
+                } else {
+                    panic!("Expected Ok with keys");
+                }
+            }
+
+            #[tokio::test]
+            async fn test_clear() {
+                let db = new_db();
+
+                // Add some data
+                db.set("key1".to_string(), b"value1".to_vec()).await;
+                db.set("key2".to_string(), b"value2".to_vec()).await;
+
+                // Clear all
+                let result = db.clear().await;
+                assert!(matches!(result, DbResult::Ok(())));
+
+                // Verify empty
+                let result = db.keys().await;
+                if let DbResult::Ok(keys) = result {
+                    assert_eq!(keys.len(), 0);
+                } else {
+                    panic!("Expected Ok with empty keys");
+                }
+            }
+
+            #[tokio::test]
+            async fn test_exists() {
+                let db = new_db();
+
+                // Test non-existent key
+                let result = db.exists("nonexistent").await;
+                assert!(matches!(result, DbResult::Ok(false)));
+
+                // Add a key
+                db.set("existing".to_string(), b"value".to_vec()).await;
+
+                // Test existing key
+                let result = db.exists("existing").await;
+                assert!(matches!(result, DbResult::Ok(true)));
+            }
+
+            #[tokio::test]
+            async fn test_concurrent_access() {
+                use std::sync::Arc;
+
+                let db = Arc::new(new_db());
+                let mut handles = vec![];
+
+                // Spawn multiple tasks
+                for i in 0..10 {
+                    let db_clone = db.clone();
+                    let handle = tokio::spawn(async move {
+                        let key = format!("key_{}", i);
+                        let value = format!("value_{}", i).into_bytes();
+
+                        // Set value
+                        db_clone.set(key.clone(), value.clone()).await;
+
+                        // Get value back
+                        let result = db_clone.get(&key).await;
+                        matches!(result, DbResult::Ok(ref data) if data == &value)
+                    });
+                    handles.push(handle);
+                }
+
+                // Wait for all tasks
+                for handle in handles {
+                    let success = handle.await.unwrap();
+                    assert!(success);
+                }
+
+                // Verify all keys exist
+                let result = db.keys().await;
+                if let DbResult::Ok(keys) = result {
+                    assert_eq!(keys.len(), 10);
+                }
+            }
+
+            #[tokio::test]
+            async fn test_set_with_ttl_expires() {
+                use std::time::Duration;
+
+                let db = new_db();
+                db.set_with_ttl("session".to_string(), b"token".to_vec(), Some(Duration::from_millis(50))).await;
+                db.set("forever".to_string(), b"value".to_vec()).await;
+
+                // Antes de expirar
+                assert!(matches!(db.exists("session").await, DbResult::Ok(true)));
+                assert!(matches!(db.ttl("session").await, DbResult::Ok(Some(ttl)) if ttl <= Duration::from_millis(50)));
+                assert!(matches!(db.ttl("forever").await, DbResult::Ok(None)));
+
+                tokio::time::sleep(Duration::from_millis(80)).await;
+
+                // Expirada: nunca se devuelve
+                assert!(matches!(db.get("session").await, DbResult::NotFound));
+                assert!(matches!(db.exists("session").await, DbResult::Ok(false)));
+                assert!(matches!(db.ttl("session").await, DbResult::NotFound));
+                if let DbResult::Ok(keys) = db.keys().await {
+                    assert_eq!(keys, vec!["forever".to_string()]);
+                } else {
+                    panic!("Expected Ok with keys");
+                }
+            }
+
+            #[tokio::test]
+            async fn test_expire_and_persist() {
+                use std::time::Duration;
+
+                let db = new_db();
+                assert!(matches!(db.expire("missing", Duration::from_secs(1)).await, DbResult::Ok(false)));
+
+                db.set("key".to_string(), b"value".to_vec()).await;
+                assert!(matches!(db.expire("key", Duration::from_secs(60)).await, DbResult::Ok(true)));
+                assert!(matches!(db.ttl("key").await, DbResult::Ok(Some(_))));
+
+                assert!(matches!(db.persist("key").await, DbResult::Ok(true)));
+                assert!(matches!(db.ttl("key").await, DbResult::Ok(None)));
+                assert!(matches!(db.persist("key").await, DbResult::Ok(false)));
+
+                // SET sin TTL elimina la expiracion previa
+                db.expire("key", Duration::from_secs(60)).await;
+                db.set("key".to_string(), b"new".to_vec()).await;
+                assert!(matches!(db.ttl("key").await, DbResult::Ok(None)));
+            }
+
+            #[tokio::test]
+            async fn test_background_reaper() {
+                use std::sync::Arc;
+                use std::time::Duration;
+
+                let db = Arc::new(new_db());
+                let reaper = db.spawn_reaper(Duration::from_millis(10));
+                for i in 0..5 {
+                    db.set_with_ttl(format!("tmp_{}", i), b"x".to_vec(), Some(Duration::from_millis(20))).await;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+
+                // El reaper ya las borro, sin necesidad de accederlas
+                assert_eq!(db.purge_expired(), 0);
+                reaper.abort();
+            }
+
+            #[tokio::test]
+            async fn test_ttl_survives_restart() {
+                use std::time::Duration;
+
+                let dir = tempfile::tempdir().unwrap();
+                let path = dir.path().join("nanodb.aof");
+                {
+                    let db = with_engine(DbConfig { aof_path: Some(path.clone()), ..DbConfig::default() });
+                    db.set_with_ttl("short".to_string(), b"a".to_vec(), Some(Duration::from_millis(30))).await;
+                    db.set_with_ttl("long".to_string(), b"b".to_vec(), Some(Duration::from_secs(60))).await;
+                }
+                tokio::time::sleep(Duration::from_millis(60)).await;
+
+                let db = with_engine(DbConfig { aof_path: Some(path.clone()), ..DbConfig::default() });
+                assert!(matches!(db.get("short").await, DbResult::NotFound));
+                assert!(matches!(db.ttl("long").await, DbResult::Ok(Some(ttl)) if ttl > Duration::from_secs(50)));
+            }
+
+            #[tokio::test]
+            async fn test_max_memory_no_eviction() {
+                let db = with_engine(DbConfig {
+                    max_memory: Some(300),
+                    ..DbConfig::default()
+                });
+
+                assert!(matches!(db.set("a".to_string(), vec![0; 100]).await, DbResult::Ok(())));
+                assert!(matches!(db.set("b".to_string(), vec![0; 100]).await, DbResult::Err(_)));
+                // Sobrescribir la misma clave reutiliza su espacio
+                assert!(matches!(db.set("a".to_string(), vec![1; 120]).await, DbResult::Ok(())));
+                assert!(db.used_memory() <= 300);
+                assert_eq!(db.metrics().get_stats().evictions, 0);
+            }
+
+            #[tokio::test]
+            async fn test_typed_errors() {
+                let db = with_engine(DbConfig {
+                    max_memory: Some(300),
+                    ..DbConfig::default()
+                });
+                let long_key = "k".repeat(errors::MAX_KEY_SIZE + 1);
+                assert!(matches!(db.set(long_key, vec![0]).await, DbResult::Err(DbError::KeyTooLarge { .. })));
+                let response = db.execute(DbOperation::Set { key: "a".to_string(), value: vec![0; 400], ttl: None }).await;
+                assert!(matches!(response, DbResponse::Error(ref e) if e.code() == 5), "{:?}", response);
+
+                let read_only = with_engine(DbConfig { read_only: true, ..DbConfig::default() });
+                assert!(matches!(read_only.set("a".to_string(), vec![0]).await, DbResult::Err(DbError::ReadOnly)));
+                assert!(matches!(read_only.get("a").await, DbResult::NotFound));
+            }
+
+            #[tokio::test]
+            async fn test_memory_accounting() {
+                let db = new_db();
+                db.set("key".to_string(), b"value".to_vec()).await;
+                let used = db.used_memory();
+                assert!(used >= 8);
+                db.set("key".to_string(), b"value".to_vec()).await;
+                assert_eq!(db.used_memory(), used);
+                db.delete("key").await;
+                assert_eq!(db.used_memory(), 0);
+                db.set("a".to_string(), b"1".to_vec()).await;
+                db.clear().await;
+                assert_eq!(db.used_memory(), 0);
+            }
+
+            #[tokio::test]
+            async fn test_execute_dispatch() {
+                let db = new_db();
+                let set = |key: &str, value: &str| DbOperation::Set { key: key.to_string(), value: value.as_bytes().to_vec(), ttl: None };
+                assert_eq!(db.execute(set("user:2", "b")).await, DbResponse::Ok);
+                assert_eq!(db.execute(set("user:1", "a")).await, DbResponse::Ok);
+                assert_eq!(db.execute(set("other", "c")).await, DbResponse::Ok);
+
+                let get = DbOperation::Get { key: "missing".to_string(), default: Some(b"dflt".to_vec()) };
+                assert_eq!(db.execute(get).await, DbResponse::Value(b"dflt".to_vec()));
+                assert_eq!(db.execute(DbOperation::Exists { key: "user:1".to_string() }).await, DbResponse::Bool(true));
+                assert_eq!(db.execute(DbOperation::Size).await, DbResponse::Count(3));
+                assert_eq!(
+                    db.execute(DbOperation::KeysPrefix { prefix: "user:".to_string() }).await,
+                    DbResponse::Keys(vec!["user:1".to_string(), "user:2".to_string()])
+                );
+                assert_eq!(
+                    db.execute(DbOperation::ValuesPrefix { prefix: "user:".to_string() }).await,
+                    DbResponse::Values(vec![b"a".to_vec(), b"b".to_vec()])
+                );
+                assert_eq!(
+                    db.execute(DbOperation::GetPrefix { prefix: "oth".to_string() }).await,
+                    DbResponse::Entries(vec![("other".to_string(), b"c".to_vec())])
+                );
+                assert_eq!(db.execute(DbOperation::DeletePrefix { prefix: "user:".to_string() }).await, DbResponse::Count(2));
+                assert_eq!(db.execute(DbOperation::Exists { key: "user:1".to_string() }).await, DbResponse::Bool(false));
+                assert!(matches!(db.execute(DbOperation::Save).await, DbResponse::Error(_)));
+            }
+
+            #[tokio::test]
+            async fn test_keys_cursor_pagination() {
+                let db = new_db();
+                for i in 0..5 {
+                    db.set(format!("k{}", i), vec![]).await;
+                }
+                let page = |cursor: Option<String>| DbOperation::KeysCursor { prefix: Some("k".to_string()), cursor, limit: 2 };
+                let DbResponse::KeysPage(first) = db.execute(page(None)).await else { panic!("Expected page") };
+                assert_eq!(first.keys, vec!["k0", "k1"]);
+                let DbResponse::KeysPage(second) = db.execute(page(first.next_cursor)).await else { panic!("Expected page") };
+                assert_eq!(second.keys, vec!["k2", "k3"]);
+                let DbResponse::KeysPage(last) = db.execute(page(second.next_cursor)).await else { panic!("Expected page") };
+                assert_eq!(last.keys, vec!["k4"]);
+                assert_eq!(last.next_cursor, None);
+            }
+
+            #[tokio::test]
+            async fn test_compare_and_swap() {
+                let db = new_db();
+                // Crear si no existe
+                let outcome = db.compare_and_swap("lock", None, Some(b"a".to_vec())).await;
+                assert!(matches!(outcome, DbResult::Ok(CasOutcome { swapped: true, .. })));
+                // Valor esperado incorrecto: devuelve el actual
+                let outcome = db.compare_and_swap("lock", Some(b"x".to_vec()), Some(b"b".to_vec())).await;
+                assert!(matches!(outcome, DbResult::Ok(CasOutcome { swapped: false, current: Some(ref v) }) if v == b"a"));
+                // Borrar si coincide
+                let outcome = db.compare_and_swap("lock", Some(b"a".to_vec()), None).await;
+                assert!(matches!(outcome, DbResult::Ok(CasOutcome { swapped: true, current: None })));
+                assert!(matches!(db.get("lock").await, DbResult::NotFound));
+            }
+
+            #[tokio::test]
+            async fn test_compare_and_swap_keeps_ttl() {
+                use std::time::Duration;
+
+                let db = new_db();
+                db.set_with_ttl("lock".to_string(), b"a".to_vec(), Some(Duration::from_secs(60))).await;
+                let outcome = db.compare_and_swap("lock", Some(b"a".to_vec()), Some(b"b".to_vec())).await;
+                assert!(matches!(outcome, DbResult::Ok(CasOutcome { swapped: true, .. })));
+                assert!(matches!(db.ttl("lock").await, DbResult::Ok(Some(ttl)) if ttl > Duration::from_secs(50)));
+            }
+
+            #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+            async fn test_compare_and_swap_concurrent_counter() {
+                let db = std::sync::Arc::new(new_db());
+                db.set("counter".to_string(), b"0".to_vec()).await;
+                let mut handles = Vec::new();
+                for _ in 0..8 {
+                    let db = db.clone();
+                    handles.push(tokio::spawn(async move {
+                        for _ in 0..50 {
+                            // Reintentar hasta que nadie haya cambiado el valor entre lectura y escritura
+                            let mut current = b"0".to_vec();
+                            loop {
+                                let next: u64 = String::from_utf8_lossy(&current).parse::<u64>().unwrap() + 1;
+                                match db.compare_and_swap("counter", Some(current), Some(next.to_string().into_bytes())).await {
+                                    DbResult::Ok(CasOutcome { swapped: true, .. }) => break,
+                                    DbResult::Ok(CasOutcome { current: Some(actual), .. }) => current = actual,
+                                    other => panic!("Unexpected CAS result {:?}", other),
+                                }
+                            }
+                        }
+                    }));
+                }
+                for handle in handles {
+                    handle.await.unwrap();
+                }
+                assert!(matches!(db.get("counter").await, DbResult::Ok(ref v) if v == b"400"));
+            }
+        };
     }
 
-    #[tokio::test]
-    async fn test_get_nonexistent() {
-        let db = NanoDb::new();
-        
-        let result = db.get("nonexistent").await;
-        assert!(matches!(result, DbResult::NotFound));
+    // Desalojo: solo los motores en memoria (el LSM lo rechaza)
+    macro_rules! eviction_suite {
+        () => {
+            #[tokio::test]
+            async fn test_lru_eviction() {
+                let db = with_engine(DbConfig {
+                    max_memory: Some(3 * 100),
+                    eviction: EvictionPolicy::AllKeysLru,
+                    ..DbConfig::default()
+                });
+
+                db.set("k1".to_string(), vec![0; 30]).await;
+                db.set("k2".to_string(), vec![0; 30]).await;
+                db.set("k3".to_string(), vec![0; 30]).await;
+                // k1 se usa, k2 queda como el menos reciente
+                db.get("k1").await;
+                db.set("k4".to_string(), vec![0; 30]).await;
+
+                assert!(matches!(db.exists("k2").await, DbResult::Ok(false)));
+                assert!(matches!(db.exists("k1").await, DbResult::Ok(true)));
+                assert!(matches!(db.exists("k4").await, DbResult::Ok(true)));
+                assert_eq!(db.metrics().get_stats().evictions, 1);
+                assert!(db.used_memory() <= 300);
+            }
+
+            #[tokio::test]
+            async fn test_lfu_eviction() {
+                let db = with_engine(DbConfig {
+                    max_memory: Some(3 * 100),
+                    eviction: EvictionPolicy::AllKeysLfu,
+                    ..DbConfig::default()
+                });
+
+                db.set("hot".to_string(), vec![0; 30]).await;
+                db.set("warm".to_string(), vec![0; 30]).await;
+                db.set("cold".to_string(), vec![0; 30]).await;
+                for _ in 0..5 {
+                    db.get("hot").await;
+                }
+                db.get("warm").await;
+                db.get("cold").await;
+                db.get("warm").await;
+                db.set("new".to_string(), vec![0; 30]).await;
+
+                assert!(matches!(db.exists("cold").await, DbResult::Ok(false)));
+                assert!(matches!(db.exists("hot").await, DbResult::Ok(true)));
+                assert!(matches!(db.exists("warm").await, DbResult::Ok(true)));
+            }
+
+            #[tokio::test]
+            async fn test_volatile_ttl_eviction() {
+                use std::time::Duration;
+
+                let db = with_engine(DbConfig {
+                    max_memory: Some(3 * 200),
+                    eviction: EvictionPolicy::VolatileTtl,
+                    ..DbConfig::default()
+                });
+
+                db.set("persistent".to_string(), vec![0; 120]).await;
+                db.set_with_ttl("later".to_string(), vec![0; 120], Some(Duration::from_secs(600))).await;
+                db.set_with_ttl("soon".to_string(), vec![0; 120], Some(Duration::from_secs(60))).await;
+                db.set("next".to_string(), vec![0; 120]).await;
+
+                assert!(matches!(db.exists("soon").await, DbResult::Ok(false)));
+                assert!(matches!(db.exists("later").await, DbResult::Ok(true)));
+
+                // Sin claves volatiles no hay nada que desalojar
+                db.delete("later").await;
+                db.set("other".to_string(), vec![0; 120]).await;
+                assert!(matches!(db.set("full".to_string(), vec![0; 120]).await, DbResult::Err(_)));
+            }
+        };
     }
 
-    #[tokio::test]
-    async fn test_delete() {
-        let db = NanoDb::new();
-        
-        // Set a value
-        db.set("delete_me".to_string(), b"value".to_vec()).await;
-        
-        // Delete it
-        let result = db.delete("delete_me").await;
-        assert!(matches!(result, DbResult::Ok(())));
-        
-        // Verify it's gone
-        let result = db.get("delete_me").await;
-        assert!(matches!(result, DbResult::NotFound));
+    mod hash_engine {
+        use super::*;
+        engine_suite!(StorageEngine::Hash);
+        eviction_suite!();
     }
 
-    #[tokio::test]
-    async fn test_keys() {
-        let db = NanoDb::new();
-        
-        // Add some keys
-        db.set("key1".to_string(), b"value1".to_vec()).await;
-        db.set("key2".to_string(), b"value2".to_vec()).await;
-        
-        // Get keys
-        let result = db.keys().await;
-        if let DbResult::Ok(keys) = result {
-            assert_eq!(keys.len(), 2);
-            assert!(keys.contains(&"key1".to_string()));
-            assert!(keys.contains(&"key2".to_string()));
-        } else {
-            panic!("Expected Ok with keys");
+    mod ordered_engine {
+        use super::*;
+        engine_suite!(StorageEngine::Ordered);
+        eviction_suite!();
+    }
+
+    mod lsm_engine {
+        use super::*;
+        engine_suite!(StorageEngine::Lsm);
+
+        #[test]
+        fn test_eviction_is_rejected() {
+            let config = DbConfig { engine: StorageEngine::Lsm, eviction: EvictionPolicy::AllKeysLru, ..DbConfig::default() };
+            assert!(NanoDb::with_config(config).is_err());
         }
     }
 
+    fn ordered() -> NanoDb {
+        NanoDb::with_config(DbConfig { engine: StorageEngine::Ordered, ..DbConfig::default() }).unwrap()
+    }
+
     #[tokio::test]
-    async fn test_clear() {
-        let db = NanoDb::new();
-        
-        // Add some data
-        db.set("key1".to_string(), b"value1".to_vec()).await;
-        db.set("key2".to_string(), b"value2".to_vec()).await;
-        
-        // Clear all
-        let result = db.clear().await;
-        assert!(matches!(result, DbResult::Ok(())));
-        
-        // Verify empty
-        let result = db.keys().await;
-        if let DbResult::Ok(keys) = result {
-            assert_eq!(keys.len(), 0);
-        } else {
-            panic!("Expected Ok with empty keys");
+    async fn test_ordered_engine_range_scans() {
+        use std::time::Duration;
+        let db = ordered();
+        assert_eq!(db.engine(), StorageEngine::Ordered);
+        for key in ["c", "a", "e", "b", "d"] {
+            db.set(key.to_string(), key.as_bytes().to_vec()).await;
         }
+        assert!(matches!(db.keys().await, DbResult::Ok(ref keys) if keys == &["a", "b", "c", "d", "e"]));
+
+        let keys = |entries: Vec<(String, Vec<u8>)>| entries.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        let DbResult::Ok(forward) = db.range(Some("b"), Some("e"), false, 0).await else { panic!("Expected range") };
+        assert_eq!(keys(forward), vec!["b", "c", "d"]);
+        let DbResult::Ok(backward) = db.range(None, Some("e"), true, 2).await else { panic!("Expected range") };
+        assert_eq!(keys(backward), vec!["d", "c"]);
+
+        // Las claves expiradas no aparecen en los rangos
+        db.set_with_ttl("bb".to_string(), vec![], Some(Duration::from_millis(1))).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let DbResult::Ok(forward) = db.range(Some("b"), Some("c"), false, 0).await else { panic!("Expected range") };
+        assert_eq!(keys(forward), vec!["b"]);
     }
 
     #[tokio::test]
-    async fn test_exists() {
-        let db = NanoDb::new();
-        
-        // Test non-existent key
-        let result = db.exists("nonexistent").await;
-        assert!(matches!(result, DbResult::Ok(false)));
-        
-        // Add a key
-        db.set("existing".to_string(), b"value".to_vec()).await;
-        
-        // Test existing key
-        let result = db.exists("existing").await;
-        assert!(matches!(result, DbResult::Ok(true)));
+    async fn test_cursor_survives_concurrent_writes() {
+        let db = ordered();
+        for i in 0..6 {
+            db.set(format!("k{}", i), vec![]).await;
+        }
+        let DbResult::Ok(first) = db.keys_page(Some("k"), None, 3).await else { panic!("Expected page") };
+        assert_eq!(first.keys, vec!["k0", "k1", "k2"]);
+        // Cambios entre paginas: borrar la clave del cursor, insertar antes y despues
+        db.delete("k2").await;
+        db.set("k00".to_string(), vec![]).await;
+        db.set("k45".to_string(), vec![]).await;
+        let DbResult::Ok(second) = db.keys_page(Some("k"), first.next_cursor.as_deref(), 3).await else { panic!("Expected page") };
+        assert_eq!(second.keys, vec!["k3", "k4", "k45"]);
+        let DbResult::Ok(last) = db.keys_page(Some("k"), second.next_cursor.as_deref(), 3).await else { panic!("Expected page") };
+        assert_eq!(last.keys, vec!["k5"]);
+        assert_eq!(last.next_cursor, None);
     }
 
     #[tokio::test]
-    async fn test_concurrent_access() {
-        use std::sync::Arc;
-        
-        let db = Arc::new(NanoDb::new());
-        let mut handles = vec![];
-        
-        // Spawn multiple tasks
-        for i in 0..10 {
-            let db_clone = db.clone();
-            let handle = tokio::spawn(async move {
-                let key = format!("key_{}", i);
-                let value = format!("value_{}", i).into_bytes();
-                
-                // Set value
-                db_clone.set(key.clone(), value.clone()).await;
-                
-                // Get value back
-                let result = db_clone.get(&key).await;
-                matches!(result, DbResult::Ok(ref data) if data == &value)
-            });
-            handles.push(handle);
-        }
-        
-        // Wait for all tasks
-        for handle in handles {
-            let success = handle.await.unwrap();
-            assert!(success);
-        }
-        
-        // Verify all keys exist
-        let result = db.keys().await;
-        if let DbResult::Ok(keys) = result {
-            assert_eq!(keys.len(), 10);
+    async fn test_ordered_engine_persistence_and_eviction() {
+        use std::time::Duration;
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            aof_path: Some(dir.path().join("ordered.aof")),
+            fsync: FsyncPolicy::Always,
+            engine: StorageEngine::Ordered,
+            max_memory: Some(3 * 100),
+            eviction: EvictionPolicy::AllKeysLru,
+            ..DbConfig::default()
+        };
+        let db = NanoDb::with_config(config.clone()).unwrap();
+        for i in 0..4 {
+            db.set(format!("k{}", i), vec![0; 20]).await;
         }
+        db.expire("k3", Duration::from_secs(60)).await;
+        assert!(matches!(db.exists("k0").await, DbResult::Ok(false)));
+        drop(db);
+
+        let restored = NanoDb::with_config(config).unwrap();
+        assert!(matches!(restored.keys().await, DbResult::Ok(ref keys) if keys == &["k1", "k2", "k3"]));
+        assert!(matches!(restored.ttl("k3").await, DbResult::Ok(Some(_))));
     }
-}
\ No newline at end of file
+}
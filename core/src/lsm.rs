@@ -0,0 +1,1244 @@
+// Motor de almacenamiento LSM en disco (StorageEngine::Lsm), para datos mas grandes que la RAM:
+//   - cada escritura va al WAL y al memtable (skiplist en memoria)
+//   - un memtable lleno se congela y un hilo de fondo lo vuelca a una SSTable del nivel 0
+//   - compactacion por niveles en segundo plano: L0 -> L1 cuando se acumulan tablas,
+//     Ln -> Ln+1 cuando un nivel supera su tamaño (cada nivel 10 veces mas grande que el anterior)
+//   - lecturas: memtables y despues tablas de la mas nueva a la mas vieja, con filtro de bloom
+//     por tabla y cache de bloques compartida
+// Todas las escrituras se serializan bajo el lock del WAL, que tambien hace atomica
+// la lectura del valor anterior (insert devuelve su tamaño, remove_if evalua la condicion).
+// Los errores de disco de una lectura o escritura se devuelven a quien la hizo; los del hilo
+// de fondo (volcado, compactacion) se registran y se reintentan en la siguiente vuelta.
+// Las estadisticas de acceso (LRU/LFU) solo se conservan mientras la clave esta en un memtable.
+// El manifiesto guarda cuantas claves y bytes hay en las tablas, asi que abrir no las recorre;
+// cada tabla guarda ademas sus claves con expiracion para purgarlas sin leerla entera.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use crossbeam_skiplist::SkipMap;
+use tracing::{debug, error, info, warn};
+use crate::compression::{Codec, Compressed};
+use crate::config::FsyncPolicy;
+use crate::keyspace::{Entry, Totals};
+use crate::value::Value;
+use self::cache::BlockCache;
+use self::sstable::{Table, TableBuilder};
+use self::wal::Wal;
+
+mod bloom;
+mod cache;
+mod sstable;
+mod wal;
+
+const MANIFEST: &str = "MANIFEST";
+const MANIFEST_HEADER: &str = "nanodb-lsm 1";
+const MAX_LEVELS: usize = 7;
+// Cada nivel puede ser este factor mas grande que el anterior
+const LEVEL_MULTIPLIER: u64 = 10;
+// Memtables esperando volcado; con mas, el escritor que congela vuelca el mismo
+const MAX_FROZEN: usize = 4;
+// Tags de tipo extra para valores comprimidos (los demas son los de Value::encode)
+const TYPE_LZ4: u8 = 16;
+const TYPE_ZSTD: u8 = 17;
+// Bytes contabilizados por registro en el memtable ademas de clave y valor
+const RECORD_OVERHEAD: usize = 32;
+
+// Parametros del motor
+#[derive(Debug, Clone)]
+pub(crate) struct LsmOptions {
+    pub(crate) memtable_size: usize,    // Bytes del memtable antes de congelarlo
+    pub(crate) block_size: usize,       // Tamaño objetivo de un bloque de tabla
+    pub(crate) table_size: u64,         // Tamaño objetivo de una tabla compactada
+    pub(crate) cache_size: usize,       // Capacidad de la cache de bloques
+    pub(crate) l0_trigger: usize,       // Tablas en L0 que disparan la compactacion
+    pub(crate) level_base: u64,         // Tamaño maximo de L1
+    pub(crate) fsync: FsyncPolicy,
+}
+
+impl Default for LsmOptions {
+    fn default() -> Self {
+        LsmOptions {
+            memtable_size: 4 * 1024 * 1024,
+            block_size: 4 * 1024,
+            table_size: 2 * 1024 * 1024,
+            cache_size: 8 * 1024 * 1024,
+            l0_trigger: 4,
+            level_base: 16 * 1024 * 1024,
+            fsync: FsyncPolicy::default(),
+        }
+    }
+}
+
+// Valor de una clave tal como se guarda en disco (Entry sin estadisticas de acceso)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Record {
+    pub(crate) value: Value,
+    pub(crate) expires_at: Option<u64>,
+    pub(crate) version: u64,
+}
+
+impl Record {
+    fn parts(&self) -> Parts<'_> {
+        (&self.value, self.expires_at, self.version)
+    }
+
+    fn into_entry(self) -> Entry {
+        Entry::new(self.value, self.expires_at, self.version, 0)
+    }
+}
+
+// Valor, expiracion y version de un registro, para codificarlo sin copiarlo
+pub(crate) type Parts<'a> = (&'a Value, Option<u64>, u64);
+
+fn entry_parts(entry: &Entry) -> Parts<'_> {
+    (&entry.value, entry.expires_at, entry.version)
+}
+
+fn copy_entry(entry: &Entry) -> Entry {
+    entry.with_value(entry.value.clone(), entry.expires_at, entry.version)
+}
+
+// None = la clave fue borrada (tapa las versiones de tablas mas viejas)
+type Memtable = SkipMap<String, Option<Entry>>;
+
+struct Frozen {
+    memtable: Arc<Memtable>,
+    wal: u64,
+    stats: Stats,       // Cambios que hizo el memtable
+}
+
+// Claves, bytes (clave + valor) y version mas alta. Para las tablas son totales (se guardan
+// en el manifiesto); para un memtable, lo que cambio mientras estaba activo.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Stats {
+    keys: i64,
+    bytes: i64,
+    version: u64,
+}
+
+impl Stats {
+    // Cuenta el reemplazo de un valor de `old` bytes (None = no existia) por `new` (None = borrado)
+    fn change(&mut self, key: &str, old: Option<usize>, new: Option<(usize, u64)>) {
+        let size = |value: usize| (key.len() + value) as i64;
+        self.keys += i64::from(new.is_some()) - i64::from(old.is_some());
+        self.bytes += new.map_or(0, |(value, _)| size(value)) - old.map_or(0, size);
+        self.version = self.version.max(new.map_or(0, |(_, version)| version));
+    }
+
+    fn add(&mut self, other: Stats) {
+        self.keys += other.keys;
+        self.bytes += other.bytes;
+        self.version = self.version.max(other.version);
+    }
+}
+
+// Estado visible para las lecturas; se reemplaza (copy-on-write) en cada cambio
+#[derive(Clone)]
+struct State {
+    active: Arc<Memtable>,
+    frozen: Arc<Vec<Frozen>>,                   // Del mas nuevo al mas viejo
+    levels: Arc<Vec<Vec<Arc<Table>>>>,          // L0: de la mas nueva a la mas vieja; resto: por clave
+    stats: Stats,                               // De las tablas
+}
+
+impl State {
+    fn memtables(&self) -> impl Iterator<Item = &Arc<Memtable>> {
+        std::iter::once(&self.active).chain(self.frozen.iter().map(|frozen| &frozen.memtable))
+    }
+}
+
+struct Inner {
+    dir: PathBuf,
+    options: LsmOptions,
+    state: RwLock<State>,
+    wal: Mutex<Wal>,                    // Serializa las escrituras
+    maintenance: Mutex<Vec<String>>,    // Volcados, compactaciones y clear; ultima clave compactada por nivel
+    active_size: AtomicUsize,
+    active_stats: Mutex<Stats>,         // Cambios del memtable activo (bajo el lock del WAL)
+    len: AtomicUsize,
+    purged_until: AtomicU64,            // Las expiraciones hasta aqui ya se purgaron de las tablas
+    next_id: AtomicU64,                 // Ids de tablas y logs (no se reutilizan)
+    wal_start: AtomicU64,               // Logs anteriores a este id ya estan en tablas
+    cache: BlockCache,
+    // Fallo del volcado que hizo una escritura al llenar el memtable (ver take_error)
+    error: Mutex<Option<io::Error>>,
+    signals: Mutex<mpsc::Sender<Signal>>,
+}
+
+enum Signal {
+    Work,
+    Stop,
+}
+
+pub(crate) struct Lsm {
+    inner: Arc<Inner>,
+    worker: Option<JoinHandle<()>>,
+    temporary: bool,
+}
+
+impl Lsm {
+    // Abre (o crea) el motor en el directorio; recupera los logs que no llegaron a volcarse
+    pub(crate) fn open(dir: &Path, options: LsmOptions) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let manifest = Manifest::read(dir)?;
+        let mut levels: Vec<Vec<Arc<Table>>> = vec![Vec::new(); MAX_LEVELS];
+        for &(level, id) in &manifest.tables {
+            let table = Table::open(&table_path(dir, id), id)?;
+            levels.get_mut(level).ok_or_else(|| invalid("invalid level in lsm manifest"))?.push(Arc::new(table));
+        }
+        levels[0].sort_by_key(|table| std::cmp::Reverse(table.id));
+        for level in levels.iter_mut().skip(1) {
+            level.sort_by(|a, b| a.min_key().cmp(b.min_key()));
+        }
+
+        // Archivos que no figuran en el manifiesto: tablas a medio escribir o logs ya volcados
+        let mut next_id = manifest.next;
+        let mut logs = Vec::new();
+        for file in fs::read_dir(dir)? {
+            let path = file?.path();
+            let Some((id, extension)) = file_id(&path) else { continue };
+            next_id = next_id.max(id + 1);
+            match extension {
+                "wal" if id >= manifest.wal => logs.push(id),
+                "sst" if manifest.tables.iter().any(|&(_, table)| table == id) => {}
+                "wal" | "sst" => fs::remove_file(&path)?,
+                _ => {}
+            }
+        }
+        logs.sort_unstable();
+
+        let (signals, receiver) = mpsc::channel();
+        let wal_id = next_id;
+        let inner = Arc::new(Inner {
+            dir: dir.to_path_buf(),
+            wal: Mutex::new(Wal::create(&wal_path(dir, wal_id), wal_id, options.fsync)?),
+            state: RwLock::new(State {
+                active: Arc::new(SkipMap::new()),
+                frozen: Arc::new(Vec::new()),
+                levels: Arc::new(levels),
+                stats: manifest.stats,
+            }),
+            maintenance: Mutex::new(vec![String::new(); MAX_LEVELS]),
+            active_size: AtomicUsize::new(0),
+            active_stats: Mutex::new(Stats::default()),
+            len: AtomicUsize::new(0),
+            purged_until: AtomicU64::new(0),
+            next_id: AtomicU64::new(wal_id + 1),
+            wal_start: AtomicU64::new(manifest.wal),
+            cache: BlockCache::new(options.cache_size),
+            error: Mutex::new(None),
+            signals: Mutex::new(signals),
+            options,
+        });
+        inner.recover(&logs)?;
+        let len = inner.view().stats.keys.max(0) as usize;
+        inner.len.store(len, Ordering::Relaxed);
+        info!(dir = %dir.display(), keys = len, tables = inner.table_count(), "Lsm storage opened");
+
+        let worker = {
+            let inner = inner.clone();
+            std::thread::spawn(move || inner.run(receiver))
+        };
+        Ok(Lsm { inner, worker: Some(worker), temporary: false })
+    }
+
+    // Motor en un directorio temporal que se borra al cerrarlo
+    pub(crate) fn temporary(options: LsmOptions) -> io::Result<Self> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "nanodb-lsm-{}-{}-{}",
+            std::process::id(),
+            crate::storage::now_millis(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut lsm = Self::open(&dir, options)?;
+        lsm.temporary = true;
+        Ok(lsm)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.inner.len.load(Ordering::Relaxed)
+    }
+
+    // Claves, bytes (clave + valor) y version mas alta de todos los datos
+    pub(crate) fn totals(&self) -> Totals {
+        let wal = self.inner.wal();
+        let state = self.inner.view();
+        let mut stats = state.stats;
+        for frozen in state.frozen.iter() {
+            stats.add(frozen.stats);
+        }
+        stats.add(*self.inner.active_stats());
+        drop(wal);
+        Totals { keys: stats.keys.max(0) as usize, bytes: stats.bytes.max(0) as u64, version: stats.version }
+    }
+
+    pub(crate) fn get<R>(&self, key: &str, read: impl FnOnce(&Entry) -> R) -> io::Result<Option<R>> {
+        let state = self.inner.view();
+        self.inner.find(&state, key, |entry| entry.map(read))
+    }
+
+    // Inserta o reemplaza; devuelve el tamaño del valor anterior
+    pub(crate) fn insert(&self, key: String, entry: Entry) -> io::Result<Option<usize>> {
+        self.inner.mutate(&key, |old| (Some(Some(entry)), old.map(|old| old.value.size())))
+    }
+
+    pub(crate) fn remove(&self, key: &str) -> io::Result<Option<usize>> {
+        self.remove_if(key, |_| true)
+    }
+
+    pub(crate) fn remove_if(&self, key: &str, condition: impl FnOnce(&Entry) -> bool) -> io::Result<Option<usize>> {
+        self.inner.mutate(key, |old| match old {
+            Some(old) if condition(old) => (Some(None), Some(old.value.size())),
+            _ => (None, None),
+        })
+    }
+
+    pub(crate) fn set_expiry(&self, key: &str, expires_at: Option<u64>, version: u64) -> io::Result<()> {
+        self.inner.mutate(key, |old| (old.map(|old| Some(old.with_expiry(expires_at, version))), ()))
+    }
+
+    // Igual que Keyspace::update: modifica una copia del valor (o uno nuevo con `init`)
+    pub(crate) fn update(
+        &self,
+        key: &str,
+        version: u64,
+        clock: u64,
+        init: impl FnOnce() -> Value,
+        change: impl FnOnce(&mut Value),
+    ) -> io::Result<(Option<usize>, usize)> {
+        self.inner.mutate(key, |old| {
+            let entry = match old {
+                Some(old) => {
+                    let mut value = old.value.clone();
+                    change(&mut value);
+                    old.with_value(value, old.expires_at, version)
+                }
+                None => {
+                    let mut value = init();
+                    change(&mut value);
+                    Entry::new(value, None, version, clock)
+                }
+            };
+            let sizes = (old.map(|old| old.value.size()), entry.value.size());
+            (Some(Some(entry)), sizes)
+        })
+    }
+
+    // Borra las claves expiradas sin recorrer las tablas: los candidatos salen de los memtables
+    // y de las claves de cada tabla que vencieron desde la purga anterior. Las que vencen con
+    // una fecha ya purgada (ej. una escritura con la expiracion en el pasado) se borran al leerlas.
+    pub(crate) fn purge_expired(&self, now: u64, mut removed: impl FnMut(&str, usize)) -> io::Result<()> {
+        let after = self.inner.purged_until.load(Ordering::Relaxed);
+        let state = self.inner.view();
+        let mut candidates: BTreeSet<String> = BTreeSet::new();
+        for memtable in state.memtables() {
+            let expired = memtable.iter().filter(|slot| slot.value().as_ref().is_some_and(|entry| entry.is_expired(now)));
+            candidates.extend(expired.map(|slot| slot.key().clone()));
+        }
+        for table in state.levels.iter().flatten() {
+            candidates.extend(table.expiring(after, now).map(str::to_string));
+        }
+        for key in candidates {
+            if let Some(size) = self.remove_if(&key, |entry| entry.is_expired(now))? {
+                removed(&key, size);
+            }
+        }
+        self.inner.purged_until.fetch_max(now, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub(crate) fn for_each(&self, mut visit: impl FnMut(&str, &Entry)) -> io::Result<()> {
+        let error = ReadError::default();
+        for (key, entry) in self.inner.scan(Bound::Unbounded, Bound::Unbounded, false, &error) {
+            visit(&key, &entry);
+        }
+        error.into_result()
+    }
+
+    // Mismo contrato que Keyspace::range
+    pub(crate) fn range<R>(
+        &self,
+        lower: Bound<&str>,
+        upper: Bound<&str>,
+        reverse: bool,
+        limit: usize,
+        mut visit: impl FnMut(&str, &Entry) -> Option<R>,
+    ) -> io::Result<Vec<(String, R)>> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let error = ReadError::default();
+        let found = self
+            .inner
+            .scan(lower.map(str::to_string), upper.map(str::to_string), reverse, &error)
+            .filter_map(|(key, entry)| visit(&key, &entry).map(|r| (key, r)))
+            .take(limit)
+            .collect();
+        error.into_result()?;
+        Ok(found)
+    }
+
+    // Borra todo: tablas y logs se descartan enteros en lugar de escribir un borrado por clave
+    pub(crate) fn clear(&self) -> io::Result<()> {
+        self.inner.clear()
+    }
+
+    // Error del congelado o volcado que hizo la ultima escritura (el valor ya esta en el WAL)
+    pub(crate) fn take_error(&self) -> Option<io::Error> {
+        self.inner.error.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+impl Drop for Lsm {
+    fn drop(&mut self) {
+        let _ = self.inner.signal(Signal::Stop);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        if let Err(e) = self.inner.wal().sync() {
+            warn!(error = %e, "Failed to sync lsm write-ahead log");
+        }
+        let (hits, misses) = self.inner.cache.stats();
+        debug!(dir = %self.inner.dir.display(), cache_hits = hits, cache_misses = misses, "Lsm storage closed");
+        if self.temporary {
+            let _ = fs::remove_dir_all(&self.inner.dir);
+        }
+    }
+}
+
+impl Inner {
+    fn view(&self) -> State {
+        self.state.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn wal(&self) -> MutexGuard<'_, Wal> {
+        self.wal.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn active_stats(&self) -> MutexGuard<'_, Stats> {
+        self.active_stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn signal(&self, signal: Signal) -> Result<(), mpsc::SendError<Signal>> {
+        self.signals.lock().unwrap_or_else(|e| e.into_inner()).send(signal)
+    }
+
+    fn fail(&self, e: &io::Error) {
+        error!(error = %e, dir = %self.dir.display(), "Lsm storage error");
+    }
+
+    fn table_count(&self) -> usize {
+        self.view().levels.iter().map(Vec::len).sum()
+    }
+
+    // Busca la version mas nueva de la clave
+    fn find<R>(&self, state: &State, key: &str, read: impl FnOnce(Option<&Entry>) -> R) -> io::Result<R> {
+        for memtable in state.memtables() {
+            if let Some(slot) = memtable.get(key) {
+                return Ok(read(slot.value().as_ref()));
+            }
+        }
+        for (level, tables) in state.levels.iter().enumerate() {
+            // Desde L1 las tablas no se solapan: a lo sumo una puede tener la clave
+            let candidates: &[Arc<Table>] = if level == 0 {
+                tables
+            } else {
+                let i = tables.partition_point(|table| table.max_key() < key);
+                &tables[i..tables.len().min(i + 1)]
+            };
+            for table in candidates {
+                if let Some(slot) = table.get(key, &self.cache)? {
+                    return Ok(read(slot.map(Record::into_entry).as_ref()));
+                }
+            }
+        }
+        Ok(read(None))
+    }
+
+    // Lee el valor actual y aplica el cambio que devuelve `change` (None = no escribir nada).
+    // Si la lectura o el WAL fallan no se aplica nada. Si falla el congelado la escritura
+    // ya esta en el WAL: el error queda para take_error y se reintenta con la siguiente.
+    fn mutate<R>(&self, key: &str, change: impl FnOnce(Option<&Entry>) -> (Option<Option<Entry>>, R)) -> io::Result<R> {
+        let (result, full) = {
+            let mut wal = self.wal();
+            // Con el lock del WAL tomado nadie puede congelar el memtable activo
+            let state = self.view();
+            let mut old = None;
+            let (write, result) = self.find(&state, key, |current| {
+                old = current.map(|entry| entry.value.size());
+                change(current)
+            })?;
+            let Some(slot) = write else { return Ok(result) };
+            wal.append(key, slot.as_ref().map(entry_parts))?;
+            self.active_stats().change(key, old, slot.as_ref().map(|entry| (entry.value.size(), entry.version)));
+            match (old.is_some(), slot.is_some()) {
+                (false, true) => {
+                    self.len.fetch_add(1, Ordering::Relaxed);
+                }
+                (true, false) => {
+                    self.len.fetch_sub(1, Ordering::Relaxed);
+                }
+                _ => {}
+            }
+            let size = key.len() + slot.as_ref().map_or(0, |entry| entry.value.size()) + RECORD_OVERHEAD;
+            state.active.insert(key.to_string(), slot);
+            (result, self.active_size.fetch_add(size, Ordering::Relaxed) + size >= self.options.memtable_size)
+        };
+        if full {
+            if let Err(e) = self.freeze() {
+                self.fail(&e);
+                *self.error.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+            }
+        }
+        Ok(result)
+    }
+
+    // Congela el memtable activo y empieza uno nuevo con su propio log
+    fn freeze(&self) -> io::Result<()> {
+        let frozen = {
+            let mut wal = self.wal();
+            if self.active_size.load(Ordering::Relaxed) < self.options.memtable_size {
+                return Ok(()); // Otro escritor ya lo congelo
+            }
+            let id = self.next_id();
+            let mut old = std::mem::replace(&mut *wal, Wal::create(&wal_path(&self.dir, id), id, self.options.fsync)?);
+            old.sync()?;
+            let stats = std::mem::take(&mut *self.active_stats());
+            let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+            let mut frozen = vec![Frozen { memtable: state.active.clone(), wal: old.id, stats }];
+            frozen.extend(state.frozen.iter().map(|f| Frozen { memtable: f.memtable.clone(), wal: f.wal, stats: f.stats }));
+            let count = frozen.len();
+            state.frozen = Arc::new(frozen);
+            state.active = Arc::new(SkipMap::new());
+            self.active_size.store(0, Ordering::Relaxed);
+            count
+        };
+        debug!(frozen = frozen, "Memtable frozen");
+        if frozen > MAX_FROZEN {
+            // El volcado no da abasto: frenar a este escritor hasta que se ponga al dia
+            let _maintenance = self.maintenance.lock().unwrap_or_else(|e| e.into_inner());
+            self.flush()?;
+        } else {
+            let _ = self.signal(Signal::Work);
+        }
+        Ok(())
+    }
+
+    // Hilo de fondo: fsync periodico, volcado de memtables y compactacion
+    fn run(self: Arc<Self>, signals: mpsc::Receiver<Signal>) {
+        loop {
+            match signals.recv_timeout(Duration::from_secs(1)) {
+                Ok(Signal::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Ok(Signal::Work) | Err(RecvTimeoutError::Timeout) => {}
+            }
+            if self.options.fsync == FsyncPolicy::EverySecond {
+                if let Err(e) = self.wal().sync() {
+                    self.fail(&e);
+                }
+            }
+            if let Err(e) = self.maintain() {
+                self.fail(&e);
+            }
+        }
+    }
+
+    fn maintain(&self) -> io::Result<()> {
+        let mut maintenance = self.maintenance.lock().unwrap_or_else(|e| e.into_inner());
+        self.flush()?;
+        while self.compact(&mut maintenance)? {}
+        Ok(())
+    }
+
+    // Vuelca los memtables congelados (del mas viejo al mas nuevo) a tablas de L0.
+    // Se llama con el lock de mantenimiento tomado.
+    fn flush(&self) -> io::Result<()> {
+        loop {
+            let state = self.view();
+            let Some(oldest) = state.frozen.last() else { return Ok(()) };
+            let id = self.next_id();
+            let mut builder = TableBuilder::create(table_path(&self.dir, id), id, self.options.block_size)?;
+            for entry in oldest.memtable.iter() {
+                builder.add(entry.key(), entry.value().as_ref().map(entry_parts))?;
+            }
+            let table = match builder.is_empty() {
+                true => {
+                    builder.abandon()?;
+                    None
+                }
+                false => Some(Arc::new(builder.finish()?)),
+            };
+            let (levels, stats) = {
+                let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+                let mut levels = (*state.levels).clone();
+                if let Some(table) = &table {
+                    levels[0].insert(0, table.clone());
+                }
+                let frozen = state.frozen.iter().filter(|f| f.wal != oldest.wal);
+                state.frozen = Arc::new(frozen.map(|f| Frozen { memtable: f.memtable.clone(), wal: f.wal, stats: f.stats }).collect());
+                state.levels = Arc::new(levels);
+                state.stats.add(oldest.stats);
+                (state.levels.clone(), state.stats)
+            };
+            self.wal_start.store(oldest.wal + 1, Ordering::Relaxed);
+            self.write_manifest(&levels, stats)?;
+            fs::remove_file(wal_path(&self.dir, oldest.wal))?;
+            debug!(table = id, entries = table.as_ref().map_or(0, |t| t.entries), "Memtable flushed");
+        }
+    }
+
+    // Una compactacion si algun nivel la necesita; false si no habia nada que hacer
+    fn compact(&self, cursors: &mut [String]) -> io::Result<bool> {
+        let levels = self.view().levels;
+        let (level, inputs): (usize, Vec<Arc<Table>>) = if levels[0].len() >= self.options.l0_trigger {
+            (0, levels[0].clone())
+        } else {
+            let over = (1..MAX_LEVELS - 1).find(|&level| {
+                let limit = self.options.level_base.saturating_mul(LEVEL_MULTIPLIER.saturating_pow(level as u32 - 1));
+                levels[level].iter().map(|table| table.size).sum::<u64>() > limit
+            });
+            let Some(level) = over else { return Ok(false) };
+            // Rotar por el nivel: la primera tabla despues de la ultima compactada
+            let tables = &levels[level];
+            let i = tables.iter().position(|table| table.min_key() > cursors[level].as_str()).unwrap_or(0);
+            cursors[level] = tables[i].max_key().to_string();
+            (level, vec![tables[i].clone()])
+        };
+        let target = level + 1;
+        let lower = inputs.iter().map(|table| table.min_key()).min().unwrap_or_default().to_string();
+        let upper = inputs.iter().map(|table| table.max_key()).max().unwrap_or_default().to_string();
+        let overlapping: Vec<Arc<Table>> = levels[target].iter().filter(|table| table.overlaps(&lower, &upper)).cloned().collect();
+        // Sin datos mas abajo, los borrados ya no tapan nada y se descartan
+        let bottom = levels[target + 1..].iter().all(Vec::is_empty);
+
+        let error = ReadError::default();
+        let mut sources: Vec<Source<'_, Option<Record>>> = inputs.iter().map(|table| self.table_source(table, Bound::Unbounded, Bound::Unbounded, false, &error)).collect();
+        let older: Vec<Source<'_, Option<Record>>> = overlapping.iter().map(|table| self.table_source(table, Bound::Unbounded, Bound::Unbounded, false, &error)).collect();
+        sources.push(Box::new(older.into_iter().flatten()));
+        let mut outputs: Vec<Arc<Table>> = Vec::new();
+        let mut builder: Option<TableBuilder> = None;
+        for (key, slot) in Merge::new(sources, false) {
+            if slot.is_none() && bottom {
+                continue;
+            }
+            let current = match &mut builder {
+                Some(builder) => builder,
+                None => {
+                    let id = self.next_id();
+                    builder.insert(TableBuilder::create(table_path(&self.dir, id), id, self.options.block_size)?)
+                }
+            };
+            current.add(&key, slot.as_ref().map(Record::parts))?;
+            if current.size() >= self.options.table_size {
+                outputs.push(Arc::new(builder.take().unwrap().finish()?));
+            }
+        }
+        if let Some(builder) = builder {
+            outputs.push(Arc::new(builder.finish()?));
+        }
+        // Un error de lectura corta la mezcla: no reemplazar las tablas con un resultado parcial
+        if let Err(e) = error.into_result() {
+            for table in &outputs {
+                let _ = fs::remove_file(table.path());
+            }
+            return Err(e);
+        }
+
+        let removed: Vec<u64> = inputs.iter().chain(&overlapping).map(|table| table.id).collect();
+        let (levels, stats) = {
+            let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+            let mut levels = (*state.levels).clone();
+            levels[level].retain(|table| !removed.contains(&table.id));
+            levels[target].retain(|table| !removed.contains(&table.id));
+            levels[target].extend(outputs.iter().cloned());
+            levels[target].sort_by(|a, b| a.min_key().cmp(b.min_key()));
+            state.levels = Arc::new(levels);
+            (state.levels.clone(), state.stats)
+        };
+        self.write_manifest(&levels, stats)?;
+        for table in inputs.iter().chain(&overlapping) {
+            fs::remove_file(table.path())?;
+            self.cache.evict_table(table.id);
+        }
+        debug!(level = level, inputs = removed.len(), outputs = outputs.len(), "Lsm tables compacted");
+        Ok(true)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        let _maintenance = self.maintenance.lock().unwrap_or_else(|e| e.into_inner());
+        let mut wal = self.wal();
+        let id = self.next_id();
+        let old = std::mem::replace(&mut *wal, Wal::create(&wal_path(&self.dir, id), id, self.options.fsync)?);
+        // Se conserva la version mas alta: al reabrir las versiones siguen creciendo
+        let active = std::mem::take(&mut *self.active_stats());
+        let (old_state, stats) = {
+            let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+            let version = state.frozen.iter().map(|f| f.stats.version).chain([state.stats.version, active.version]).max().unwrap_or(0);
+            let stats = Stats { version, ..Stats::default() };
+            let old_state = std::mem::replace(
+                &mut *state,
+                State {
+                    active: Arc::new(SkipMap::new()),
+                    frozen: Arc::new(Vec::new()),
+                    levels: Arc::new(vec![Vec::new(); MAX_LEVELS]),
+                    stats,
+                },
+            );
+            (old_state, stats)
+        };
+        self.wal_start.store(id, Ordering::Relaxed);
+        self.write_manifest(&[], stats)?;
+        self.len.store(0, Ordering::Relaxed);
+        self.active_size.store(0, Ordering::Relaxed);
+        drop(wal);
+        for log in std::iter::once(old.id).chain(old_state.frozen.iter().map(|frozen| frozen.wal)) {
+            fs::remove_file(wal_path(&self.dir, log))?;
+        }
+        for table in old_state.levels.iter().flatten() {
+            fs::remove_file(table.path())?;
+            self.cache.evict_table(table.id);
+        }
+        Ok(())
+    }
+
+    // Aplica los logs que quedaron sin volcar y los pasa a una tabla de L0.
+    // Los totales del manifiesto se ajustan buscando el valor anterior de cada clave.
+    fn recover(&self, logs: &[u64]) -> io::Result<()> {
+        let mut records: BTreeMap<String, Option<Record>> = BTreeMap::new();
+        for &log in logs {
+            records.extend(wal::replay(&wal_path(&self.dir, log))?);
+        }
+        if !records.is_empty() {
+            let mut stats = Stats::default();
+            let before = self.view();
+            let id = self.next_id();
+            let mut builder = TableBuilder::create(table_path(&self.dir, id), id, self.options.block_size)?;
+            for (key, slot) in &records {
+                let old = self.find(&before, key, |old| old.map(|entry| entry.value.size()))?;
+                stats.change(key, old, slot.as_ref().map(|record| (record.value.size(), record.version)));
+                builder.add(key, slot.as_ref().map(Record::parts))?;
+            }
+            let table = Arc::new(builder.finish()?);
+            let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+            let mut levels = (*state.levels).clone();
+            levels[0].insert(0, table);
+            state.levels = Arc::new(levels);
+            state.stats.add(stats);
+        }
+        self.wal_start.store(self.wal().id, Ordering::Relaxed);
+        let state = self.view();
+        self.write_manifest(&state.levels, state.stats)?;
+        for &log in logs {
+            fs::remove_file(wal_path(&self.dir, log))?;
+        }
+        if !logs.is_empty() {
+            info!(dir = %self.dir.display(), records = records.len(), logs = logs.len(), "Lsm write-ahead logs recovered");
+        }
+        Ok(())
+    }
+
+    // Reemplazo atomico del manifiesto (archivo temporal + rename)
+    fn write_manifest(&self, levels: &[Vec<Arc<Table>>], stats: Stats) -> io::Result<()> {
+        let mut text = format!(
+            "{}\nnext {}\nwal {}\nstats {} {} {}\n",
+            MANIFEST_HEADER,
+            self.next_id.load(Ordering::Relaxed),
+            self.wal_start.load(Ordering::Relaxed),
+            stats.keys.max(0),
+            stats.bytes.max(0),
+            stats.version
+        );
+        for (level, tables) in levels.iter().enumerate() {
+            for table in tables {
+                text.push_str(&format!("table {} {}\n", level, table.id));
+            }
+        }
+        let tmp = self.dir.join(format!("{}.tmp", MANIFEST));
+        {
+            let mut file = fs::File::create(&tmp)?;
+            io::Write::write_all(&mut file, text.as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, self.dir.join(MANIFEST))?;
+        #[cfg(unix)]
+        fs::File::open(&self.dir)?.sync_all()?;
+        Ok(())
+    }
+
+    // Entradas vivas dentro de los limites, mezclando memtables y tablas.
+    // Un error de lectura termina la iteracion y queda en `error`.
+    fn scan<'a>(&'a self, lower: Bound<String>, upper: Bound<String>, reverse: bool, error: &'a ReadError) -> impl Iterator<Item = (String, Entry)> + 'a {
+        let state = self.view();
+        let mut sources: Vec<Source<'_, Option<Entry>>> = Vec::new();
+        for memtable in state.memtables() {
+            // Copia del rango: el memtable es chico y la copia no retiene el skiplist
+            let range = memtable.range::<str, _>((bound_ref(&lower), bound_ref(&upper)));
+            let copy = |entry: crossbeam_skiplist::map::Entry<'_, String, Option<Entry>>| {
+                (entry.key().clone(), entry.value().as_ref().map(copy_entry))
+            };
+            let items: Vec<(String, Option<Entry>)> = if reverse { range.rev().map(copy).collect() } else { range.map(copy).collect() };
+            sources.push(Box::new(items.into_iter()));
+        }
+        for (level, tables) in state.levels.iter().enumerate() {
+            let mut tables: Vec<Source<'_, Option<Record>>> = tables
+                .iter()
+                .map(|table| self.table_source(table, lower.clone(), upper.clone(), reverse, error))
+                .collect();
+            if level == 0 {
+                sources.extend(tables.into_iter().map(into_entries));
+            } else {
+                // Tablas sin solapamiento: una sola fuente en orden de clave
+                if reverse {
+                    tables.reverse();
+                }
+                sources.push(into_entries(Box::new(tables.into_iter().flatten())));
+            }
+        }
+        Merge::new(sources, reverse).filter_map(|(key, slot)| slot.map(|entry| (key, entry)))
+    }
+
+    // Registros de una tabla; un error de lectura queda en `error` y termina la iteracion
+    fn table_source<'a>(&'a self, table: &Arc<Table>, lower: Bound<String>, upper: Bound<String>, reverse: bool, error: &'a ReadError) -> Source<'a, Option<Record>> {
+        if !overlaps_bounds(table, &lower, &upper) {
+            return Box::new(std::iter::empty());
+        }
+        Box::new(table.iter(&self.cache, lower, upper, reverse).map_while(move |item| match item {
+            Ok(item) => Some(item),
+            Err(e) => {
+                error.set(e);
+                None
+            }
+        }))
+    }
+}
+
+// Primer error de lectura de un recorrido; las fuentes de la mezcla lo comparten
+#[derive(Default)]
+struct ReadError(Mutex<Option<io::Error>>);
+
+impl ReadError {
+    fn set(&self, e: io::Error) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(e);
+    }
+
+    fn into_result(self) -> io::Result<()> {
+        match self.0.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+type Source<'a, T> = Box<dyn Iterator<Item = (String, T)> + Send + 'a>;
+
+fn into_entries(source: Source<'_, Option<Record>>) -> Source<'_, Option<Entry>> {
+    Box::new(source.map(|(key, slot)| (key, slot.map(Record::into_entry))))
+}
+
+fn overlaps_bounds(table: &Table, lower: &Bound<String>, upper: &Bound<String>) -> bool {
+    let below = match lower {
+        Bound::Included(key) => table.max_key() < key.as_str(),
+        Bound::Excluded(key) => table.max_key() <= key.as_str(),
+        Bound::Unbounded => false,
+    };
+    let above = match upper {
+        Bound::Included(key) => table.min_key() > key.as_str(),
+        Bound::Excluded(key) => table.min_key() >= key.as_str(),
+        Bound::Unbounded => false,
+    };
+    !below && !above
+}
+
+fn bound_ref(bound: &Bound<String>) -> Bound<&str> {
+    bound.as_ref().map(String::as_str)
+}
+
+// Mezcla de fuentes ordenadas; con claves repetidas gana la fuente de menor indice (la mas nueva)
+struct Merge<'a, T> {
+    sources: Vec<Source<'a, T>>,
+    heads: Vec<Option<(String, T)>>,
+    reverse: bool,
+}
+
+impl<'a, T> Merge<'a, T> {
+    fn new(mut sources: Vec<Source<'a, T>>, reverse: bool) -> Self {
+        let heads = sources.iter_mut().map(|source| source.next()).collect();
+        Merge { sources, heads, reverse }
+    }
+}
+
+impl<T> Iterator for Merge<'_, T> {
+    type Item = (String, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut best: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some((key, _)) = head else { continue };
+            let better = match best.and_then(|b| self.heads[b].as_ref()) {
+                None => true,
+                Some((best_key, _)) if self.reverse => key > best_key,
+                Some((best_key, _)) => key < best_key,
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        let best = best?;
+        let item = std::mem::replace(&mut self.heads[best], self.sources[best].next())?;
+        // Versiones mas viejas de la misma clave
+        for i in 0..self.heads.len() {
+            while matches!(&self.heads[i], Some((key, _)) if *key == item.0) {
+                self.heads[i] = self.sources[i].next();
+            }
+        }
+        Some(item)
+    }
+}
+
+// Tablas vivas, sus totales y desde que log hay que reproducir al abrir
+struct Manifest {
+    next: u64,
+    wal: u64,
+    stats: Stats,
+    tables: Vec<(usize, u64)>,
+}
+
+impl Manifest {
+    fn read(dir: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(dir.join(MANIFEST)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Manifest { next: 1, wal: 0, stats: Stats::default(), tables: Vec::new() }),
+            Err(e) => return Err(e),
+        };
+        let mut lines = text.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(invalid("not a NanoDb lsm manifest"));
+        }
+        let mut manifest = Manifest { next: 1, wal: 0, stats: Stats::default(), tables: Vec::new() };
+        for line in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| fields.get(i).and_then(|field| field.parse::<u64>().ok()).ok_or_else(|| invalid("corrupted lsm manifest"));
+            match fields.first() {
+                Some(&"next") => manifest.next = number(1)?,
+                Some(&"wal") => manifest.wal = number(1)?,
+                Some(&"stats") => manifest.stats = Stats { keys: number(1)? as i64, bytes: number(2)? as i64, version: number(3)? },
+                Some(&"table") => manifest.tables.push((number(1)? as usize, number(2)?)),
+                _ => return Err(invalid("corrupted lsm manifest")),
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+fn table_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:08}.sst", id))
+}
+
+fn wal_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:08}.wal", id))
+}
+
+// Id y extension de un archivo del motor ("00000012.sst" -> (12, "sst"))
+fn file_id(path: &Path) -> Option<(u64, &str)> {
+    let name = path.file_name()?.to_str()?;
+    let (stem, extension) = name.split_once('.')?;
+    Some((stem.parse().ok()?, extension))
+}
+
+// Formato de un registro (big-endian), en el WAL y en los bloques de las tablas:
+//   key_len (4) | key | kind (1: 0 = borrado, 1 = valor) | [version (8) | expires_at (8, 0 = sin expiracion) |
+//   type (1) | value_len (4) | value]
+// Los valores comprimidos se guardan comprimidos: raw_len (8) | datos.
+pub(crate) fn encode_record(out: &mut Vec<u8>, key: &str, slot: Option<Parts<'_>>) {
+    out.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    out.extend_from_slice(key.as_bytes());
+    let Some((value, expires_at, version)) = slot else {
+        out.push(0);
+        return;
+    };
+    out.push(1);
+    out.extend_from_slice(&version.to_be_bytes());
+    out.extend_from_slice(&expires_at.unwrap_or(0).to_be_bytes());
+    let (tag, body) = match value {
+        Value::Compressed(compressed) => {
+            let tag = match compressed.codec() {
+                Codec::Lz4 => TYPE_LZ4,
+                Codec::Zstd => TYPE_ZSTD,
+            };
+            (tag, [&(compressed.raw_len() as u64).to_be_bytes()[..], compressed.data()].concat())
+        }
+        value => value.encode(),
+    };
+    out.push(tag);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(&body);
+}
+
+pub(crate) fn decode_record(buf: &[u8], pos: &mut usize) -> io::Result<(String, Option<Record>)> {
+    let key = take_string(buf, pos)?;
+    if take(buf, pos, 1)?[0] == 0 {
+        return Ok((key, None));
+    }
+    let version = take_u64(buf, pos)?;
+    let expires_at = Some(take_u64(buf, pos)?).filter(|&at| at != 0);
+    let tag = take(buf, pos, 1)?[0];
+    let len = take_u32(buf, pos)? as usize;
+    let body = take(buf, pos, len)?;
+    let value = match tag {
+        TYPE_LZ4 | TYPE_ZSTD => {
+            let codec = if tag == TYPE_LZ4 { Codec::Lz4 } else { Codec::Zstd };
+            let mut offset = 0;
+            let raw_len = take_u64(body, &mut offset)? as usize;
+            let compressed = Compressed::from_parts(codec, raw_len, body[offset..].to_vec());
+            Value::Compressed(compressed.ok_or_else(|| invalid("corrupt compressed value in lsm storage"))?)
+        }
+        tag => Value::decode(tag, body)?,
+    };
+    Ok((key, Some(Record { value, expires_at, version })))
+}
+
+fn take<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+    let bytes = buf.get(*pos..*pos + len).ok_or_else(|| invalid("truncated lsm record"))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+fn take_u32(buf: &[u8], pos: &mut usize) -> io::Result<u32> {
+    Ok(u32::from_be_bytes(take(buf, pos, 4)?.try_into().unwrap()))
+}
+
+fn take_u64(buf: &[u8], pos: &mut usize) -> io::Result<u64> {
+    Ok(u64::from_be_bytes(take(buf, pos, 8)?.try_into().unwrap()))
+}
+
+fn take_string(buf: &[u8], pos: &mut usize) -> io::Result<String> {
+    let len = take_u32(buf, pos)? as usize;
+    String::from_utf8(take(buf, pos, len)?.to_vec()).map_err(|_| invalid("invalid UTF-8 key in lsm storage"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbConfig, DbResult, NanoDb, StorageEngine};
+
+    // Tamaños minimos para que unas pocas claves generen volcados y compactaciones
+    fn small() -> LsmOptions {
+        LsmOptions {
+            memtable_size: 2 * 1024,
+            block_size: 256,
+            table_size: 4 * 1024,
+            cache_size: 16 * 1024,
+            l0_trigger: 2,
+            level_base: 8 * 1024,
+            fsync: FsyncPolicy::Never,
+        }
+    }
+
+    fn bytes(text: &str, version: u64) -> Entry {
+        Entry::new(Value::Bytes(text.as_bytes().to_vec()), None, version, 0)
+    }
+
+    fn value(lsm: &Lsm, key: &str) -> Option<Vec<u8>> {
+        lsm.get(key, |entry| entry.value.to_bytes()).unwrap().flatten()
+    }
+
+    #[test]
+    fn test_compaction_matches_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let lsm = Lsm::open(dir.path(), small()).unwrap();
+        let mut model = BTreeMap::new();
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        for version in 1..4000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let key = format!("key:{:04}", state % 500);
+            if state.is_multiple_of(5) {
+                assert_eq!(lsm.remove(&key).unwrap().is_some(), model.remove(&key).is_some());
+            } else {
+                let text = format!("v{}", version);
+                lsm.insert(key.clone(), bytes(&text, version)).unwrap();
+                model.insert(key, text.into_bytes());
+            }
+        }
+        lsm.inner.maintain().unwrap();
+        let levels = lsm.inner.view().levels;
+        assert!(levels[0].len() < 2 && levels[1..].iter().any(|level| !level.is_empty()), "{:?}", levels.iter().map(Vec::len).collect::<Vec<_>>());
+
+        assert_eq!(lsm.len(), model.len());
+        for i in 0..500 {
+            let key = format!("key:{:04}", i);
+            assert_eq!(value(&lsm, &key), model.get(&key).cloned(), "{}", key);
+        }
+        let scan = |lower: Bound<&str>, upper: Bound<&str>, reverse| {
+            lsm.range(lower, upper, reverse, 0, |_, entry| entry.value.to_bytes()).unwrap()
+        };
+        let expected: Vec<(String, Vec<u8>)> = model.range::<str, _>((Bound::Included("key:0100"), Bound::Excluded("key:0200"))).map(|(k, v)| (k.clone(), v.clone())).collect();
+        assert_eq!(scan(Bound::Included("key:0100"), Bound::Excluded("key:0200"), false), expected);
+        let mut reversed = expected;
+        reversed.reverse();
+        assert_eq!(scan(Bound::Included("key:0100"), Bound::Excluded("key:0200"), true), reversed);
+        assert_eq!(scan(Bound::Unbounded, Bound::Unbounded, false).len(), model.len());
+        assert!(lsm.inner.cache.stats().0 > 0);
+    }
+
+    #[test]
+    fn test_reopen_recovers_log_and_discards_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let lsm = Lsm::open(dir.path(), small()).unwrap();
+            for i in 0..200 {
+                lsm.insert(format!("k{}", i), bytes("flushed", i)).unwrap();
+            }
+            lsm.inner.maintain().unwrap();
+            lsm.insert("k1".to_string(), Entry::new(Value::Bytes(b"in log".to_vec()), Some(u64::MAX), 500, 0)).unwrap();
+            lsm.remove("k2").unwrap();
+            lsm.update("list", 501, 0, || Value::List(Default::default()), |list| {
+                if let Value::List(items) = list {
+                    items.push_back(b"a".to_vec());
+                }
+            })
+            .unwrap();
+        }
+        // Escritura cortada por un crash al final del log
+        let log = fs::read_dir(dir.path()).unwrap().map(|f| f.unwrap().path()).find(|p| file_id(p).is_some_and(|(_, ext)| ext == "wal") && fs::metadata(p).unwrap().len() > 0).unwrap();
+        let mut torn = fs::read(&log).unwrap();
+        torn.extend_from_slice(&[0, 0, 0, 40, 1, 2, 3]);
+        fs::write(&log, torn).unwrap();
+
+        let lsm = Lsm::open(dir.path(), small()).unwrap();
+        assert_eq!(lsm.len(), 200);
+        // Los totales salen del manifiesto mas lo recuperado de los logs, sin recorrer las tablas
+        let mut scanned = Totals::default();
+        lsm.for_each(|key, entry| {
+            scanned.keys += 1;
+            scanned.bytes += (key.len() + entry.value.size()) as u64;
+        })
+        .unwrap();
+        assert_eq!(lsm.totals(), Totals { version: 501, ..scanned });
+        assert_eq!(lsm.get("k1", |entry| (entry.value.to_bytes(), entry.expires_at, entry.version)).unwrap(), Some((Some(b"in log".to_vec()), Some(u64::MAX), 500)));
+        assert_eq!(value(&lsm, "k2"), None);
+        assert_eq!(value(&lsm, "k3"), Some(b"flushed".to_vec()));
+        assert_eq!(lsm.get("list", |entry| entry.value.type_name()).unwrap(), Some("list"));
+
+        lsm.clear().unwrap();
+        assert_eq!(lsm.len(), 0);
+        assert!(lsm.inner.view().levels.iter().all(Vec::is_empty));
+        drop(lsm);
+        assert_eq!(Lsm::open(dir.path(), small()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_purge_checks_only_expiring_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let lsm = Lsm::open(dir.path(), small()).unwrap();
+        let expiring = |at, version| Entry::new(Value::Bytes(b"x".to_vec()), Some(at), version, 0);
+        for i in 0..100 {
+            lsm.insert(format!("k{:03}", i), bytes("kept", i)).unwrap();
+        }
+        lsm.insert("gone".to_string(), expiring(10, 100)).unwrap();
+        lsm.insert("renewed".to_string(), expiring(10, 101)).unwrap();
+        lsm.insert("later".to_string(), expiring(1_000, 102)).unwrap();
+        lsm.inner.maintain().unwrap();
+        // La version nueva queda en el memtable y tapa la expiracion de la tabla
+        lsm.insert("renewed".to_string(), bytes("fresh", 103)).unwrap();
+        lsm.insert("memtable".to_string(), expiring(20, 104)).unwrap();
+
+        let mut removed = Vec::new();
+        lsm.purge_expired(500, |key, _| removed.push(key.to_string())).unwrap();
+        assert_eq!(removed, vec!["gone", "memtable"]);
+        assert_eq!(value(&lsm, "renewed"), Some(b"fresh".to_vec()));
+        assert_eq!(lsm.len(), 102);
+        // Lo que vencio antes de la purga anterior ya no se vuelve a buscar en las tablas
+        removed.clear();
+        lsm.purge_expired(2_000, |key, _| removed.push(key.to_string())).unwrap();
+        assert_eq!(removed, vec!["later"]);
+    }
+
+    #[test]
+    fn test_read_error_is_returned_to_the_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let lsm = Lsm::open(dir.path(), LsmOptions { cache_size: 0, ..small() }).unwrap();
+        for i in 0..200 {
+            lsm.insert(format!("k{:03}", i), bytes("flushed", i)).unwrap();
+        }
+        lsm.inner.maintain().unwrap();
+        // Se dañan los bloques de todas las tablas
+        for path in fs::read_dir(dir.path()).unwrap().map(|f| f.unwrap().path()) {
+            if file_id(&path).is_some_and(|(_, ext)| ext == "sst") {
+                let mut table = fs::read(&path).unwrap();
+                table[..64].fill(0xff);
+                fs::write(&path, table).unwrap();
+            }
+        }
+        assert!(lsm.get("k000", |_| ()).is_err());
+        assert!(lsm.insert("k000".to_string(), bytes("new", 500)).is_err());
+        assert!(lsm.for_each(|_, _| {}).is_err());
+        // Una escritura que no lee las tablas no hereda el error anterior
+        assert_eq!(lsm.insert("other".to_string(), bytes("fine", 501)).unwrap(), None);
+        assert_eq!(value(&lsm, "other"), Some(b"fine".to_vec()));
+        assert!(lsm.take_error().is_none());
+    }
+
+    #[test]
+    fn test_corrupt_compressed_value_is_an_error() {
+        let raw = b"nanodb ".repeat(64);
+        let value = Value::Compressed(Codec::Zstd.compress(&raw).unwrap());
+        let mut buf = Vec::new();
+        encode_record(&mut buf, "k", Some((&value, None, 1)));
+        assert!(decode_record(&buf, &mut 0).is_ok());
+        // raw_len va despues de clave, marca, version, expiracion, tag y largo del cuerpo
+        let at = 4 + 1 + 1 + 8 + 8 + 1 + 4;
+        buf[at..at + 8].copy_from_slice(&(raw.len() as u64 + 1).to_be_bytes());
+        let err = decode_record(&buf, &mut 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_nanodb_restarts_from_lsm_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            engine: StorageEngine::Lsm,
+            lsm_dir: Some(dir.path().join("data")),
+            ..DbConfig::default()
+        };
+        {
+            let db = NanoDb::with_config(config.clone()).unwrap();
+            for i in 0..100 {
+                db.set(format!("user:{:03}", i), vec![b'x'; 64]).await;
+            }
+            db.list_push("queue", vec![b"a".to_vec(), b"b".to_vec()], false).await;
+            db.incr_by("hits", 5).await;
+            db.create_namespace("tenant");
+            db.namespace("tenant").unwrap().set("inside".to_string(), b"1".to_vec()).await;
+        }
+        let db = NanoDb::with_config(config.clone()).unwrap();
+        assert!(matches!(db.size().await, DbResult::Ok(102)));
+        assert!(db.used_memory() > 100 * 64);
+        // Una escritura nueva no reutiliza versiones anteriores al reinicio
+        let DbResult::Ok(before) = db.version("hits").await else { panic!() };
+        assert!(matches!(db.incr_by("hits", 1).await, DbResult::Ok(6)));
+        assert!(matches!(db.version("hits").await, DbResult::Ok(after) if after > before));
+        assert!(matches!(db.list_range("queue", 0, -1).await, DbResult::Ok(ref items) if items.len() == 2));
+        let DbResult::Ok(keys) = db.keys_page(Some("user:"), Some("user:097"), 0).await else { panic!() };
+        assert_eq!(keys.keys, vec!["user:098", "user:099"]);
+        assert!(matches!(db.namespace("tenant").unwrap().get("inside").await, DbResult::Ok(ref v) if v == b"1"));
+
+        let with_log = DbConfig { aof_path: Some(dir.path().join("db.aof")), ..config };
+        assert!(NanoDb::with_config(with_log).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+// Filtro de bloom por tabla: descarta sin leer disco las tablas que no tienen la clave
+use std::io;
+
+// ~1% de falsos positivos
+const BITS_PER_KEY: usize = 10;
+const HASHES: u32 = 7;
+
+#[derive(Debug)]
+pub(crate) struct Bloom {
+    bits: Vec<u8>,
+    hashes: u32,
+}
+
+impl Bloom {
+    pub(crate) fn build<'a>(keys: impl ExactSizeIterator<Item = &'a str>) -> Self {
+        let len = (keys.len() * BITS_PER_KEY).div_ceil(8).max(8);
+        let mut bloom = Bloom { bits: vec![0; len], hashes: HASHES };
+        for key in keys {
+            for bit in bloom.positions(key) {
+                bloom.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        bloom
+    }
+
+    // false = la clave seguro no esta; true = puede estar
+    pub(crate) fn may_contain(&self, key: &str) -> bool {
+        self.positions(key).all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    // Doble hashing (Kirsch-Mitzenmacher) a partir de un solo hash de 64 bits
+    fn positions(&self, key: &str) -> impl Iterator<Item = usize> {
+        let hash = fnv1a(key.as_bytes());
+        let (h1, h2) = (hash as u32, (hash >> 32) as u32 | 1);
+        let bits = self.bits.len() as u32 * 8;
+        (0..self.hashes).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    // Formato: hashes (4) | bits
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.hashes.to_be_bytes());
+        out.extend_from_slice(&self.bits);
+    }
+
+    pub(crate) fn decode(buf: &[u8]) -> io::Result<Self> {
+        match buf {
+            [a, b, c, d, bits @ ..] if !bits.is_empty() => Ok(Bloom {
+                hashes: u32::from_be_bytes([*a, *b, *c, *d]),
+                bits: bits.to_vec(),
+            }),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid bloom filter")),
+        }
+    }
+}
+
+// Hash estable entre versiones de Rust (el filtro se guarda en disco)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    // Mezcla final: FNV reparte mal los bits altos con claves cortas
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^ (hash >> 33)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let keys: Vec<String> = (0..1000).map(|i| format!("key:{}", i)).collect();
+        let bloom = Bloom::build(keys.iter().map(String::as_str));
+        assert!(keys.iter().all(|key| bloom.may_contain(key)));
+
+        let mut encoded = Vec::new();
+        bloom.encode(&mut encoded);
+        let bloom = Bloom::decode(&encoded).unwrap();
+        let false_positives = (0..1000).filter(|i| bloom.may_contain(&format!("other:{}", i))).count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+    }
+}
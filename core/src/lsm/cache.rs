@@ -0,0 +1,89 @@
+// Cache LRU de bloques ya decodificados, compartida por todas las tablas
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use super::sstable::Block;
+
+// (id de tabla, numero de bloque); los ids de tabla no se reutilizan
+type BlockId = (u64, u32);
+
+pub(crate) struct BlockCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    blocks: HashMap<BlockId, (Arc<Block>, u64)>,   // bloque y ultimo acceso
+    recency: BTreeMap<u64, BlockId>,               // acceso -> bloque, el menor es el LRU
+    clock: u64,
+    size: usize,
+}
+
+impl BlockCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        BlockCache {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self, id: BlockId) -> Option<Arc<Block>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.clock += 1;
+        let clock = state.clock;
+        let Some((block, last)) = state.blocks.get_mut(&id) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        let (block, previous) = (block.clone(), std::mem::replace(last, clock));
+        state.recency.remove(&previous);
+        state.recency.insert(clock, id);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(block)
+    }
+
+    pub(crate) fn insert(&self, id: BlockId, block: Arc<Block>) {
+        if block.size > self.capacity {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.clock += 1;
+        let clock = state.clock;
+        state.size += block.size;
+        if let Some((old, last)) = state.blocks.insert(id, (block, clock)) {
+            state.size -= old.size;
+            state.recency.remove(&last);
+        }
+        state.recency.insert(clock, id);
+        while state.size > self.capacity {
+            let Some((_, victim)) = state.recency.pop_first() else { break };
+            if let Some((old, _)) = state.blocks.remove(&victim) {
+                state.size -= old.size;
+            }
+        }
+    }
+
+    // Descarta los bloques de una tabla borrada por la compactacion
+    pub(crate) fn evict_table(&self, table: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let state = &mut *state;
+        state.blocks.retain(|(id, _), (block, last)| {
+            if *id != table {
+                return true;
+            }
+            state.size -= block.size;
+            state.recency.remove(last);
+            false
+        });
+    }
+
+    // (aciertos, fallos)
+    pub(crate) fn stats(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
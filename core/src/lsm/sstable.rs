@@ -0,0 +1,345 @@
+// Tablas ordenadas inmutables (SSTables)
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::{Bound, Range};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use super::bloom::Bloom;
+use super::cache::BlockCache;
+use super::{decode_record, encode_record, invalid, Parts, Record};
+
+// Formato (big-endian):
+//   [bloque]* | meta | meta_offset (8) | meta_len (4) | crc32 de meta (4) | magic (8)
+//   bloque: registros ordenados por clave (ver encode_record) | crc32 (4)
+//   meta:   entradas (8) | min_key_len (4) | min_key | bloom_len (4) | bloom |
+//           bloques (4) | [last_key_len (4) | last_key | offset (8) | len (4)]* |
+//           con expiracion (4) | [expires_at (8) | key_len (4) | key]*
+const MAGIC: &[u8; 8] = b"NANOSST1";
+const FOOTER_LEN: usize = 8 + 4 + 4 + 8;
+
+// Bloque decodificado: registros ordenados y su tamaño en disco (para la cache)
+pub(crate) struct Block {
+    pub(crate) records: Vec<(String, Option<Record>)>,
+    pub(crate) size: usize,
+}
+
+// Ubicacion de un bloque y su ultima clave (el indice se busca por ultima clave)
+struct BlockHandle {
+    last_key: String,
+    offset: u64,
+    len: u32,
+}
+
+pub(crate) struct Table {
+    pub(crate) id: u64,
+    path: PathBuf,
+    file: Mutex<File>,
+    index: Vec<BlockHandle>,
+    bloom: Bloom,
+    min_key: String,
+    expiring: Vec<(u64, String)>,   // Claves con expiracion, por fecha (para purgarlas sin leer la tabla)
+    pub(crate) entries: u64,
+    pub(crate) size: u64,
+}
+
+impl Table {
+    pub(crate) fn open(path: &Path, id: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        if size < FOOTER_LEN as u64 {
+            return Err(invalid("truncated lsm table"));
+        }
+        let mut footer = [0u8; FOOTER_LEN];
+        file.seek(SeekFrom::Start(size - FOOTER_LEN as u64))?;
+        file.read_exact(&mut footer)?;
+        if &footer[16..] != MAGIC {
+            return Err(invalid("not a NanoDb lsm table"));
+        }
+        let meta_offset = u64::from_be_bytes(footer[..8].try_into().unwrap());
+        let meta_len = u32::from_be_bytes(footer[8..12].try_into().unwrap()) as usize;
+        let crc = u32::from_be_bytes(footer[12..16].try_into().unwrap());
+        let mut meta = vec![0u8; meta_len];
+        file.seek(SeekFrom::Start(meta_offset))?;
+        file.read_exact(&mut meta)?;
+        if crc32fast::hash(&meta) != crc {
+            return Err(invalid("lsm table index checksum mismatch"));
+        }
+
+        let mut pos = 0;
+        let entries = super::take_u64(&meta, &mut pos)?;
+        let min_key = super::take_string(&meta, &mut pos)?;
+        let bloom_len = super::take_u32(&meta, &mut pos)? as usize;
+        let bloom = Bloom::decode(super::take(&meta, &mut pos, bloom_len)?)?;
+        let blocks = super::take_u32(&meta, &mut pos)?;
+        let mut index = Vec::with_capacity(blocks as usize);
+        for _ in 0..blocks {
+            index.push(BlockHandle {
+                last_key: super::take_string(&meta, &mut pos)?,
+                offset: super::take_u64(&meta, &mut pos)?,
+                len: super::take_u32(&meta, &mut pos)?,
+            });
+        }
+        if index.is_empty() {
+            return Err(invalid("empty lsm table"));
+        }
+        let count = super::take_u32(&meta, &mut pos)?;
+        let mut expiring = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            expiring.push((super::take_u64(&meta, &mut pos)?, super::take_string(&meta, &mut pos)?));
+        }
+        Ok(Table { id, path: path.to_path_buf(), file: Mutex::new(file), index, bloom, min_key, expiring, entries, size })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn min_key(&self) -> &str {
+        &self.min_key
+    }
+
+    pub(crate) fn max_key(&self) -> &str {
+        &self.index[self.index.len() - 1].last_key
+    }
+
+    // Claves que vencen en (after, until]; pueden tener versiones mas nuevas en otras tablas
+    pub(crate) fn expiring(&self, after: u64, until: u64) -> impl Iterator<Item = &str> {
+        let start = self.expiring.partition_point(|(at, _)| *at <= after);
+        let end = self.expiring.partition_point(|(at, _)| *at <= until).max(start);
+        self.expiring[start..end].iter().map(|(_, key)| key.as_str())
+    }
+
+    // La tabla tiene claves dentro de [lower, upper]
+    pub(crate) fn overlaps(&self, lower: &str, upper: &str) -> bool {
+        self.min_key() <= upper && self.max_key() >= lower
+    }
+
+    // Some(None) = la tabla tiene un borrado para la clave
+    pub(crate) fn get(&self, key: &str, cache: &BlockCache) -> io::Result<Option<Option<Record>>> {
+        if key < self.min_key() || key > self.max_key() || !self.bloom.may_contain(key) {
+            return Ok(None);
+        }
+        let block = self.block(self.index.partition_point(|handle| handle.last_key.as_str() < key), cache)?;
+        Ok(block
+            .records
+            .binary_search_by(|(k, _)| k.as_str().cmp(key))
+            .ok()
+            .map(|i| block.records[i].1.clone()))
+    }
+
+    fn block(&self, i: usize, cache: &BlockCache) -> io::Result<Arc<Block>> {
+        if let Some(block) = cache.get((self.id, i as u32)) {
+            return Ok(block);
+        }
+        let handle = &self.index[i];
+        let mut buf = vec![0u8; handle.len as usize];
+        {
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            file.seek(SeekFrom::Start(handle.offset))?;
+            file.read_exact(&mut buf)?;
+        }
+        if buf.len() < 4 {
+            return Err(invalid("truncated lsm block"));
+        }
+        let (body, trailer) = buf.split_at(buf.len() - 4);
+        if crc32fast::hash(body) != u32::from_be_bytes(trailer.try_into().unwrap()) {
+            return Err(invalid(&format!("lsm block checksum mismatch in {}", self.path.display())));
+        }
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while pos < body.len() {
+            records.push(decode_record(body, &mut pos)?);
+        }
+        let block = Arc::new(Block { records, size: buf.len() });
+        cache.insert((self.id, i as u32), block.clone());
+        Ok(block)
+    }
+
+    // Registros dentro de los limites, en orden o en orden inverso
+    pub(crate) fn iter<'a>(self: &Arc<Self>, cache: &'a BlockCache, lower: Bound<String>, upper: Bound<String>, reverse: bool) -> TableIter<'a> {
+        let next = if reverse {
+            // El bloque que contiene el limite superior (o el ultimo)
+            let i = self.index.partition_point(|handle| before_upper(&handle.last_key, &upper));
+            Some(i.min(self.index.len() - 1))
+        } else {
+            let i = self.index.partition_point(|handle| !after_lower(&handle.last_key, &lower));
+            (i < self.index.len()).then_some(i)
+        };
+        TableIter { table: self.clone(), cache, lower, upper, reverse, next, block: None, range: 0..0 }
+    }
+}
+
+pub(crate) struct TableIter<'a> {
+    table: Arc<Table>,
+    cache: &'a BlockCache,
+    lower: Bound<String>,
+    upper: Bound<String>,
+    reverse: bool,
+    next: Option<usize>,            // Proximo bloque a leer (None = no quedan)
+    block: Option<Arc<Block>>,
+    range: Range<usize>,            // Registros del bloque actual dentro de los limites
+}
+
+impl TableIter<'_> {
+    fn load(&mut self, i: usize) -> io::Result<()> {
+        let block = self.table.block(i, self.cache)?;
+        let start = block.records.partition_point(|(key, _)| !after_lower(key, &self.lower));
+        let end = block.records.partition_point(|(key, _)| before_upper(key, &self.upper)).max(start);
+        // Si el limite cae dentro de este bloque no hace falta seguir
+        self.next = if self.reverse {
+            (start == 0 && i > 0).then(|| i - 1)
+        } else {
+            (end == block.records.len() && i + 1 < self.table.index.len()).then_some(i + 1)
+        };
+        self.range = start..end;
+        self.block = Some(block);
+        Ok(())
+    }
+}
+
+impl Iterator for TableIter<'_> {
+    type Item = io::Result<(String, Option<Record>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(block) = &self.block {
+                let pos = if self.reverse { self.range.next_back() } else { self.range.next() };
+                if let Some(pos) = pos {
+                    return Some(Ok(block.records[pos].clone()));
+                }
+            }
+            let i = self.next.take()?;
+            if let Err(e) = self.load(i) {
+                self.block = None;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+fn after_lower(key: &str, lower: &Bound<String>) -> bool {
+    match lower {
+        Bound::Included(bound) => key >= bound.as_str(),
+        Bound::Excluded(bound) => key > bound.as_str(),
+        Bound::Unbounded => true,
+    }
+}
+
+fn before_upper(key: &str, upper: &Bound<String>) -> bool {
+    match upper {
+        Bound::Included(bound) => key <= bound.as_str(),
+        Bound::Excluded(bound) => key < bound.as_str(),
+        Bound::Unbounded => true,
+    }
+}
+
+// Escribe una tabla nueva; las claves tienen que llegar ordenadas y sin repetir
+pub(crate) struct TableBuilder {
+    id: u64,
+    path: PathBuf,
+    file: BufWriter<File>,
+    block_size: usize,
+    block: Vec<u8>,
+    index: Vec<BlockHandle>,
+    keys: Vec<String>,
+    expiring: Vec<(u64, String)>,
+    offset: u64,
+}
+
+impl TableBuilder {
+    pub(crate) fn create(path: PathBuf, id: u64, block_size: usize) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(&path)?);
+        Ok(TableBuilder {
+            id,
+            path,
+            file,
+            block_size,
+            block: Vec::new(),
+            index: Vec::new(),
+            keys: Vec::new(),
+            expiring: Vec::new(),
+            offset: 0,
+        })
+    }
+
+    pub(crate) fn add(&mut self, key: &str, slot: Option<Parts<'_>>) -> io::Result<()> {
+        encode_record(&mut self.block, key, slot);
+        self.keys.push(key.to_string());
+        if let Some((_, Some(expires_at), _)) = slot {
+            self.expiring.push((expires_at, key.to_string()));
+        }
+        if self.block.len() >= self.block_size {
+            self.finish_block()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Tamaño aproximado en disco
+    pub(crate) fn size(&self) -> u64 {
+        self.offset + self.block.len() as u64
+    }
+
+    fn finish_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let crc = crc32fast::hash(&self.block);
+        self.block.extend_from_slice(&crc.to_be_bytes());
+        self.file.write_all(&self.block)?;
+        self.index.push(BlockHandle {
+            last_key: self.keys.last().cloned().unwrap_or_default(),
+            offset: self.offset,
+            len: self.block.len() as u32,
+        });
+        self.offset += self.block.len() as u64;
+        self.block.clear();
+        Ok(())
+    }
+
+    // Escribe el indice y el filtro, sincroniza y abre la tabla para lectura
+    pub(crate) fn finish(mut self) -> io::Result<Table> {
+        self.finish_block()?;
+        let bloom = Bloom::build(self.keys.iter().map(String::as_str));
+        let mut meta = Vec::new();
+        meta.extend_from_slice(&(self.keys.len() as u64).to_be_bytes());
+        put_string(&mut meta, self.keys.first().map_or("", String::as_str));
+        let mut encoded = Vec::new();
+        bloom.encode(&mut encoded);
+        meta.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        meta.extend_from_slice(&encoded);
+        meta.extend_from_slice(&(self.index.len() as u32).to_be_bytes());
+        for handle in &self.index {
+            put_string(&mut meta, &handle.last_key);
+            meta.extend_from_slice(&handle.offset.to_be_bytes());
+            meta.extend_from_slice(&handle.len.to_be_bytes());
+        }
+        self.expiring.sort_unstable();
+        meta.extend_from_slice(&(self.expiring.len() as u32).to_be_bytes());
+        for (expires_at, key) in &self.expiring {
+            meta.extend_from_slice(&expires_at.to_be_bytes());
+            put_string(&mut meta, key);
+        }
+        self.file.write_all(&meta)?;
+        self.file.write_all(&self.offset.to_be_bytes())?;
+        self.file.write_all(&(meta.len() as u32).to_be_bytes())?;
+        self.file.write_all(&crc32fast::hash(&meta).to_be_bytes())?;
+        self.file.write_all(MAGIC)?;
+        self.file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Table::open(&self.path, self.id)
+    }
+
+    // Descarta una tabla que quedo vacia
+    pub(crate) fn abandon(self) -> io::Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path)
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(&(text.len() as u32).to_be_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
@@ -0,0 +1,126 @@
+// Log de escritura del memtable: cada escritura se agrega aca antes de aplicarla en memoria.
+// Cuando el memtable se vuelca a una tabla, su log se borra.
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tracing::warn;
+use crate::config::FsyncPolicy;
+use super::{decode_record, encode_record, Parts, Record};
+
+// Formato de cada registro (big-endian): len (4) | crc32 (4) | registro (ver encode_record)
+const HEADER_LEN: usize = 8;
+
+pub(crate) struct Wal {
+    pub(crate) id: u64,
+    file: File,
+    fsync: FsyncPolicy,
+    dirty: bool,
+    failed: Option<String>, // Tras un error de escritura o fsync no se sabe que llego al disco
+    buf: Vec<u8>,
+}
+
+impl Wal {
+    pub(crate) fn create(path: &Path, id: u64, fsync: FsyncPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Wal { id, file, fsync, dirty: false, failed: None, buf: Vec::new() })
+    }
+
+    // Tras un error no se agrega nada mas: un registro cortado en el medio del log haria
+    // que el replay descarte todo lo que sigue, aunque se haya confirmado.
+    // El memtable siguiente empieza con un log nuevo.
+    pub(crate) fn append(&mut self, key: &str, slot: Option<Parts<'_>>) -> io::Result<()> {
+        if let Some(e) = &self.failed {
+            return Err(unusable(e));
+        }
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0; HEADER_LEN]);
+        encode_record(&mut self.buf, key, slot);
+        let len = (self.buf.len() - HEADER_LEN) as u32;
+        let crc = crc32fast::hash(&self.buf[HEADER_LEN..]);
+        self.buf[..4].copy_from_slice(&len.to_be_bytes());
+        self.buf[4..HEADER_LEN].copy_from_slice(&crc.to_be_bytes());
+        let result = match self.file.write_all(&self.buf) {
+            Ok(()) if self.fsync == FsyncPolicy::Always => self.file.sync_data(),
+            Ok(()) => {
+                self.dirty = true;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        };
+        self.latch(result)
+    }
+
+    // fsync pendiente (politica EverySecond, o antes de congelar el memtable)
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        if let Some(e) = &self.failed {
+            return Err(unusable(e));
+        }
+        if self.dirty && self.fsync != FsyncPolicy::Never {
+            let result = self.file.sync_data();
+            self.latch(result)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn latch(&mut self, result: io::Result<()>) -> io::Result<()> {
+        if let Err(e) = &result {
+            self.failed = Some(e.to_string());
+        }
+        result
+    }
+}
+
+fn unusable(cause: &str) -> io::Error {
+    io::Error::other(format!("lsm write-ahead log unusable after a failed write: {}", cause))
+}
+
+// Registros del log en orden. Un registro incompleto o con checksum invalido al final
+// es una escritura cortada por un crash: se descarta junto con lo que sigue.
+pub(crate) fn replay(path: &Path) -> io::Result<Vec<(String, Option<Record>)>> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while pos + HEADER_LEN <= buf.len() {
+        let len = u32::from_be_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]]) as usize;
+        let crc = u32::from_be_bytes([buf[pos + 4], buf[pos + 5], buf[pos + 6], buf[pos + 7]]);
+        let Some(body) = buf.get(pos + HEADER_LEN..pos + HEADER_LEN + len) else { break };
+        if crc32fast::hash(body) != crc {
+            break;
+        }
+        let mut offset = 0;
+        records.push(decode_record(body, &mut offset)?);
+        pos += HEADER_LEN + len;
+    }
+    if pos < buf.len() {
+        warn!(path = %path.display(), discarded = buf.len() - pos, "Discarding torn tail of lsm write-ahead log");
+    }
+    Ok(records)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Value;
+
+    #[test]
+    fn test_refuses_appends_after_failed_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1.wal");
+        let mut wal = Wal::create(&path, 1, FsyncPolicy::Always).unwrap();
+        let value = Value::Bytes(b"v".to_vec());
+        wal.append("a", Some((&value, None, 1))).unwrap();
+
+        // Un archivo abierto solo para lectura hace fallar la escritura
+        let writable = std::mem::replace(&mut wal.file, File::open(&path).unwrap());
+        assert!(wal.append("b", Some((&value, None, 2))).is_err());
+        wal.file = writable;
+        assert!(wal.append("c", Some((&value, None, 3))).is_err());
+        assert!(wal.sync().is_err());
+
+        let keys: Vec<String> = replay(&path).unwrap().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["a"]);
+    }
+}
@@ -1,5 +1,9 @@
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use dashmap::DashMap;
+use serde::Serialize;
 
 #[derive(Debug, Default)]
 pub struct Metrics {
@@ -8,6 +12,29 @@ pub struct Metrics {
     pub delete_operations: AtomicU64,
     pub keys_operations: AtomicU64,
     pub clear_operations: AtomicU64,
+    pub evictions: AtomicU64,
+    pub published_messages: AtomicU64,
+    // Compresion: valores comprimidos al escribir y sus tamaños antes y despues
+    pub compressed_values: AtomicU64,
+    pub compression_input_bytes: AtomicU64,
+    pub compression_output_bytes: AtomicU64,
+    // Log: escrituras confirmadas en disco (politica always; las demas no esperan el fsync),
+    // su latencia y fsyncs hechos.
+    // Cada fsync confirma un grupo de escrituras concurrentes.
+    pub wal_commits: AtomicU64,
+    pub wal_commit_latency_us: AtomicU64,      // Suma, para el promedio
+    pub wal_max_commit_latency_us: AtomicU64,
+    pub wal_syncs: AtomicU64,
+    pub wal_synced_records: AtomicU64,
+    // Pub/sub: solo canales y patrones con al menos un suscriptor
+    pub channels: DashMap<String, ChannelStats>,
+    pub patterns: DashMap<String, ChannelStats>,
+}
+
+#[derive(Debug, Default)]
+pub struct ChannelStats {
+    pub subscribers: AtomicU64,
+    pub messages: AtomicU64,     // Mensajes entregados desde que tiene suscriptores
 }
 
 impl Metrics {
@@ -35,24 +62,116 @@ impl Metrics {
         self.clear_operations.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn increment_evictions(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_published(&self) {
+        self.published_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_compression(&self, input: usize, output: usize) {
+        self.compressed_values.fetch_add(1, Ordering::Relaxed);
+        self.compression_input_bytes.fetch_add(input as u64, Ordering::Relaxed);
+        self.compression_output_bytes.fetch_add(output as u64, Ordering::Relaxed);
+    }
+
+    // Una escritura que espero su fsync (incluye la espera por el grupo)
+    pub fn record_wal_commit(&self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        self.wal_commits.fetch_add(1, Ordering::Relaxed);
+        self.wal_commit_latency_us.fetch_add(micros, Ordering::Relaxed);
+        self.wal_max_commit_latency_us.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    // Un fsync del log que confirmo `records` registros
+    pub fn record_wal_sync(&self, records: u64) {
+        self.wal_syncs.fetch_add(1, Ordering::Relaxed);
+        self.wal_synced_records.fetch_add(records, Ordering::Relaxed);
+    }
+
+    // Alta de un suscriptor en un canal (o patron)
+    pub fn channel_subscribed(&self, name: &str, pattern: bool) {
+        let map = if pattern { &self.patterns } else { &self.channels };
+        map.entry(name.to_string()).or_default().subscribers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Baja de un suscriptor; el canal desaparece de las metricas con el ultimo
+    pub fn channel_unsubscribed(&self, name: &str, pattern: bool) {
+        let map = if pattern { &self.patterns } else { &self.channels };
+        if let Some(stats) = map.get(name) {
+            stats.subscribers.fetch_sub(1, Ordering::Relaxed);
+        }
+        map.remove_if(name, |_, stats| stats.subscribers.load(Ordering::Relaxed) == 0);
+    }
+
     pub fn get_stats(&self) -> MetricsSnapshot {
+        let input = self.compression_input_bytes.load(Ordering::Relaxed);
+        let output = self.compression_output_bytes.load(Ordering::Relaxed);
+        let commits = self.wal_commits.load(Ordering::Relaxed);
+        let syncs = self.wal_syncs.load(Ordering::Relaxed);
         MetricsSnapshot {
             get_operations: self.get_operations.load(Ordering::Relaxed),
             set_operations: self.set_operations.load(Ordering::Relaxed),
             delete_operations: self.delete_operations.load(Ordering::Relaxed),
             keys_operations: self.keys_operations.load(Ordering::Relaxed),
             clear_operations: self.clear_operations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            published_messages: self.published_messages.load(Ordering::Relaxed),
+            compressed_values: self.compressed_values.load(Ordering::Relaxed),
+            compression_input_bytes: input,
+            compression_output_bytes: output,
+            // Original / comprimido (1.0 si todavia no se comprimio nada)
+            compression_ratio: if output == 0 { 1.0 } else { input as f64 / output as f64 },
+            compression_saved_bytes: input.saturating_sub(output),
+            wal_commits: commits,
+            wal_avg_commit_latency_us: self.wal_commit_latency_us.load(Ordering::Relaxed).checked_div(commits).unwrap_or(0),
+            wal_max_commit_latency_us: self.wal_max_commit_latency_us.load(Ordering::Relaxed),
+            wal_syncs: syncs,
+            // Registros por fsync (0.0 si todavia no hubo ninguno)
+            wal_avg_group_size: if syncs == 0 { 0.0 } else { self.wal_synced_records.load(Ordering::Relaxed) as f64 / syncs as f64 },
+            channels: snapshot_channels(&self.channels),
+            patterns: snapshot_channels(&self.patterns),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+fn snapshot_channels(map: &DashMap<String, ChannelStats>) -> BTreeMap<String, ChannelSnapshot> {
+    map.iter()
+        .map(|kv| (kv.key().clone(), ChannelSnapshot {
+            subscribers: kv.subscribers.load(Ordering::Relaxed),
+            messages: kv.messages.load(Ordering::Relaxed),
+        }))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct MetricsSnapshot {
     pub get_operations: u64,
     pub set_operations: u64,
     pub delete_operations: u64,
     pub keys_operations: u64,
     pub clear_operations: u64,
+    pub evictions: u64,
+    pub published_messages: u64,
+    pub compressed_values: u64,
+    pub compression_input_bytes: u64,
+    pub compression_output_bytes: u64,
+    pub compression_ratio: f64,
+    pub compression_saved_bytes: u64,
+    pub wal_commits: u64,
+    pub wal_avg_commit_latency_us: u64,
+    pub wal_max_commit_latency_us: u64,
+    pub wal_syncs: u64,
+    pub wal_avg_group_size: f64,
+    pub channels: BTreeMap<String, ChannelSnapshot>,
+    pub patterns: BTreeMap<String, ChannelSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelSnapshot {
+    pub subscribers: u64,
+    pub messages: u64,
 }
 
 impl MetricsSnapshot {
@@ -1,27 +1,167 @@
-// Operaciones de la base de datos
-#[derive(Debug, Clone, PartialEq)]
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::{DbError, IndexDef, IndexQuery, ReplicationInfo, SnapshotInfo, WatchFilter};
+
+// Operaciones de la base de datos (serializables para el log del cluster)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DbOperation {
     Get { key: String, default: Option<Vec<u8>> },
-    Set { key: String, value: Vec<u8> },
+    Set { key: String, value: Vec<u8>, ttl: Option<Duration> },
     Delete { key: String },
     Exists { key: String },
     Flush,
     Keys,
     KeysCursor { prefix: Option<String>, cursor: Option<String>, limit: usize },
     KeysPrefix { prefix: String },
+    Range { start: Option<String>, end: Option<String>, reverse: bool, limit: usize },  // [start, end)
     Values,
     ValuesPrefix { prefix: String },
     GetPrefix { prefix: String },
     DeletePrefix { prefix: String },
     Size,
-    CompareAndSwap { key: String, old_value: Option<Vec<u8>>, new_value: Option<Vec<u8>> }
+    CompareAndSwap { key: String, old_value: Option<Vec<u8>>, new_value: Option<Vec<u8>> },
+    Save,
+    Expire { key: String, ttl: Duration },
+    Ttl { key: String },
+    Persist { key: String },
+    Version { key: String },
+    // Lote atomico: si alguna precondicion falla no se aplica ninguna operacion
+    Transaction { preconditions: Vec<Precondition>, operations: Vec<DbOperation> },
+    // Suscripcion al change-feed: solo la atienden transportes con streaming (ver NanoDb::watch)
+    Watch { filter: WatchFilter },
+    // Pub/sub: Subscribe y Unsubscribe tambien necesitan un transporte con streaming
+    Publish { channel: String, message: Vec<u8> },
+    Subscribe { channel: String, pattern: bool },
+    Unsubscribe { channel: String, pattern: bool },
+    // Namespaces: solo sobre la base principal. Select cambia el namespace de una conexion
+    // (None = base principal); execute solo verifica que exista.
+    Select { namespace: Option<String> },
+    CreateNamespace { name: String },
+    DropNamespace { name: String },
+    Namespaces,
+    // Listas (front = inicio de la lista; rangos inclusivos, negativos desde el final)
+    ListPush { key: String, values: Vec<Vec<u8>>, front: bool },
+    ListPop { key: String, count: usize, front: bool },
+    ListRange { key: String, start: i64, stop: i64 },
+    // Hashes
+    HashSet { key: String, fields: Vec<(String, Vec<u8>)> },
+    HashGet { key: String, field: String },
+    HashDelete { key: String, fields: Vec<String> },
+    // Conjuntos
+    SetAdd { key: String, members: Vec<Vec<u8>> },
+    SetRemove { key: String, members: Vec<Vec<u8>> },
+    SetMembers { key: String },
+    SetIntersection { keys: Vec<String> },
+    // Conjuntos ordenados por puntaje
+    ZSetAdd { key: String, members: Vec<(f64, Vec<u8>)> },
+    ZSetRemove { key: String, members: Vec<Vec<u8>> },
+    ZSetRange { key: String, start: i64, stop: i64 },
+    ZSetRangeByScore { key: String, min: f64, max: f64 },
+    // Contadores atomicos (la clave ausente vale 0; se conserva el TTL)
+    Increment { key: String, delta: i64 },
+    Decrement { key: String, delta: i64 },
+    IncrementFloat { key: String, delta: f64 },
+    // Lotes: una respuesta para muchas claves (atomic = MSET todo-o-nada)
+    MultiGet { keys: Vec<String> },
+    MultiSet { entries: Vec<(String, Vec<u8>)>, atomic: bool },
+    MultiDelete { keys: Vec<String> },
+    // Documentos JSON (path vacio o "$" = el documento entero; ver JsonPath)
+    JsonGet { key: String, path: String },
+    JsonSet { key: String, path: String, value: serde_json::Value },
+    JsonDelete { key: String, path: String },
+    JsonAppend { key: String, path: String, values: Vec<serde_json::Value> },
+    JsonIncrement { key: String, path: String, delta: f64 },
+    // Indices secundarios sobre documentos JSON (ver IndexDef; limit 0 = sin limite)
+    CreateIndex { name: String, prefix: String, path: String },
+    DropIndex { name: String },
+    Indexes,
+    QueryIndex { name: String, query: IndexQuery, cursor: Option<String>, limit: usize },
+    // Scripts atomicos (ver NanoDb::eval): el script solo puede tocar las claves de `keys`
+    Eval { script: String, keys: Vec<String>, args: Vec<String> },
+    EvalSha { sha: String, keys: Vec<String>, args: Vec<String> },
+    ScriptLoad { script: String },
+    // Estado de la replicacion de la base principal (ver NanoDb::replication_info)
+    ReplicationInfo,
+}
+
+// Condiciones que se verifican antes de aplicar una transaccion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Precondition {
+    Value { key: String, expected: Option<Vec<u8>> },  // None = la clave no debe existir
+    Version { key: String, version: u64 },             // 0 = la clave no debe existir
+}
 
+impl Precondition {
+    pub fn key(&self) -> &str {
+        match self {
+            Precondition::Value { key, .. } | Precondition::Version { key, .. } => key,
+        }
+    }
 }
 
 // Resultados de las operaciones
 #[derive(Debug, Clone)]
 pub enum DbResult <T> {
     Ok(T),
-    Err(String),
+    Err(DbError),
     NotFound
 }
+
+// Pagina de claves para KeysCursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeysPage {
+    pub keys: Vec<String>,
+    pub next_cursor: Option<String>,  // None = no hay mas claves
+}
+
+// Resultado de CompareAndSwap
+#[derive(Debug, Clone, PartialEq)]
+pub struct CasOutcome {
+    pub swapped: bool,
+    pub current: Option<Vec<u8>>,     // Valor actual despues de la operacion
+}
+
+// Resultado de una transaccion
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxnOutcome {
+    Committed(Vec<DbResponse>),        // Una respuesta por operacion
+    Aborted { precondition: usize },   // Indice de la primera precondicion que fallo
+}
+
+// Respuesta tipada de execute(), una variante por forma de resultado
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbResponse {
+    Ok,                                 // Set, Delete, Flush, JsonSet
+    Value(Vec<u8>),                     // Get, HashGet, ScriptLoad (el hash)
+    NotFound,
+    Bool(bool),                         // Exists, Expire, Persist, CreateNamespace, DropNamespace, CreateIndex, DropIndex
+    Keys(Vec<String>),                  // Keys, KeysPrefix, Namespaces
+    KeysPage(KeysPage),                 // KeysCursor, QueryIndex
+    Values(Vec<Vec<u8>>),               // Values, ValuesPrefix, ListPop, ListRange, SetMembers, SetIntersection
+    OptionalValues(Vec<Option<Vec<u8>>>), // MultiGet (None = clave ausente)
+    Entries(Vec<(String, Vec<u8>)>),    // GetPrefix, Range
+    ScoredMembers(Vec<(Vec<u8>, f64)>), // ZSetRange, ZSetRangeByScore
+    Count(usize),                       // Size, DeletePrefix, Publish, MultiSet, MultiDelete, ListPush, JsonAppend y altas/bajas en colecciones
+    Version(u64),                       // Version
+    Integer(i64),                       // Increment, Decrement
+    Float(f64),                         // IncrementFloat
+    Json(serde_json::Value),            // JsonGet, JsonIncrement (el numero resultante), Eval, EvalSha
+    Indexes(Vec<IndexDef>),             // Indexes
+    Replication(ReplicationInfo),       // ReplicationInfo
+    Ttl(Option<Duration>),              // Ttl (None = sin expiracion)
+    Cas(CasOutcome),                    // CompareAndSwap
+    Snapshot(SnapshotInfo),             // Save
+    Txn(TxnOutcome),                    // Transaction
+    Error(DbError),
+}
+
+impl<T> DbResult<T> {
+    // Convertir a DbResponse usando `ok` para el caso exitoso
+    pub fn into_response(self, ok: impl FnOnce(T) -> DbResponse) -> DbResponse {
+        match self {
+            DbResult::Ok(value) => ok(value),
+            DbResult::NotFound => DbResponse::NotFound,
+            DbResult::Err(msg) => DbResponse::Error(msg),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// Importaciones
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::{DbOperation, DbResponse, DbResult, Metrics, NanoDb, SnapshotInfo, Subscription, WatchFilter, Watcher};
+
+// Puerto de almacenamiento: lo que los adaptadores (TCP, HTTP, gRPC) necesitan del nucleo.
+// NanoDb es la implementacion en memoria; otros motores (disco, remoto, mocks)
+// pueden implementarlo sin tocar el codigo de protocolo.
+pub trait StoragePort: Send + Sync + 'static {
+    fn get(&self, key: &str) -> impl Future<Output = DbResult<Vec<u8>>> + Send;
+    fn set(&self, key: String, value: Vec<u8>) -> impl Future<Output = DbResult<()>> + Send;
+    fn set_with_ttl(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> impl Future<Output = DbResult<()>> + Send;
+    fn delete(&self, key: &str) -> impl Future<Output = DbResult<()>> + Send;
+    fn exists(&self, key: &str) -> impl Future<Output = DbResult<bool>> + Send;
+    fn keys(&self) -> impl Future<Output = DbResult<Vec<String>>> + Send;
+    fn clear(&self) -> impl Future<Output = DbResult<()>> + Send;
+    fn expire(&self, key: &str, ttl: Duration) -> impl Future<Output = DbResult<bool>> + Send;
+    fn persist(&self, key: &str) -> impl Future<Output = DbResult<bool>> + Send;
+    fn ttl(&self, key: &str) -> impl Future<Output = DbResult<Option<Duration>>> + Send;
+    fn save(&self) -> impl Future<Output = DbResult<SnapshotInfo>> + Send;
+    // Punto de entrada unico: cualquier DbOperation
+    fn execute(&self, op: DbOperation) -> impl Future<Output = DbResponse> + Send;
+    // Change-feed para los transportes con streaming
+    fn watch(&self, filter: WatchFilter) -> Watcher;
+    // Pub/sub (la publicacion va por execute)
+    fn subscriber(&self) -> Subscription;
+    // Namespace existente; se crean y eliminan con execute
+    fn namespace(&self, name: &str) -> Option<Arc<Self>>;
+
+    // Observabilidad
+    fn metrics(&self) -> Arc<Metrics>;
+    fn used_memory(&self) -> u64 {
+        0
+    }
+}
+
+// Implementacion del puerto para el motor en memoria
+impl StoragePort for NanoDb {
+    fn get(&self, key: &str) -> impl Future<Output = DbResult<Vec<u8>>> + Send {
+        NanoDb::get(self, key)
+    }
+
+    fn set(&self, key: String, value: Vec<u8>) -> impl Future<Output = DbResult<()>> + Send {
+        NanoDb::set(self, key, value)
+    }
+
+    fn set_with_ttl(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> impl Future<Output = DbResult<()>> + Send {
+        NanoDb::set_with_ttl(self, key, value, ttl)
+    }
+
+    fn delete(&self, key: &str) -> impl Future<Output = DbResult<()>> + Send {
+        NanoDb::delete(self, key)
+    }
+
+    fn exists(&self, key: &str) -> impl Future<Output = DbResult<bool>> + Send {
+        NanoDb::exists(self, key)
+    }
+
+    fn keys(&self) -> impl Future<Output = DbResult<Vec<String>>> + Send {
+        NanoDb::keys(self)
+    }
+
+    fn clear(&self) -> impl Future<Output = DbResult<()>> + Send {
+        NanoDb::clear(self)
+    }
+
+    fn expire(&self, key: &str, ttl: Duration) -> impl Future<Output = DbResult<bool>> + Send {
+        NanoDb::expire(self, key, ttl)
+    }
+
+    fn persist(&self, key: &str) -> impl Future<Output = DbResult<bool>> + Send {
+        NanoDb::persist(self, key)
+    }
+
+    fn ttl(&self, key: &str) -> impl Future<Output = DbResult<Option<Duration>>> + Send {
+        NanoDb::ttl(self, key)
+    }
+
+    fn save(&self) -> impl Future<Output = DbResult<SnapshotInfo>> + Send {
+        NanoDb::save(self)
+    }
+
+    fn execute(&self, op: DbOperation) -> impl Future<Output = DbResponse> + Send {
+        NanoDb::execute(self, op)
+    }
+
+    fn watch(&self, filter: WatchFilter) -> Watcher {
+        NanoDb::watch(self, filter)
+    }
+
+    fn subscriber(&self) -> Subscription {
+        NanoDb::subscriber(self)
+    }
+
+    fn namespace(&self, name: &str) -> Option<Arc<Self>> {
+        NanoDb::namespace(self, name)
+    }
+
+    fn metrics(&self) -> Arc<Metrics> {
+        NanoDb::metrics(self)
+    }
+
+    fn used_memory(&self) -> u64 {
+        NanoDb::used_memory(self)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Los adaptadores solo dependen del puerto
+    async fn roundtrip<S: StoragePort>(db: Arc<S>) -> bool {
+        db.set("port".to_string(), b"value".to_vec()).await;
+        matches!(db.get("port").await, DbResult::Ok(ref v) if v == b"value")
+    }
+
+    #[tokio::test]
+    async fn test_nanodb_implements_port() {
+        let db = Arc::new(NanoDb::new());
+        let handle = tokio::spawn(roundtrip(db.clone()));
+        assert!(handle.await.unwrap());
+        assert_eq!(StoragePort::metrics(db.as_ref()).get_stats().set_operations, 1);
+    }
+}
@@ -0,0 +1,237 @@
+// Importaciones
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use crate::{DbError, Metrics};
+
+// Mensajes que se pueden acumular por suscriptor antes de que se considere atrasado
+pub const PUBSUB_CAPACITY: usize = 1024;
+
+// Mensaje entregado a un suscriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub channel: String,
+    pub pattern: Option<String>,      // Patron que coincidio (None = suscripcion exacta)
+    pub payload: Vec<u8>,
+}
+
+// Bus de mensajes: no se persiste ni pasa por el log
+pub(crate) struct PubSub {
+    sender: broadcast::Sender<Message>,
+    metrics: Arc<Metrics>,            // Registro de suscriptores por canal y patron
+}
+
+impl PubSub {
+    pub(crate) fn new(metrics: Arc<Metrics>) -> Self {
+        PubSub { sender: broadcast::channel(PUBSUB_CAPACITY).0, metrics }
+    }
+
+    // Publica en el canal; devuelve cuantas suscripciones (canal + patrones) coincidieron
+    pub(crate) fn publish(&self, channel: &str, payload: Vec<u8>) -> usize {
+        self.metrics.increment_published();
+        let mut receivers = 0;
+        if let Some(stats) = self.metrics.channels.get(channel) {
+            receivers += stats.subscribers.load(Ordering::Relaxed) as usize;
+            stats.messages.fetch_add(1, Ordering::Relaxed);
+        }
+        for stats in self.metrics.patterns.iter().filter(|kv| glob_match(kv.key(), channel)) {
+            receivers += stats.subscribers.load(Ordering::Relaxed) as usize;
+            stats.messages.fetch_add(1, Ordering::Relaxed);
+        }
+        if receivers > 0 {
+            let _ = self.sender.send(Message { channel: channel.to_string(), pattern: None, payload });
+        }
+        receivers
+    }
+
+    pub(crate) fn subscriber(&self) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+// Suscripcion a canales y patrones; se pueden agregar o quitar mientras esta activa.
+// Si un mensaje coincide con el canal y con algun patron se entrega una sola vez.
+pub struct Subscription {
+    receiver: broadcast::Receiver<Message>,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+    metrics: Arc<Metrics>,
+}
+
+impl Subscription {
+    // Devuelven el numero de suscripciones activas despues del cambio
+    pub fn subscribe(&mut self, channel: &str) -> usize {
+        if self.channels.insert(channel.to_string()) {
+            self.metrics.channel_subscribed(channel, false);
+        }
+        self.count()
+    }
+
+    pub fn psubscribe(&mut self, pattern: &str) -> usize {
+        if self.patterns.insert(pattern.to_string()) {
+            self.metrics.channel_subscribed(pattern, true);
+        }
+        self.count()
+    }
+
+    pub fn unsubscribe(&mut self, channel: &str) -> usize {
+        if self.channels.remove(channel) {
+            self.metrics.channel_unsubscribed(channel, false);
+        }
+        self.count()
+    }
+
+    pub fn punsubscribe(&mut self, pattern: &str) -> usize {
+        if self.patterns.remove(pattern) {
+            self.metrics.channel_unsubscribed(pattern, true);
+        }
+        self.count()
+    }
+
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    // Siguiente mensaje de un canal o patron suscrito.
+    // Si el suscriptor se atrasa se pierden mensajes y se devuelve DbError::Busy.
+    pub async fn recv(&mut self) -> Result<Message, DbError> {
+        loop {
+            let mut message = match self.receiver.recv().await {
+                Ok(message) => message,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    return Err(DbError::Busy(format!("subscriber lagged behind, {} messages dropped", missed)));
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(DbError::Internal("pub/sub bus closed".to_string()));
+                }
+            };
+            if self.channels.contains(&message.channel) {
+                return Ok(message);
+            }
+            if let Some(pattern) = self.patterns.iter().find(|p| glob_match(p, &message.channel)) {
+                message.pattern = Some(pattern.clone());
+                return Ok(message);
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        for channel in &self.channels {
+            self.metrics.channel_unsubscribed(channel, false);
+        }
+        for pattern in &self.patterns {
+            self.metrics.channel_unsubscribed(pattern, true);
+        }
+    }
+}
+
+// Patrones estilo glob: `*` = cualquier secuencia, `?` = un caracter, `\` escapa el siguiente
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Ultimo `*` visto y la posicion del texto donde se reintenta
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some('\\') if pattern.get(p + 1) == Some(&text[t]) => {
+                p += 2;
+                t += 1;
+                continue;
+            }
+            Some(&c) if c != '\\' && c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack {
+            Some((star, retry)) => {
+                p = star + 1;
+                t = retry + 1;
+                backtrack = Some((star, retry + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NanoDb;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("news.*", "news.sports"));
+        assert!(glob_match("news.*", "news."));
+        assert!(!glob_match("news.*", "weather"));
+        assert!(glob_match("h?llo", "hello"));
+        assert!(glob_match("*a*b", "xxaybzab"));
+        assert!(glob_match("a\\*", "a*"));
+        assert!(!glob_match("a\\*", "ab"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_channels_and_patterns() {
+        let db = NanoDb::new();
+        let mut exact = db.subscriber();
+        let mut pattern = db.subscriber();
+        assert_eq!(exact.subscribe("news.sports"), 1);
+        assert_eq!(pattern.psubscribe("news.*"), 1);
+
+        assert_eq!(db.publish("weather", b"rain".to_vec()), 0);
+        assert_eq!(db.publish("news.sports", b"goal".to_vec()), 2);
+        assert_eq!(db.publish("news.tech", b"rust".to_vec()), 1);
+
+        let message = exact.recv().await.unwrap();
+        assert_eq!((message.channel.as_str(), message.pattern, message.payload), ("news.sports", None, b"goal".to_vec()));
+        let first = pattern.recv().await.unwrap();
+        assert_eq!(first.pattern.as_deref(), Some("news.*"));
+        assert_eq!(pattern.recv().await.unwrap().payload, b"rust");
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_counts_in_metrics() {
+        let db = NanoDb::new();
+        let mut a = db.subscriber();
+        let mut b = db.subscriber();
+        a.subscribe("chat");
+        b.subscribe("chat");
+        b.psubscribe("chat.*");
+        db.publish("chat", b"hi".to_vec());
+
+        let stats = db.metrics().get_stats();
+        assert_eq!(stats.channels["chat"].subscribers, 2);
+        assert_eq!(stats.channels["chat"].messages, 1);
+        assert_eq!(stats.patterns["chat.*"].subscribers, 1);
+        assert_eq!(stats.published_messages, 1);
+
+        assert_eq!(a.unsubscribe("chat"), 0);
+        drop(b);
+        let stats = db.metrics().get_stats();
+        assert!(stats.channels.is_empty() && stats.patterns.is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+// Replicacion lider-seguidor sobre TCP: mensajes, backlog del lider y estado.
+//
+// El seguidor se conecta al puerto de replicacion del lider y envia el id de la historia y el
+// offset del ultimo registro que aplico. Si el lider todavia tiene en el backlog todo lo
+// posterior, continua desde ahi (CONTINUE); si no, envia un snapshot completo con su offset
+// (FULL). Despues reenvia cada registro del log con su offset. Cada HEARTBEAT manda un PING y
+// el seguidor contesta con el offset aplicado (ACK).
+//
+// Formato (big-endian):
+//   handshake: "NANOREPL" | id_len (2) | id | offset (8)
+//   FULL:      1 | id_len (2) | id | offset (8) | len (8) | snapshot (ver snapshot::encode)
+//   CONTINUE:  2 | offset (8)
+//   RECORD:    3 | offset (8) | len (4) | registro (ver LogRecord::encode)
+//   PING:      4 | offset (8)
+//   ACK:       offset (8), del seguidor al lider
+// Solo se replica la base principal (no los namespaces) y el trafico va en claro.
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{BuildHasher, RandomState};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use crate::aof::LogRecord;
+
+const MAGIC: &[u8; 8] = b"NANOREPL";
+const FRAME_FULL: u8 = 1;
+const FRAME_CONTINUE: u8 = 2;
+const FRAME_RECORD: u8 = 3;
+const FRAME_PING: u8 = 4;
+
+// Cada cuanto el lider da señales de vida, y cuanto espera el seguidor antes de reconectar
+pub(crate) const HEARTBEAT: Duration = Duration::from_secs(1);
+pub(crate) const LEADER_TIMEOUT: Duration = Duration::from_secs(5);
+// Bytes de registros recientes que se guardan para que un seguidor continue sin snapshot
+const BACKLOG_BYTES: usize = 16 * 1024 * 1024;
+// Registros en vuelo por seguidor; uno mas lento se desconecta y continua desde el backlog
+const FEED_CAPACITY: usize = 4096;
+
+// Registro ya codificado, con su offset
+pub(crate) type FeedRecord = (u64, Arc<[u8]>);
+
+// Estado de replicacion de una base (ver NanoDb::replication_info)
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct ReplicationInfo {
+    pub id: String,                 // Historia de replicacion (la del lider, en un seguidor)
+    pub offset: u64,                // Ultimo registro producido (lider) o aplicado (seguidor)
+    pub leader: Option<String>,     // Some = seguidor de esa direccion
+    pub connected: bool,            // Seguidor sincronizado con el lider
+    pub followers: Vec<FollowerInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FollowerInfo {
+    pub addr: String,
+    pub offset: u64,                // Ultimo offset confirmado (ACK)
+}
+
+// Mensajes del lider al seguidor
+#[derive(Debug, PartialEq)]
+pub(crate) enum Frame {
+    Full { id: String, offset: u64, snapshot: Vec<u8> },
+    Continue { offset: u64 },
+    Record { offset: u64, record: LogRecord },
+    Ping { offset: u64 },
+}
+
+impl Frame {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Frame::Full { id, offset, snapshot } => {
+                out.push(FRAME_FULL);
+                out.extend_from_slice(&(id.len() as u16).to_be_bytes());
+                out.extend_from_slice(id.as_bytes());
+                out.extend_from_slice(&offset.to_be_bytes());
+                out.extend_from_slice(&(snapshot.len() as u64).to_be_bytes());
+                out.extend_from_slice(snapshot);
+            }
+            Frame::Continue { offset } => {
+                out.push(FRAME_CONTINUE);
+                out.extend_from_slice(&offset.to_be_bytes());
+            }
+            Frame::Record { offset, record } => {
+                let mut bytes = Vec::new();
+                record.encode(&mut bytes);
+                return record_frame(*offset, &bytes);
+            }
+            Frame::Ping { offset } => {
+                out.push(FRAME_PING);
+                out.extend_from_slice(&offset.to_be_bytes());
+            }
+        }
+        out
+    }
+
+    pub(crate) async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Frame> {
+        match reader.read_u8().await? {
+            FRAME_FULL => {
+                let id = read_id(reader).await?;
+                let offset = reader.read_u64().await?;
+                let len = reader.read_u64().await? as usize;
+                let mut snapshot = vec![0; len];
+                reader.read_exact(&mut snapshot).await?;
+                Ok(Frame::Full { id, offset, snapshot })
+            }
+            FRAME_CONTINUE => Ok(Frame::Continue { offset: reader.read_u64().await? }),
+            FRAME_RECORD => {
+                let offset = reader.read_u64().await?;
+                let len = reader.read_u32().await? as usize;
+                let mut bytes = vec![0; len];
+                reader.read_exact(&mut bytes).await?;
+                match LogRecord::decode(&bytes)? {
+                    Some((record, used)) if used == len => Ok(Frame::Record { offset, record }),
+                    _ => Err(invalid("malformed replicated record")),
+                }
+            }
+            FRAME_PING => Ok(Frame::Ping { offset: reader.read_u64().await? }),
+            other => Err(invalid(&format!("unknown replication frame {}", other))),
+        }
+    }
+}
+
+// RECORD a partir de un registro ya codificado (el lider lo codifica una sola vez)
+pub(crate) fn record_frame(offset: u64, record: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(13 + record.len());
+    out.push(FRAME_RECORD);
+    out.extend_from_slice(&offset.to_be_bytes());
+    out.extend_from_slice(&(record.len() as u32).to_be_bytes());
+    out.extend_from_slice(record);
+    out
+}
+
+pub(crate) fn handshake(id: &str, offset: u64) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&(id.len() as u16).to_be_bytes());
+    out.extend_from_slice(id.as_bytes());
+    out.extend_from_slice(&offset.to_be_bytes());
+    out
+}
+
+pub(crate) async fn read_handshake<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<(String, u64)> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic).await?;
+    if &magic != MAGIC {
+        return Err(invalid("not a NanoDb replication handshake"));
+    }
+    let id = read_id(reader).await?;
+    Ok((id, reader.read_u64().await?))
+}
+
+async fn read_id<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let len = reader.read_u16().await? as usize;
+    let mut id = vec![0; len];
+    reader.read_exact(&mut id).await?;
+    String::from_utf8(id).map_err(|_| invalid("invalid replication id"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Historia y ultimos registros. El offset cuenta registros desde que el lider abrio su puerto.
+struct Log {
+    id: String,
+    offset: u64,
+    records: VecDeque<FeedRecord>,
+    bytes: usize,
+}
+
+// Estado de replicacion de un NanoDb (como lider, como seguidor o ninguno)
+pub(crate) struct Replication {
+    serving: AtomicBool,            // Lider con puerto abierto: se publican las escrituras
+    log: Mutex<Log>,
+    feed: broadcast::Sender<FeedRecord>,
+    leader: Mutex<Option<String>>,
+    connected: AtomicBool,
+    followers: Mutex<BTreeMap<u64, FollowerInfo>>,
+    next_follower: AtomicU64,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Replication {
+            serving: AtomicBool::new(false),
+            log: Mutex::new(Log { id: new_id(), offset: 0, records: VecDeque::new(), bytes: 0 }),
+            feed: broadcast::channel(FEED_CAPACITY).0,
+            leader: Mutex::new(None),
+            connected: AtomicBool::new(false),
+            followers: Mutex::new(BTreeMap::new()),
+            next_follower: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Replication {
+    pub(crate) fn serve(&self) {
+        self.serving.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn is_serving(&self) -> bool {
+        self.serving.load(Ordering::Acquire)
+    }
+    // Asigna el siguiente offset al registro y lo envia a los seguidores conectados.
+    // Se llama con el lock de la clave tomado, despues de aplicar el cambio.
+    pub(crate) fn publish(&self, record: Vec<u8>) {
+        let record: Arc<[u8]> = record.into();
+        let mut log = self.log();
+        log.offset += 1;
+        let offset = log.offset;
+        log.bytes += record.len();
+        log.records.push_back((offset, record.clone()));
+        while log.bytes > BACKLOG_BYTES {
+            let Some((_, old)) = log.records.pop_front() else { break };
+            log.bytes -= old.len();
+        }
+        // Sin seguidores no hay receptores: no es un error
+        let _ = self.feed.send((offset, record));
+    }
+    // Para una sincronizacion completa (con las escrituras detenidas): lo que llegue
+    // despues del offset devuelto sale por el receptor
+    pub(crate) fn subscribe(&self) -> (broadcast::Receiver<FeedRecord>, String, u64) {
+        let log = self.log();
+        (self.feed.subscribe(), log.id.clone(), log.offset)
+    }
+    // Registros posteriores a `offset` si el seguidor viene de esta historia y el backlog
+    // todavia los tiene todos; None = hace falta una sincronizacion completa
+    pub(crate) fn resume(&self, id: &str, offset: u64) -> Option<(broadcast::Receiver<FeedRecord>, Vec<FeedRecord>)> {
+        let log = self.log();
+        if id != log.id || offset > log.offset {
+            return None;
+        }
+        let first = log.records.front().map_or(log.offset + 1, |(first, _)| *first);
+        if offset + 1 < first {
+            return None;
+        }
+        let pending = log.records.iter().filter(|(at, _)| *at > offset).cloned().collect();
+        Some((self.feed.subscribe(), pending))
+    }
+
+    pub(crate) fn position(&self) -> (String, u64) {
+        let log = self.log();
+        (log.id.clone(), log.offset)
+    }
+
+    pub(crate) fn offset(&self) -> u64 {
+        self.log().offset
+    }
+    // Seguidor: historia y offset del lider despues de una sincronizacion completa
+    pub(crate) fn set_position(&self, id: String, offset: u64) {
+        let mut log = self.log();
+        log.id = id;
+        log.offset = offset;
+    }
+    // Seguidor: registro aplicado
+    pub(crate) fn advance(&self, offset: u64) {
+        self.log().offset = offset;
+    }
+
+    pub(crate) fn set_leader(&self, leader: String) {
+        *self.leader.lock().unwrap_or_else(|e| e.into_inner()) = Some(leader);
+    }
+
+    pub(crate) fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Release);
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn add_follower(&self, addr: String, offset: u64) -> u64 {
+        let id = self.next_follower.fetch_add(1, Ordering::Relaxed);
+        self.followers().insert(id, FollowerInfo { addr, offset });
+        id
+    }
+
+    pub(crate) fn ack(&self, follower: u64, offset: u64) {
+        if let Some(info) = self.followers().get_mut(&follower) {
+            info.offset = offset;
+        }
+    }
+
+    pub(crate) fn remove_follower(&self, follower: u64) {
+        self.followers().remove(&follower);
+    }
+
+    pub(crate) fn info(&self) -> ReplicationInfo {
+        let (id, offset) = self.position();
+        ReplicationInfo {
+            id,
+            offset,
+            leader: self.leader.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            connected: self.is_connected(),
+            followers: self.followers().values().cloned().collect(),
+        }
+    }
+
+    fn log(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn followers(&self) -> MutexGuard<'_, BTreeMap<u64, FollowerInfo>> {
+        self.followers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Id aleatorio de 128 bits en hexadecimal (RandomState trae claves aleatorias por proceso)
+fn new_id() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let high = RandomState::new().hash_one(nanos);
+    let low = RandomState::new().hash_one(high);
+    format!("{:016x}{:016x}", high, low)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let frames = vec![
+            Frame::Full { id: "abc".to_string(), offset: 7, snapshot: b"snap".to_vec() },
+            Frame::Continue { offset: 3 },
+            Frame::Record { offset: 4, record: LogRecord::Delete { key: "k".to_string() } },
+            Frame::Ping { offset: 9 },
+        ];
+        let bytes: Vec<u8> = frames.iter().flat_map(Frame::encode).collect();
+        let mut reader = bytes.as_slice();
+        for frame in frames {
+            assert_eq!(Frame::read(&mut reader).await.unwrap(), frame);
+        }
+        let mut reader = &handshake("abc", 5)[..];
+        assert_eq!(read_handshake(&mut reader).await.unwrap(), ("abc".to_string(), 5));
+        assert!(read_handshake(&mut &b"NOTREPL!\0\0"[..]).await.is_err());
+    }
+
+    #[test]
+    fn test_resume_from_backlog() {
+        let replication = Replication::default();
+        let (id, _) = replication.position();
+        for _ in 0..3 {
+            replication.publish(vec![0; 8]);
+        }
+        let (_, pending) = replication.resume(&id, 1).unwrap();
+        assert_eq!(pending.iter().map(|(offset, _)| *offset).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(replication.resume(&id, 3).unwrap().1.is_empty());
+        assert!(replication.resume(&id, 4).is_none());
+        assert!(replication.resume("other", 1).is_none());
+
+        // Lo que ya salio del backlog obliga a una sincronizacion completa
+        replication.publish(vec![0; BACKLOG_BYTES]);
+        assert!(replication.resume(&id, 2).is_none());
+        assert_eq!(replication.resume(&id, 3).unwrap().1.len(), 1);
+    }
+}
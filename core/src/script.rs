@@ -0,0 +1,164 @@
+// Scripts del lado del servidor (Rhai): motor aislado, cache por hash y conversion del resultado
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use rhai::packages::{Package, StandardPackage};
+use rhai::{Dynamic, Engine, EvalAltResult, Module, Shared, AST};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+use crate::errors::{DbError, MAX_VALUE_SIZE};
+
+// Tiempo maximo de ejecucion si la configuracion no dice otro
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+// Scripts compilados que se conservan; al llenarse se descarta uno cualquiera
+const MAX_CACHED: usize = 1024;
+// Cada cuantas operaciones del script se mira el reloj
+const CLOCK_EVERY: u64 = 256;
+// Limites del lenguaje
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_COLLECTION_SIZE: usize = 1 << 20;
+
+// Hash con el que se identifica un script (SHA-256 en hexadecimal)
+pub fn script_hash(source: &str) -> String {
+    Sha256::digest(source.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Biblioteca estandar compartida y scripts compilados por hash
+pub(crate) struct Scripts {
+    stdlib: Shared<Module>,
+    cache: RwLock<HashMap<String, Arc<AST>>>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Scripts { stdlib: StandardPackage::new().as_shared_module(), cache: RwLock::new(HashMap::new()) }
+    }
+}
+
+impl Scripts {
+    // Motor aislado: sin modulos, sin eval ni print y con limites de tamaño.
+    // El tiempo se cuenta desde la primera operacion del script.
+    pub(crate) fn engine(&self, timeout: Duration) -> Engine {
+        let mut engine = Engine::new_raw();
+        engine.register_global_module(self.stdlib.clone());
+        engine.disable_symbol("eval");
+        engine
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+            .set_max_string_size(MAX_VALUE_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE);
+        let start = OnceLock::new();
+        engine.on_progress(move |operations| {
+            let start = start.get_or_init(Instant::now);
+            (operations % CLOCK_EVERY == 0 && start.elapsed() > timeout).then_some(Dynamic::UNIT)
+        });
+        engine
+    }
+    // Compila el script (si no estaba) y lo guarda en la cache
+    pub(crate) fn load(&self, engine: &Engine, source: &str) -> Result<(String, Arc<AST>), DbError> {
+        let sha = script_hash(source);
+        if let Some(ast) = self.get(&sha) {
+            return Ok((sha, ast));
+        }
+        let ast = Arc::new(engine.compile(source).map_err(|e| DbError::Script(e.to_string()))?);
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        if cache.len() >= MAX_CACHED {
+            if let Some(victim) = cache.keys().next().cloned() {
+                cache.remove(&victim);
+            }
+        }
+        cache.insert(sha.clone(), ast.clone());
+        Ok((sha, ast))
+    }
+
+    pub(crate) fn get(&self, sha: &str) -> Option<Arc<AST>> {
+        self.cache.read().unwrap_or_else(|e| e.into_inner()).get(&sha.to_ascii_lowercase()).cloned()
+    }
+}
+
+// Error de ejecucion; la terminacion por on_progress es el limite de tiempo
+pub(crate) fn eval_error(err: EvalAltResult, timeout: Duration) -> DbError {
+    match err {
+        EvalAltResult::ErrorTerminated(..) => DbError::ScriptTimeout { limit_ms: timeout.as_millis() as u64 },
+        other => DbError::Script(other.to_string()),
+    }
+}
+
+// Valor de Rhai como bytes para guardarlo en una clave
+pub(crate) fn to_bytes(value: Dynamic) -> Vec<u8> {
+    if value.is_blob() {
+        return value.cast::<rhai::Blob>();
+    }
+    // Numeros, booleanos, etc. se guardan con su forma de texto
+    value.to_string().into_bytes()
+}
+
+// Bytes de una clave como valor de Rhai: texto si es UTF-8, blob si no
+pub(crate) fn from_bytes(bytes: Vec<u8>) -> Dynamic {
+    match String::from_utf8(bytes) {
+        Ok(text) => text.into(),
+        Err(e) => Dynamic::from_blob(e.into_bytes()),
+    }
+}
+
+// Resultado del script como JSON (lo que no tiene equivalente se devuelve como texto)
+pub(crate) fn to_json(value: Dynamic) -> Json {
+    if value.is_unit() {
+        return Json::Null;
+    }
+    if let Ok(flag) = value.as_bool() {
+        return Json::Bool(flag);
+    }
+    if let Ok(number) = value.as_int() {
+        return Json::from(number);
+    }
+    if let Ok(number) = value.as_float() {
+        return serde_json::Number::from_f64(number).map_or(Json::Null, Json::Number);
+    }
+    if value.is_blob() {
+        return match String::from_utf8(value.cast::<rhai::Blob>()) {
+            Ok(text) => Json::String(text),
+            Err(e) => Json::from(e.into_bytes()),
+        };
+    }
+    if value.is_array() {
+        return Json::Array(value.cast::<rhai::Array>().into_iter().map(to_json).collect());
+    }
+    if value.is_map() {
+        return Json::Object(value.cast::<rhai::Map>().into_iter().map(|(k, v)| (k.to_string(), to_json(v))).collect());
+    }
+    Json::String(value.to_string())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_script_hash() {
+        assert_eq!(script_hash(""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        let scripts = Scripts::default();
+        let engine = scripts.engine(DEFAULT_TIMEOUT);
+        let (sha, _) = scripts.load(&engine, "1 + 1").unwrap();
+        assert!(scripts.get(&sha.to_uppercase()).is_some());
+        assert!(matches!(scripts.load(&engine, "1 +"), Err(DbError::Script(_))));
+    }
+
+    #[test]
+    fn test_sandbox_and_conversion() {
+        let scripts = Scripts::default();
+        let engine = scripts.engine(DEFAULT_TIMEOUT);
+        let value: Dynamic = engine.eval(r#"#{ n: 1, f: 1.5, list: [true, ()], text: "a" + "b" }"#).unwrap();
+        assert_eq!(to_json(value), json!({"n": 1, "f": 1.5, "list": [true, null], "text": "ab"}));
+        assert!(engine.eval::<Dynamic>(r#"eval("1")"#).is_err());
+        assert!(engine.eval::<Dynamic>(r#"import "os" as os; 1"#).is_err());
+
+        let engine = scripts.engine(Duration::from_millis(20));
+        let err = engine.eval::<Dynamic>("loop {}").unwrap_err();
+        assert_eq!(eval_error(*err, Duration::from_millis(20)), DbError::ScriptTimeout { limit_ms: 20 });
+    }
+}
@@ -0,0 +1,245 @@
+// Importaciones
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use crate::encryption::{missing_key, Keyring};
+use crate::value::Value;
+
+// Cabecera del archivo de snapshot
+const MAGIC: &[u8; 8] = b"NANOSNAP";
+pub const SNAPSHOT_VERSION: u32 = 3;
+// Snapshot cifrado: magic | bloque sellado con el snapshot completo (ver Keyring::seal)
+const ENCRYPTED_MAGIC: &[u8; 8] = b"NANOSENC";
+const SNAPSHOT_CONTEXT: &[u8] = b"nanodb-snapshot";
+
+// Entrada del snapshot (expiracion absoluta en milisegundos unix)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub key: String,
+    pub value: Value,
+    pub expires_at: Option<u64>,
+}
+
+// Resultado de guardar un snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotInfo {
+    pub path: PathBuf,
+    pub keys: usize,
+    pub bytes: u64,
+}
+
+// Formato (big-endian):
+//   magic (8) | version (4) | [checkpoint (8), desde v3] | entries (8) | [entry]* | crc32 (4)
+//   entry v1: key_len (4) | key | value_len (4) | value
+//   entry v2: key_len (4) | key | value_len (4) | value | expires_at (8, 0 = sin expiracion)
+//   entry v3: key_len (4) | key | type (1) | value_len (4) | value | expires_at (8)
+// v1 y v2 solo guardan cadenas; en v3 las colecciones van codificadas segun su tipo (ver Value::encode).
+// El checkpoint identifica el registro del log que marca el momento del snapshot (0 = ninguno).
+// El crc32 cubre todo lo anterior.
+pub fn encode(checkpoint: u64, entries: &[SnapshotEntry]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+    buf.extend_from_slice(&checkpoint.to_be_bytes());
+    buf.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+    for entry in entries {
+        buf.extend_from_slice(&(entry.key.len() as u32).to_be_bytes());
+        buf.extend_from_slice(entry.key.as_bytes());
+        let (tag, value) = entry.value.encode();
+        buf.push(tag);
+        buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&value);
+        buf.extend_from_slice(&entry.expires_at.unwrap_or(0).to_be_bytes());
+    }
+    let crc = crc32fast::hash(&buf);
+    buf.extend_from_slice(&crc.to_be_bytes());
+    buf
+}
+
+// Devuelve el checkpoint y las entradas
+pub fn decode(buf: &[u8]) -> io::Result<(u64, Vec<SnapshotEntry>)> {
+    // 1. Verificar cabecera y checksum
+    if buf.len() < MAGIC.len() + 4 + 8 + 4 || &buf[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not a NanoDb snapshot file"));
+    }
+    let (body, trailer) = buf.split_at(buf.len() - 4);
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if crc32fast::hash(body) != expected {
+        return Err(invalid("snapshot checksum mismatch"));
+    }
+    let mut reader = Cursor { buf: body, pos: MAGIC.len() };
+    let version = reader.u32()?;
+    if version == 0 || version > SNAPSHOT_VERSION {
+        return Err(invalid(&format!("unsupported snapshot version {}", version)));
+    }
+
+    // 2. Leer entradas
+    let checkpoint = match version {
+        1 | 2 => 0,
+        _ => reader.u64()?,
+    };
+    let count = reader.u64()?;
+    let mut entries = Vec::with_capacity(count.min(1 << 20) as usize);
+    for _ in 0..count {
+        let key_len = reader.u32()? as usize;
+        let key = String::from_utf8(reader.bytes(key_len)?.to_vec())
+            .map_err(|_| invalid("invalid UTF-8 key in snapshot"))?;
+        let tag = match version {
+            1 | 2 => 0,
+            _ => reader.bytes(1)?[0],
+        };
+        let value_len = reader.u32()? as usize;
+        let value = Value::decode(tag, reader.bytes(value_len)?)?;
+        let expires_at = match version {
+            1 => None,
+            _ => Some(reader.u64()?).filter(|&at| at != 0),
+        };
+        entries.push(SnapshotEntry { key, value, expires_at });
+    }
+    if reader.pos != body.len() {
+        return Err(invalid("trailing bytes in snapshot"));
+    }
+    Ok((checkpoint, entries))
+}
+
+// Escribe el snapshot en un archivo temporal y lo renombra (reemplazo atomico).
+// Con keyring se cifra completo con la clave actual.
+pub fn write(path: &Path, checkpoint: u64, entries: &[SnapshotEntry], keyring: Option<&Keyring>) -> io::Result<SnapshotInfo> {
+    let mut bytes = encode(checkpoint, entries);
+    if let Some(keyring) = keyring {
+        let sealed = keyring.seal(SNAPSHOT_CONTEXT, &bytes);
+        bytes = [&ENCRYPTED_MAGIC[..], &sealed].concat();
+    }
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    info!(path = %path.display(), keys = entries.len(), bytes = bytes.len(), "Snapshot written");
+    Ok(SnapshotInfo {
+        path: path.to_path_buf(),
+        keys: entries.len(),
+        bytes: bytes.len() as u64,
+    })
+}
+
+// Lee un snapshot en claro o cifrado (con la clave actual o una anterior)
+pub fn read(path: &Path, keyring: Option<&Keyring>) -> io::Result<(u64, Vec<SnapshotEntry>)> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;
+    match buf.strip_prefix(ENCRYPTED_MAGIC) {
+        Some(sealed) => match keyring {
+            Some(keyring) => decode(&keyring.open(SNAPSHOT_CONTEXT, sealed)?),
+            None => Err(missing_key(path)),
+        },
+        None => decode(&buf),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Lector sencillo sobre un slice
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let slice = self.buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated snapshot"))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let b = self.bytes(8)?;
+        let mut arr = [0u8; 8];
+        arr.copy_from_slice(b);
+        Ok(u64::from_be_bytes(arr))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbConfig, DbResult, NanoDb};
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let entries = vec![
+            SnapshotEntry { key: "a".to_string(), value: Value::Bytes(b"1".to_vec()), expires_at: None },
+            SnapshotEntry { key: "empty".to_string(), value: Value::Bytes(Vec::new()), expires_at: Some(1234) },
+            SnapshotEntry { key: "set".to_string(), value: Value::Set([b"m".to_vec()].into()), expires_at: None },
+        ];
+        assert_eq!(decode(&encode(7, &entries)).unwrap(), (7, entries));
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let mut body = Vec::new();
+        body.extend_from_slice(MAGIC);
+        body.extend_from_slice(&1u32.to_be_bytes());
+        body.extend_from_slice(&1u64.to_be_bytes());
+        body.extend_from_slice(&1u32.to_be_bytes());
+        body.extend_from_slice(b"k");
+        body.extend_from_slice(&1u32.to_be_bytes());
+        body.extend_from_slice(b"v");
+        let crc = crc32fast::hash(&body);
+        body.extend_from_slice(&crc.to_be_bytes());
+
+        let (checkpoint, entries) = decode(&body).unwrap();
+        assert_eq!(checkpoint, 0);
+        assert_eq!(entries, vec![SnapshotEntry { key: "k".to_string(), value: Value::Bytes(b"v".to_vec()), expires_at: None }]);
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let entry = SnapshotEntry { key: "key".to_string(), value: Value::Bytes(b"value".to_vec()), expires_at: None };
+        let mut bytes = encode(0, &[entry]);
+        let last = bytes.len() - 14;
+        bytes[last] ^= 0xff;
+        let err = decode(&bytes).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_save_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            snapshot_path: Some(dir.path().join("nanodb.snap")),
+            ..DbConfig::default()
+        };
+
+        let db = NanoDb::with_config(config.clone()).unwrap();
+        db.set("user:1".to_string(), b"ana".to_vec()).await;
+        db.set("user:2".to_string(), b"luis".to_vec()).await;
+        let info = match db.save().await {
+            DbResult::Ok(info) => info,
+            other => panic!("Expected snapshot info, got {:?}", other),
+        };
+        assert_eq!(info.keys, 2);
+
+        let restored = NanoDb::with_config(config).unwrap();
+        assert!(matches!(restored.get("user:1").await, DbResult::Ok(ref v) if v == b"ana"));
+        assert!(matches!(restored.get("user:2").await, DbResult::Ok(ref v) if v == b"luis"));
+    }
+
+    #[tokio::test]
+    async fn test_save_without_path() {
+        let db = NanoDb::new();
+        assert!(matches!(db.save().await, DbResult::Err(_)));
+    }
+}
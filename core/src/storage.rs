@@ -1,32 +1,354 @@
 // Importaciones
-use dashmap::DashMap;   // <- Import necesario
-use crate::DbResult;   // <- Import de DbResult
-use tracing::{info, debug, warn};
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+use dashmap::DashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use crate::{DbError, DbResult};   // <- Import de DbResult
+use crate::operations::{CasOutcome, DbOperation, DbResponse, KeysPage};
+use crate::aof::{AppendLog, LogRecord};
+use crate::compression::Compression;
+use crate::index::{self, IndexDef, IndexQuery, Indexes};
+use crate::config::{DbConfig, EvictionPolicy, FsyncPolicy, StorageEngine};
+use crate::keyspace::{prefix_end, Entry, Keyspace};
+use crate::metrics::Metrics;
+use crate::snapshot::{self, SnapshotEntry, SnapshotInfo};
+use crate::pubsub::{PubSub, Subscription};
+use crate::replication::{Replication, ReplicationInfo};
+use crate::script::Scripts;
+use crate::value::{CollectionOp, Value};
+use crate::watch::{ChangeEvent, ChangeKind, WatchFilter, Watcher, WATCH_CAPACITY};
+use tokio::sync::broadcast;
+use tracing::{info, debug, warn, error};
+
+mod batch;
+mod collections;
+mod counters;
+mod indexes;
+mod json;
+mod namespace;
+mod replication;
+mod script;
+mod txn;
+
+// Bytes extra contabilizados por entrada (estructura, hash, metadatos)
+const ENTRY_OVERHEAD: u64 = 64;
+// Candidatos que se recogen en cada pasada de desalojo
+const EVICTION_POOL_SIZE: usize = 16;
+// Locks por franja de claves para escrituras compuestas (CAS, prefijos)
+const LOCK_STRIPES: usize = 64;
+
+// Memoria contabilizada para una entrada
+fn entry_size(key: &str, value_len: usize) -> u64 {
+    key.len() as u64 + value_len as u64 + ENTRY_OVERHEAD
+}
+
+// Error de disco al leer el keyspace (motor LSM)
+fn read_failed(e: io::Error) -> DbError {
+    DbError::Storage(format!("lsm storage read failed: {}", e))
+}
+
+// Tiempo actual en milisegundos unix
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Expiracion absoluta a partir de un TTL relativo
+fn deadline(ttl: Duration) -> u64 {
+    now_millis().saturating_add(ttl.as_millis() as u64)
+}
 
 // Definicion de la base de datos
 pub struct NanoDb {
-    data: DashMap<String, Vec<u8>>,    // <- Dashmap (no Dashmap)
+    data: Keyspace,                    // <- DashMap o skiplist segun el motor
+    aof: Option<AppendLog>,            // <- Log append-only opcional
+    snapshot_path: Option<PathBuf>,    // <- Destino de save()
+    saving: AtomicBool,                // <- Evita snapshots simultaneos
+    used_memory: AtomicU64,            // <- Bytes de claves + valores
+    max_memory: Option<u64>,           // <- Limite opcional
+    read_only: bool,                   // <- Rechaza escrituras de clientes
+    compression: Option<Compression>,  // <- Compresion de cadenas grandes en memoria
+    eviction: EvictionPolicy,
+    clock: AtomicU64,                  // <- Reloj logico para LRU
+    revision: AtomicU64,               // <- Contador de escrituras (versiones de clave)
+    metrics: Arc<Metrics>,
+    stripes: Vec<Mutex<()>>,           // <- Serializa escrituras sobre la misma clave
+    changes: broadcast::Sender<ChangeEvent>,  // <- Change-feed para watch()
+    pubsub: PubSub,                    // <- Canales de mensajes (no persistentes)
+    indexes: Indexes,                  // <- Indices secundarios sobre documentos JSON
+    scripts: Arc<Scripts>,             // <- Scripts compilados, compartidos con los namespaces
+    replication: Replication,          // <- Backlog del lider o posicion del seguidor
+    config: DbConfig,                  // <- Base para crear namespaces
+    namespace_name: Option<String>,    // <- None = base principal
+    namespaces: DashMap<String, Arc<NanoDb>>,
 }
 
+impl Default for NanoDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 // Implementaciones
 impl NanoDb {
 
     // Constructor
     pub fn new() -> Self {
+        let metrics = Metrics::new();
         NanoDb {
-            data: DashMap::new(),
+            data: Keyspace::new(StorageEngine::default()),
+            aof: None,
+            snapshot_path: None,
+            saving: AtomicBool::new(false),
+            used_memory: AtomicU64::new(0),
+            max_memory: None,
+            read_only: false,
+            compression: None,
+            eviction: EvictionPolicy::default(),
+            clock: AtomicU64::new(0),
+            revision: AtomicU64::new(0),
+            pubsub: PubSub::new(metrics.clone()),
+            indexes: Indexes::default(),
+            scripts: Arc::new(Scripts::default()),
+            replication: Replication::default(),
+            metrics,
+            config: DbConfig::default(),
+            namespace_name: None,
+            namespaces: DashMap::new(),
+            stripes: (0..LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            changes: broadcast::channel(WATCH_CAPACITY).0,
+        }
+    }
+    // Constructor con log append-only (fsync cada segundo)
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_config(DbConfig {
+            aof_path: Some(path.as_ref().to_path_buf()),
+            fsync: FsyncPolicy::default(),
+            ..DbConfig::default()
+        })
+    }
+    // Constructor a partir de una configuracion
+    pub fn with_config(config: DbConfig) -> io::Result<Self> {
+        check_engine(&config)?;
+        let mut db = Self::new();
+        db.data = Keyspace::open(&config)?;
+        db.max_memory = config.max_memory;
+        // Un seguidor solo cambia con lo que recibe del lider
+        db.read_only = config.read_only || config.replica_of.is_some();
+        db.compression = config.compression;
+        db.eviction = config.eviction;
+        // 0. El motor LSM con directorio ya tiene sus datos: solo falta la contabilidad en memoria
+        if db.data.len() > 0 {
+            db.restore_accounting();
+            db.purge_expired();
+            info!(keys = db.data.len(), "Data restored from lsm storage");
+        }
+        // 1. Cargar el snapshot (si existe) sobre un keyspace vacio
+        let mut checkpoint = 0;
+        if let Some(path) = &config.snapshot_path {
+            if path.exists() && db.data.len() == 0 {
+                let now = now_millis();
+                let (id, entries) = snapshot::read(path, config.encryption.as_ref())?;
+                checkpoint = id;
+                for SnapshotEntry { key, value, expires_at } in entries {
+                    if !matches!(expires_at, Some(at) if at <= now) {
+                        db.store(key, value, expires_at)?;
+                    }
+                }
+                info!(path = %path.display(), keys = db.data.len(), "Data restored from snapshot");
+            }
+            db.snapshot_path = Some(path.clone());
+        }
+        // 2. Reproducir el log append-only encima del snapshot: solo lo posterior a su
+        // checkpoint, o el log completo si el snapshot no tiene un checkpoint en este log
+        if let Some(path) = &config.aof_path {
+            let (aof, mut records) = AppendLog::open_encrypted(path, config.fsync, config.encryption.clone(), db.metrics.clone())?;
+            let start = records
+                .iter()
+                .rposition(|record| checkpoint != 0 && *record == LogRecord::Checkpoint { id: checkpoint })
+                .map_or(0, |position| position + 1);
+            for record in records.drain(start..) {
+                db.replay(record)?;
+            }
+            db.purge_expired();
+            info!(path = %path.display(), keys = db.data.len(), "Data restored from append-only log");
+            db.aof = Some(aof);
+        }
+        // 3. Reconstruir los indices declarados y reabrir los namespaces persistidos
+        db.config = config;
+        db.restore_indexes()?;
+        db.restore_namespaces()?;
+        Ok(db)
+    }
+    // Memoria usada y ultima revision a partir de un keyspace ya cargado
+    fn restore_accounting(&self) {
+        let totals = self.data.totals();
+        self.used_memory.store(totals.bytes + totals.keys as u64 * ENTRY_OVERHEAD, Ordering::Relaxed);
+        self.revision.store(totals.version, Ordering::Relaxed);
+    }
+    // Aplicar un registro en memoria (sin escribirlo en el log).
+    // Un error de disco (motor LSM) deja sin aplicar el registro que fallo.
+    fn replay(&self, record: LogRecord) -> io::Result<()> {
+        match record {
+            LogRecord::Set { key, value, expires_at } => return self.store(key, Value::Bytes(value), expires_at),
+            LogRecord::Delete { key } => {
+                if let Some(old_len) = self.data.remove(&key)? {
+                    self.indexes.remove(&key);
+                    self.release(entry_size(&key, old_len));
+                    self.notify(ChangeKind::Delete, &key, None, 0, None);
+                }
+            }
+            LogRecord::Clear => {
+                self.data.clear()?;
+                self.indexes.clear();
+                self.used_memory.store(0, Ordering::Relaxed);
+                self.notify(ChangeKind::Clear, "", None, 0, None);
+            }
+            LogRecord::Expire { key, expires_at } => {
+                let version = self.next_revision();
+                self.data.set_expiry(&key, expires_at, version)?;
+                self.notify(ChangeKind::Expire, &key, None, version, expires_at);
+            }
+            LogRecord::Batch(records) => {
+                for record in records {
+                    self.replay(record)?;
+                }
+            }
+            LogRecord::Checkpoint { .. } => {}
+            LogRecord::Restore { key, value, expires_at } => return self.store(key, value, expires_at),
+            LogRecord::Update { key, op } => {
+                let version = self.next_revision();
+                let (old, new) = self.data.update(&key, version, self.tick(), || op.empty(), |value| op.apply(value))?;
+                self.used_memory.fetch_add(entry_size(&key, new), Ordering::Relaxed);
+                if let Some(old) = old {
+                    self.release(entry_size(&key, old));
+                }
+                // Una coleccion vacia no se conserva
+                if let Some(old) = self.data.remove_if(&key, |entry| entry.value.is_empty())? {
+                    self.release(entry_size(&key, old));
+                    self.notify(ChangeKind::Delete, &key, None, 0, None);
+                } else {
+                    let expires_at = self.data.get(&key, |entry| entry.expires_at)?.flatten();
+                    self.notify(ChangeKind::Set, &key, None, version, expires_at);
+                }
+                self.reindex(&key)?;
+            }
+        }
+        Ok(())
+    }
+    // Guardar un valor reemplazando el anterior (SET o carga del snapshot)
+    fn store(&self, key: String, value: Value, expires_at: Option<u64>) -> io::Result<()> {
+        let key_len = key.len();
+        let version = self.next_revision();
+        if self.watched() {
+            self.notify(ChangeKind::Set, &key, value.to_bytes(), version, expires_at);
+        }
+        let value = self.compress(value);
+        let size = entry_size(&key, value.size());
+        let entry = Entry::new(value, expires_at, version, self.tick());
+        // La clave solo se copia si hay indices que mantener
+        let indexed = (!self.indexes.is_empty()).then(|| key.clone());
+        let old_len = self.data.insert(key, entry)?;
+        self.used_memory.fetch_add(size, Ordering::Relaxed);
+        if let Some(old_len) = old_len {
+            self.release(key_len as u64 + old_len as u64 + ENTRY_OVERHEAD);
+        }
+        if let Some(key) = indexed {
+            self.reindex(&key)?;
+        }
+        Ok(())
+    }
+    // Actualiza los indices que cubren la clave con su valor actual
+    fn reindex(&self, key: &str) -> io::Result<()> {
+        if self.indexes.is_empty() {
+            return Ok(());
+        }
+        let paths = self.indexes.matching(key);
+        if paths.is_empty() {
+            return Ok(());
         }
+        let values = self
+            .data
+            .get(key, |entry| paths.iter().map(|(_, path)| index::extract(&entry.value, path)).collect())?
+            .unwrap_or_else(|| vec![None; paths.len()]);
+        self.indexes.apply(key, paths.into_iter().map(|(name, _)| name).zip(values).collect());
+        Ok(())
+    }
+    // Version comprimida de una cadena si la compresion esta activa y conviene
+    fn compress(&self, value: Value) -> Value {
+        let (Some(compression), Value::Bytes(raw)) = (&self.compression, &value) else {
+            return value;
+        };
+        match compression.apply(raw) {
+            Some(compressed) => {
+                self.metrics.record_compression(raw.len(), compressed.len());
+                Value::Compressed(compressed)
+            }
+            None => value,
+        }
+    }
+    // Escritura de un cliente
+    fn write(&self, record: LogRecord) -> DbResult<()> {
+        if self.read_only {
+            return DbResult::Err(DbError::ReadOnly);
+        }
+        self.commit(record)
+    }
+    // Registrar en el log (si existe), aplicar el cambio en memoria y enviarlo a los seguidores.
+    // Un seguidor aplica asi lo que recibe del lider.
+    fn commit(&self, record: LogRecord) -> DbResult<()> {
+        // Se codifica antes de aplicarlo porque el log se queda con el registro
+        let replicated = self.replication.is_serving().then(|| {
+            let mut bytes = Vec::new();
+            record.encode(&mut bytes);
+            bytes
+        });
+        let applied = match &self.aof {
+            Some(aof) => {
+                let mut applied = Ok(());
+                if let Err(e) = aof.record(record, |record| applied = self.replay(record)) {
+                    error!(error = %e, path = %aof.path().display(), "Failed to append to log");
+                    return DbResult::Err(DbError::Storage(format!("append-only log write failed: {}", e)));
+                }
+                applied
+            }
+            None => self.replay(record),
+        };
+        // El motor LSM escribe en su propio log al aplicar el registro; ademas puede fallar
+        // el volcado que dispara esta misma escritura
+        match applied.err().or_else(|| self.data.take_error()) {
+            Some(e) => DbResult::Err(DbError::Storage(format!("lsm storage write failed: {}", e))),
+            None => {
+                if let Some(bytes) = replicated {
+                    self.replication.publish(bytes);
+                }
+                DbResult::Ok(())
+            }
+        }
+    }
+    // Las escrituras pasan por el log propio: base de escritura o seguidor
+    fn logs_writes(&self) -> bool {
+        !self.read_only || self.config.replica_of.is_some()
     }
     // Metodos
     pub async fn get(&self, key: &str) -> DbResult<Vec<u8>> {
         debug!(key = %key, "Getting value");
-        match self.data.get(key) {
-            Some(value) => {
+        self.metrics.increment_get();
+        match self.live_bytes(key) {
+            Ok(Some(value)) => {
                 debug!(key = %key, size = value.len(), "Value found");
-                DbResult::Ok(value.clone())
+                DbResult::Ok(value)
             },
-            None => {
+            Err(e) => DbResult::Err(e),
+            Ok(None) => {
                 debug!(key = %key, "Value not found");
                 DbResult::NotFound
             },
@@ -34,38 +356,714 @@ impl NanoDb {
     }
     // Metodos
     pub async fn set(&self, key: String, value: Vec<u8>) -> DbResult<()> {
-        debug!(key = %key, size = value.len(), "Setting value");
-        self.data.insert(key.clone(), value);
-        info!(key = %key, "Value set successfully");
-        DbResult::Ok(())
+        self.set_with_ttl(key, value, None).await
+    }
+    // SET con expiracion opcional (sin TTL la clave queda persistente)
+    pub async fn set_with_ttl(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> DbResult<()> {
+        debug!(key = %key, size = value.len(), ttl_ms = ttl.map(|t| t.as_millis() as u64), "Setting value");
+        self.metrics.increment_set();
+        if let DbResult::Err(e) = self.reserve(&key, value.len()) {
+            return DbResult::Err(e);
+        }
+        let expires_at = ttl.map(deadline);
+        let _lock = self.lock_key(&key);
+        let result = self.write(LogRecord::Set { key: key.clone(), value, expires_at });
+        if let DbResult::Ok(()) = result {
+            info!(key = %key, "Value set successfully");
+        }
+        result
     }
     // Metodos
     pub async fn delete(&self, key: &str) -> DbResult<()> {
         debug!(key = %key, "Deleting value");
-        let existed = self.data.remove(key).is_some();
-        if existed {
+        self.metrics.increment_delete();
+        let _lock = self.lock_key(key);
+        match self.live(key, |_| ()) {
+            Ok(Some(())) => {}
+            Ok(None) => {
+                warn!(key = %key, "Attempted to delete non-existent key");
+                return DbResult::Ok(());
+            }
+            Err(e) => return DbResult::Err(e),
+        }
+        let result = self.write(LogRecord::Delete { key: key.to_string() });
+        if let DbResult::Ok(()) = result {
             info!(key = %key, "Value deleted successfully");
-        } else {
-            warn!(key = %key, "Attempted to delete non-existent key");
         }
-        DbResult::Ok(())
+        result
     }
     // Metodos
     pub async fn clear(&self) -> DbResult<()> {
         let count = self.data.len();
         debug!(count = count, "Clearing all data");
-        self.data.clear();
-        info!(count = count, "All data cleared successfully");
-        DbResult::Ok(())
+        self.metrics.increment_clear();
+        let _locks = self.lock_all();
+        let result = self.write(LogRecord::Clear);
+        if let DbResult::Ok(()) = result {
+            info!(count = count, "All data cleared successfully");
+        }
+        result
     }
     // Metodos
     pub async fn exists(&self, key: &str) -> DbResult<bool> {
-        DbResult::Ok(self.data.contains_key(key))
+        match self.live(key, |_| ()) {
+            Ok(found) => DbResult::Ok(found.is_some()),
+            Err(e) => DbResult::Err(e),
+        }
     }
     // Metodos
     pub async fn keys(&self) -> DbResult<Vec<String>> {
-        let keys: Vec<String> = self.data.iter().map(|kv| kv.key().clone()).collect();
+        self.metrics.increment_keys();
+        let now = now_millis();
+        let mut keys: Vec<String> = Vec::new();
+        let listed = self.data.for_each(|key, entry| {
+            if !entry.is_expired(now) {
+                keys.push(key.to_string());
+            }
+        });
+        if let Err(e) = listed {
+            return DbResult::Err(read_failed(e));
+        }
         debug!(count = keys.len(), "Retrieved keys");
         DbResult::Ok(keys)
     }
-}
\ No newline at end of file
+    // Fijar un TTL sobre una clave existente
+    pub async fn expire(&self, key: &str, ttl: Duration) -> DbResult<bool> {
+        let _lock = self.lock_key(key);
+        match self.live(key, |_| ()) {
+            Ok(Some(())) => {}
+            Ok(None) => return DbResult::Ok(false),
+            Err(e) => return DbResult::Err(e),
+        }
+        let expires_at = Some(deadline(ttl));
+        match self.write(LogRecord::Expire { key: key.to_string(), expires_at }) {
+            DbResult::Ok(()) => {
+                debug!(key = %key, ttl_ms = ttl.as_millis() as u64, "Expiration set");
+                DbResult::Ok(true)
+            }
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+    // Quitar el TTL de una clave; false si no existe o no tenia TTL
+    pub async fn persist(&self, key: &str) -> DbResult<bool> {
+        let _lock = self.lock_key(key);
+        match self.live(key, |entry| entry.expires_at.is_some()) {
+            Ok(Some(true)) => {}
+            Ok(_) => return DbResult::Ok(false),
+            Err(e) => return DbResult::Err(e),
+        }
+        match self.write(LogRecord::Expire { key: key.to_string(), expires_at: None }) {
+            DbResult::Ok(()) => DbResult::Ok(true),
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+    // Tiempo restante de vida; Ok(None) si la clave no expira
+    pub async fn ttl(&self, key: &str) -> DbResult<Option<Duration>> {
+        match self.live(key, |entry| entry.expires_at) {
+            Ok(Some(expires_at)) => DbResult::Ok(expires_at.map(|at| {
+                Duration::from_millis(at.saturating_sub(now_millis()))
+            })),
+            Ok(None) => DbResult::NotFound,
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Version de la clave: cambia con cada escritura (SET, EXPIRE, PERSIST).
+    // Se asigna al aplicar el log, asi que solo es comparable dentro del mismo proceso.
+    pub async fn version(&self, key: &str) -> DbResult<u64> {
+        match self.live(key, |entry| entry.version) {
+            Ok(Some(version)) => DbResult::Ok(version),
+            Ok(None) => DbResult::NotFound,
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Numero de claves vivas
+    pub async fn size(&self) -> DbResult<usize> {
+        let now = now_millis();
+        let mut count = 0;
+        match self.data.for_each(|_, entry| count += usize::from(!entry.is_expired(now))) {
+            Ok(()) => DbResult::Ok(count),
+            Err(e) => DbResult::Err(read_failed(e)),
+        }
+    }
+    // Claves con un prefijo, en orden lexicografico
+    pub async fn keys_prefix(&self, prefix: &str) -> DbResult<Vec<String>> {
+        self.metrics.increment_keys();
+        match self.scan(Some(prefix), |_| Some(())) {
+            Ok(found) => DbResult::Ok(found.into_iter().map(|(key, _)| key).collect()),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Pagina de claves (orden lexicografico) estrictamente posteriores al cursor.
+    // El cursor es la ultima clave devuelta, asi que sigue siendo valido aunque
+    // se inserten o borren claves entre paginas. limit = 0 devuelve todas las restantes.
+    pub async fn keys_page(&self, prefix: Option<&str>, cursor: Option<&str>, limit: usize) -> DbResult<KeysPage> {
+        self.metrics.increment_keys();
+        let (mut lower, upper) = prefix_bounds(prefix);
+        if let Some(cursor) = cursor {
+            if prefix.is_none_or(|p| cursor >= p) {
+                lower = Bound::Excluded(cursor);
+            }
+        }
+        // Un elemento extra para saber si quedan mas claves
+        let fetch = if limit == 0 { 0 } else { limit + 1 };
+        let mut keys: Vec<String> = match self.visible(lower, bound_ref(&upper), false, fetch, |_| Some(())) {
+            Ok(found) => found.into_iter().map(|(key, _)| key).collect(),
+            Err(e) => return DbResult::Err(e),
+        };
+        let next_cursor = if limit > 0 && keys.len() > limit {
+            keys.truncate(limit);
+            keys.last().cloned()
+        } else {
+            None
+        };
+        DbResult::Ok(KeysPage { keys, next_cursor })
+    }
+    // Pares clave/valor en [start, end), en orden lexicografico o inverso.
+    // Los rangos y escaneos de valores solo incluyen cadenas, no colecciones.
+    // Para paginar hacia adelante: start = ultima clave + "\0"; hacia atras: end = ultima clave.
+    pub async fn range(&self, start: Option<&str>, end: Option<&str>, reverse: bool, limit: usize) -> DbResult<Vec<(String, Vec<u8>)>> {
+        self.metrics.increment_get();
+        let lower = start.map_or(Bound::Unbounded, Bound::Included);
+        let upper = end.map_or(Bound::Unbounded, Bound::Excluded);
+        match self.visible(lower, upper, reverse, limit, |entry| entry.value.to_bytes()) {
+            Ok(found) => DbResult::Ok(found),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Valores ordenados por clave, opcionalmente filtrados por prefijo
+    pub async fn values(&self, prefix: Option<&str>) -> DbResult<Vec<Vec<u8>>> {
+        match self.scan(prefix, |entry| entry.value.to_bytes()) {
+            Ok(found) => DbResult::Ok(found.into_iter().map(|(_, value)| value).collect()),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Pares clave/valor con un prefijo, ordenados por clave
+    pub async fn get_prefix(&self, prefix: &str) -> DbResult<Vec<(String, Vec<u8>)>> {
+        self.metrics.increment_get();
+        match self.scan(Some(prefix), |entry| entry.value.to_bytes()) {
+            Ok(found) => DbResult::Ok(found),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Borra todas las claves con un prefijo; devuelve cuantas se borraron
+    pub async fn delete_prefix(&self, prefix: &str) -> DbResult<usize> {
+        self.metrics.increment_delete();
+        let mut deleted = 0;
+        let found = match self.scan(Some(prefix), |_| Some(())) {
+            Ok(found) => found,
+            Err(e) => return DbResult::Err(e),
+        };
+        for (key, _) in found {
+            let _lock = self.lock_key(&key);
+            match self.live(&key, |_| ()) {
+                Ok(Some(())) => {}
+                Ok(None) => continue,
+                Err(e) => return DbResult::Err(e),
+            }
+            if let DbResult::Err(e) = self.write(LogRecord::Delete { key }) {
+                return DbResult::Err(e);
+            }
+            deleted += 1;
+        }
+        info!(prefix = %prefix, deleted = deleted, "Prefix deleted");
+        DbResult::Ok(deleted)
+    }
+    // Compare-and-swap atomico:
+    //   old_value None  -> solo si la clave no existe (crear si no existe)
+    //   new_value None  -> borrar si el valor coincide
+    // Si no coincide devuelve el valor actual.
+    pub async fn compare_and_swap(&self, key: &str, old_value: Option<Vec<u8>>, new_value: Option<Vec<u8>>) -> DbResult<CasOutcome> {
+        if let Some(value) = &new_value {
+            self.metrics.increment_set();
+            if let DbResult::Err(e) = self.reserve(key, value.len()) {
+                return DbResult::Err(e);
+            }
+        }
+        let _lock = self.lock_key(key);
+        // El nuevo valor conserva el TTL de la clave
+        let (current, expires_at) = match self.live(key, |entry| (entry.value.to_bytes(), entry.expires_at)) {
+            Ok(Some((Some(value), expires_at))) => (Some(value), expires_at),
+            Ok(Some((None, _))) => return DbResult::Err(DbError::TypeMismatch { key: key.to_string(), expected: "string" }),
+            Ok(None) => (None, None),
+            Err(e) => return DbResult::Err(e),
+        };
+        if current != old_value {
+            debug!(key = %key, "Compare-and-swap mismatch");
+            return DbResult::Ok(CasOutcome { swapped: false, current });
+        }
+        let record = match &new_value {
+            Some(value) => LogRecord::Set { key: key.to_string(), value: value.clone(), expires_at },
+            None if current.is_some() => LogRecord::Delete { key: key.to_string() },
+            None => return DbResult::Ok(CasOutcome { swapped: true, current: None }),
+        };
+        match self.write(record) {
+            DbResult::Ok(()) => {
+                debug!(key = %key, "Compare-and-swap applied");
+                DbResult::Ok(CasOutcome { swapped: true, current: new_value })
+            }
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+    // Ejecuta cualquier operacion y devuelve una respuesta tipada
+    pub async fn execute(&self, op: DbOperation) -> DbResponse {
+        match op {
+            DbOperation::Get { key, default } => match (self.get(&key).await, default) {
+                (DbResult::NotFound, Some(default)) => DbResponse::Value(default),
+                (result, _) => result.into_response(DbResponse::Value),
+            },
+            DbOperation::Set { key, value, ttl } => self.set_with_ttl(key, value, ttl).await.into_response(|_| DbResponse::Ok),
+            DbOperation::Delete { key } => self.delete(&key).await.into_response(|_| DbResponse::Ok),
+            DbOperation::Exists { key } => self.exists(&key).await.into_response(DbResponse::Bool),
+            DbOperation::Flush => self.clear().await.into_response(|_| DbResponse::Ok),
+            DbOperation::Keys => self.keys().await.into_response(DbResponse::Keys),
+            DbOperation::KeysCursor { prefix, cursor, limit } => self
+                .keys_page(prefix.as_deref(), cursor.as_deref(), limit)
+                .await
+                .into_response(DbResponse::KeysPage),
+            DbOperation::KeysPrefix { prefix } => self.keys_prefix(&prefix).await.into_response(DbResponse::Keys),
+            DbOperation::Values => self.values(None).await.into_response(DbResponse::Values),
+            DbOperation::ValuesPrefix { prefix } => self.values(Some(&prefix)).await.into_response(DbResponse::Values),
+            DbOperation::GetPrefix { prefix } => self.get_prefix(&prefix).await.into_response(DbResponse::Entries),
+            DbOperation::DeletePrefix { prefix } => self.delete_prefix(&prefix).await.into_response(DbResponse::Count),
+            DbOperation::Size => self.size().await.into_response(DbResponse::Count),
+            DbOperation::CompareAndSwap { key, old_value, new_value } => self
+                .compare_and_swap(&key, old_value, new_value)
+                .await
+                .into_response(DbResponse::Cas),
+            DbOperation::Range { start, end, reverse, limit } => self
+                .range(start.as_deref(), end.as_deref(), reverse, limit)
+                .await
+                .into_response(DbResponse::Entries),
+            DbOperation::Save => self.save().await.into_response(DbResponse::Snapshot),
+            DbOperation::Expire { key, ttl } => self.expire(&key, ttl).await.into_response(DbResponse::Bool),
+            DbOperation::Ttl { key } => self.ttl(&key).await.into_response(DbResponse::Ttl),
+            DbOperation::Persist { key } => self.persist(&key).await.into_response(DbResponse::Bool),
+            DbOperation::Version { key } => self.version(&key).await.into_response(DbResponse::Version),
+            DbOperation::Transaction { preconditions, operations } => self
+                .transaction(preconditions, operations)
+                .await
+                .into_response(DbResponse::Txn),
+            DbOperation::Publish { channel, message } => DbResponse::Count(self.publish(&channel, message)),
+            DbOperation::Watch { .. } => {
+                DbResponse::Error(DbError::NotImplemented("watch requires a streaming connection".to_string()))
+            }
+            DbOperation::CreateNamespace { name } => self.create_namespace(&name).into_response(DbResponse::Bool),
+            DbOperation::DropNamespace { name } => self.drop_namespace(&name).into_response(DbResponse::Bool),
+            DbOperation::Namespaces => DbResponse::Keys(self.namespaces()),
+            DbOperation::Select { namespace: None } => DbResponse::Ok,
+            DbOperation::Select { namespace: Some(name) } => match self.namespaces.contains_key(&name) {
+                true => DbResponse::Ok,
+                false => DbResponse::NotFound,
+            },
+            DbOperation::Subscribe { .. } | DbOperation::Unsubscribe { .. } => {
+                DbResponse::Error(DbError::NotImplemented("subscribe requires a streaming connection".to_string()))
+            }
+            DbOperation::ListPush { key, values, front } => self.list_push(&key, values, front).await.into_response(DbResponse::Count),
+            DbOperation::ListPop { key, count, front } => self.list_pop(&key, count, front).await.into_response(DbResponse::Values),
+            DbOperation::ListRange { key, start, stop } => self.list_range(&key, start, stop).await.into_response(DbResponse::Values),
+            DbOperation::HashSet { key, fields } => self.hash_set(&key, fields).await.into_response(DbResponse::Count),
+            DbOperation::HashGet { key, field } => self.hash_get(&key, &field).await.into_response(DbResponse::Value),
+            DbOperation::HashDelete { key, fields } => self.hash_delete(&key, fields).await.into_response(DbResponse::Count),
+            DbOperation::SetAdd { key, members } => self.set_add(&key, members).await.into_response(DbResponse::Count),
+            DbOperation::SetRemove { key, members } => self.set_remove(&key, members).await.into_response(DbResponse::Count),
+            DbOperation::SetMembers { key } => self.set_members(&key).await.into_response(DbResponse::Values),
+            DbOperation::SetIntersection { keys } => self.set_intersection(&keys).await.into_response(DbResponse::Values),
+            DbOperation::ZSetAdd { key, members } => self.zset_add(&key, members).await.into_response(DbResponse::Count),
+            DbOperation::ZSetRemove { key, members } => self.zset_remove(&key, members).await.into_response(DbResponse::Count),
+            DbOperation::ZSetRange { key, start, stop } => self.zset_range(&key, start, stop).await.into_response(DbResponse::ScoredMembers),
+            DbOperation::ZSetRangeByScore { key, min, max } => self
+                .zset_range_by_score(&key, min, max)
+                .await
+                .into_response(DbResponse::ScoredMembers),
+            DbOperation::Increment { key, delta } => self.incr_by(&key, delta).await.into_response(DbResponse::Integer),
+            DbOperation::Decrement { key, delta } => self.decr_by(&key, delta).await.into_response(DbResponse::Integer),
+            DbOperation::IncrementFloat { key, delta } => self.incr_by_float(&key, delta).await.into_response(DbResponse::Float),
+            DbOperation::MultiGet { keys } => self.mget(&keys).await.into_response(DbResponse::OptionalValues),
+            DbOperation::MultiSet { entries, atomic } => self.mset(entries, atomic).await.into_response(DbResponse::Count),
+            DbOperation::MultiDelete { keys } => self.mdelete(&keys).await.into_response(DbResponse::Count),
+            DbOperation::JsonGet { key, path } => self.json_get(&key, &path).await.into_response(DbResponse::Json),
+            DbOperation::JsonSet { key, path, value } => self.json_set(&key, &path, value).await.into_response(|_| DbResponse::Ok),
+            DbOperation::JsonDelete { key, path } => self.json_delete(&key, &path).await.into_response(DbResponse::Count),
+            DbOperation::JsonAppend { key, path, values } => self.json_append(&key, &path, values).await.into_response(DbResponse::Count),
+            DbOperation::JsonIncrement { key, path, delta } => self.json_increment(&key, &path, delta).await.into_response(DbResponse::Json),
+            DbOperation::CreateIndex { name, prefix, path } => self.create_index(IndexDef { name, prefix, path }).into_response(DbResponse::Bool),
+            DbOperation::DropIndex { name } => self.drop_index(&name).into_response(DbResponse::Bool),
+            DbOperation::Indexes => DbResponse::Indexes(self.indexes()),
+            DbOperation::QueryIndex { name, query, cursor, limit } => self
+                .query_index(&name, &query, cursor.as_deref(), limit)
+                .await
+                .into_response(DbResponse::KeysPage),
+            DbOperation::Eval { script, keys, args } => self.eval(&script, keys, args).await.into_response(DbResponse::Json),
+            DbOperation::EvalSha { sha, keys, args } => self.eval_sha(&sha, keys, args).await.into_response(DbResponse::Json),
+            DbOperation::ScriptLoad { script } => self.script_load(&script).into_response(|sha| DbResponse::Value(sha.into_bytes())),
+            DbOperation::ReplicationInfo => DbResponse::Replication(self.replication_info()),
+        }
+    }
+    // Entradas vivas (ordenadas por clave) que empiezan con el prefijo
+    fn scan<R>(&self, prefix: Option<&str>, read: impl Fn(&Entry) -> Option<R>) -> Result<Vec<(String, R)>, DbError> {
+        let (lower, upper) = prefix_bounds(prefix);
+        self.visible(lower, bound_ref(&upper), false, 0, read)
+    }
+    // Entradas no expiradas dentro de los limites (`read` devuelve None para saltar una entrada)
+    fn visible<R>(&self, lower: Bound<&str>, upper: Bound<&str>, reverse: bool, limit: usize, read: impl Fn(&Entry) -> Option<R>) -> Result<Vec<(String, R)>, DbError> {
+        let now = now_millis();
+        self.data
+            .range(lower, upper, reverse, limit, |_, entry| {
+                if entry.is_expired(now) { None } else { read(entry) }
+            })
+            .map_err(read_failed)
+    }
+    // Lock de la franja que contiene la clave
+    fn lock_key(&self, key: &str) -> MutexGuard<'_, ()> {
+        self.stripes[stripe_of(key)].lock().unwrap_or_else(|e| e.into_inner())
+    }
+    // Franjas de varias claves, en orden de indice (igual que lock_all)
+    fn lock_keys(&self, keys: &[&str]) -> Vec<MutexGuard<'_, ()>> {
+        let mut stripes: Vec<usize> = keys.iter().map(|key| stripe_of(key)).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        stripes.into_iter().map(|i| self.stripes[i].lock().unwrap_or_else(|e| e.into_inner())).collect()
+    }
+    // Todas las franjas, siempre en el mismo orden para evitar deadlocks
+    fn lock_all(&self) -> Vec<MutexGuard<'_, ()>> {
+        self.stripes.iter().map(|m| m.lock().unwrap_or_else(|e| e.into_inner())).collect()
+    }
+    // Elimina todas las claves expiradas; devuelve cuantas se borraron
+    pub fn purge_expired(&self) -> usize {
+        let now = now_millis();
+        let mut removed = 0;
+        // Los indices se actualizan despues, fuera del lock del keyspace
+        let mut unindex = Vec::new();
+        let indexed = !self.indexes.is_empty();
+        let purged = self.data.purge_expired(now, |key, size| {
+            self.release(entry_size(key, size));
+            self.notify(ChangeKind::Expired, key, None, 0, None);
+            if indexed {
+                unindex.push(key.to_string());
+            }
+            removed += 1;
+        });
+        if let Err(e) = purged {
+            error!(error = %e, namespace = ?self.namespace_name, "Failed to purge expired keys");
+        }
+        for key in unindex {
+            self.indexes.remove(&key);
+        }
+        if removed > 0 {
+            debug!(removed = removed, namespace = ?self.namespace_name, "Expired keys purged");
+        }
+        removed + self.namespaces.iter().map(|db| db.purge_expired()).sum::<usize>()
+    }
+    // Tarea en segundo plano que purga claves expiradas; termina cuando se libera la base
+    pub fn spawn_reaper(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let db = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(db) = db.upgrade() else { break };
+                db.purge_expired();
+            }
+        })
+    }
+    // Entrada viva: las claves expiradas se eliminan al leerlas (expiracion perezosa)
+    // Un error de disco se devuelve como Storage (no como clave inexistente).
+    fn live<R>(&self, key: &str, read: impl FnOnce(&Entry) -> R) -> Result<Option<R>, DbError> {
+        let now = now_millis();
+        let mut read = Some(read);
+        let found = self.data.get(key, |entry| {
+            if entry.is_expired(now) {
+                return None;
+            }
+            entry.touch(self.tick());
+            read.take().map(|read| read(entry))
+        });
+        match found.map_err(read_failed)? {
+            Some(Some(value)) => return Ok(Some(value)),
+            Some(None) => {}
+            None => return Ok(None),
+        }
+        if let Some(old_len) = self.data.remove_if(key, |entry| entry.is_expired(now)).map_err(read_failed)? {
+            self.indexes.remove(key);
+            self.release(entry_size(key, old_len));
+            self.notify(ChangeKind::Expired, key, None, 0, None);
+        }
+        debug!(key = %key, "Expired key removed on access");
+        Ok(None)
+    }
+    // Valor de una cadena viva; TypeMismatch si la clave guarda una coleccion
+    fn live_bytes(&self, key: &str) -> Result<Option<Vec<u8>>, DbError> {
+        match self.live(key, |entry| entry.value.to_bytes())? {
+            Some(Some(value)) => Ok(Some(value)),
+            Some(None) => Err(DbError::TypeMismatch { key: key.to_string(), expected: "string" }),
+            None => Ok(None),
+        }
+    }
+    // Suscripcion a los cambios de las claves que cumplen el filtro.
+    // Solo recibe los eventos posteriores a la llamada.
+    pub fn watch(&self, filter: WatchFilter) -> Watcher {
+        Watcher::new(filter, self.changes.subscribe())
+    }
+    // Publica un mensaje en el canal; devuelve cuantas suscripciones lo recibieron
+    pub fn publish(&self, channel: &str, message: Vec<u8>) -> usize {
+        self.pubsub.publish(channel, message)
+    }
+    // Suscripcion vacia: agregar canales con subscribe() / psubscribe()
+    pub fn subscriber(&self) -> Subscription {
+        self.pubsub.subscriber()
+    }
+    // Hay algun suscriptor (evita copiar valores si nadie escucha)
+    fn watched(&self) -> bool {
+        self.changes.receiver_count() > 0
+    }
+    fn notify(&self, kind: ChangeKind, key: &str, value: Option<Vec<u8>>, version: u64, expires_at: Option<u64>) {
+        if self.watched() {
+            // Sin suscriptores send() falla; no es un error
+            let _ = self.changes.send(ChangeEvent { kind, key: key.to_string(), value, version, expires_at });
+        }
+    }
+    // Motor del keyspace en uso
+    pub fn engine(&self) -> StorageEngine {
+        self.data.engine()
+    }
+    // Metricas de operaciones y desalojos
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+    // Memoria contabilizada (claves + valores + overhead por entrada)
+    pub fn used_memory(&self) -> u64 {
+        self.used_memory.load(Ordering::Relaxed)
+    }
+    fn next_revision(&self) -> u64 {
+        self.revision.fetch_add(1, Ordering::Relaxed) + 1
+    }
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+    fn release(&self, bytes: u64) {
+        let _ = self.used_memory.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            Some(used.saturating_sub(bytes))
+        });
+    }
+    // Hacer espacio para escribir `key` con un valor de `value_len` bytes,
+    // desalojando claves segun la politica configurada
+    fn reserve(&self, key: &str, value_len: usize) -> DbResult<()> {
+        if let Err(e) = DbError::check_sizes(key, value_len) {
+            return DbResult::Err(e);
+        }
+        self.make_room(&[key], |_| entry_size(key, value_len))
+    }
+    // Como reserve(), pero para varias escrituras que se aplican juntas: se reserva una sola vez
+    // el tamaño total, descontando los valores que se reemplazan. Si una clave se repite cuenta
+    // su ultimo valor.
+    fn reserve_many(&self, entries: &[(&str, usize)]) -> DbResult<()> {
+        let mut sizes: Vec<(&str, usize)> = Vec::with_capacity(entries.len());
+        for &(key, value_len) in entries {
+            if let Err(e) = DbError::check_sizes(key, value_len) {
+                return DbResult::Err(e);
+            }
+            match sizes.iter_mut().find(|(seen, _)| *seen == key) {
+                Some(size) => size.1 = value_len,
+                None => sizes.push((key, value_len)),
+            }
+        }
+        let keys: Vec<&str> = sizes.iter().map(|(key, _)| *key).collect();
+        self.make_room(&keys, |_| sizes.iter().map(|&(key, len)| entry_size(key, len)).sum())
+    }
+    // Como reserve(), pero para una coleccion que crece `growth` bytes
+    fn reserve_growth(&self, key: &str, growth: usize) -> DbResult<()> {
+        if let Err(e) = DbError::check_sizes(key, growth) {
+            return DbResult::Err(e);
+        }
+        self.make_room(&[key], |replaced| replaced.max(entry_size(key, 0)) + growth as u64)
+    }
+    // Desaloja claves hasta que quepan las nuevas entradas de `keys` (sin repetidas);
+    // `incoming` recibe el tamaño actual de esas entradas (0 si no existen)
+    fn make_room(&self, keys: &[&str], incoming: impl FnOnce(u64) -> u64) -> DbResult<()> {
+        let Some(max) = self.max_memory else { return DbResult::Ok(()) };
+        let mut replaced = 0;
+        for key in keys {
+            match self.data.get(key, |e| entry_size(key, e.value.size())) {
+                Ok(size) => replaced += size.unwrap_or(0),
+                Err(e) => return DbResult::Err(read_failed(e)),
+            }
+        }
+        let incoming = incoming(replaced);
+        if incoming > max {
+            return DbResult::Err(DbError::OutOfMemory { requested: incoming, max });
+        }
+        let mut pool: Vec<String> = Vec::new();
+        while self.used_memory().saturating_sub(replaced) + incoming > max {
+            if pool.is_empty() {
+                pool = match self.eviction_candidates(keys) {
+                    Ok(pool) => pool,
+                    Err(e) => return DbResult::Err(e),
+                };
+            }
+            let Some(victim) = pool.pop() else {
+                warn!(used = self.used_memory(), max = max, policy = ?self.eviction, "Memory limit reached");
+                return DbResult::Err(DbError::OutOfMemory { requested: incoming, max });
+            };
+            let _lock = self.lock_key(&victim);
+            match self.data.contains_key(&victim) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => return DbResult::Err(read_failed(e)),
+            }
+            if let DbResult::Err(e) = self.write(LogRecord::Delete { key: victim.clone() }) {
+                return DbResult::Err(e);
+            }
+            self.metrics.increment_evictions();
+            info!(key = %victim, policy = ?self.eviction, "Key evicted");
+        }
+        DbResult::Ok(())
+    }
+    // Mejores candidatos a desalojar; el mejor queda al final del vector
+    fn eviction_candidates(&self, protected: &[&str]) -> Result<Vec<String>, DbError> {
+        // Puntaje: menor = se desaloja antes
+        let score = |entry: &Entry| -> Option<(u64, u64)> {
+            let last_access = entry.last_access.load(Ordering::Relaxed);
+            match self.eviction {
+                EvictionPolicy::NoEviction => None,
+                EvictionPolicy::AllKeysLru => Some((last_access, 0)),
+                EvictionPolicy::AllKeysLfu => Some((entry.hits.load(Ordering::Relaxed), last_access)),
+                EvictionPolicy::VolatileTtl => entry.expires_at.map(|at| (at, last_access)),
+            }
+        };
+        let mut candidates: Vec<((u64, u64), String)> = Vec::new();
+        self.data.for_each(|key, entry| {
+            if protected.contains(&key) {
+                return;
+            }
+            let Some(s) = score(entry) else { return };
+            if candidates.len() < EVICTION_POOL_SIZE {
+                candidates.push((s, key.to_string()));
+                candidates.sort_by_key(|c| std::cmp::Reverse(c.0));
+            } else if s < candidates[0].0 {
+                // El peor candidato esta al inicio (orden descendente)
+                candidates[0] = (s, key.to_string());
+                candidates.sort_by_key(|c| std::cmp::Reverse(c.0));
+            }
+        })
+        .map_err(read_failed)?;
+        Ok(candidates.into_iter().map(|(_, key)| key).collect())
+    }
+    // Copia de las claves vivas; el llamador detiene las escrituras con lock_all
+    fn snapshot_entries(&self) -> io::Result<Vec<SnapshotEntry>> {
+        let now = now_millis();
+        let mut entries = Vec::new();
+        self.data.for_each(|key, entry| {
+            if !entry.is_expired(now) {
+                entries.push(SnapshotEntry {
+                    key: key.to_string(),
+                    value: entry.value.clone(),
+                    expires_at: entry.expires_at,
+                });
+            }
+        })?;
+        Ok(entries)
+    }
+    // Guardar un snapshot del keyspace completo en la ruta configurada
+    pub async fn save(&self) -> DbResult<SnapshotInfo> {
+        let Some(path) = self.snapshot_path.clone() else {
+            return DbResult::Err(DbError::NotImplemented("no snapshot path configured".to_string()));
+        };
+        if self.saving.swap(true, Ordering::AcqRel) {
+            return DbResult::Err(DbError::Busy("snapshot already in progress".to_string()));
+        }
+        let _guard = SavingGuard(&self.saving);
+
+        // La copia se hace con las escrituras detenidas y se marca en el log con un checkpoint:
+        // al reiniciar solo se reproduce lo posterior (las operaciones sobre colecciones,
+        // como PUSH, no se pueden aplicar dos veces). La escritura a disco ocurre fuera de los locks.
+        let checkpoint = (now_millis().max(1) << 16) | (self.tick() & 0xffff);
+        let entries = {
+            let _locks = self.lock_all();
+            let entries = match self.snapshot_entries() {
+                Ok(entries) => entries,
+                Err(e) => return DbResult::Err(read_failed(e)),
+            };
+            if let (Some(aof), true) = (&self.aof, self.logs_writes()) {
+                if let Err(e) = aof.record(LogRecord::Checkpoint { id: checkpoint }, |_| {}) {
+                    error!(error = %e, "Failed to append checkpoint to log");
+                    return DbResult::Err(DbError::Storage(format!("append-only log write failed: {}", e)));
+                }
+            }
+            entries
+        };
+        debug!(keys = entries.len(), path = %path.display(), "Writing snapshot");
+
+        let keyring = self.config.encryption.clone();
+        match tokio::task::spawn_blocking(move || snapshot::write(&path, checkpoint, &entries, keyring.as_ref())).await {
+            Ok(Ok(info)) => {
+                // Lo anterior al checkpoint ya esta en el snapshot: se compacta el log
+                // (y se vuelve a cifrar con la clave actual si hubo rotacion)
+                if let (Some(aof), true) = (&self.aof, self.logs_writes()) {
+                    if let Err(e) = aof.compact(checkpoint) {
+                        warn!(error = %e, path = %aof.path().display(), "Failed to compact append-only log");
+                    }
+                }
+                DbResult::Ok(info)
+            }
+            Ok(Err(e)) => {
+                error!(error = %e, "Failed to write snapshot");
+                DbResult::Err(DbError::Storage(format!("snapshot failed: {}", e)))
+            }
+            Err(e) => DbResult::Err(DbError::Internal(format!("snapshot task failed: {}", e))),
+        }
+    }
+}
+
+// Combinaciones de motor y persistencia no soportadas
+fn check_engine(config: &DbConfig) -> io::Result<()> {
+    let unsupported = |msg: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, msg.to_string()));
+    if config.engine != StorageEngine::Lsm {
+        return Ok(());
+    }
+    // Reproducir el log sobre un motor que ya persiste sus datos aplicaria dos veces los PUSH
+    if config.lsm_dir.is_some() && config.aof_path.is_some() {
+        return unsupported("the lsm engine keeps its own write-ahead log; aof_path cannot be used with lsm_dir");
+    }
+    if config.encryption.is_some() {
+        return unsupported("encryption at rest is not supported by the lsm engine");
+    }
+    // Desalojar borraria para siempre datos que estan en disco (y elegir victimas leeria todas las tablas)
+    if config.eviction != EvictionPolicy::NoEviction {
+        return unsupported("eviction is not supported by the lsm engine; use max_memory with no-eviction");
+    }
+    Ok(())
+}
+
+// Franja de locks que corresponde a una clave
+fn stripe_of(key: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() as usize) % LOCK_STRIPES
+}
+
+// Limites [prefijo, fin del prefijo) para un escaneo por prefijo
+fn prefix_bounds(prefix: Option<&str>) -> (Bound<&str>, Bound<String>) {
+    match prefix {
+        Some(prefix) => (
+            Bound::Included(prefix),
+            prefix_end(prefix).map_or(Bound::Unbounded, Bound::Excluded),
+        ),
+        None => (Bound::Unbounded, Bound::Unbounded),
+    }
+}
+
+fn bound_ref(bound: &Bound<String>) -> Bound<&str> {
+    bound.as_ref().map(String::as_str)
+}
+
+// Libera el flag de snapshot aunque el futuro se cancele
+struct SavingGuard<'a>(&'a AtomicBool);
+
+impl Drop for SavingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
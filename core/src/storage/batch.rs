@@ -0,0 +1,167 @@
+// Operaciones por lotes: MGET, MSET y MDELETE en una sola peticion
+use std::collections::HashSet;
+use super::*;
+
+impl NanoDb {
+    // Un resultado por clave, en el mismo orden; None si no existe o guarda una coleccion
+    pub async fn mget(&self, keys: &[String]) -> DbResult<Vec<Option<Vec<u8>>>> {
+        debug!(count = keys.len(), "Getting batch");
+        let values = keys
+            .iter()
+            .map(|key| {
+                self.metrics.increment_get();
+                self.live(key, |entry| entry.value.to_bytes()).map(Option::flatten)
+            })
+            .collect();
+        match values {
+            Ok(values) => DbResult::Ok(values),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Escribe todas las entradas y devuelve cuantas se escribieron.
+    // Con `atomic` van al log como un unico registro Batch bajo los locks de todas las claves;
+    // si no, cada clave se escribe por separado y un error deja escritas las anteriores.
+    pub async fn mset(&self, entries: Vec<(String, Vec<u8>)>, atomic: bool) -> DbResult<usize> {
+        debug!(count = entries.len(), atomic = atomic, "Setting batch");
+        if !atomic {
+            let mut written = 0;
+            for (key, value) in entries {
+                match self.set_with_ttl(key, value, None).await {
+                    DbResult::Ok(()) => written += 1,
+                    DbResult::NotFound => return DbResult::NotFound,
+                    DbResult::Err(e) => return DbResult::Err(e),
+                }
+            }
+            return DbResult::Ok(written);
+        }
+        // Reservar memoria antes de tomar los locks (el desalojo toma los suyos), una sola vez
+        // para el lote completo
+        let incoming: Vec<(&str, usize)> = entries
+            .iter()
+            .map(|(key, value)| {
+                self.metrics.increment_set();
+                (key.as_str(), value.len())
+            })
+            .collect();
+        if let DbResult::Err(e) = self.reserve_many(&incoming) {
+            return DbResult::Err(e);
+        }
+        let count = entries.len();
+        if count == 0 {
+            return DbResult::Ok(0);
+        }
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        let _locks = self.lock_keys(&keys);
+        let records = entries
+            .into_iter()
+            .map(|(key, value)| LogRecord::Set { key, value, expires_at: None })
+            .collect();
+        match self.write(LogRecord::Batch(records)) {
+            DbResult::Ok(()) => {
+                info!(count = count, "Batch set successfully");
+                DbResult::Ok(count)
+            }
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+    // Borra las claves en un solo registro del log; devuelve cuantas existian
+    pub async fn mdelete(&self, keys: &[String]) -> DbResult<usize> {
+        debug!(count = keys.len(), "Deleting batch");
+        let refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let _locks = self.lock_keys(&refs);
+        let mut seen = HashSet::new();
+        let mut live: Vec<&str> = Vec::new();
+        for key in refs {
+            self.metrics.increment_delete();
+            if !seen.insert(key) {
+                continue;
+            }
+            match self.live(key, |_| ()) {
+                Ok(Some(())) => live.push(key),
+                Ok(None) => {}
+                Err(e) => return DbResult::Err(e),
+            }
+        }
+        if live.is_empty() {
+            return DbResult::Ok(0);
+        }
+        let count = live.len();
+        let records = live.into_iter().map(|key| LogRecord::Delete { key: key.to_string() }).collect();
+        match self.write(LogRecord::Batch(records)) {
+            DbResult::Ok(()) => {
+                info!(count = count, "Batch deleted successfully");
+                DbResult::Ok(count)
+            }
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_batch_roundtrip() {
+        let db = NanoDb::new();
+        let entries: Vec<(String, Vec<u8>)> = (0..1000).map(|i| (format!("k{}", i), i.to_string().into_bytes())).collect();
+        assert!(matches!(db.mset(entries, true).await, DbResult::Ok(1000)));
+        assert!(matches!(db.mset(vec![("extra".to_string(), b"x".to_vec())], false).await, DbResult::Ok(1)));
+        db.list_push("list", vec![b"a".to_vec()], false).await;
+
+        let DbResult::Ok(values) = db.mget(&keys(&["k7", "missing", "extra", "list"])).await else { panic!() };
+        assert_eq!(values, vec![Some(b"7".to_vec()), None, Some(b"x".to_vec()), None]);
+
+        assert!(matches!(db.mdelete(&keys(&["k1", "k1", "k2", "missing", "list"])).await, DbResult::Ok(3)));
+        assert!(matches!(db.size().await, DbResult::Ok(999)));
+    }
+
+    #[tokio::test]
+    async fn test_atomic_mset_is_all_or_nothing() {
+        let db = NanoDb::new();
+        let too_long = "k".repeat(crate::errors::MAX_KEY_SIZE + 1);
+        let entries = vec![("a".to_string(), b"1".to_vec()), (too_long.clone(), b"2".to_vec())];
+        assert!(matches!(db.mset(entries, true).await, DbResult::Err(DbError::KeyTooLarge { .. })));
+        assert!(matches!(db.exists("a").await, DbResult::Ok(false)));
+
+        let entries = vec![("a".to_string(), b"1".to_vec()), (too_long, b"2".to_vec())];
+        assert!(matches!(db.mset(entries, false).await, DbResult::Err(DbError::KeyTooLarge { .. })));
+        assert!(matches!(db.exists("a").await, DbResult::Ok(true)));
+    }
+
+    #[tokio::test]
+    async fn test_atomic_mset_reserves_total_size() {
+        let db = NanoDb::with_config(DbConfig { max_memory: Some(1024), ..DbConfig::default() }).unwrap();
+        let value = vec![0; 400];
+        db.set("a".to_string(), value.clone()).await;
+        // Reemplazar "a" no cuenta su tamaño dos veces
+        let entries = vec![("a".to_string(), value.clone()), ("b".to_string(), value.clone())];
+        assert!(matches!(db.mset(entries, true).await, DbResult::Ok(2)));
+
+        let entries = ["c", "d", "e"].iter().map(|key| (key.to_string(), value.clone())).collect();
+        assert!(matches!(db.mset(entries, true).await, DbResult::Err(DbError::OutOfMemory { .. })));
+        assert!(matches!(db.size().await, DbResult::Ok(2)));
+    }
+
+    #[tokio::test]
+    async fn test_batch_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.aof");
+        {
+            let db = NanoDb::open(&path).unwrap();
+            let entries = vec![("a".to_string(), b"1".to_vec()), ("b".to_string(), b"2".to_vec())];
+            db.mset(entries, true).await;
+            db.mdelete(&keys(&["a"])).await;
+        }
+        let db = NanoDb::open(&path).unwrap();
+        let DbResult::Ok(values) = db.mget(&keys(&["a", "b"])).await else { panic!() };
+        assert_eq!(values, vec![None, Some(b"2".to_vec())]);
+    }
+}
@@ -0,0 +1,341 @@
+// Listas, hashes, conjuntos y conjuntos ordenados
+use std::collections::BTreeSet;
+use super::*;
+use crate::value::rank_bounds;
+
+// Bytes extra estimados por elemento nuevo al reservar memoria
+const ELEMENT_RESERVE: usize = 16;
+
+impl NanoDb {
+    // Agrega valores al inicio (front) o al final de la lista; devuelve la nueva longitud.
+    // Al insertar al inicio quedan en orden inverso, igual que LPUSH.
+    pub async fn list_push(&self, key: &str, values: Vec<Vec<u8>>, front: bool) -> DbResult<usize> {
+        if values.is_empty() {
+            return DbResult::Err(DbError::InvalidArgument("push requires at least one value".to_string()));
+        }
+        let growth = values.iter().map(|v| v.len() + ELEMENT_RESERVE).sum();
+        let pushed = values.len();
+        let op = CollectionOp::Push { values, front };
+        self.modify(key, op, growth, |value| match value {
+            Some(Value::List(list)) => (list.len() + pushed, true),
+            _ => (pushed, true),
+        })
+    }
+    // Quita hasta `count` valores del inicio o del final; NotFound si la lista no existe
+    pub async fn list_pop(&self, key: &str, count: usize, front: bool) -> DbResult<Vec<Vec<u8>>> {
+        if count == 0 {
+            return DbResult::Err(DbError::InvalidArgument("pop count must be at least 1".to_string()));
+        }
+        let popped = self.modify(key, CollectionOp::Pop { count, front }, 0, |value| match value {
+            Some(Value::List(list)) => {
+                let popped: Vec<Vec<u8>> = if front {
+                    list.iter().take(count).cloned().collect()
+                } else {
+                    list.iter().rev().take(count).cloned().collect()
+                };
+                (Some(popped), true)
+            }
+            _ => (None, false),
+        });
+        match popped {
+            DbResult::Ok(Some(values)) => DbResult::Ok(values),
+            DbResult::Ok(None) | DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+    // Elementos en las posiciones [start, stop] (inclusivo, negativos desde el final)
+    pub async fn list_range(&self, key: &str, start: i64, stop: i64) -> DbResult<Vec<Vec<u8>>> {
+        self.read_collection(key, "list", |value| match value {
+            Value::List(list) => match rank_bounds(list.len(), start, stop) {
+                Some((from, to)) => list.range(from..=to).cloned().collect(),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        })
+    }
+    // Fija campos del hash; devuelve cuantos campos son nuevos
+    pub async fn hash_set(&self, key: &str, fields: Vec<(String, Vec<u8>)>) -> DbResult<usize> {
+        if fields.is_empty() {
+            return DbResult::Err(DbError::InvalidArgument("hash set requires at least one field".to_string()));
+        }
+        let growth = fields.iter().map(|(f, v)| f.len() + v.len() + ELEMENT_RESERVE).sum();
+        let names: BTreeSet<String> = fields.iter().map(|(field, _)| field.clone()).collect();
+        self.modify(key, CollectionOp::HashSet { fields }, growth, |value| match value {
+            Some(Value::Hash(hash)) => (names.iter().filter(|f| !hash.contains_key(*f)).count(), true),
+            _ => (names.len(), true),
+        })
+    }
+    pub async fn hash_get(&self, key: &str, field: &str) -> DbResult<Vec<u8>> {
+        let value = self.read_collection(key, "hash", |value| match value {
+            Value::Hash(hash) => hash.get(field).cloned(),
+            _ => None,
+        });
+        match value {
+            DbResult::Ok(Some(value)) => DbResult::Ok(value),
+            DbResult::Ok(None) | DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+    // Borra campos del hash; devuelve cuantos existian
+    pub async fn hash_delete(&self, key: &str, fields: Vec<String>) -> DbResult<usize> {
+        let names: BTreeSet<String> = fields.iter().cloned().collect();
+        self.modify(key, CollectionOp::HashDelete { fields }, 0, |value| {
+            let removed = match value {
+                Some(Value::Hash(hash)) => names.iter().filter(|f| hash.contains_key(*f)).count(),
+                _ => 0,
+            };
+            (removed, removed > 0)
+        })
+    }
+    // Agrega miembros al conjunto; devuelve cuantos son nuevos
+    pub async fn set_add(&self, key: &str, members: Vec<Vec<u8>>) -> DbResult<usize> {
+        if members.is_empty() {
+            return DbResult::Err(DbError::InvalidArgument("set add requires at least one member".to_string()));
+        }
+        let growth = members.iter().map(|m| m.len() + ELEMENT_RESERVE).sum();
+        let unique: BTreeSet<Vec<u8>> = members.iter().cloned().collect();
+        self.modify(key, CollectionOp::SetAdd { members }, growth, |value| {
+            let added = match value {
+                Some(Value::Set(set)) => unique.difference(set).count(),
+                _ => unique.len(),
+            };
+            (added, added > 0)
+        })
+    }
+    // Quita miembros del conjunto; devuelve cuantos existian
+    pub async fn set_remove(&self, key: &str, members: Vec<Vec<u8>>) -> DbResult<usize> {
+        let unique: BTreeSet<Vec<u8>> = members.iter().cloned().collect();
+        self.modify(key, CollectionOp::SetRemove { members }, 0, |value| {
+            let removed = match value {
+                Some(Value::Set(set)) => unique.intersection(set).count(),
+                _ => 0,
+            };
+            (removed, removed > 0)
+        })
+    }
+    // Miembros del conjunto en orden lexicografico (vacio si no existe)
+    pub async fn set_members(&self, key: &str) -> DbResult<Vec<Vec<u8>>> {
+        self.read_collection(key, "set", |value| match value {
+            Value::Set(set) => set.iter().cloned().collect(),
+            _ => Vec::new(),
+        })
+    }
+    // Miembros presentes en todos los conjuntos (un conjunto inexistente cuenta como vacio)
+    pub async fn set_intersection(&self, keys: &[String]) -> DbResult<Vec<Vec<u8>>> {
+        let mut result: Option<BTreeSet<Vec<u8>>> = None;
+        for key in keys {
+            let members = match self.read_collection(key, "set", |value| match value {
+                Value::Set(set) => match &result {
+                    Some(acc) => acc.intersection(set).cloned().collect(),
+                    None => set.clone(),
+                },
+                _ => BTreeSet::new(),
+            }) {
+                DbResult::Ok(members) => members,
+                DbResult::NotFound => BTreeSet::new(),
+                DbResult::Err(e) => return DbResult::Err(e),
+            };
+            result = Some(members);
+        }
+        DbResult::Ok(result.unwrap_or_default().into_iter().collect())
+    }
+    // Agrega miembros con su puntaje (o actualiza el puntaje); devuelve cuantos son nuevos
+    pub async fn zset_add(&self, key: &str, members: Vec<(f64, Vec<u8>)>) -> DbResult<usize> {
+        if members.is_empty() {
+            return DbResult::Err(DbError::InvalidArgument("sorted set add requires at least one member".to_string()));
+        }
+        if members.iter().any(|(score, _)| score.is_nan()) {
+            return DbResult::Err(DbError::InvalidArgument("score is not a number".to_string()));
+        }
+        let growth = members.iter().map(|(_, m)| m.len() + 8 + ELEMENT_RESERVE).sum();
+        let unique: BTreeSet<Vec<u8>> = members.iter().map(|(_, member)| member.clone()).collect();
+        self.modify(key, CollectionOp::SortedSetAdd { members }, growth, |value| match value {
+            Some(Value::SortedSet(zset)) => (unique.iter().filter(|m| zset.score(m).is_none()).count(), true),
+            _ => (unique.len(), true),
+        })
+    }
+    // Quita miembros del conjunto ordenado; devuelve cuantos existian
+    pub async fn zset_remove(&self, key: &str, members: Vec<Vec<u8>>) -> DbResult<usize> {
+        let unique: BTreeSet<Vec<u8>> = members.iter().cloned().collect();
+        self.modify(key, CollectionOp::SortedSetRemove { members }, 0, |value| {
+            let removed = match value {
+                Some(Value::SortedSet(zset)) => unique.iter().filter(|m| zset.score(m).is_some()).count(),
+                _ => 0,
+            };
+            (removed, removed > 0)
+        })
+    }
+    // Miembros y puntajes por posicion [start, stop] en orden ascendente de puntaje
+    pub async fn zset_range(&self, key: &str, start: i64, stop: i64) -> DbResult<Vec<(Vec<u8>, f64)>> {
+        self.read_collection(key, "zset", |value| match value {
+            Value::SortedSet(zset) => zset.range_by_rank(start, stop),
+            _ => Vec::new(),
+        })
+    }
+    // Miembros con min <= puntaje <= max
+    pub async fn zset_range_by_score(&self, key: &str, min: f64, max: f64) -> DbResult<Vec<(Vec<u8>, f64)>> {
+        self.read_collection(key, "zset", |value| match value {
+            Value::SortedSet(zset) => zset.range_by_score(min, max),
+            _ => Vec::new(),
+        })
+    }
+
+    // Escritura sobre una coleccion. `plan` recibe el valor actual (None si la clave no existe)
+    // y devuelve la respuesta y si hay algo que escribir.
+    fn modify<R>(&self, key: &str, op: CollectionOp, growth: usize, plan: impl Fn(Option<&Value>) -> (R, bool)) -> DbResult<R> {
+        self.try_modify(key, op, growth, |value| DbResult::Ok(plan(value)))
+    }
+    // Igual que modify(), pero `plan` puede rechazar la operacion (no se escribe nada)
+    pub(super) fn try_modify<R>(&self, key: &str, op: CollectionOp, growth: usize, plan: impl Fn(Option<&Value>) -> DbResult<(R, bool)>) -> DbResult<R> {
+        self.metrics.increment_set();
+        if growth > 0 {
+            if let DbResult::Err(e) = self.reserve_growth(key, growth) {
+                return DbResult::Err(e);
+            }
+        }
+        let _lock = self.lock_key(key);
+        let expected = op.type_name();
+        match self.live(key, |entry| entry.value.type_name()) {
+            Ok(Some(found)) if found != expected => {
+                return DbResult::Err(DbError::TypeMismatch { key: key.to_string(), expected });
+            }
+            Ok(_) => {}
+            Err(e) => return DbResult::Err(e),
+        }
+        let (exists, planned) = match self.data.get(key, |entry| plan(Some(&entry.value))) {
+            Ok(Some(planned)) => (true, planned),
+            Ok(None) => (false, plan(None)),
+            Err(e) => return DbResult::Err(read_failed(e)),
+        };
+        let (result, changed) = match planned {
+            DbResult::Ok(planned) => planned,
+            DbResult::NotFound => return DbResult::NotFound,
+            DbResult::Err(e) => return DbResult::Err(e),
+        };
+        if !changed {
+            return DbResult::Ok(result);
+        }
+        let update = LogRecord::Update { key: key.to_string(), op };
+        // Clave nueva: al reproducir el log el Update no debe caer sobre una entrada
+        // que ya habia expirado (la expiracion perezosa no queda registrada)
+        let record = match exists {
+            true => update,
+            false => LogRecord::Batch(vec![LogRecord::Delete { key: key.to_string() }, update]),
+        };
+        match self.write(record) {
+            DbResult::Ok(()) => {
+                debug!(key = %key, kind = expected, "Collection updated");
+                DbResult::Ok(result)
+            }
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+
+    // Lectura de una coleccion viva; una clave inexistente se lee como coleccion vacia
+    fn read_collection<R: Default>(&self, key: &str, expected: &'static str, read: impl FnOnce(&Value) -> R) -> DbResult<R> {
+        self.metrics.increment_get();
+        match self.live(key, |entry| (entry.value.type_name() == expected).then(|| read(&entry.value))) {
+            Ok(Some(Some(result))) => DbResult::Ok(result),
+            Ok(Some(None)) => DbResult::Err(DbError::TypeMismatch { key: key.to_string(), expected }),
+            Ok(None) => DbResult::Ok(R::default()),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(values: &[&str]) -> Vec<Vec<u8>> {
+        values.iter().map(|v| v.as_bytes().to_vec()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_lists() {
+        let db = NanoDb::new();
+        assert!(matches!(db.list_push("jobs", items(&["b", "c"]), false).await, DbResult::Ok(2)));
+        assert!(matches!(db.list_push("jobs", items(&["a"]), true).await, DbResult::Ok(3)));
+        assert!(matches!(db.list_range("jobs", 0, -1).await, DbResult::Ok(ref v) if *v == items(&["a", "b", "c"])));
+        assert!(matches!(db.list_pop("jobs", 2, false).await, DbResult::Ok(ref v) if *v == items(&["c", "b"])));
+        assert!(matches!(db.list_pop("jobs", 5, true).await, DbResult::Ok(ref v) if *v == items(&["a"])));
+        // La lista vacia se borra
+        assert!(matches!(db.exists("jobs").await, DbResult::Ok(false)));
+        assert!(matches!(db.list_pop("jobs", 1, true).await, DbResult::NotFound));
+        assert_eq!(db.used_memory(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hashes_and_sets() {
+        let db = NanoDb::new();
+        let fields = vec![("name".to_string(), b"ana".to_vec()), ("age".to_string(), b"30".to_vec())];
+        assert!(matches!(db.hash_set("user:1", fields).await, DbResult::Ok(2)));
+        assert!(matches!(db.hash_set("user:1", vec![("age".to_string(), b"31".to_vec())]).await, DbResult::Ok(0)));
+        assert!(matches!(db.hash_get("user:1", "age").await, DbResult::Ok(ref v) if v == b"31"));
+        assert!(matches!(db.hash_delete("user:1", vec!["age".to_string(), "zip".to_string()]).await, DbResult::Ok(1)));
+        assert!(matches!(db.hash_get("user:1", "age").await, DbResult::NotFound));
+
+        db.set_add("a", items(&["x", "y", "z"])).await;
+        db.set_add("b", items(&["y", "z", "w"])).await;
+        assert!(matches!(db.set_add("a", items(&["x"])).await, DbResult::Ok(0)));
+        assert!(matches!(db.set_remove("b", items(&["w", "q"])).await, DbResult::Ok(1)));
+        let keys = ["a".to_string(), "b".to_string()];
+        assert!(matches!(db.set_intersection(&keys).await, DbResult::Ok(ref v) if *v == items(&["y", "z"])));
+        assert!(matches!(db.set_members("missing").await, DbResult::Ok(ref v) if v.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_sets() {
+        let db = NanoDb::new();
+        let members = vec![(30.0, b"carl".to_vec()), (10.0, b"ana".to_vec()), (20.0, b"bob".to_vec())];
+        assert!(matches!(db.zset_add("scores", members).await, DbResult::Ok(3)));
+        assert!(matches!(db.zset_add("scores", vec![(5.0, b"carl".to_vec())]).await, DbResult::Ok(0)));
+        let DbResult::Ok(top) = db.zset_range("scores", -2, -1).await else { panic!("Expected range") };
+        assert_eq!(top, vec![(b"ana".to_vec(), 10.0), (b"bob".to_vec(), 20.0)]);
+        let DbResult::Ok(low) = db.zset_range_by_score("scores", 0.0, 10.0).await else { panic!("Expected range") };
+        assert_eq!(low, vec![(b"carl".to_vec(), 5.0), (b"ana".to_vec(), 10.0)]);
+        assert!(matches!(db.zset_add("scores", vec![(f64::NAN, b"x".to_vec())]).await, DbResult::Err(DbError::InvalidArgument(_))));
+        assert!(matches!(db.zset_remove("scores", items(&["ana", "zed"])).await, DbResult::Ok(1)));
+    }
+
+    #[tokio::test]
+    async fn test_type_mismatch() {
+        let db = NanoDb::new();
+        db.set("plain".to_string(), b"v".to_vec()).await;
+        db.list_push("queue", items(&["a"]), false).await;
+        let mismatch = |expected: &'static str| DbError::TypeMismatch { key: String::new(), expected };
+        assert!(matches!(db.list_push("plain", items(&["a"]), false).await,
+            DbResult::Err(DbError::TypeMismatch { expected: "list", .. })));
+        assert!(matches!(db.get("queue").await, DbResult::Err(DbError::TypeMismatch { expected: "string", .. })));
+        assert!(matches!(db.set_members("queue").await, DbResult::Err(e) if e.code() == mismatch("set").code()));
+        assert!(matches!(db.compare_and_swap("queue", None, Some(b"x".to_vec())).await, DbResult::Err(_)));
+        // Los escaneos de valores saltan las colecciones; SET las reemplaza
+        assert!(matches!(db.values(None).await, DbResult::Ok(ref v) if v.len() == 1));
+        db.set("queue".to_string(), b"v".to_vec()).await;
+        assert!(matches!(db.get("queue").await, DbResult::Ok(_)));
+    }
+
+    #[tokio::test]
+    async fn test_collections_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            aof_path: Some(dir.path().join("db.aof")),
+            snapshot_path: Some(dir.path().join("db.snap")),
+            ..DbConfig::default()
+        };
+        {
+            let db = NanoDb::with_config(config.clone()).unwrap();
+            db.list_push("list", items(&["a", "b"]), false).await;
+            db.zset_add("zset", vec![(1.0, b"m".to_vec())]).await;
+            db.save().await;
+            db.list_pop("list", 1, true).await;
+            db.set_add("set", items(&["x"])).await;
+        }
+        let db = NanoDb::with_config(config).unwrap();
+        assert!(matches!(db.list_range("list", 0, -1).await, DbResult::Ok(ref v) if *v == items(&["b"])));
+        assert!(matches!(db.zset_range("zset", 0, -1).await, DbResult::Ok(ref v) if v.len() == 1));
+        assert!(matches!(db.set_members("set").await, DbResult::Ok(ref v) if *v == items(&["x"])));
+    }
+}
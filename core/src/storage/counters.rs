@@ -0,0 +1,157 @@
+// Contadores atomicos: INCR, DECR e INCRBYFLOAT sobre valores guardados como texto decimal
+use super::*;
+
+// Reserva para el valor nuevo (un i64 o un f64 formateado ocupa menos)
+const MAX_NUMBER_LEN: usize = 32;
+
+impl NanoDb {
+    // Suma `delta` al entero de la clave (0 si no existe) y devuelve el resultado
+    pub async fn incr_by(&self, key: &str, delta: i64) -> DbResult<i64> {
+        self.update_number(key, |current| {
+            let current = match current {
+                Some(bytes) => parse_integer(key, bytes)?,
+                None => 0,
+            };
+            let next = current.checked_add(delta).ok_or_else(|| DbError::Overflow { key: key.to_string() })?;
+            Ok((next, next.to_string()))
+        })
+    }
+    pub async fn decr_by(&self, key: &str, delta: i64) -> DbResult<i64> {
+        self.update_number(key, |current| {
+            let current = match current {
+                Some(bytes) => parse_integer(key, bytes)?,
+                None => 0,
+            };
+            let next = current.checked_sub(delta).ok_or_else(|| DbError::Overflow { key: key.to_string() })?;
+            Ok((next, next.to_string()))
+        })
+    }
+    // Suma `delta` al numero de la clave; el resultado tiene que ser finito
+    pub async fn incr_by_float(&self, key: &str, delta: f64) -> DbResult<f64> {
+        if !delta.is_finite() {
+            return DbResult::Err(DbError::InvalidArgument(format!("increment must be finite, got {}", delta)));
+        }
+        self.update_number(key, |current| {
+            let current = match current {
+                Some(bytes) => parse_float(key, bytes)?,
+                None => 0.0,
+            };
+            let next = current + delta;
+            if !next.is_finite() {
+                return Err(DbError::Overflow { key: key.to_string() });
+            }
+            Ok((next, format_float(next)))
+        })
+    }
+
+    // Lee, calcula y escribe bajo el lock de la clave; conserva el TTL
+    fn update_number<N>(&self, key: &str, next: impl FnOnce(Option<&[u8]>) -> Result<(N, String), DbError>) -> DbResult<N> {
+        self.metrics.increment_set();
+        if let DbResult::Err(e) = self.reserve(key, MAX_NUMBER_LEN) {
+            return DbResult::Err(e);
+        }
+        let _lock = self.lock_key(key);
+        let current = match self.live(key, |entry| (entry.value.to_bytes(), entry.expires_at)) {
+            Ok(Some((Some(value), expires_at))) => Some((value, expires_at)),
+            Ok(Some((None, _))) => return DbResult::Err(DbError::TypeMismatch { key: key.to_string(), expected: "string" }),
+            Ok(None) => None,
+            Err(e) => return DbResult::Err(e),
+        };
+        let (number, text) = match next(current.as_ref().map(|(value, _)| value.as_slice())) {
+            Ok(result) => result,
+            Err(e) => return DbResult::Err(e),
+        };
+        let expires_at = current.and_then(|(_, expires_at)| expires_at);
+        match self.write(LogRecord::Set { key: key.to_string(), value: text.into_bytes(), expires_at }) {
+            DbResult::Ok(()) => DbResult::Ok(number),
+            DbResult::NotFound => DbResult::NotFound,
+            DbResult::Err(e) => DbResult::Err(e),
+        }
+    }
+}
+
+fn parse_integer(key: &str, bytes: &[u8]) -> Result<i64, DbError> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| DbError::NotANumber { key: key.to_string() })
+}
+
+// Acepta tambien enteros; "inf" y "NaN" no cuentan como numeros
+fn parse_float(key: &str, bytes: &[u8]) -> Result<f64, DbError> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.parse::<f64>().ok())
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| DbError::NotANumber { key: key.to_string() })
+}
+
+// Notacion decimal para magnitudes usuales y exponencial para el resto,
+// asi el texto no crece con numeros como 1e300
+fn format_float(number: f64) -> String {
+    let magnitude = number.abs();
+    if magnitude == 0.0 || (1e-6..1e21).contains(&magnitude) {
+        format!("{}", number)
+    } else {
+        format!("{:e}", number)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_integer_counters() {
+        let db = NanoDb::new();
+        assert!(matches!(db.incr_by("hits", 1).await, DbResult::Ok(1)));
+        assert!(matches!(db.incr_by("hits", 41).await, DbResult::Ok(42)));
+        assert!(matches!(db.decr_by("hits", 50).await, DbResult::Ok(-8)));
+        assert!(matches!(db.get("hits").await, DbResult::Ok(ref v) if v == b"-8"));
+
+        db.set("max".to_string(), i64::MAX.to_string().into_bytes()).await;
+        assert!(matches!(db.incr_by("max", 1).await, DbResult::Err(DbError::Overflow { .. })));
+        assert!(matches!(db.get("max").await, DbResult::Ok(ref v) if v == i64::MAX.to_string().as_bytes()));
+
+        db.set("name".to_string(), b"nano".to_vec()).await;
+        assert!(matches!(db.incr_by("name", 1).await, DbResult::Err(DbError::NotANumber { .. })));
+        db.list_push("list", vec![b"a".to_vec()], false).await;
+        assert!(matches!(db.incr_by("list", 1).await, DbResult::Err(DbError::TypeMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_float_counters_keep_ttl() {
+        let db = NanoDb::new();
+        db.set_with_ttl("temp".to_string(), b"10".to_vec(), Some(Duration::from_secs(60))).await;
+        assert!(matches!(db.incr_by_float("temp", 0.5).await, DbResult::Ok(v) if v == 10.5));
+        assert!(matches!(db.get("temp").await, DbResult::Ok(ref v) if v == b"10.5"));
+        assert!(matches!(db.ttl("temp").await, DbResult::Ok(Some(_))));
+        // Un valor con decimales ya no es un entero
+        assert!(matches!(db.incr_by("temp", 1).await, DbResult::Err(DbError::NotANumber { .. })));
+
+        assert!(matches!(db.incr_by_float("big", 1e300).await, DbResult::Ok(_)));
+        assert!(matches!(db.get("big").await, DbResult::Ok(ref v) if v == b"1e300"));
+        assert!(matches!(db.incr_by_float("big", f64::MAX).await, DbResult::Err(DbError::Overflow { .. })));
+        assert!(matches!(db.incr_by_float("big", f64::NAN).await, DbResult::Err(DbError::InvalidArgument(_))));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_increments() {
+        let db = Arc::new(NanoDb::new());
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        db.incr_by("counter", 1).await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(matches!(db.get("counter").await, DbResult::Ok(ref v) if v == b"800"));
+    }
+}
@@ -0,0 +1,257 @@
+// Indices secundarios: declaracion, persistencia de las definiciones y consultas
+use std::fs::{self, File};
+use std::io::Write;
+use crate::index::{Position, Scalar, ValueRange};
+use crate::json::JsonPath;
+use super::*;
+
+// Definiciones de los indices, junto al log, al snapshot o al directorio LSM.
+// Las entradas no se guardan: se reconstruyen a partir de los datos al abrir la base.
+const INDEXES_FILE: &str = "indexes.json";
+
+impl NanoDb {
+    // Declara un indice y lo llena con las claves existentes; false si ya existia igual.
+    // No modifica datos, asi que tambien se permite en modo solo lectura.
+    pub fn create_index(&self, def: IndexDef) -> DbResult<bool> {
+        let path = match def.validate() {
+            Ok(path) => path,
+            Err(e) => return DbResult::Err(DbError::InvalidArgument(e)),
+        };
+        // Con las escrituras detenidas el indice nace consistente con los datos
+        let _locks = self.lock_all();
+        if let Some(existing) = self.indexes.definition(&def.name) {
+            if existing == def {
+                return DbResult::Ok(false);
+            }
+            return DbResult::Err(DbError::InvalidArgument(format!("index {} already exists with a different definition", def.name)));
+        }
+        let entries = match self.index_entries(&def.prefix, &path) {
+            Ok(entries) => entries,
+            Err(e) => return DbResult::Err(read_failed(e)),
+        };
+        let mut defs = self.indexes.definitions();
+        defs.push(def.clone());
+        if let DbResult::Err(e) = self.save_indexes(&defs) {
+            return DbResult::Err(e);
+        }
+        info!(index = %def.name, keys = entries.len(), namespace = ?self.namespace_name, "Index created");
+        self.indexes.create(def, path, entries);
+        DbResult::Ok(true)
+    }
+    // Elimina el indice; false si no existia
+    pub fn drop_index(&self, name: &str) -> DbResult<bool> {
+        let _locks = self.lock_all();
+        let mut defs = self.indexes.definitions();
+        let before = defs.len();
+        defs.retain(|def| def.name != name);
+        if defs.len() == before {
+            return DbResult::Ok(false);
+        }
+        if let DbResult::Err(e) = self.save_indexes(&defs) {
+            return DbResult::Err(e);
+        }
+        self.indexes.drop_index(name);
+        info!(index = %name, namespace = ?self.namespace_name, "Index dropped");
+        DbResult::Ok(true)
+    }
+    // Indices declarados, ordenados por nombre
+    pub fn indexes(&self) -> Vec<IndexDef> {
+        self.indexes.definitions()
+    }
+    // Claves cuyo valor indexado cumple la consulta, en orden de valor (y de clave entre
+    // valores iguales). Para seguir, pasar el next_cursor de la pagina anterior.
+    pub async fn query_index(&self, name: &str, query: &IndexQuery, cursor: Option<&str>, limit: usize) -> DbResult<KeysPage> {
+        self.metrics.increment_keys();
+        let range = match ValueRange::new(query) {
+            Ok(range) => range,
+            Err(e) => return DbResult::Err(DbError::InvalidArgument(e)),
+        };
+        let mut after = match cursor.map(index::decode_cursor) {
+            Some(None) => return DbResult::Err(DbError::InvalidArgument("invalid index cursor".to_string())),
+            Some(position) => position,
+            None => None,
+        };
+        // Un elemento extra para saber si quedan mas claves
+        let fetch = if limit == 0 { 0 } else { limit + 1 };
+        let now = now_millis();
+        let mut keys = Vec::new();
+        let mut last: Option<Position> = None;
+        loop {
+            let Some(batch) = self.indexes.scan(name, &range, after.as_ref(), fetch) else {
+                return DbResult::Err(DbError::InvalidArgument(format!("index {} does not exist", name)));
+            };
+            let exhausted = fetch == 0 || batch.len() < fetch;
+            for position in batch {
+                after = Some(position.clone());
+                // Las claves expiradas siguen en el indice hasta que se leen o se purgan
+                match self.data.get(&position.1, |entry| !entry.is_expired(now)) {
+                    Ok(Some(true)) => {}
+                    Ok(_) => continue,
+                    Err(e) => return DbResult::Err(read_failed(e)),
+                }
+                if limit > 0 && keys.len() == limit {
+                    return DbResult::Ok(KeysPage { keys, next_cursor: last.as_ref().map(index::encode_cursor) });
+                }
+                keys.push(position.1.clone());
+                last = Some(position);
+            }
+            if exhausted {
+                return DbResult::Ok(KeysPage { keys, next_cursor: None });
+            }
+        }
+    }
+    // Reconstruye los indices declarados a partir de los datos ya cargados
+    pub(super) fn restore_indexes(&self) -> io::Result<()> {
+        let Some(file) = self.indexes_file() else { return Ok(()) };
+        let defs: Vec<IndexDef> = match fs::read(&file) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for def in defs {
+            let path = def.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let entries = self.index_entries(&def.prefix, &path)?;
+            info!(index = %def.name, keys = entries.len(), "Index rebuilt");
+            self.indexes.create(def, path, entries);
+        }
+        Ok(())
+    }
+
+    fn index_entries(&self, prefix: &str, path: &JsonPath) -> io::Result<Vec<(String, Scalar)>> {
+        let now = now_millis();
+        let mut entries = Vec::new();
+        self.data.for_each(|key, entry| {
+            if key.starts_with(prefix) && !entry.is_expired(now) {
+                if let Some(value) = index::extract(&entry.value, path) {
+                    entries.push((key.to_string(), value));
+                }
+            }
+        })?;
+        Ok(entries)
+    }
+
+    fn indexes_file(&self) -> Option<PathBuf> {
+        [&self.config.aof_path, &self.config.snapshot_path, &self.config.lsm_dir]
+            .into_iter()
+            .flatten()
+            .find_map(|path| path.parent())
+            .map(|dir| dir.join(INDEXES_FILE))
+    }
+
+    // Se guarda antes de cambiar los indices en memoria: si falla, no cambia nada
+    fn save_indexes(&self, defs: &[IndexDef]) -> DbResult<()> {
+        match self.write_indexes_file(defs) {
+            Ok(()) => DbResult::Ok(()),
+            Err(e) => {
+                error!(error = %e, "Failed to save index definitions");
+                DbResult::Err(DbError::Storage(format!("index definitions could not be saved: {}", e)))
+            }
+        }
+    }
+    // Reemplazo atomico del archivo (sin indices se borra)
+    fn write_indexes_file(&self, defs: &[IndexDef]) -> io::Result<()> {
+        let Some(file) = self.indexes_file() else { return Ok(()) };
+        if defs.is_empty() {
+            return match fs::remove_file(&file) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let tmp = file.with_extension("tmp");
+        {
+            let mut out = File::create(&tmp)?;
+            out.write_all(&serde_json::to_vec_pretty(defs)?)?;
+            out.sync_all()?;
+        }
+        fs::rename(&tmp, &file)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn age_index() -> IndexDef {
+        IndexDef { name: "age".to_string(), prefix: "user:".to_string(), path: "$.age".to_string() }
+    }
+
+    async fn query(db: &NanoDb, query: IndexQuery) -> Vec<String> {
+        let DbResult::Ok(page) = db.query_index("age", &query, None, 0).await else { panic!("Expected page") };
+        page.keys
+    }
+
+    #[tokio::test]
+    async fn test_index_follows_writes() {
+        let db = NanoDb::new();
+        db.json_set("user:1", "$", json!({"age": 30})).await;
+        db.json_set("other:1", "$", json!({"age": 30})).await;
+        db.set("user:raw".to_string(), b"30".to_vec()).await;
+        assert!(matches!(db.create_index(age_index()), DbResult::Ok(true)));
+        assert!(matches!(db.create_index(age_index()), DbResult::Ok(false)));
+        assert!(matches!(db.create_index(IndexDef { path: "$.name".to_string(), ..age_index() }), DbResult::Err(DbError::InvalidArgument(_))));
+
+        db.json_set("user:2", "$", json!({"age": 25})).await;
+        db.json_set("user:3", "$", json!({"age": 41})).await;
+        let equals = |value| IndexQuery::Equals(value);
+        assert_eq!(query(&db, equals(json!(30))).await, vec!["user:1"]);
+        assert_eq!(query(&db, IndexQuery::Range { min: Some(json!(26)), max: None }).await, vec!["user:1", "user:3"]);
+
+        // Cada escritura mueve la clave dentro del indice
+        db.json_increment("user:2", "$.age", 5.0).await;
+        db.json_delete("user:1", "$.age").await;
+        assert_eq!(query(&db, equals(json!(30))).await, vec!["user:2"]);
+        db.set("user:3".to_string(), b"plain".to_vec()).await;
+        db.expire("user:2", Duration::from_millis(1)).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(query(&db, IndexQuery::Range { min: None, max: None }).await.is_empty());
+
+        db.json_set("user:4", "$", json!({"age": 30})).await;
+        db.clear().await;
+        assert!(query(&db, equals(json!(30))).await.is_empty());
+        assert!(matches!(db.query_index("missing", &equals(json!(1)), None, 0).await, DbResult::Err(DbError::InvalidArgument(_))));
+        assert!(matches!(db.drop_index("age"), DbResult::Ok(true)));
+        assert!(db.indexes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_pagination() {
+        let db = NanoDb::new();
+        db.create_index(age_index());
+        for i in 0..5 {
+            db.json_set(&format!("user:{}", i), "$", json!({"age": 20 + i % 2})).await;
+        }
+        let range = IndexQuery::Range { min: Some(json!(20)), max: Some(json!(21)) };
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let DbResult::Ok(page) = db.query_index("age", &range, cursor.as_deref(), 2).await else { panic!("Expected page") };
+            pages.push(page.keys);
+            // Un cambio entre paginas no repite ni salta las claves ya recorridas
+            db.json_set("user:0", "$.age", json!(99)).await;
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![vec!["user:0", "user:2"], vec!["user:4", "user:1"], vec!["user:3"]]);
+        assert!(matches!(db.query_index("age", &range, Some("bogus"), 2).await, DbResult::Err(DbError::InvalidArgument(_))));
+    }
+
+    #[tokio::test]
+    async fn test_indexes_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig { aof_path: Some(dir.path().join("db.aof")), ..DbConfig::default() };
+        {
+            let db = NanoDb::with_config(config.clone()).unwrap();
+            db.create_index(age_index());
+            db.create_index(IndexDef { name: "name".to_string(), prefix: String::new(), path: "name".to_string() });
+            db.json_set("user:1", "$", json!({"age": 30, "name": "ana"})).await;
+            db.drop_index("name");
+        }
+        let db = NanoDb::with_config(config).unwrap();
+        assert_eq!(db.indexes(), vec![age_index()]);
+        assert_eq!(query(&db, IndexQuery::Equals(json!(30))).await, vec!["user:1"]);
+    }
+}
@@ -0,0 +1,177 @@
+// Documentos JSON: lectura y cambios por ruta (ver json::JsonPath)
+use serde_json::Value as Json;
+use super::*;
+use crate::json::{self, JsonPath};
+
+// Reserva para el numero nuevo de un incremento
+const MAX_NUMBER_LEN: usize = 32;
+
+impl NanoDb {
+    // Valor en la ruta; NotFound si la clave o la ruta no existen
+    pub async fn json_get(&self, key: &str, path: &str) -> DbResult<Json> {
+        self.metrics.increment_get();
+        let parsed = match parse_path(path) {
+            Ok(parsed) => parsed,
+            Err(e) => return DbResult::Err(e),
+        };
+        match self.live(key, |entry| match &entry.value {
+            Value::Json(doc) => Some(json::get(doc, &parsed).cloned()),
+            _ => None,
+        }) {
+            Ok(Some(Some(Some(value)))) => DbResult::Ok(value),
+            Ok(Some(Some(None)) | None) => DbResult::NotFound,
+            Ok(Some(None)) => DbResult::Err(DbError::TypeMismatch { key: key.to_string(), expected: "json" }),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Escribe el valor en la ruta. En la raiz crea o reemplaza el documento; en otra ruta
+    // el documento y el contenedor tienen que existir.
+    pub async fn json_set(&self, key: &str, path: &str, value: Json) -> DbResult<()> {
+        let parsed = match parse_path(path) {
+            Ok(parsed) => parsed,
+            Err(e) => return DbResult::Err(e),
+        };
+        let growth = json::size(&value);
+        let op = CollectionOp::JsonSet { path: path.to_string(), value };
+        self.try_modify(key, op, growth, |current| match current {
+            Some(Value::Json(doc)) => match json::check_set(doc, &parsed) {
+                Ok(()) => DbResult::Ok(((), true)),
+                Err(reason) => DbResult::Err(path_error(path, reason)),
+            },
+            _ if parsed.is_root() => DbResult::Ok(((), true)),
+            _ => DbResult::NotFound,
+        })
+    }
+    // Quita el valor en la ruta (en la raiz borra la clave); devuelve cuantos valores se quitaron
+    pub async fn json_delete(&self, key: &str, path: &str) -> DbResult<usize> {
+        let parsed = match parse_path(path) {
+            Ok(parsed) => parsed,
+            Err(e) => return DbResult::Err(e),
+        };
+        if parsed.is_root() {
+            self.metrics.increment_delete();
+            let _lock = self.lock_key(key);
+            return match self.live(key, |entry| entry.value.type_name()) {
+                Ok(None) => DbResult::Ok(0),
+                Ok(Some("json")) => match self.write(LogRecord::Delete { key: key.to_string() }) {
+                    DbResult::Ok(()) => DbResult::Ok(1),
+                    DbResult::NotFound => DbResult::NotFound,
+                    DbResult::Err(e) => DbResult::Err(e),
+                },
+                Ok(Some(_)) => DbResult::Err(DbError::TypeMismatch { key: key.to_string(), expected: "json" }),
+                Err(e) => DbResult::Err(e),
+            };
+        }
+        self.try_modify(key, CollectionOp::JsonDelete { path: path.to_string() }, 0, |current| match current {
+            Some(Value::Json(doc)) => {
+                let found = json::get(doc, &parsed).is_some();
+                DbResult::Ok((found as usize, found))
+            }
+            _ => DbResult::Ok((0, false)),
+        })
+    }
+    // Agrega valores al final del arreglo de la ruta; devuelve la nueva longitud
+    pub async fn json_append(&self, key: &str, path: &str, values: Vec<Json>) -> DbResult<usize> {
+        if values.is_empty() {
+            return DbResult::Err(DbError::InvalidArgument("append requires at least one value".to_string()));
+        }
+        let parsed = match parse_path(path) {
+            Ok(parsed) => parsed,
+            Err(e) => return DbResult::Err(e),
+        };
+        let growth = values.iter().map(json::size).sum();
+        let appended = values.len();
+        let op = CollectionOp::JsonAppend { path: path.to_string(), values };
+        self.try_modify(key, op, growth, |current| match current {
+            Some(Value::Json(doc)) => match json::get(doc, &parsed) {
+                Some(Json::Array(items)) => DbResult::Ok((items.len() + appended, true)),
+                Some(_) => DbResult::Err(path_error(path, "value is not an array".to_string())),
+                None => DbResult::Err(path_error(path, "path does not exist".to_string())),
+            },
+            _ => DbResult::NotFound,
+        })
+    }
+    // Suma `delta` al numero de la ruta y devuelve el resultado
+    pub async fn json_increment(&self, key: &str, path: &str, delta: f64) -> DbResult<Json> {
+        if !delta.is_finite() {
+            return DbResult::Err(DbError::InvalidArgument(format!("increment must be finite, got {}", delta)));
+        }
+        let parsed = match parse_path(path) {
+            Ok(parsed) => parsed,
+            Err(e) => return DbResult::Err(e),
+        };
+        let op = CollectionOp::JsonIncrement { path: path.to_string(), delta };
+        self.try_modify(key, op, MAX_NUMBER_LEN, |current| match current {
+            Some(Value::Json(doc)) => match json::get(doc, &parsed) {
+                Some(Json::Number(number)) => match json::add(number, delta) {
+                    Some(next) => DbResult::Ok((Json::Number(next), true)),
+                    None => DbResult::Err(DbError::Overflow { key: key.to_string() }),
+                },
+                Some(_) => DbResult::Err(path_error(path, "value is not a number".to_string())),
+                None => DbResult::Err(path_error(path, "path does not exist".to_string())),
+            },
+            _ => DbResult::NotFound,
+        })
+    }
+}
+
+fn parse_path(path: &str) -> Result<JsonPath, DbError> {
+    JsonPath::parse(path).map_err(DbError::InvalidArgument)
+}
+
+fn path_error(path: &str, reason: String) -> DbError {
+    DbError::JsonPath { path: path.to_string(), reason }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_json_documents() {
+        let db = NanoDb::new();
+        assert!(matches!(db.json_set("user", "$.name", json!("ana")).await, DbResult::NotFound));
+        assert!(matches!(db.json_set("user", "$", json!({"name": "ana", "tags": []})).await, DbResult::Ok(())));
+        assert!(matches!(db.json_set("user", "$.age", json!(30)).await, DbResult::Ok(())));
+        assert!(matches!(db.json_append("user", "$.tags", vec![json!("a"), json!("b")]).await, DbResult::Ok(2)));
+        assert!(matches!(db.json_increment("user", "$.age", 1.0).await, DbResult::Ok(ref v) if *v == json!(31)));
+        assert!(matches!(db.json_get("user", "$.tags[-1]").await, DbResult::Ok(ref v) if *v == json!("b")));
+        assert!(matches!(db.json_delete("user", "$.tags[0]").await, DbResult::Ok(1)));
+        assert!(matches!(db.json_delete("user", "$.zip").await, DbResult::Ok(0)));
+        assert!(matches!(db.json_get("user", "$").await, DbResult::Ok(ref v) if *v == json!({"name": "ana", "age": 31, "tags": ["b"]})));
+
+        assert!(matches!(db.json_get("user", "$.zip").await, DbResult::NotFound));
+        assert!(matches!(db.json_set("user", "$.a.b", json!(1)).await, DbResult::Err(DbError::JsonPath { .. })));
+        assert!(matches!(db.json_increment("user", "$.name", 1.0).await, DbResult::Err(DbError::JsonPath { .. })));
+        assert!(matches!(db.json_append("user", "$.age", vec![json!(1)]).await, DbResult::Err(DbError::JsonPath { .. })));
+        assert!(matches!(db.json_get("user", "$[").await, DbResult::Err(DbError::InvalidArgument(_))));
+
+        db.set("name".to_string(), b"nano".to_vec()).await;
+        assert!(matches!(db.json_get("name", "$").await, DbResult::Err(DbError::TypeMismatch { .. })));
+        assert!(matches!(db.json_set("name", "$", json!(1)).await, DbResult::Err(DbError::TypeMismatch { .. })));
+
+        assert!(matches!(db.json_delete("user", "$").await, DbResult::Ok(1)));
+        assert!(matches!(db.exists("user").await, DbResult::Ok(false)));
+    }
+
+    #[tokio::test]
+    async fn test_json_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            aof_path: Some(dir.path().join("db.aof")),
+            snapshot_path: Some(dir.path().join("db.snap")),
+            ..DbConfig::default()
+        };
+        {
+            let db = NanoDb::with_config(config.clone()).unwrap();
+            db.json_set("doc", "$", json!({"n": 1, "items": [1]})).await;
+            db.save().await;
+            db.json_increment("doc", "$.n", 0.5).await;
+            db.json_append("doc", "$.items", vec![json!({"k": "v"})]).await;
+        }
+        let db = NanoDb::with_config(config).unwrap();
+        assert!(matches!(db.json_get("doc", "$").await, DbResult::Ok(ref v) if *v == json!({"n": 1.5, "items": [1, {"k": "v"}]})));
+    }
+}
@@ -0,0 +1,188 @@
+// Namespaces: bases logicas independientes dentro del mismo servidor
+use std::fs;
+use dashmap::mapref::entry::Entry as MapEntry;
+use super::*;
+
+// Directorio (junto al log, al snapshot o al directorio LSM) donde vive cada namespace persistente
+const NAMESPACES_DIR: &str = "namespaces";
+const MAX_NAMESPACE_LEN: usize = 64;
+
+// Cada namespace es un NanoDb completo con la misma configuracion que la base principal:
+// keyspace, log, snapshot, limite de memoria, change-feed, canales y metricas propios.
+// Flush, Keys, Size, Save, etc. solo ven el namespace sobre el que se ejecutan.
+impl NanoDb {
+    // Namespace existente (None si no existe)
+    pub fn namespace(&self, name: &str) -> Option<Arc<NanoDb>> {
+        self.namespaces.get(name).map(|db| db.clone())
+    }
+    // Nombres de los namespaces, ordenados
+    pub fn namespaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.namespaces.iter().map(|kv| kv.key().clone()).collect();
+        names.sort();
+        names
+    }
+    // Crea el namespace; false si ya existia
+    pub fn create_namespace(&self, name: &str) -> DbResult<bool> {
+        if let Err(e) = self.check_namespace_change(name) {
+            return DbResult::Err(e);
+        }
+        match self.namespaces.entry(name.to_string()) {
+            MapEntry::Occupied(_) => DbResult::Ok(false),
+            MapEntry::Vacant(slot) => match self.open_namespace(name) {
+                Ok(db) => {
+                    slot.insert(Arc::new(db));
+                    info!(namespace = %name, "Namespace created");
+                    DbResult::Ok(true)
+                }
+                Err(e) => {
+                    error!(error = %e, namespace = %name, "Failed to create namespace");
+                    DbResult::Err(DbError::Storage(format!("namespace {} could not be created: {}", name, e)))
+                }
+            },
+        }
+    }
+    // Elimina el namespace con todos sus datos; false si no existia.
+    // Quien aun tenga una referencia al namespace lo ve vacio.
+    pub fn drop_namespace(&self, name: &str) -> DbResult<bool> {
+        if let Err(e) = self.check_namespace_change(name) {
+            return DbResult::Err(e);
+        }
+        let Some((_, db)) = self.namespaces.remove(name) else {
+            return DbResult::Ok(false);
+        };
+        {
+            let _locks = db.lock_all();
+            // Sus directorios se borran a continuacion de todos modos
+            if let Err(e) = db.replay(LogRecord::Clear) {
+                warn!(namespace = %name, error = %e, "Failed to clear dropped namespace");
+            }
+        }
+        for dir in self.namespace_dirs(name) {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    return DbResult::Err(e.into());
+                }
+            }
+        }
+        info!(namespace = %name, "Namespace dropped");
+        DbResult::Ok(true)
+    }
+    // Reabre los namespaces persistidos junto al log, al snapshot o al directorio LSM
+    pub(super) fn restore_namespaces(&self) -> io::Result<()> {
+        let roots = [&self.config.aof_path, &self.config.snapshot_path, &self.config.lsm_dir];
+        for root in roots.into_iter().flatten().filter_map(|path| path.parent()) {
+            let dir = root.join(NAMESPACES_DIR);
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !entry.file_type()?.is_dir() || validate_name(&name).is_err() || self.namespaces.contains_key(&name) {
+                    continue;
+                }
+                let db = self.open_namespace(&name)?;
+                info!(namespace = %name, keys = db.data.len(), "Namespace restored");
+                self.namespaces.insert(name, Arc::new(db));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_namespace_change(&self, name: &str) -> Result<(), DbError> {
+        if self.namespace_name.is_some() {
+            return Err(DbError::InvalidArgument("namespaces cannot be nested".to_string()));
+        }
+        if self.read_only {
+            return Err(DbError::ReadOnly);
+        }
+        validate_name(name)
+    }
+
+    fn open_namespace(&self, name: &str) -> io::Result<NanoDb> {
+        let mut config = self.config.clone();
+        config.aof_path = config.aof_path.map(|path| namespace_path(&path, name));
+        config.snapshot_path = config.snapshot_path.map(|path| namespace_path(&path, name));
+        config.lsm_dir = config.lsm_dir.map(|path| namespace_path(&path, name));
+        for dir in self.namespace_dirs(name) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut db = NanoDb::with_config(config)?;
+        db.namespace_name = Some(name.to_string());
+        db.scripts = self.scripts.clone();
+        Ok(db)
+    }
+
+    fn namespace_dirs(&self, name: &str) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = [&self.config.aof_path, &self.config.snapshot_path, &self.config.lsm_dir]
+            .into_iter()
+            .flatten()
+            .filter_map(|path| namespace_path(path, name).parent().map(Path::to_path_buf))
+            .collect();
+        dirs.dedup();
+        dirs
+    }
+}
+
+// Nombres validos: letras, digitos, '-' y '_' (tambien se usan como directorio)
+fn validate_name(name: &str) -> Result<(), DbError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAMESPACE_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(DbError::InvalidArgument(format!("invalid namespace name: {:?}", name)))
+    }
+}
+
+// data/nanodb.aof -> data/namespaces/<name>/nanodb.aof
+fn namespace_path(path: &Path, name: &str) -> PathBuf {
+    let file = path.file_name().map(|f| f.to_os_string()).unwrap_or_default();
+    path.parent().unwrap_or(Path::new("")).join(NAMESPACES_DIR).join(name).join(file)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated() {
+        let db = NanoDb::new();
+        assert!(matches!(db.create_namespace("team-a"), DbResult::Ok(true)));
+        assert!(matches!(db.create_namespace("team-a"), DbResult::Ok(false)));
+        assert!(matches!(db.create_namespace("../etc"), DbResult::Err(DbError::InvalidArgument(_))));
+        let team = db.namespace("team-a").unwrap();
+
+        db.set("key".to_string(), b"root".to_vec()).await;
+        team.set("key".to_string(), b"team".to_vec()).await;
+        team.set("other".to_string(), b"team".to_vec()).await;
+        assert!(matches!(db.size().await, DbResult::Ok(1)));
+        assert!(matches!(team.size().await, DbResult::Ok(2)));
+
+        team.clear().await;
+        assert!(matches!(db.get("key").await, DbResult::Ok(ref v) if v == b"root"));
+        assert!(matches!(team.create_namespace("nested"), DbResult::Err(DbError::InvalidArgument(_))));
+        assert_eq!(db.namespaces(), vec!["team-a"]);
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.aof");
+        {
+            let db = NanoDb::open(&path).unwrap();
+            db.create_namespace("cache");
+            db.create_namespace("gone");
+            db.namespace("cache").unwrap().set("k".to_string(), b"v".to_vec()).await;
+            db.namespace("gone").unwrap().set("k".to_string(), b"v".to_vec()).await;
+            assert!(matches!(db.drop_namespace("gone"), DbResult::Ok(true)));
+        }
+        let db = NanoDb::open(&path).unwrap();
+        assert_eq!(db.namespaces(), vec!["cache"]);
+        assert!(matches!(db.namespace("cache").unwrap().get("k").await, DbResult::Ok(ref v) if v == b"v"));
+        assert!(matches!(db.keys().await, DbResult::Ok(ref keys) if keys.is_empty()));
+        assert!(!dir.path().join("namespaces/gone").exists());
+    }
+}
@@ -0,0 +1,386 @@
+// Replicacion lider-seguidor: el lider atiende a sus seguidores en replication_addr y el
+// seguidor (replica_of) se mantiene conectado, reconectando solo si se corta el enlace.
+// Protocolo y backlog en crate::replication.
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::timeout;
+use crate::replication::{self, FeedRecord, Frame, HEARTBEAT, LEADER_TIMEOUT};
+use super::*;
+
+// Registros RESTORE por lote al cargar el snapshot del lider
+const RESTORE_BATCH: usize = 512;
+// Espera entre reconexiones del seguidor
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+impl NanoDb {
+    // Arranca la replicacion segun la configuracion: atiende seguidores (replication_addr)
+    // o sigue a un lider (replica_of)
+    pub async fn start_replication(self: &Arc<Self>) -> io::Result<()> {
+        match (&self.config.replica_of, &self.config.replication_addr) {
+            (Some(_), Some(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a follower cannot serve replication; set either replica_of or replication_addr",
+            )),
+            (Some(leader), None) => {
+                self.spawn_replica(leader.clone());
+                Ok(())
+            }
+            (None, Some(addr)) => {
+                let listener = TcpListener::bind(addr).await?;
+                info!(addr = %addr, "Serving replication");
+                self.spawn_replication_server(listener);
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+    // Lider: acepta seguidores en el listener. Desde aqui las escrituras se publican.
+    pub fn spawn_replication_server(self: &Arc<Self>, listener: TcpListener) -> JoinHandle<()> {
+        self.replication.serve();
+        let db = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to accept follower");
+                        continue;
+                    }
+                };
+                let Some(db) = db.upgrade() else { break };
+                tokio::spawn(async move {
+                    let addr = addr.to_string();
+                    if let Err(e) = db.feed_follower(stream, &addr).await {
+                        info!(follower = %addr, error = %e, "Follower disconnected");
+                    }
+                });
+            }
+        })
+    }
+    // Seguidor: se conecta al lider y aplica lo que recibe hasta que se libera la base
+    pub fn spawn_replica(self: &Arc<Self>, leader: String) -> JoinHandle<()> {
+        self.replication.set_leader(leader.clone());
+        let db = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+            loop {
+                let Some(db) = db.upgrade() else { break };
+                let result = db.follow(&leader).await;
+                // Un enlace que llego a sincronizar reintenta enseguida
+                if db.replication.is_connected() {
+                    backoff = MIN_BACKOFF;
+                } else {
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                db.replication.set_connected(false);
+                drop(db);
+                match result {
+                    Ok(()) => break,
+                    Err(e) => warn!(leader = %leader, error = %e, "Replication link lost, reconnecting"),
+                }
+                tokio::time::sleep(backoff).await;
+            }
+        })
+    }
+    // Rol, offset y seguidores conectados
+    pub fn replication_info(&self) -> ReplicationInfo {
+        self.replication.info()
+    }
+
+    async fn feed_follower(self: Arc<Self>, stream: TcpStream, addr: &str) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut writer = BufWriter::new(writer);
+        let (id, offset) = match timeout(LEADER_TIMEOUT, replication::read_handshake(&mut reader)).await {
+            Ok(handshake) => handshake?,
+            Err(_) => return Err(timed_out("replication handshake")),
+        };
+        let (mut feed, start) = match self.replication.resume(&id, offset) {
+            Some((feed, pending)) => {
+                info!(follower = %addr, offset, pending = pending.len(), "Follower resumed");
+                writer.write_all(&Frame::Continue { offset }.encode()).await?;
+                let mut start = offset;
+                for (at, record) in pending {
+                    writer.write_all(&replication::record_frame(at, &record)).await?;
+                    start = at;
+                }
+                (feed, start)
+            }
+            None => {
+                // Con las escrituras detenidas, lo posterior al snapshot sale por el feed
+                let (feed, id, offset, entries) = {
+                    let _locks = self.lock_all();
+                    let (feed, id, offset) = self.replication.subscribe();
+                    (feed, id, offset, self.snapshot_entries()?)
+                };
+                info!(follower = %addr, offset, keys = entries.len(), "Sending full resync");
+                let snapshot = snapshot::encode(offset, &entries);
+                writer.write_all(&Frame::Full { id, offset, snapshot }.encode()).await?;
+                (feed, offset)
+            }
+        };
+        writer.flush().await?;
+        let follower = self.replication.add_follower(addr.to_string(), start);
+        let acks = tokio::spawn(read_acks(self.clone(), follower, reader));
+        let result = self.stream_records(&mut feed, &mut writer, acks).await;
+        self.replication.remove_follower(follower);
+        result
+    }
+
+    async fn stream_records(&self, feed: &mut broadcast::Receiver<FeedRecord>, writer: &mut BufWriter<OwnedWriteHalf>, mut acks: JoinHandle<()>) -> io::Result<()> {
+        let mut ping = tokio::time::interval(HEARTBEAT);
+        let result = loop {
+            let frame = tokio::select! {
+                received = feed.recv() => match received {
+                    Ok((offset, record)) => replication::record_frame(offset, &record),
+                    Err(RecvError::Lagged(missed)) => {
+                        break Err(io::Error::other(format!("follower fell behind by {} records", missed)));
+                    }
+                    Err(RecvError::Closed) => break Ok(()),
+                },
+                _ = ping.tick() => Frame::Ping { offset: self.replication.offset() }.encode(),
+                _ = &mut acks => break Err(timed_out("follower acknowledgement")),
+            };
+            if let Err(e) = writer.write_all(&frame).await {
+                break Err(e);
+            }
+            // Los registros seguidos se envian juntos
+            if feed.is_empty() {
+                if let Err(e) = writer.flush().await {
+                    break Err(e);
+                }
+            }
+        };
+        acks.abort();
+        result
+    }
+
+    async fn follow(self: &Arc<Self>, leader: &str) -> io::Result<()> {
+        let stream = match timeout(LEADER_TIMEOUT, TcpStream::connect(leader)).await {
+            Ok(stream) => stream?,
+            Err(_) => return Err(timed_out("connection to leader")),
+        };
+        stream.set_nodelay(true)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let (id, offset) = self.replication.position();
+        writer.write_all(&replication::handshake(&id, offset)).await?;
+        loop {
+            let frame = match timeout(LEADER_TIMEOUT, Frame::read(&mut reader)).await {
+                Ok(frame) => frame?,
+                Err(_) => return Err(timed_out("leader heartbeat")),
+            };
+            match frame {
+                Frame::Full { id, offset, snapshot } => {
+                    let keys = self.full_sync(id, offset, &snapshot)?;
+                    info!(leader = %leader, offset, keys, "Full resync from leader");
+                    self.replication.set_connected(true);
+                }
+                Frame::Continue { offset } => {
+                    if offset != self.replication.offset() {
+                        return Err(invalid_data(format!("leader resumed at {}, expected {}", offset, self.replication.offset())));
+                    }
+                    info!(leader = %leader, offset, "Replication resumed");
+                    self.replication.set_connected(true);
+                }
+                Frame::Record { offset, record } => {
+                    let expected = self.replication.offset() + 1;
+                    if !self.replication.is_connected() || offset != expected {
+                        return Err(invalid_data(format!("replication gap: got record {}, expected {}", offset, expected)));
+                    }
+                    self.apply_replicated(record)?;
+                    self.replication.advance(offset);
+                }
+                Frame::Ping { .. } => {
+                    // Nadie mas usa la base: se deja de seguir al lider
+                    if Arc::strong_count(self) == 1 {
+                        return Ok(());
+                    }
+                    writer.write_u64(self.replication.offset()).await?;
+                }
+            }
+        }
+    }
+    // Reemplaza los datos por el snapshot del lider; devuelve cuantas claves se cargaron
+    fn full_sync(&self, id: String, offset: u64, snapshot: &[u8]) -> io::Result<usize> {
+        let keys = self.restore_snapshot(snapshot)?;
+        self.replication.set_position(id, offset);
+        Ok(keys)
+    }
+    // Copia consistente de los datos (ver snapshot::encode), para otra base
+    pub(crate) fn snapshot_bytes(&self) -> io::Result<Vec<u8>> {
+        let entries = {
+            let _locks = self.lock_all();
+            self.snapshot_entries()?
+        };
+        Ok(snapshot::encode(0, &entries))
+    }
+    // Reemplaza los datos por los de snapshot_bytes(), pasando por el log propio
+    pub(crate) fn restore_snapshot(&self, snapshot: &[u8]) -> io::Result<usize> {
+        let (_, entries) = snapshot::decode(snapshot)?;
+        let now = now_millis();
+        let _locks = self.lock_all();
+        self.commit_replicated(LogRecord::Clear)?;
+        let mut restores = entries
+            .into_iter()
+            .filter(|entry| !matches!(entry.expires_at, Some(at) if at <= now))
+            .map(|SnapshotEntry { key, value, expires_at }| LogRecord::Restore { key, value, expires_at })
+            .peekable();
+        let mut keys = 0;
+        while restores.peek().is_some() {
+            let batch: Vec<LogRecord> = restores.by_ref().take(RESTORE_BATCH).collect();
+            keys += batch.len();
+            self.commit_replicated(LogRecord::Batch(batch))?;
+        }
+        Ok(keys)
+    }
+    // Con los locks de las claves, para que save() no vea un registro a medias
+    fn apply_replicated(&self, record: LogRecord) -> io::Result<()> {
+        let _locks = match &record {
+            LogRecord::Clear => self.lock_all(),
+            record => {
+                let mut keys = Vec::new();
+                record_keys(record, &mut keys);
+                self.lock_keys(&keys)
+            }
+        };
+        self.commit_replicated(record)
+    }
+
+    fn commit_replicated(&self, record: LogRecord) -> io::Result<()> {
+        match self.commit(record) {
+            DbResult::Err(e) => Err(io::Error::other(e.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Claves que toca un registro
+fn record_keys<'a>(record: &'a LogRecord, keys: &mut Vec<&'a str>) {
+    match record {
+        LogRecord::Set { key, .. }
+        | LogRecord::Delete { key }
+        | LogRecord::Expire { key, .. }
+        | LogRecord::Update { key, .. }
+        | LogRecord::Restore { key, .. } => keys.push(key),
+        LogRecord::Batch(records) => records.iter().for_each(|record| record_keys(record, keys)),
+        LogRecord::Clear | LogRecord::Checkpoint { .. } => {}
+    }
+}
+
+// Offsets confirmados por el seguidor; termina si deja de contestar
+async fn read_acks(db: Arc<NanoDb>, follower: u64, mut reader: BufReader<OwnedReadHalf>) {
+    while let Ok(Ok(offset)) = timeout(LEADER_TIMEOUT, reader.read_u64()).await {
+        db.replication.ack(follower, offset);
+    }
+}
+
+fn timed_out(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, format!("{} timed out", what))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn leader() -> (Arc<NanoDb>, String) {
+        let db = Arc::new(NanoDb::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        db.spawn_replication_server(listener);
+        (db, addr)
+    }
+
+    async fn follower(leader: &str) -> Arc<NanoDb> {
+        let db = Arc::new(NanoDb::with_config(DbConfig { replica_of: Some(leader.to_string()), ..DbConfig::default() }).unwrap());
+        db.start_replication().await.unwrap();
+        db
+    }
+
+    // Espera a que el seguidor este conectado y al dia con el lider
+    async fn caught_up(leader: &NanoDb, follower: &NanoDb) {
+        for _ in 0..200 {
+            let info = follower.replication_info();
+            if info.connected && info.offset == leader.replication_info().offset {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("follower did not catch up: {:?}", follower.replication_info());
+    }
+
+    #[tokio::test]
+    async fn test_follower_syncs_and_streams() {
+        let (leader, addr) = leader().await;
+        leader.set("before".to_string(), b"1".to_vec()).await;
+        leader.list_push("list", vec![b"a".to_vec()], false).await;
+
+        let follower = follower(&addr).await;
+        caught_up(&leader, &follower).await;
+        assert!(matches!(follower.get("before").await, DbResult::Ok(ref v) if v == b"1"));
+        assert!(matches!(follower.list_range("list", 0, -1).await, DbResult::Ok(ref items) if items == &[b"a".to_vec()]));
+
+        // Lo posterior llega por el stream de registros
+        leader.set_with_ttl("after".to_string(), b"2".to_vec(), Some(Duration::from_secs(60))).await;
+        leader.list_push("list", vec![b"b".to_vec()], false).await;
+        leader.delete("before").await;
+        caught_up(&leader, &follower).await;
+        assert!(matches!(follower.ttl("after").await, DbResult::Ok(Some(_))));
+        assert!(matches!(follower.exists("before").await, DbResult::Ok(false)));
+        assert!(matches!(follower.list_range("list", 0, -1).await, DbResult::Ok(ref items) if items.len() == 2));
+
+        // El seguidor no acepta escrituras de clientes
+        assert!(matches!(follower.set("x".to_string(), vec![]).await, DbResult::Err(DbError::ReadOnly)));
+        let info = follower.replication_info();
+        assert_eq!(info.leader.as_deref(), Some(addr.as_str()));
+        assert_eq!(info.id, leader.replication_info().id);
+        assert_eq!(leader.replication_info().followers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_follower_resumes_after_disconnect() {
+        let (leader, leader_addr) = leader().await;
+        // Proxy que se puede cortar entre el seguidor y el lider
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let run_proxy = |proxy: TcpListener| {
+            let leader_addr = leader_addr.clone();
+            tokio::spawn(async move {
+                let (mut inbound, _) = proxy.accept().await.unwrap();
+                let mut outbound = TcpStream::connect(&leader_addr).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+            })
+        };
+        let link = run_proxy(proxy);
+        let follower = follower(&proxy_addr.to_string()).await;
+        leader.set("a".to_string(), b"1".to_vec()).await;
+        caught_up(&leader, &follower).await;
+
+        link.abort();
+        let _ = link.await;
+        leader.set("b".to_string(), b"2".to_vec()).await;
+        let link = run_proxy(TcpListener::bind(proxy_addr).await.unwrap());
+        caught_up(&leader, &follower).await;
+        assert!(matches!(follower.get("b").await, DbResult::Ok(ref v) if v == b"2"));
+        link.abort();
+
+        // Una posicion que el backlog cubre continua; una historia ajena recibe el snapshot
+        let ReplicationInfo { id, offset, .. } = follower.replication_info();
+        let mut stream = TcpStream::connect(&leader_addr).await.unwrap();
+        stream.write_all(&replication::handshake(&id, offset - 1)).await.unwrap();
+        assert_eq!(Frame::read(&mut stream).await.unwrap(), Frame::Continue { offset: offset - 1 });
+        assert!(matches!(Frame::read(&mut stream).await.unwrap(), Frame::Record { offset: at, .. } if at == offset));
+        let mut stream = TcpStream::connect(&leader_addr).await.unwrap();
+        stream.write_all(&replication::handshake("other", offset)).await.unwrap();
+        assert_eq!(stream.read_u8().await.unwrap(), 1);
+    }
+}
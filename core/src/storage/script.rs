@@ -0,0 +1,262 @@
+// Scripts atomicos (estilo EVAL): el script solo puede tocar las claves declaradas en KEYS,
+// que quedan bloqueadas mientras corre. Sus escrituras van al log como un unico registro
+// Batch al terminar; si falla o supera el tiempo maximo no se aplica ninguna.
+use std::collections::HashMap;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde_json::Value as Json;
+use super::*;
+use crate::script;
+
+// Claves declaradas vistas desde el script: None = ausente o borrada.
+// El valor es None cuando la clave guarda una coleccion o un documento.
+type Current = Option<(Option<Vec<u8>>, Option<u64>)>;
+type View = HashMap<String, Current>;
+
+#[derive(Default)]
+struct ScriptState {
+    view: View,
+    records: Vec<LogRecord>,
+}
+
+type SharedState = Arc<Mutex<ScriptState>>;
+
+impl ScriptState {
+    fn current(&self, key: &str) -> Result<&Current, Box<EvalAltResult>> {
+        self.view.get(key).ok_or_else(|| format!("key {} was not declared in KEYS", key).into())
+    }
+
+    fn set(&mut self, key: &str, value: Dynamic, expires_at: Option<u64>) -> Result<(), Box<EvalAltResult>> {
+        self.current(key)?;
+        let value = script::to_bytes(value);
+        DbError::check_sizes(key, value.len()).map_err(|e| e.to_string())?;
+        self.view.insert(key.to_string(), Some((Some(value.clone()), expires_at)));
+        self.records.push(LogRecord::Set { key: key.to_string(), value, expires_at });
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, Box<EvalAltResult>> {
+        if self.current(key)?.is_none() {
+            return Ok(false);
+        }
+        self.view.insert(key.to_string(), None);
+        self.records.push(LogRecord::Delete { key: key.to_string() });
+        Ok(true)
+    }
+}
+
+fn state(shared: &SharedState) -> MutexGuard<'_, ScriptState> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl NanoDb {
+    // Compila el script y lo deja en la cache; devuelve su hash para eval_sha()
+    pub fn script_load(&self, source: &str) -> DbResult<String> {
+        let engine = self.scripts.engine(self.script_timeout());
+        match self.scripts.load(&engine, source) {
+            Ok((sha, _)) => DbResult::Ok(sha),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Ejecuta el script con KEYS y ARGV como arreglos de texto; devuelve su resultado como JSON.
+    // El script tambien queda en la cache.
+    pub async fn eval(&self, source: &str, keys: Vec<String>, args: Vec<String>) -> DbResult<Json> {
+        let shared = SharedState::default();
+        let engine = self.script_engine(&shared);
+        match self.scripts.load(&engine, source) {
+            Ok((_, ast)) => self.run_script(&engine, &ast, &shared, keys, args),
+            Err(e) => DbResult::Err(e),
+        }
+    }
+    // Como eval(), con un script cargado antes (NoScript si no esta en la cache)
+    pub async fn eval_sha(&self, sha: &str, keys: Vec<String>, args: Vec<String>) -> DbResult<Json> {
+        let Some(ast) = self.scripts.get(sha) else {
+            return DbResult::Err(DbError::NoScript(sha.to_string()));
+        };
+        let shared = SharedState::default();
+        let engine = self.script_engine(&shared);
+        self.run_script(&engine, &ast, &shared, keys, args)
+    }
+
+    fn script_timeout(&self) -> Duration {
+        self.config.script_timeout.unwrap_or(script::DEFAULT_TIMEOUT)
+    }
+    // API del script:
+    //   get(key) -> texto, blob o () si no existe
+    //   set(key, value) / set(key, value, ttl_ms)
+    //   del(key) -> bool
+    //   exists(key) -> bool
+    fn script_engine(&self, shared: &SharedState) -> Engine {
+        let mut engine = self.scripts.engine(self.script_timeout());
+        let view = shared.clone();
+        engine.register_fn("get", move |key: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+            match state(&view).current(key)? {
+                Some((Some(value), _)) => Ok(script::from_bytes(value.clone())),
+                Some((None, _)) => Err(DbError::TypeMismatch { key: key.to_string(), expected: "string" }.to_string().into()),
+                None => Ok(Dynamic::UNIT),
+            }
+        });
+        let view = shared.clone();
+        engine.register_fn("exists", move |key: &str| -> Result<bool, Box<EvalAltResult>> {
+            Ok(state(&view).current(key)?.is_some())
+        });
+        let view = shared.clone();
+        engine.register_fn("set", move |key: &str, value: Dynamic| state(&view).set(key, value, None));
+        let view = shared.clone();
+        engine.register_fn("set", move |key: &str, value: Dynamic, ttl_ms: i64| -> Result<(), Box<EvalAltResult>> {
+            if ttl_ms <= 0 {
+                return Err(format!("ttl must be positive, got {}", ttl_ms).into());
+            }
+            state(&view).set(key, value, Some(deadline(Duration::from_millis(ttl_ms as u64))))
+        });
+        let view = shared.clone();
+        engine.register_fn("del", move |key: &str| state(&view).delete(key));
+        engine
+    }
+
+    fn run_script(&self, engine: &Engine, ast: &AST, shared: &SharedState, keys: Vec<String>, args: Vec<String>) -> DbResult<Json> {
+        let mut declared: Vec<&str> = keys.iter().map(String::as_str).collect();
+        declared.sort_unstable();
+        declared.dedup();
+        let _locks = self.lock_keys(&declared);
+        {
+            let mut state = state(shared);
+            for key in declared {
+                match self.live(key, |entry| (entry.value.to_bytes(), entry.expires_at)) {
+                    Ok(current) => state.view.insert(key.to_string(), current),
+                    Err(e) => return DbResult::Err(e),
+                };
+            }
+        }
+        let mut scope = Scope::new();
+        scope.push_constant("KEYS", keys.into_iter().map(Dynamic::from).collect::<Array>());
+        scope.push_constant("ARGV", args.into_iter().map(Dynamic::from).collect::<Array>());
+        let result = match engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast) {
+            Ok(result) => result,
+            Err(e) => {
+                debug!(error = %e, "Script failed");
+                return DbResult::Err(script::eval_error(*e, self.script_timeout()));
+            }
+        };
+        let records = std::mem::take(&mut state(shared).records);
+        if !records.is_empty() {
+            if let DbResult::Err(e) = self.script_room(&records) {
+                return DbResult::Err(e);
+            }
+            if let DbResult::Err(e) = self.write(LogRecord::Batch(records)) {
+                return DbResult::Err(e);
+            }
+        }
+        DbResult::Ok(script::to_json(result))
+    }
+    // Los scripts no desalojan (el desalojo tomaria locks que el script ya tiene):
+    // si lo que escriben no entra en max_memory, fallan sin aplicar nada. Como en
+    // reserve_many(), cuenta la ultima escritura de cada clave y descuenta lo que reemplaza.
+    fn script_room(&self, records: &[LogRecord]) -> DbResult<()> {
+        let Some(max) = self.max_memory else { return DbResult::Ok(()) };
+        let mut last: Vec<(&str, u64)> = Vec::new();
+        for record in records {
+            let (key, size) = match record {
+                LogRecord::Set { key, value, .. } => (key.as_str(), entry_size(key, value.len())),
+                LogRecord::Delete { key } => (key.as_str(), 0),
+                _ => continue,
+            };
+            match last.iter_mut().find(|(seen, _)| *seen == key) {
+                Some(seen) => seen.1 = size,
+                None => last.push((key, size)),
+            }
+        }
+        let mut replaced = 0;
+        for (key, _) in &last {
+            match self.data.get(key, |e| entry_size(key, e.value.size())) {
+                Ok(size) => replaced += size.unwrap_or(0),
+                Err(e) => return DbResult::Err(read_failed(e)),
+            }
+        }
+        let incoming: u64 = last.iter().map(|&(_, size)| size).sum();
+        if self.used_memory().saturating_sub(replaced) + incoming > max {
+            return DbResult::Err(DbError::OutOfMemory { requested: incoming, max });
+        }
+        DbResult::Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_eval_reads_and_writes_declared_keys() {
+        let db = NanoDb::new();
+        db.set("stock".to_string(), b"3".to_vec()).await;
+        // Descontar del stock solo si alcanza y registrar la orden
+        let source = r#"
+            let stock = parse_int(get(KEYS[0]));
+            let wanted = parse_int(ARGV[0]);
+            if stock < wanted { return false; }
+            set(KEYS[0], stock - wanted);
+            set(KEYS[1], ARGV[0], 60000);
+            true
+        "#;
+        let keys = strings(&["stock", "order:1"]);
+        assert!(matches!(db.eval(source, keys.clone(), strings(&["2"])).await, DbResult::Ok(Json::Bool(true))));
+        assert!(matches!(db.eval(source, keys, strings(&["2"])).await, DbResult::Ok(Json::Bool(false))));
+        assert!(matches!(db.get("stock").await, DbResult::Ok(ref v) if v == b"1"));
+        assert!(matches!(db.ttl("order:1").await, DbResult::Ok(Some(_))));
+
+        let sha = script::script_hash(source);
+        assert!(matches!(db.eval_sha(&sha, strings(&["stock", "order:2"]), strings(&["1"])).await, DbResult::Ok(Json::Bool(true))));
+        assert!(matches!(db.get("stock").await, DbResult::Ok(ref v) if v == b"0"));
+        assert!(matches!(db.eval_sha("missing", vec![], vec![]).await, DbResult::Err(DbError::NoScript(_))));
+
+        let result = db.eval(r#"[exists(KEYS[0]), del(KEYS[0]), get(KEYS[0])]"#, strings(&["stock"]), vec![]).await;
+        assert!(matches!(result, DbResult::Ok(ref v) if *v == json!([true, true, null])));
+    }
+
+    #[tokio::test]
+    async fn test_failed_script_applies_nothing() {
+        let db = NanoDb::with_config(DbConfig { script_timeout: Some(Duration::from_millis(20)), ..DbConfig::default() }).unwrap();
+        let keys = strings(&["a"]);
+        assert!(matches!(db.eval(r#"set(KEYS[0], "1"); throw "stop""#, keys.clone(), vec![]).await, DbResult::Err(DbError::Script(_))));
+        assert!(matches!(db.eval(r#"set(KEYS[0], "1"); loop {}"#, keys.clone(), vec![]).await, DbResult::Err(DbError::ScriptTimeout { limit_ms: 20 })));
+        assert!(matches!(db.eval(r#"set(KEYS[0], "1"); set("b", "1")"#, keys, vec![]).await, DbResult::Err(DbError::Script(_))));
+        assert!(matches!(db.size().await, DbResult::Ok(0)));
+
+        let DbResult::Ok(sha) = db.script_load("KEYS.len()") else { panic!("Expected hash") };
+        assert_eq!(sha, script::script_hash("KEYS.len()"));
+        assert!(matches!(db.script_load("let"), DbResult::Err(DbError::Script(_))));
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_at_memory_limit() {
+        let db = NanoDb::with_config(DbConfig { max_memory: Some(1024), ..DbConfig::default() }).unwrap();
+        let value = vec![b'x'; 400];
+        db.set("a".to_string(), value.clone()).await;
+        db.set("b".to_string(), value.clone()).await;
+        // Reescribir "a" (dos veces) ocupa lo mismo que ya ocupaba
+        let source = "set(KEYS[0], ARGV[0]); set(KEYS[0], ARGV[0]);";
+        let args = vec![String::from_utf8(value).unwrap()];
+        assert!(matches!(db.eval(source, strings(&["a"]), args.clone()).await, DbResult::Ok(_)));
+        // Una clave nueva del mismo tamaño no entra
+        assert!(matches!(db.eval(source, strings(&["c"]), args).await, DbResult::Err(DbError::OutOfMemory { .. })));
+        assert!(matches!(db.exists("c").await, DbResult::Ok(false)));
+    }
+
+    #[tokio::test]
+    async fn test_script_writes_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.aof");
+        {
+            let db = NanoDb::open(&path).unwrap();
+            db.set("from".to_string(), b"x".to_vec()).await;
+            db.eval("let v = get(KEYS[0]); del(KEYS[0]); set(KEYS[1], v);", strings(&["from", "to"]), vec![]).await;
+        }
+        let db = NanoDb::open(&path).unwrap();
+        assert!(matches!(db.keys().await, DbResult::Ok(ref keys) if keys == &["to"]));
+    }
+}
@@ -0,0 +1,357 @@
+// Transacciones multi-clave (estilo MULTI/EXEC)
+use std::collections::HashMap;
+use super::*;
+use crate::operations::{Precondition, TxnOutcome};
+
+// Valor y expiracion de una clave vista desde la transaccion: None = no existe (o borrada).
+// El valor es None cuando la clave guarda una coleccion (solo se le puede cambiar el TTL).
+type Current = Option<(Option<Vec<u8>>, Option<u64>)>;
+
+// Estado de las claves escritas dentro de la transaccion
+type Pending = HashMap<String, Current>;
+
+impl NanoDb {
+    // Aplica las operaciones todo-o-nada:
+    //   1. Se toman los locks de todas las claves involucradas (aislamiento frente a otros escritores)
+    //   2. Se verifican las precondiciones; si alguna falla no se escribe nada
+    //   3. Si una operacion falla (p. ej. GET sobre una coleccion) se devuelve su error sin escribir nada
+    //   4. Las escrituras van al log como un unico registro Batch
+    // Las lecturas dentro de la transaccion ven las escrituras anteriores de la misma transaccion.
+    pub async fn transaction(&self, preconditions: Vec<Precondition>, operations: Vec<DbOperation>) -> DbResult<TxnOutcome> {
+        let mut keys: Vec<&str> = preconditions.iter().map(Precondition::key).collect();
+        for op in &operations {
+            match txn_key(op) {
+                Some(key) => keys.push(key),
+                None => return DbResult::Err(not_allowed(op)),
+            }
+        }
+
+        // Reservar memoria antes de tomar los locks (el desalojo toma los suyos), una sola vez
+        // para todas las escrituras: reservar por operacion dejaria pasar una transaccion que
+        // en conjunto no cabe
+        let incoming: Vec<(&str, usize)> = operations
+            .iter()
+            .filter_map(|op| match op {
+                DbOperation::Set { key, value, .. } => Some((key.as_str(), value.len())),
+                DbOperation::CompareAndSwap { key, new_value: Some(value), .. } => Some((key.as_str(), value.len())),
+                _ => None,
+            })
+            .collect();
+        if let DbResult::Err(e) = self.reserve_many(&incoming) {
+            return DbResult::Err(e);
+        }
+
+        let _locks = self.lock_keys(&keys);
+        for (index, precondition) in preconditions.iter().enumerate() {
+            match self.check(precondition) {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!(index = index, key = %precondition.key(), "Transaction aborted by precondition");
+                    return DbResult::Ok(TxnOutcome::Aborted { precondition: index });
+                }
+                Err(e) => return DbResult::Err(e),
+            }
+        }
+
+        let mut pending = Pending::new();
+        let mut records = Vec::new();
+        let mut results = Vec::with_capacity(operations.len());
+        for (index, op) in operations.into_iter().enumerate() {
+            match self.plan(op, &mut pending, &mut records) {
+                DbResponse::Error(e) => {
+                    debug!(index = index, error = %e, "Transaction aborted by operation error");
+                    return DbResult::Err(e);
+                }
+                response => results.push(response),
+            }
+        }
+
+        if !records.is_empty() {
+            let count = records.len();
+            if let DbResult::Err(e) = self.write(LogRecord::Batch(records)) {
+                return DbResult::Err(e);
+            }
+            info!(writes = count, "Transaction committed");
+        }
+        DbResult::Ok(TxnOutcome::Committed(results))
+    }
+
+    fn check(&self, precondition: &Precondition) -> Result<bool, DbError> {
+        Ok(match precondition {
+            Precondition::Value { key, expected } => match self.live(key, |entry| entry.value.to_bytes())? {
+                Some(Some(value)) => expected.as_ref() == Some(&value),
+                Some(None) => false,    // Coleccion: nunca coincide con un valor
+                None => expected.is_none(),
+            },
+            Precondition::Version { key, version } => self.live(key, |entry| entry.version)?.unwrap_or(0) == *version,
+        })
+    }
+
+    // Valor y expiracion de la clave vistos desde la transaccion
+    fn txn_current(&self, key: &str, pending: &Pending) -> Result<Current, DbError> {
+        match pending.get(key) {
+            Some(state) => Ok(state.clone()),
+            None => self.live(key, |entry| (entry.value.to_bytes(), entry.expires_at)),
+        }
+    }
+
+    // Calcula la respuesta de una operacion y los registros que escribiria
+    fn plan(&self, op: DbOperation, pending: &mut Pending, records: &mut Vec<LogRecord>) -> DbResponse {
+        // Estado actual de la clave (SET no lo necesita). Un error de lectura (motor LSM)
+        // aborta la transaccion como cualquier otro error.
+        let current = match txn_key(&op).filter(|_| !matches!(op, DbOperation::Set { .. })) {
+            Some(key) => match self.txn_current(key, pending) {
+                Ok(current) => current,
+                Err(e) => return DbResponse::Error(e),
+            },
+            None => None,
+        };
+        match op {
+            DbOperation::Get { key, default } => {
+                self.metrics.increment_get();
+                match (current, default) {
+                    (Some((Some(value), _)), _) => DbResponse::Value(value),
+                    (Some((None, _)), _) => DbResponse::Error(DbError::TypeMismatch { key, expected: "string" }),
+                    (None, Some(default)) => DbResponse::Value(default),
+                    (None, None) => DbResponse::NotFound,
+                }
+            }
+            DbOperation::Exists { .. } => DbResponse::Bool(current.is_some()),
+            DbOperation::Ttl { .. } => match current {
+                Some((_, expires_at)) => DbResponse::Ttl(expires_at.map(|at| Duration::from_millis(at.saturating_sub(now_millis())))),
+                None => DbResponse::NotFound,
+            },
+            DbOperation::Set { key, value, ttl } => {
+                self.metrics.increment_set();
+                let expires_at = ttl.map(deadline);
+                pending.insert(key.clone(), Some((Some(value.clone()), expires_at)));
+                records.push(LogRecord::Set { key, value, expires_at });
+                DbResponse::Ok
+            }
+            DbOperation::Delete { key } => {
+                self.metrics.increment_delete();
+                if current.is_some() {
+                    pending.insert(key.clone(), None);
+                    records.push(LogRecord::Delete { key });
+                }
+                DbResponse::Ok
+            }
+            DbOperation::Expire { key, ttl } => plan_expiry(key, current, Some(deadline(ttl)), pending, records),
+            DbOperation::Persist { key } => plan_expiry(key, current, None, pending, records),
+            DbOperation::CompareAndSwap { key, old_value, new_value } => {
+                let (current, expires_at) = match current {
+                    Some((None, _)) => return DbResponse::Error(DbError::TypeMismatch { key, expected: "string" }),
+                    Some((value, expires_at)) => (value, expires_at),
+                    None => (None, None),
+                };
+                if current != old_value {
+                    return DbResponse::Cas(CasOutcome { swapped: false, current });
+                }
+                match &new_value {
+                    // Igual que fuera de la transaccion, se conserva el TTL
+                    Some(value) => {
+                        pending.insert(key.clone(), Some((Some(value.clone()), expires_at)));
+                        records.push(LogRecord::Set { key, value: value.clone(), expires_at });
+                    }
+                    None if current.is_some() => {
+                        pending.insert(key.clone(), None);
+                        records.push(LogRecord::Delete { key });
+                    }
+                    None => {}
+                }
+                DbResponse::Cas(CasOutcome { swapped: true, current: new_value })
+            }
+            // txn_key() ya rechazo el resto
+            other => DbResponse::Error(not_allowed(&other)),
+        }
+    }
+
+}
+
+// EXPIRE (Some) o PERSIST (None) dentro de la transaccion, sobre el estado actual de la clave
+fn plan_expiry(
+    key: String,
+    current: Current,
+    expires_at: Option<u64>,
+    pending: &mut Pending,
+    records: &mut Vec<LogRecord>,
+) -> DbResponse {
+    let Some((value, previous)) = current else {
+        return DbResponse::Bool(false);
+    };
+    // PERSIST sobre una clave sin TTL no cambia nada
+    if expires_at.is_none() && previous.is_none() {
+        return DbResponse::Bool(false);
+    }
+    pending.insert(key.clone(), Some((value, expires_at)));
+    records.push(LogRecord::Expire { key, expires_at });
+    DbResponse::Bool(true)
+}
+
+fn not_allowed(op: &DbOperation) -> DbError {
+    DbError::InvalidArgument(format!("operation not allowed in transaction: {:?}", op))
+}
+
+// Clave de una operacion permitida dentro de una transaccion
+fn txn_key(op: &DbOperation) -> Option<&str> {
+    match op {
+        DbOperation::Get { key, .. }
+        | DbOperation::Set { key, .. }
+        | DbOperation::Delete { key }
+        | DbOperation::Exists { key }
+        | DbOperation::Expire { key, .. }
+        | DbOperation::Persist { key }
+        | DbOperation::Ttl { key }
+        | DbOperation::CompareAndSwap { key, .. } => Some(key),
+        _ => None,
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(key: &str, value: &str) -> DbOperation {
+        DbOperation::Set { key: key.to_string(), value: value.as_bytes().to_vec(), ttl: None }
+    }
+
+    #[tokio::test]
+    async fn test_move_value_between_keys() {
+        let db = NanoDb::new();
+        db.set("from".to_string(), b"coin".to_vec()).await;
+        let outcome = db.transaction(
+            vec![Precondition::Value { key: "to".to_string(), expected: None }],
+            vec![
+                DbOperation::Get { key: "from".to_string(), default: None },
+                set("to", "coin"),
+                DbOperation::Delete { key: "from".to_string() },
+                DbOperation::Exists { key: "from".to_string() },
+            ],
+        ).await;
+        let DbResult::Ok(TxnOutcome::Committed(results)) = outcome else { panic!("Expected commit, got {:?}", outcome) };
+        assert_eq!(results, vec![
+            DbResponse::Value(b"coin".to_vec()),
+            DbResponse::Ok,
+            DbResponse::Ok,
+            DbResponse::Bool(false),
+        ]);
+        assert!(matches!(db.get("to").await, DbResult::Ok(ref v) if v == b"coin"));
+        assert!(matches!(db.get("from").await, DbResult::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_failed_precondition_applies_nothing() {
+        let db = NanoDb::new();
+        db.set("a".to_string(), b"1".to_vec()).await;
+        let DbResult::Ok(version) = db.version("a").await else { panic!("Expected version") };
+        db.set("a".to_string(), b"2".to_vec()).await;
+
+        let outcome = db.transaction(
+            vec![
+                Precondition::Value { key: "a".to_string(), expected: Some(b"2".to_vec()) },
+                Precondition::Version { key: "a".to_string(), version },
+            ],
+            vec![set("a", "3"), set("b", "3")],
+        ).await;
+        assert!(matches!(outcome, DbResult::Ok(TxnOutcome::Aborted { precondition: 1 })));
+        assert!(matches!(db.get("a").await, DbResult::Ok(ref v) if v == b"2"));
+        assert!(matches!(db.exists("b").await, DbResult::Ok(false)));
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_swap_keeps_ttl() {
+        let db = NanoDb::new();
+        db.set_with_ttl("lock".to_string(), b"a".to_vec(), Some(Duration::from_secs(60))).await;
+        let cas = DbOperation::CompareAndSwap { key: "lock".to_string(), old_value: Some(b"a".to_vec()), new_value: Some(b"b".to_vec()) };
+        let ttl = DbOperation::Ttl { key: "lock".to_string() };
+        let outcome = db.transaction(vec![], vec![cas, ttl]).await;
+        let DbResult::Ok(TxnOutcome::Committed(results)) = outcome else { panic!("Expected commit, got {:?}", outcome) };
+        assert!(matches!(results[1], DbResponse::Ttl(Some(_))));
+        assert!(matches!(db.ttl("lock").await, DbResult::Ok(Some(ttl)) if ttl > Duration::from_secs(50)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_operation_applies_nothing() {
+        let db = NanoDb::new();
+        db.list_push("list", vec![b"x".to_vec()], false).await;
+        let outcome = db.transaction(
+            vec![],
+            vec![
+                set("a", "1"),
+                DbOperation::Get { key: "list".to_string(), default: None },
+                set("b", "2"),
+            ],
+        ).await;
+        assert!(matches!(outcome, DbResult::Err(DbError::TypeMismatch { .. })));
+        assert!(matches!(db.exists("a").await, DbResult::Ok(false)));
+        assert!(matches!(db.exists("b").await, DbResult::Ok(false)));
+    }
+
+    #[tokio::test]
+    async fn test_reserves_total_size_once() {
+        let db = NanoDb::with_config(DbConfig { max_memory: Some(1024), ..DbConfig::default() }).unwrap();
+        let big = "x".repeat(400);
+        let outcome = db.transaction(vec![], vec![set("a", &big), set("b", &big), set("c", &big)]).await;
+        assert!(matches!(outcome, DbResult::Err(DbError::OutOfMemory { .. })));
+        assert!(matches!(db.keys().await, DbResult::Ok(ref keys) if keys.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unsupported_operations() {
+        let db = NanoDb::new();
+        let outcome = db.transaction(vec![], vec![set("a", "1"), DbOperation::Flush]).await;
+        assert!(matches!(outcome, DbResult::Err(_)));
+        assert!(matches!(db.exists("a").await, DbResult::Ok(false)));
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_replayed_from_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("txn.aof");
+        {
+            let db = NanoDb::open(&path).unwrap();
+            db.set("from".to_string(), b"x".to_vec()).await;
+            db.transaction(vec![], vec![set("to", "x"), DbOperation::Delete { key: "from".to_string() }]).await;
+        }
+        let db = NanoDb::open(&path).unwrap();
+        assert!(matches!(db.keys().await, DbResult::Ok(ref keys) if keys == &["to"]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_transfers_are_isolated() {
+        let db = std::sync::Arc::new(NanoDb::new());
+        db.set("a".to_string(), b"100".to_vec()).await;
+        db.set("b".to_string(), b"0".to_vec()).await;
+        let mut handles = Vec::new();
+        for _ in 0..4 {
+            let db = db.clone();
+            handles.push(tokio::spawn(async move {
+                for _ in 0..25 {
+                    // Leer ambos saldos y moverlos con precondiciones de valor
+                    loop {
+                        let DbResult::Ok(a) = db.get("a").await else { panic!() };
+                        let DbResult::Ok(b) = db.get("b").await else { panic!() };
+                        let parse = |v: &[u8]| String::from_utf8_lossy(v).parse::<u64>().unwrap();
+                        let (na, nb) = (parse(&a) - 1, parse(&b) + 1);
+                        let outcome = db.transaction(
+                            vec![
+                                Precondition::Value { key: "a".to_string(), expected: Some(a) },
+                                Precondition::Value { key: "b".to_string(), expected: Some(b) },
+                            ],
+                            vec![set("a", &na.to_string()), set("b", &nb.to_string())],
+                        ).await;
+                        if matches!(outcome, DbResult::Ok(TxnOutcome::Committed(_))) {
+                            break;
+                        }
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert!(matches!(db.get("a").await, DbResult::Ok(ref v) if v == b"0"));
+        assert!(matches!(db.get("b").await, DbResult::Ok(ref v) if v == b"100"));
+    }
+}
@@ -0,0 +1,567 @@
+// Importaciones
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io;
+use crate::compression::Compressed;
+use crate::json::{self, JsonPath};
+
+// Bytes extra contabilizados por elemento de una coleccion
+const ELEMENT_OVERHEAD: usize = 16;
+
+// Tags de tipo (snapshot)
+const TYPE_BYTES: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_HASH: u8 = 2;
+const TYPE_SET: u8 = 3;
+const TYPE_SORTED_SET: u8 = 4;
+const TYPE_JSON: u8 = 5;
+
+// Tags de las operaciones sobre colecciones (log)
+const OP_PUSH: u8 = 1;
+const OP_POP: u8 = 2;
+const OP_HASH_SET: u8 = 3;
+const OP_HASH_DELETE: u8 = 4;
+const OP_SET_ADD: u8 = 5;
+const OP_SET_REMOVE: u8 = 6;
+const OP_ZSET_ADD: u8 = 7;
+const OP_ZSET_REMOVE: u8 = 8;
+const OP_JSON_SET: u8 = 9;
+const OP_JSON_DELETE: u8 = 10;
+const OP_JSON_APPEND: u8 = 11;
+const OP_JSON_INCREMENT: u8 = 12;
+
+// Valor almacenado en una clave. Las colecciones vacias no se guardan:
+// la clave se borra cuando se quita su ultimo elemento.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bytes(Vec<u8>),
+    Compressed(Compressed),     // Cadena guardada comprimida (ver DbConfig::compression)
+    List(VecDeque<Vec<u8>>),
+    Hash(HashMap<String, Vec<u8>>),
+    Set(BTreeSet<Vec<u8>>),
+    SortedSet(SortedSet),
+    Json(serde_json::Value),    // Documento JSON (ver json::JsonPath)
+}
+
+impl Value {
+    // Nombre del tipo (se usa en DbError::TypeMismatch)
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bytes(_) | Value::Compressed(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Json(_) => "json",
+        }
+    }
+
+    // Bytes originales de una cadena (descomprimidos si hace falta)
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes.clone()),
+            Value::Compressed(compressed) => Some(compressed.decompress()),
+            _ => None,
+        }
+    }
+
+    // Coleccion sin elementos (una cadena o un documento nunca se consideran vacios)
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Value::Bytes(_) | Value::Compressed(_) | Value::Json(_) => false,
+            Value::List(list) => list.is_empty(),
+            Value::Hash(hash) => hash.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::SortedSet(zset) => zset.is_empty(),
+        }
+    }
+
+    // Bytes contabilizados para el limite de memoria
+    pub fn size(&self) -> usize {
+        match self {
+            Value::Bytes(bytes) => bytes.len(),
+            Value::Compressed(compressed) => compressed.len(),
+            Value::List(list) => list.iter().map(|item| item.len() + ELEMENT_OVERHEAD).sum(),
+            Value::Hash(hash) => hash.iter().map(|(field, value)| field.len() + value.len() + ELEMENT_OVERHEAD).sum(),
+            Value::Set(set) => set.iter().map(|member| member.len() + ELEMENT_OVERHEAD).sum(),
+            Value::SortedSet(zset) => zset.iter().map(|(member, _)| member.len() + 8 + ELEMENT_OVERHEAD).sum(),
+            Value::Json(doc) => json::size(doc),
+        }
+    }
+
+    // Tag de tipo y cuerpo codificado (para el snapshot)
+    pub fn encode(&self) -> (u8, Vec<u8>) {
+        let mut out = Vec::new();
+        let tag = match self {
+            Value::Bytes(bytes) => return (TYPE_BYTES, bytes.clone()),
+            // El snapshot guarda los bytes originales; se recomprimen al cargarlo
+            Value::Compressed(compressed) => return (TYPE_BYTES, compressed.decompress()),
+            Value::List(list) => {
+                encode_items(&mut out, list.iter().map(Vec::as_slice));
+                TYPE_LIST
+            }
+            Value::Hash(hash) => {
+                encode_items(&mut out, hash.iter().flat_map(|(field, value)| [field.as_bytes(), value.as_slice()]));
+                TYPE_HASH
+            }
+            Value::Set(set) => {
+                encode_items(&mut out, set.iter().map(Vec::as_slice));
+                TYPE_SET
+            }
+            Value::SortedSet(zset) => {
+                let scores: Vec<[u8; 8]> = zset.iter().map(|(_, score)| score.to_be_bytes()).collect();
+                encode_items(&mut out, zset.iter().zip(&scores).flat_map(|((member, _), score)| [&score[..], member]));
+                TYPE_SORTED_SET
+            }
+            Value::Json(doc) => return (TYPE_JSON, doc.to_string().into_bytes()),
+        };
+        (tag, out)
+    }
+
+    pub fn decode(tag: u8, body: &[u8]) -> io::Result<Value> {
+        if tag == TYPE_BYTES {
+            return Ok(Value::Bytes(body.to_vec()));
+        }
+        if tag == TYPE_JSON {
+            return serde_json::from_slice(body).map(Value::Json).map_err(|_| invalid("invalid json document"));
+        }
+        let items = decode_items(body)?;
+        match tag {
+            TYPE_LIST => Ok(Value::List(items.into_iter().collect())),
+            TYPE_HASH => Ok(Value::Hash(decode_fields(items)?.into_iter().collect())),
+            TYPE_SET => Ok(Value::Set(items.into_iter().collect())),
+            TYPE_SORTED_SET => {
+                let mut zset = SortedSet::default();
+                for (score, member) in decode_scores(items)? {
+                    zset.insert(member, score);
+                }
+                Ok(Value::SortedSet(zset))
+            }
+            other => Err(invalid(&format!("unknown value type {}", other))),
+        }
+    }
+}
+
+// Puntaje con orden total (f64 no implementa Ord)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Score(f64);
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Conjunto ordenado por puntaje (a igual puntaje, por miembro)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    order: BTreeSet<(Score, Vec<u8>)>,
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    // Inserta o actualiza el puntaje; true si el miembro es nuevo
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> bool {
+        let new = match self.scores.insert(member.clone(), score) {
+            Some(old) => {
+                self.order.remove(&(Score(old), member.clone()));
+                false
+            }
+            None => true,
+        };
+        self.order.insert((Score(score), member));
+        new
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.order.remove(&(Score(score), member.to_vec())),
+            None => false,
+        }
+    }
+
+    // Miembros en orden ascendente de puntaje
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Vec<u8>, f64)> {
+        self.order.iter().map(|(score, member)| (member, score.0))
+    }
+
+    // Posiciones [start, stop] inclusivas; los negativos cuentan desde el final
+    pub fn range_by_rank(&self, start: i64, stop: i64) -> Vec<(Vec<u8>, f64)> {
+        match rank_bounds(self.len(), start, stop) {
+            Some((from, to)) => self.iter().skip(from).take(to - from + 1).map(|(m, s)| (m.clone(), s)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Miembros con min <= puntaje <= max
+    pub fn range_by_score(&self, min: f64, max: f64) -> Vec<(Vec<u8>, f64)> {
+        if min > max {
+            return Vec::new();
+        }
+        self.order
+            .range((Score(min), Vec::new())..)
+            .take_while(|(score, _)| score.0 <= max)
+            .map(|(score, member)| (member.clone(), score.0))
+            .collect()
+    }
+}
+
+// Convierte [start, stop] (inclusivo, negativos desde el final) a indices validos
+pub(crate) fn rank_bounds(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+    (start <= stop && start < len).then_some((start as usize, stop as usize))
+}
+
+// Cambio sobre una coleccion. Es lo que se registra en el log: al reproducirlo
+// sobre el mismo estado se obtiene el mismo resultado.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollectionOp {
+    Push { values: Vec<Vec<u8>>, front: bool },
+    Pop { count: usize, front: bool },
+    HashSet { fields: Vec<(String, Vec<u8>)> },
+    HashDelete { fields: Vec<String> },
+    SetAdd { members: Vec<Vec<u8>> },
+    SetRemove { members: Vec<Vec<u8>> },
+    SortedSetAdd { members: Vec<(f64, Vec<u8>)> },
+    SortedSetRemove { members: Vec<Vec<u8>> },
+    // Documentos JSON: la ruta se valida antes de escribir
+    JsonSet { path: String, value: serde_json::Value },
+    JsonDelete { path: String },
+    JsonAppend { path: String, values: Vec<serde_json::Value> },
+    JsonIncrement { path: String, delta: f64 },
+}
+
+impl CollectionOp {
+    // Tipo de valor sobre el que opera
+    pub fn type_name(&self) -> &'static str {
+        self.empty().type_name()
+    }
+
+    // Coleccion vacia que se crea si la clave no existe
+    pub fn empty(&self) -> Value {
+        match self {
+            CollectionOp::Push { .. } | CollectionOp::Pop { .. } => Value::List(VecDeque::new()),
+            CollectionOp::HashSet { .. } | CollectionOp::HashDelete { .. } => Value::Hash(HashMap::new()),
+            CollectionOp::SetAdd { .. } | CollectionOp::SetRemove { .. } => Value::Set(BTreeSet::new()),
+            CollectionOp::SortedSetAdd { .. } | CollectionOp::SortedSetRemove { .. } => Value::SortedSet(SortedSet::default()),
+            CollectionOp::JsonSet { .. }
+            | CollectionOp::JsonDelete { .. }
+            | CollectionOp::JsonAppend { .. }
+            | CollectionOp::JsonIncrement { .. } => Value::Json(serde_json::Value::Null),
+        }
+    }
+
+    // Aplica el cambio; un valor de otro tipo no se toca (se verifica antes de escribir)
+    pub fn apply(&self, value: &mut Value) {
+        match (self, value) {
+            (CollectionOp::Push { values, front }, Value::List(list)) => {
+                for item in values {
+                    if *front {
+                        list.push_front(item.clone());
+                    } else {
+                        list.push_back(item.clone());
+                    }
+                }
+            }
+            (CollectionOp::Pop { count, front }, Value::List(list)) => {
+                let count = (*count).min(list.len());
+                if *front {
+                    list.drain(..count);
+                } else {
+                    list.truncate(list.len() - count);
+                }
+            }
+            (CollectionOp::HashSet { fields }, Value::Hash(hash)) => {
+                for (field, value) in fields {
+                    hash.insert(field.clone(), value.clone());
+                }
+            }
+            (CollectionOp::HashDelete { fields }, Value::Hash(hash)) => {
+                for field in fields {
+                    hash.remove(field);
+                }
+            }
+            (CollectionOp::SetAdd { members }, Value::Set(set)) => set.extend(members.iter().cloned()),
+            (CollectionOp::SetRemove { members }, Value::Set(set)) => {
+                for member in members {
+                    set.remove(member);
+                }
+            }
+            (CollectionOp::SortedSetAdd { members }, Value::SortedSet(zset)) => {
+                for (score, member) in members {
+                    zset.insert(member.clone(), *score);
+                }
+            }
+            (CollectionOp::SortedSetRemove { members }, Value::SortedSet(zset)) => {
+                for member in members {
+                    zset.remove(member);
+                }
+            }
+            (CollectionOp::JsonSet { path, value }, Value::Json(doc)) => {
+                if let Ok(path) = JsonPath::parse(path) {
+                    let _ = json::set(doc, &path, value.clone());
+                }
+            }
+            (CollectionOp::JsonDelete { path }, Value::Json(doc)) => {
+                if let Ok(path) = JsonPath::parse(path) {
+                    json::delete(doc, &path);
+                }
+            }
+            (CollectionOp::JsonAppend { path, values }, Value::Json(doc)) => {
+                if let Ok(path) = JsonPath::parse(path) {
+                    let _ = json::append(doc, &path, values);
+                }
+            }
+            (CollectionOp::JsonIncrement { path, delta }, Value::Json(doc)) => {
+                if let Ok(path) = JsonPath::parse(path) {
+                    let _ = json::increment(doc, &path, *delta);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Formato: tag (1 byte) | items (ver encode_items). Pop: tag | front (1) | count (4 bytes BE)
+    // JSON: items con la ruta y los valores como texto (incremento: ruta y delta, 8 bytes BE)
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            CollectionOp::Push { values, front } => {
+                out.extend_from_slice(&[OP_PUSH, *front as u8]);
+                encode_items(out, values.iter().map(Vec::as_slice));
+            }
+            CollectionOp::Pop { count, front } => {
+                out.extend_from_slice(&[OP_POP, *front as u8]);
+                out.extend_from_slice(&(*count as u32).to_be_bytes());
+            }
+            CollectionOp::HashSet { fields } => {
+                out.push(OP_HASH_SET);
+                encode_items(out, fields.iter().flat_map(|(field, value)| [field.as_bytes(), value.as_slice()]));
+            }
+            CollectionOp::HashDelete { fields } => {
+                out.push(OP_HASH_DELETE);
+                encode_items(out, fields.iter().map(String::as_bytes));
+            }
+            CollectionOp::SetAdd { members } => {
+                out.push(OP_SET_ADD);
+                encode_items(out, members.iter().map(Vec::as_slice));
+            }
+            CollectionOp::SetRemove { members } => {
+                out.push(OP_SET_REMOVE);
+                encode_items(out, members.iter().map(Vec::as_slice));
+            }
+            CollectionOp::SortedSetAdd { members } => {
+                out.push(OP_ZSET_ADD);
+                let scores: Vec<[u8; 8]> = members.iter().map(|(score, _)| score.to_be_bytes()).collect();
+                encode_items(out, members.iter().zip(&scores).flat_map(|((_, member), score)| [&score[..], member]));
+            }
+            CollectionOp::SortedSetRemove { members } => {
+                out.push(OP_ZSET_REMOVE);
+                encode_items(out, members.iter().map(Vec::as_slice));
+            }
+            CollectionOp::JsonSet { path, value } => {
+                out.push(OP_JSON_SET);
+                encode_items(out, [path.as_bytes(), value.to_string().as_bytes()].into_iter());
+            }
+            CollectionOp::JsonDelete { path } => {
+                out.push(OP_JSON_DELETE);
+                encode_items(out, [path.as_bytes()].into_iter());
+            }
+            CollectionOp::JsonAppend { path, values } => {
+                out.push(OP_JSON_APPEND);
+                let texts: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                encode_items(out, [path.as_bytes()].into_iter().chain(texts.iter().map(String::as_bytes)));
+            }
+            CollectionOp::JsonIncrement { path, delta } => {
+                out.push(OP_JSON_INCREMENT);
+                encode_items(out, [path.as_bytes(), &delta.to_be_bytes()[..]].into_iter());
+            }
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> io::Result<CollectionOp> {
+        let Some((&tag, body)) = buf.split_first() else {
+            return Err(invalid("empty collection operation"));
+        };
+        let op = match tag {
+            OP_PUSH | OP_POP => {
+                let Some((&front, rest)) = body.split_first() else {
+                    return Err(invalid("truncated list operation"));
+                };
+                let front = front != 0;
+                if tag == OP_POP {
+                    let count = rest.try_into().map(u32::from_be_bytes).map_err(|_| invalid("invalid pop count"))?;
+                    CollectionOp::Pop { count: count as usize, front }
+                } else {
+                    CollectionOp::Push { values: decode_items(rest)?, front }
+                }
+            }
+            OP_HASH_SET => CollectionOp::HashSet { fields: decode_fields(decode_items(body)?)? },
+            OP_HASH_DELETE => CollectionOp::HashDelete {
+                fields: decode_items(body)?.into_iter().map(utf8).collect::<io::Result<_>>()?,
+            },
+            OP_SET_ADD => CollectionOp::SetAdd { members: decode_items(body)? },
+            OP_SET_REMOVE => CollectionOp::SetRemove { members: decode_items(body)? },
+            OP_ZSET_ADD => CollectionOp::SortedSetAdd { members: decode_scores(decode_items(body)?)? },
+            OP_ZSET_REMOVE => CollectionOp::SortedSetRemove { members: decode_items(body)? },
+            OP_JSON_SET..=OP_JSON_INCREMENT => decode_json_op(tag, decode_items(body)?)?,
+            other => return Err(invalid(&format!("unknown collection operation {}", other))),
+        };
+        Ok(op)
+    }
+}
+
+// Lista de elementos: count (4 bytes BE) | [len (4 bytes BE) | bytes]*
+pub fn encode_items<'a>(out: &mut Vec<u8>, items: impl Iterator<Item = &'a [u8]>) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    let mut count: u32 = 0;
+    for item in items {
+        out.extend_from_slice(&(item.len() as u32).to_be_bytes());
+        out.extend_from_slice(item);
+        count += 1;
+    }
+    out[start..start + 4].copy_from_slice(&count.to_be_bytes());
+}
+
+pub fn decode_items(buf: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let truncated = || invalid("truncated item list");
+    let count = buf.get(..4).ok_or_else(truncated)?;
+    let count = u32::from_be_bytes([count[0], count[1], count[2], count[3]]);
+    let mut items = Vec::with_capacity(count.min(1024) as usize);
+    let mut pos = 4;
+    for _ in 0..count {
+        let len = buf.get(pos..pos + 4).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        pos += 4;
+        items.push(buf.get(pos..pos + len).ok_or_else(truncated)?.to_vec());
+        pos += len;
+    }
+    if pos != buf.len() {
+        return Err(invalid("trailing bytes after item list"));
+    }
+    Ok(items)
+}
+
+// Pares campo/valor codificados como elementos alternados
+fn decode_fields(items: Vec<Vec<u8>>) -> io::Result<Vec<(String, Vec<u8>)>> {
+    if !items.len().is_multiple_of(2) {
+        return Err(invalid("odd number of hash items"));
+    }
+    let mut items = items.into_iter();
+    let mut fields = Vec::new();
+    while let (Some(field), Some(value)) = (items.next(), items.next()) {
+        fields.push((utf8(field)?, value));
+    }
+    Ok(fields)
+}
+
+// Pares puntaje (8 bytes BE) / miembro codificados como elementos alternados
+fn decode_scores(items: Vec<Vec<u8>>) -> io::Result<Vec<(f64, Vec<u8>)>> {
+    if !items.len().is_multiple_of(2) {
+        return Err(invalid("odd number of sorted set items"));
+    }
+    let mut items = items.into_iter();
+    let mut members = Vec::new();
+    while let (Some(score), Some(member)) = (items.next(), items.next()) {
+        let score: [u8; 8] = score.try_into().map_err(|_| invalid("invalid score"))?;
+        members.push((f64::from_be_bytes(score), member));
+    }
+    Ok(members)
+}
+
+// Ruta y argumentos de una operacion JSON
+fn decode_json_op(tag: u8, items: Vec<Vec<u8>>) -> io::Result<CollectionOp> {
+    let mut items = items.into_iter();
+    let path = utf8(items.next().ok_or_else(|| invalid("json operation without path"))?)?;
+    let document = |item: Vec<u8>| serde_json::from_slice(&item).map_err(|_| invalid("invalid json in log"));
+    let op = match tag {
+        OP_JSON_SET => CollectionOp::JsonSet { path, value: document(items.next().ok_or_else(|| invalid("json set without value"))?)? },
+        OP_JSON_DELETE => CollectionOp::JsonDelete { path },
+        OP_JSON_APPEND => CollectionOp::JsonAppend { path, values: items.map(document).collect::<io::Result<_>>()? },
+        _ => {
+            let delta: [u8; 8] = items.next().and_then(|item| item.try_into().ok()).ok_or_else(|| invalid("invalid json increment"))?;
+            CollectionOp::JsonIncrement { path, delta: f64::from_be_bytes(delta) }
+        }
+    };
+    Ok(op)
+}
+
+fn utf8(bytes: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|_| invalid("invalid UTF-8 hash field"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted_set_ranges() {
+        let mut zset = SortedSet::default();
+        zset.insert(b"c".to_vec(), 3.0);
+        zset.insert(b"a".to_vec(), 1.0);
+        zset.insert(b"b".to_vec(), 2.0);
+        assert!(!zset.insert(b"a".to_vec(), 4.0));
+        let members = |range: Vec<(Vec<u8>, f64)>| range.into_iter().map(|(m, _)| m).collect::<Vec<_>>();
+        assert_eq!(members(zset.range_by_rank(0, -1)), vec![b"b".to_vec(), b"c".to_vec(), b"a".to_vec()]);
+        assert_eq!(members(zset.range_by_rank(-2, 10)), vec![b"c".to_vec(), b"a".to_vec()]);
+        assert_eq!(members(zset.range_by_score(2.0, 3.0)), vec![b"b".to_vec(), b"c".to_vec()]);
+        assert!(zset.range_by_rank(5, 10).is_empty());
+    }
+
+    #[test]
+    fn test_encode_decode_values_and_ops() {
+        let mut zset = SortedSet::default();
+        zset.insert(b"m".to_vec(), -1.5);
+        let values = [
+            Value::Bytes(b"raw".to_vec()),
+            Value::List(VecDeque::from([b"x".to_vec(), Vec::new()])),
+            Value::Hash(HashMap::from([("f".to_string(), b"v".to_vec())])),
+            Value::Set(BTreeSet::from([b"a".to_vec(), b"b".to_vec()])),
+            Value::SortedSet(zset),
+            Value::Json(serde_json::json!({"a": [1, 2.5, null], "b": "x"})),
+        ];
+        for value in values {
+            let (tag, body) = value.encode();
+            assert_eq!(Value::decode(tag, &body).unwrap(), value);
+        }
+        let ops = [
+            CollectionOp::Pop { count: 3, front: false },
+            CollectionOp::HashSet { fields: vec![("f".to_string(), b"v".to_vec())] },
+            CollectionOp::SortedSetAdd { members: vec![(2.5, b"m".to_vec())] },
+            CollectionOp::JsonAppend { path: "$.a".to_string(), values: vec![serde_json::json!({"k": 1}), serde_json::json!("v")] },
+            CollectionOp::JsonIncrement { path: "$.n".to_string(), delta: -1.5 },
+        ];
+        for op in ops {
+            let mut buf = Vec::new();
+            op.encode(&mut buf);
+            assert_eq!(CollectionOp::decode(&buf).unwrap(), op);
+        }
+    }
+}
@@ -0,0 +1,513 @@
+// Log de escritura anticipada (WAL): registros protegidos con CRC y commit en grupo.
+// Las escrituras se agregan en orden bajo un lock y esperan el fsync fuera de el:
+// un solo fsync confirma a todos los escritores que llegaron mientras corria el anterior.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+use crate::config::FsyncPolicy;
+use crate::metrics::Metrics;
+
+// Formato (big-endian): magic (8) | flags (1) | [len (4) | crc32 de payload (4) | payload]*
+const MAGIC: &[u8; 8] = b"NANOWAL1";
+pub(crate) const FLAG_ENCRYPTED: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+const FRAME_HEADER_LEN: usize = 8;
+
+// Registros validos al abrir el log y flags de su cabecera
+pub(crate) struct Recovered {
+    pub(crate) flags: u8,
+    pub(crate) records: Vec<Vec<u8>>,
+}
+
+pub(crate) struct Wal {
+    path: PathBuf,
+    policy: FsyncPolicy,
+    shared: Arc<Shared>,
+}
+
+// Estado compartido con el hilo de fsync
+struct Shared {
+    state: Mutex<State>,
+    durable: Condvar,       // Avisa cuando termina un fsync
+    metrics: Arc<Metrics>,
+}
+
+struct State {
+    file: Arc<dyn LogFile>, // Compartido con el lider del fsync; la compactacion lo reemplaza
+    appended: u64,          // Ultimo registro escrito (numerados desde la apertura)
+    durable: u64,           // Ultimo registro sincronizado a disco
+    syncing: bool,          // Hay un escritor haciendo fsync por el grupo
+    failed: Option<String>, // Tras un error de escritura o fsync no se sabe que llego al disco
+    buf: Vec<u8>,
+}
+
+// Archivo donde se agregan los registros. Los tests lo envuelven para simular
+// crashes a mitad de una escritura y fsync que fallan.
+trait LogFile: Send + Sync {
+    fn append(&self, bytes: &[u8]) -> io::Result<()>;
+    fn sync(&self) -> io::Result<()>;
+}
+
+impl LogFile for File {
+    fn append(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut file = self;
+        file.write_all(bytes)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sync_data()
+    }
+}
+
+impl Wal {
+    // Abre (o crea con los flags dados) el log y devuelve sus registros.
+    // Un registro incompleto o con CRC invalido al final es una escritura cortada: se trunca.
+    pub(crate) fn open(path: impl AsRef<Path>, policy: FsyncPolicy, flags: u8, metrics: Arc<Metrics>) -> io::Result<(Self, Recovered)> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        // Un log vacio, o cortado mientras se escribia la cabecera, se empieza de nuevo
+        if buf.len() < HEADER_LEN && MAGIC.starts_with(&buf[..buf.len().min(MAGIC.len())]) {
+            file.set_len(0)?;
+            file.write_all(MAGIC)?;
+            file.write_all(&[flags])?;
+            file.sync_all()?;
+            buf = [&MAGIC[..], &[flags]].concat();
+        }
+        if !buf.starts_with(MAGIC) {
+            return Err(invalid("not a NanoDb write-ahead log"));
+        }
+        let flags = buf[MAGIC.len()];
+        let (records, valid) = decode(&buf);
+        if valid < buf.len() {
+            warn!(path = %path.display(), dropped = buf.len() - valid, "Truncating torn tail of write-ahead log");
+            file.set_len(valid as u64)?;
+            file.sync_data()?;
+        }
+        debug!(path = %path.display(), records = records.len(), "Write-ahead log opened");
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                file: Arc::new(file),
+                appended: 0,
+                durable: 0,
+                syncing: false,
+                failed: None,
+                buf: Vec::new(),
+            }),
+            durable: Condvar::new(),
+            metrics,
+        });
+        if policy == FsyncPolicy::EverySecond {
+            spawn_fsync_thread(Arc::downgrade(&shared));
+        }
+        Ok((Wal { path, policy, shared }, Recovered { flags, records }))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Agrega un registro y ejecuta `apply` bajo el lock del log.
+    // Con la politica Always `apply` corre recien cuando el registro esta en disco (compartiendo
+    // el fsync con los escritores concurrentes); si el fsync falla no se aplica nada. Dos
+    // escrituras del mismo grupo pueden aplicarse en otro orden que el del log, pero los
+    // llamadores escriben cada clave bajo su lock, asi que el estado final es el mismo.
+    // Con las otras politicas se aplica apenas se escribe: el fsync llega despues y no hay
+    // espera que medir, por eso la latencia de commit solo se registra con Always.
+    pub(crate) fn append<F: FnOnce()>(&self, payload: &[u8], apply: F) -> io::Result<()> {
+        let start = Instant::now();
+        let mut state = self.shared.lock();
+        if let Some(e) = &state.failed {
+            return Err(unusable(e));
+        }
+        let state_ref = &mut *state;
+        state_ref.buf.clear();
+        encode_frame(&mut state_ref.buf, payload);
+        if let Err(e) = state_ref.write_frame() {
+            state_ref.failed = Some(e.to_string());
+            return Err(e);
+        }
+        state.appended += 1;
+        let _state = if self.policy == FsyncPolicy::Always {
+            let seq = state.appended;
+            let state = blocking(|| self.shared.commit(state, seq))?;
+            self.shared.metrics.record_wal_commit(start.elapsed());
+            state
+        } else {
+            state
+        };
+        apply();
+        Ok(())
+    }
+
+    // Fuerza a disco todo lo escrito hasta ahora
+    pub(crate) fn sync(&self) -> io::Result<()> {
+        let state = self.shared.lock();
+        let seq = state.appended;
+        self.shared.commit(state, seq).map(drop)
+    }
+
+    // Reescribe el log con los registros que devuelva `select` a partir de los actuales
+    // (None = no tocar nada). Las escrituras esperan mientras tanto.
+    pub(crate) fn rewrite<F>(&self, flags: u8, select: F) -> io::Result<bool>
+    where
+        F: FnOnce(Vec<Vec<u8>>) -> io::Result<Option<Vec<Vec<u8>>>>,
+    {
+        let mut state = self.shared.lock();
+        if let Some(e) = &state.failed {
+            return Err(unusable(e));
+        }
+        let mut buf = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut buf)?;
+        let Some(records) = select(decode(&buf).0)? else { return Ok(false) };
+        state.file = Arc::new(write_file(&self.path, flags, &records)?);
+        state.durable = state.appended;
+        Ok(true)
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Espera a que `seq` este en disco y devuelve el lock. Si nadie esta sincronizando, este
+    // escritor es el lider del grupo: hace un fsync que cubre todo lo escrito hasta ese momento.
+    fn commit<'a>(&'a self, mut state: MutexGuard<'a, State>, seq: u64) -> io::Result<MutexGuard<'a, State>> {
+        loop {
+            if state.durable >= seq {
+                return Ok(state);
+            }
+            if let Some(e) = &state.failed {
+                return Err(unusable(e));
+            }
+            if state.syncing {
+                state = self.durable.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+            state.syncing = true;
+            let (target, file) = (state.appended, state.file.clone());
+            drop(state);
+            let result = file.sync();
+            state = self.lock();
+            state.syncing = false;
+            match result {
+                Ok(()) => {
+                    self.metrics.record_wal_sync(target.saturating_sub(state.durable));
+                    state.durable = state.durable.max(target);
+                }
+                Err(e) => {
+                    warn!(error = %e, "Fsync of write-ahead log failed");
+                    state.failed = Some(e.to_string());
+                }
+            }
+            self.durable.notify_all();
+        }
+    }
+}
+
+impl State {
+    fn write_frame(&self) -> io::Result<()> {
+        self.file.append(&self.buf)
+    }
+}
+
+pub(crate) fn encode_frame(out: &mut Vec<u8>, payload: &[u8]) {
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(&crc32fast::hash(payload).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+// Registros completos y con CRC valido desde el principio, y bytes validos leidos
+fn decode(buf: &[u8]) -> (Vec<Vec<u8>>, usize) {
+    let mut records = Vec::new();
+    let mut pos = HEADER_LEN;
+    while let Some(header) = buf.get(pos..pos + FRAME_HEADER_LEN) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let crc = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let Some(payload) = buf.get(pos + FRAME_HEADER_LEN..pos + FRAME_HEADER_LEN + len) else { break };
+        if crc32fast::hash(payload) != crc {
+            break;
+        }
+        records.push(payload.to_vec());
+        pos += FRAME_HEADER_LEN + len;
+    }
+    (records, pos.min(buf.len()))
+}
+
+// Escribe un log completo en un temporal, lo renombra sobre `path` y lo abre para agregar.
+// El archivo se abre antes de renombrarlo: si el rename falla se sigue usando el anterior.
+fn write_file(path: &Path, flags: u8, records: &[Vec<u8>]) -> io::Result<File> {
+    let mut bytes = [&MAGIC[..], &[flags]].concat();
+    for record in records {
+        encode_frame(&mut bytes, record);
+    }
+    // Un temporal viejo de un intento fallido se descarta
+    let tmp = path.with_extension("rewrite");
+    match fs::remove_file(&tmp) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(&tmp)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    // Sin sincronizar el directorio el rename puede perderse en un crash
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    info!(path = %path.display(), records = records.len(), "Write-ahead log rewritten");
+    Ok(file)
+}
+
+// Esperas que bloquean el hilo (fsync, commit en grupo): en un worker del runtime multihilo
+// se avisa a tokio para que mueva sus otras tareas a otro hilo mientras tanto
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => tokio::task::block_in_place(f),
+        _ => f(),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn unusable(cause: &str) -> io::Error {
+    io::Error::other(format!("write-ahead log unusable after a failed write: {}", cause))
+}
+
+// Hilo de fsync para la politica EverySecond; termina cuando se cierra el log
+fn spawn_fsync_thread(shared: Weak<Shared>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let Some(shared) = shared.upgrade() else { break };
+        let state = shared.lock();
+        if state.appended > state.durable && state.failed.is_none() {
+            let seq = state.appended;
+            if let Err(e) = shared.commit(state, seq).map(drop) {
+                warn!(error = %e, "Background fsync of write-ahead log failed");
+            }
+        }
+    });
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::{DbConfig, DbResult, NanoDb};
+
+    // Escrituras "clave=valor" de distintos tamaños (algunas vacias)
+    fn writes() -> Vec<Vec<u8>> {
+        (0..12).map(|i| format!("k{}={}", i % 5, "x".repeat(i * 3)).into_bytes()).collect()
+    }
+
+    fn state(records: &[Vec<u8>]) -> BTreeMap<String, String> {
+        records
+            .iter()
+            .map(|record| {
+                let (key, value) = std::str::from_utf8(record).unwrap().split_once('=').unwrap();
+                (key.to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    fn open(path: &Path) -> (Wal, Recovered) {
+        Wal::open(path, FsyncPolicy::Always, 0, Metrics::new()).unwrap()
+    }
+
+    // Archivo del log con fallas simuladas
+    struct FaultyFile {
+        inner: Arc<dyn LogFile>,
+        budget: Mutex<Option<u64>>,     // Bytes que llegan al disco antes de un crash simulado
+        fail_sync: bool,                // Los fsync fallan (disco lleno, error de E/S)
+    }
+
+    impl LogFile for FaultyFile {
+        fn append(&self, bytes: &[u8]) -> io::Result<()> {
+            let mut budget = self.budget.lock().unwrap();
+            match *budget {
+                Some(left) if left < bytes.len() as u64 => {
+                    self.inner.append(&bytes[..left as usize])?;
+                    *budget = Some(0);
+                    Err(io::Error::other("simulated crash"))
+                }
+                Some(left) => {
+                    *budget = Some(left - bytes.len() as u64);
+                    self.inner.append(bytes)
+                }
+                None => self.inner.append(bytes),
+            }
+        }
+
+        fn sync(&self) -> io::Result<()> {
+            if self.fail_sync {
+                return Err(io::Error::other("simulated fsync failure"));
+            }
+            self.inner.sync()
+        }
+    }
+
+    fn inject(wal: &Wal, budget: Option<u64>, fail_sync: bool) {
+        let mut state = wal.shared.lock();
+        let inner = state.file.clone();
+        state.file = Arc::new(FaultyFile { inner, budget: Mutex::new(budget), fail_sync });
+    }
+
+    // Crash simulado: solo `bytes` bytes mas llegan al disco y despues todo falla
+    fn crash_after(wal: &Wal, bytes: u64) {
+        inject(wal, Some(bytes), false);
+    }
+
+    fn fail_syncs(wal: &Wal) {
+        inject(wal, None, true);
+    }
+
+    // Corta la escritura en cada byte posible: lo recuperado tiene que ser exactamente
+    // lo confirmado, aunque despues del corte haya basura en el archivo
+    #[test]
+    fn test_crash_at_every_offset() {
+        let writes = writes();
+        let total: usize = writes.iter().map(|w| FRAME_HEADER_LEN + w.len()).sum();
+        let dir = tempfile::tempdir().unwrap();
+        for offset in 0..=total {
+            let path = dir.path().join(format!("{}.wal", offset));
+            let mut acknowledged = Vec::new();
+            let mut applied = BTreeMap::new();
+            {
+                let (wal, _) = open(&path);
+                crash_after(&wal, offset as u64);
+                for write in &writes {
+                    let (key, value) = std::str::from_utf8(write).unwrap().split_once('=').unwrap();
+                    match wal.append(write, || { applied.insert(key.to_string(), value.to_string()); }) {
+                        Ok(()) => acknowledged.push(write.clone()),
+                        Err(_) => break,
+                    }
+                }
+                assert_eq!(acknowledged.len() == writes.len(), offset == total);
+                assert!(wal.append(b"k0=late", || {}).is_err() || offset == total);
+            }
+            if offset % 3 == 1 {
+                let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+                file.write_all(&[0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 1, 7]).unwrap();
+            }
+
+            let (wal, recovered) = open(&path);
+            assert_eq!(recovered.records, acknowledged, "crash at offset {}", offset);
+            assert_eq!(state(&recovered.records), applied);
+            // El log truncado sigue aceptando escrituras
+            wal.append(b"k9=after", || {}).unwrap();
+            drop(wal);
+            assert_eq!(open(&path).1.records.last().unwrap(), b"k9=after");
+        }
+    }
+
+    // Una escritura cuyo fsync falla no se aplica, y el log deja de aceptar escrituras
+    #[test]
+    fn test_failed_fsync_is_not_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wal, _) = open(&dir.path().join("db.wal"));
+        let mut applied = Vec::new();
+        wal.append(b"k=1", || applied.push(1)).unwrap();
+        fail_syncs(&wal);
+        assert!(wal.append(b"k=2", || applied.push(2)).is_err());
+        assert!(wal.append(b"k=3", || applied.push(3)).is_err());
+        assert_eq!(applied, vec![1]);
+    }
+
+    #[test]
+    fn test_crash_while_writing_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.wal");
+        fs::write(&path, &MAGIC[..5]).unwrap();
+        let (wal, recovered) = open(&path);
+        assert!(recovered.records.is_empty());
+        wal.append(b"k=v", || {}).unwrap();
+        drop(wal);
+        assert_eq!(open(&path).1.records, vec![b"k=v".to_vec()]);
+
+        fs::write(&path, b"something else").unwrap();
+        assert!(Wal::open(&path, FsyncPolicy::Always, 0, Metrics::new()).is_err());
+    }
+
+    // Escritores concurrentes cortados en un byte al azar: por escritor se recupera un prefijo
+    // de sus escrituras que incluye todas las confirmadas (y a lo sumo una mas, escrita
+    // completa pero sin fsync confirmado cuando ocurrio el crash)
+    #[test]
+    fn test_concurrent_crash_recovers_acknowledged_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for round in 0..8 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let path = dir.path().join(format!("{}.wal", round));
+            let (wal, _) = open(&path);
+            crash_after(&wal, seed % 3000);
+            let acknowledged: Vec<usize> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..4)
+                    .map(|writer| {
+                        let wal = &wal;
+                        scope.spawn(move || (0..50).take_while(|i| wal.append(format!("w{}={}", writer, i).as_bytes(), || {}).is_ok()).count())
+                    })
+                    .collect();
+                handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+            });
+            drop(wal);
+
+            let (_, recovered) = open(&path);
+            for (writer, acked) in acknowledged.into_iter().enumerate() {
+                let prefix = format!("w{}=", writer);
+                let values: Vec<usize> = recovered
+                    .records
+                    .iter()
+                    .filter_map(|record| std::str::from_utf8(record).unwrap().strip_prefix(prefix.as_str()).map(|v| v.parse().unwrap()))
+                    .collect();
+                assert_eq!(values, (0..values.len()).collect::<Vec<_>>(), "round {}", round);
+                assert!(values.len() == acked || values.len() == acked + 1, "round {}: {} recovered, {} acknowledged", round, values.len(), acked);
+            }
+        }
+    }
+
+    // Con fsync always los escritores concurrentes comparten fsyncs
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_group_commit_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DbConfig {
+            aof_path: Some(dir.path().join("db.aof")),
+            fsync: FsyncPolicy::Always,
+            ..DbConfig::default()
+        };
+        let db = Arc::new(NanoDb::with_config(config.clone()).unwrap());
+        let tasks: Vec<_> = (0..8)
+            .map(|task| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    for i in 0..25 {
+                        assert!(matches!(db.set(format!("{}:{}", task, i), b"v".to_vec()).await, DbResult::Ok(())));
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let stats = db.metrics().get_stats();
+        assert_eq!(stats.wal_commits, 200);
+        assert!(stats.wal_syncs >= 1 && stats.wal_syncs <= 200);
+        assert!(stats.wal_avg_group_size >= 1.0);
+        assert!(stats.wal_max_commit_latency_us >= stats.wal_avg_commit_latency_us);
+        drop(db);
+
+        let db = NanoDb::with_config(config).unwrap();
+        assert!(matches!(db.size().await, DbResult::Ok(200)));
+    }
+}
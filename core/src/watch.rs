@@ -0,0 +1,138 @@
+// Importaciones
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use crate::DbError;
+
+// Eventos que se pueden acumular por suscriptor antes de que se considere atrasado
+pub const WATCH_CAPACITY: usize = 1024;
+
+// Tipo de cambio en el keyspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Set,        // Valor nuevo (incluye SETEX y CAS)
+    Delete,     // Borrado explicito o desalojo
+    Clear,      // FLUSH: afecta a todas las claves
+    Expire,     // Cambio de TTL (EXPIRE / PERSIST)
+    Expired,    // Clave eliminada por expiracion
+}
+
+impl ChangeKind {
+    // Nombre en minusculas para los protocolos de texto (TCP, SSE)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Set => "set",
+            ChangeKind::Delete => "delete",
+            ChangeKind::Clear => "clear",
+            ChangeKind::Expire => "expire",
+            ChangeKind::Expired => "expired",
+        }
+    }
+}
+
+// Evento del change-feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub key: String,                  // Vacia para Clear
+    pub value: Option<Vec<u8>>,       // Solo en Set
+    pub version: u64,                 // Nueva version en Set/Expire, 0 en el resto
+    pub expires_at: Option<u64>,      // Milisegundos unix (Set/Expire)
+}
+
+// Claves que le interesan a un suscriptor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WatchFilter {
+    Key(String),
+    Prefix(String),                   // "" = todas las claves
+}
+
+impl WatchFilter {
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        match self {
+            _ if event.kind == ChangeKind::Clear => true,
+            WatchFilter::Key(key) => event.key == *key,
+            WatchFilter::Prefix(prefix) => event.key.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+// Suscripcion al change-feed filtrada por clave o prefijo
+pub struct Watcher {
+    filter: WatchFilter,
+    receiver: broadcast::Receiver<ChangeEvent>,
+}
+
+impl Watcher {
+    pub(crate) fn new(filter: WatchFilter, receiver: broadcast::Receiver<ChangeEvent>) -> Self {
+        Watcher { filter, receiver }
+    }
+
+    pub fn filter(&self) -> &WatchFilter {
+        &self.filter
+    }
+
+    // Siguiente evento que cumple el filtro.
+    // Si el suscriptor se atrasa se pierden eventos y se devuelve DbError::Busy:
+    // el cliente debe volver a leer el estado y suscribirse de nuevo.
+    pub async fn recv(&mut self) -> Result<ChangeEvent, DbError> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Ok(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    return Err(DbError::Busy(format!("watcher lagged behind, {} events dropped", missed)));
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(DbError::Internal("change feed closed".to_string()));
+                }
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::NanoDb;
+
+    #[test]
+    fn test_filter_matches() {
+        let event = |kind, key: &str| ChangeEvent { kind, key: key.to_string(), value: None, version: 0, expires_at: None };
+        let prefix = WatchFilter::Prefix("user:".to_string());
+        assert!(prefix.matches(&event(ChangeKind::Set, "user:1")));
+        assert!(!prefix.matches(&event(ChangeKind::Set, "order:1")));
+        assert!(prefix.matches(&event(ChangeKind::Clear, "")));
+        assert!(!WatchFilter::Key("user".to_string()).matches(&event(ChangeKind::Delete, "user:1")));
+    }
+
+    #[tokio::test]
+    async fn test_watch_publishes_writes_in_order() {
+        let db = NanoDb::new();
+        let mut watcher = db.watch(WatchFilter::Prefix("user:".to_string()));
+        db.set("order:1".to_string(), b"ignored".to_vec()).await;
+        db.set("user:1".to_string(), b"alice".to_vec()).await;
+        db.expire("user:1", Duration::from_secs(60)).await;
+        db.delete("user:1").await;
+        db.clear().await;
+
+        let first = watcher.recv().await.unwrap();
+        assert_eq!((first.kind, first.key.as_str(), first.value.as_deref()), (ChangeKind::Set, "user:1", Some(&b"alice"[..])));
+        assert_eq!(first.version, 2);  // order:1 tomo la revision 1
+        for kind in [ChangeKind::Expire, ChangeKind::Delete, ChangeKind::Clear] {
+            assert_eq!(watcher.recv().await.unwrap().kind, kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_expirations() {
+        let db = NanoDb::new();
+        let mut watcher = db.watch(WatchFilter::Key("session".to_string()));
+        db.set_with_ttl("session".to_string(), b"x".to_vec(), Some(Duration::from_millis(10))).await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        db.purge_expired();
+        assert_eq!(watcher.recv().await.unwrap().kind, ChangeKind::Set);
+        assert_eq!(watcher.recv().await.unwrap().kind, ChangeKind::Expired);
+    }
+}
@@ -1,3 +1,3 @@
 fn main() {
-    prost_build::compile_protos(&["proto/nanodb.proto"], &["proto/"]).unwrap();
-}
\ No newline at end of file
+    tonic_prost_build::compile_protos("proto/nanodb.proto").unwrap();
+}
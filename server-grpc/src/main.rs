@@ -1,28 +1,576 @@
 // protocol-arena/server-grpc/src/main.rs
+use std::pin::Pin;
 use std::sync::Arc;
-use nanodb_core::NanoDb;
+use std::time::Duration;
+use futures::stream::{self, Stream};
+use nanodb_core::{ChangeEvent, ClusterConfig, NanoDb, DbConfig, DbError, DbOperation, DbResponse, IndexQuery, RaftNode, StoragePort, TxnOutcome, WatchFilter};
+use tonic::{transport::Server, Request, Response, Status};
+use nano_db_service_server::{NanoDbService, NanoDbServiceServer};
 
-// Codigo generado por prost_build
+// Codigo generado por tonic-prost-build (mensajes + servicio)
 include!(concat!(env!("OUT_DIR"), "/nanodb.rs"));
 
+// Adaptador gRPC sobre cualquier motor que implemente el puerto de almacenamiento
+pub struct NanoDbGrpc<S: StoragePort> {
+    db: Arc<S>,
+}
+
+// Convertir respuestas de error del nucleo a Status de gRPC
+fn db_error(response: DbResponse) -> Status {
+    match response {
+        DbResponse::NotFound => Status::not_found("Key not found"),
+        DbResponse::Error(err) => error_status(err),
+        other => Status::internal(format!("Unexpected response: {:?}", other)),
+    }
+}
+
+// Codigo gRPC segun el tipo de error; el codigo estable de DbError va en los metadatos
+fn error_status(err: DbError) -> Status {
+    let message = err.to_string();
+    let mut status = match err {
+        DbError::KeyTooLarge { .. } | DbError::ValueTooLarge { .. } | DbError::InvalidArgument(_) => Status::invalid_argument(message),
+        DbError::TypeMismatch { .. } | DbError::ReadOnly => Status::failed_precondition(message),
+        DbError::NotANumber { .. } | DbError::JsonPath { .. } => Status::failed_precondition(message),
+        DbError::Overflow { .. } => Status::out_of_range(message),
+        DbError::CasConflict { .. } => Status::aborted(message),
+        DbError::Unauthorized => Status::unauthenticated(message),
+        DbError::Script(_) => Status::invalid_argument(message),
+        DbError::ScriptTimeout { .. } => Status::deadline_exceeded(message),
+        DbError::NoScript(_) => Status::not_found(message),
+        DbError::OutOfMemory { .. } => Status::resource_exhausted(message),
+        DbError::NotImplemented(_) => Status::unimplemented(message),
+        DbError::Busy(_) => Status::unavailable(message),
+        DbError::Storage(_) | DbError::Internal(_) => Status::internal(message),
+        DbError::NotLeader { .. } => Status::unavailable(message),
+    };
+    status.metadata_mut().insert("nanodb-error-code", err.code().into());
+    // Nodo del cluster al que reintentar
+    if let DbError::NotLeader { leader: Some(leader) } = &err {
+        if let Ok(value) = leader.parse() {
+            status.metadata_mut().insert("nanodb-leader", value);
+        }
+    }
+    status
+}
+
+impl<S: StoragePort> NanoDbGrpc<S> {
+    // Base sobre la que opera una peticion (namespace vacio = base principal)
+    fn namespace(&self, name: &str) -> Result<Arc<S>, Status> {
+        if name.is_empty() {
+            return Ok(self.db.clone());
+        }
+        self.db.namespace(name).ok_or_else(|| Status::not_found(format!("Namespace not found: {}", name)))
+    }
+}
+
+#[tonic::async_trait]
+impl<S: StoragePort> NanoDbService for NanoDbGrpc<S> {
+    async fn set(&self, request: Request<SetRequest>) -> Result<Response<SetResponse>, Status> {
+        let req = request.into_inner();
+        let ttl = req.ttl_ms.map(Duration::from_millis);
+        match self.namespace(&req.namespace)?.execute(DbOperation::Set { key: req.key, value: req.value, ttl }).await {
+            DbResponse::Ok => Ok(Response::new(SetResponse {})),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::Get { key: req.key.clone(), default: None }).await {
+            DbResponse::Value(value) => Ok(Response::new(GetResponse { value })),
+            DbResponse::NotFound => Err(Status::not_found(format!("Key not found: {}", req.key))),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn delete(&self, request: Request<DeleteRequest>) -> Result<Response<DeleteResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::Delete { key: req.key.clone() }).await {
+            DbResponse::Ok => Ok(Response::new(DeleteResponse {})),
+            DbResponse::NotFound => Err(Status::not_found(format!("Key not found: {}", req.key))),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn flush(&self, request: Request<FlushRequest>) -> Result<Response<FlushResponse>, Status> {
+        match self.namespace(&request.get_ref().namespace)?.execute(DbOperation::Flush).await {
+            DbResponse::Ok => Ok(Response::new(FlushResponse {})),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn keys(&self, request: Request<KeysRequest>) -> Result<Response<KeysResponse>, Status> {
+        match self.namespace(&request.get_ref().namespace)?.execute(DbOperation::Keys).await {
+            DbResponse::Keys(keys) => Ok(Response::new(KeysResponse { keys })),
+            DbResponse::NotFound => Ok(Response::new(KeysResponse { keys: vec![] })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn compare_and_swap(&self, request: Request<CasRequest>) -> Result<Response<CasResponse>, Status> {
+        let req = request.into_inner();
+        let op = DbOperation::CompareAndSwap { key: req.key, old_value: req.old_value, new_value: req.new_value };
+        match self.namespace(&req.namespace)?.execute(op).await {
+            // Si no coincide se devuelve el valor actual, no un error
+            DbResponse::Cas(outcome) => Ok(Response::new(CasResponse {
+                swapped: outcome.swapped,
+                current: outcome.current,
+            })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn txn(&self, request: Request<TxnRequest>) -> Result<Response<TxnResponse>, Status> {
+        let req = request.into_inner();
+        let preconditions = req.preconditions.into_iter().map(precondition).collect::<Result<Vec<_>, _>>()?;
+        let operations = req.operations.into_iter().map(txn_operation).collect::<Result<Vec<_>, _>>()?;
+        match self.namespace(&req.namespace)?.execute(DbOperation::Transaction { preconditions, operations }).await {
+            DbResponse::Txn(TxnOutcome::Committed(results)) => Ok(Response::new(TxnResponse {
+                committed: true,
+                failed_precondition: None,
+                results: results.into_iter().map(txn_result).collect::<Result<Vec<_>, _>>()?,
+            })),
+            DbResponse::Txn(TxnOutcome::Aborted { precondition }) => Ok(Response::new(TxnResponse {
+                committed: false,
+                failed_precondition: Some(precondition as u32),
+                results: vec![],
+            })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<WatchEvent, Status>> + Send>>;
+
+    // Stream de cambios; si el suscriptor se atrasa termina con el Status del error
+    async fn watch(&self, request: Request<WatchRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let req = request.into_inner();
+        let db = self.namespace(&req.namespace)?;
+        let filter = match req.target {
+            Some(watch_request::Target::Key(key)) => WatchFilter::Key(key),
+            Some(watch_request::Target::Prefix(prefix)) => WatchFilter::Prefix(prefix),
+            None => WatchFilter::Prefix(String::new()),
+        };
+        let events = stream::unfold(Some(db.watch(filter)), |watcher| async move {
+            let mut watcher = watcher?;
+            match watcher.recv().await {
+                Ok(change) => Some((Ok(watch_event(change)), Some(watcher))),
+                Err(err) => Some((Err(error_status(err)), None)),
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn publish(&self, request: Request<PublishRequest>) -> Result<Response<PublishResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::Publish { channel: req.channel, message: req.message }).await {
+            DbResponse::Count(receivers) => Ok(Response::new(PublishResponse { receivers: receivers as u32 })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<ChannelMessage, Status>> + Send>>;
+
+    async fn subscribe(&self, request: Request<SubscribeRequest>) -> Result<Response<Self::SubscribeStream>, Status> {
+        let req = request.into_inner();
+        let mut subscription = self.namespace(&req.namespace)?.subscriber();
+        for channel in &req.channels {
+            subscription.subscribe(channel);
+        }
+        for pattern in &req.patterns {
+            subscription.psubscribe(pattern);
+        }
+        if subscription.count() == 0 {
+            return Err(Status::invalid_argument("At least one channel or pattern is required"));
+        }
+        let messages = stream::unfold(Some(subscription), |subscription| async move {
+            let mut subscription = subscription?;
+            match subscription.recv().await {
+                Ok(message) => Some((Ok(ChannelMessage { channel: message.channel, pattern: message.pattern, message: message.payload }), Some(subscription))),
+                Err(err) => Some((Err(error_status(err)), None)),
+            }
+        });
+        Ok(Response::new(Box::pin(messages)))
+    }
+
+    async fn create_namespace(&self, request: Request<NamespaceRequest>) -> Result<Response<NamespaceResponse>, Status> {
+        match self.db.execute(DbOperation::CreateNamespace { name: request.into_inner().name }).await {
+            DbResponse::Bool(changed) => Ok(Response::new(NamespaceResponse { changed })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn drop_namespace(&self, request: Request<NamespaceRequest>) -> Result<Response<NamespaceResponse>, Status> {
+        match self.db.execute(DbOperation::DropNamespace { name: request.into_inner().name }).await {
+            DbResponse::Bool(changed) => Ok(Response::new(NamespaceResponse { changed })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn list_namespaces(&self, _request: Request<ListNamespacesRequest>) -> Result<Response<ListNamespacesResponse>, Status> {
+        match self.db.execute(DbOperation::Namespaces).await {
+            DbResponse::Keys(names) => Ok(Response::new(ListNamespacesResponse { names })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn list_push(&self, request: Request<ListPushRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let op = DbOperation::ListPush { key: req.key, values: req.values, front: req.front };
+        count_response(self.namespace(&req.namespace)?.execute(op).await)
+    }
+
+    async fn list_pop(&self, request: Request<ListPopRequest>) -> Result<Response<ValuesResponse>, Status> {
+        let req = request.into_inner();
+        let op = DbOperation::ListPop { key: req.key, count: req.count as usize, front: req.front };
+        values_response(self.namespace(&req.namespace)?.execute(op).await)
+    }
+
+    async fn list_range(&self, request: Request<RankRangeRequest>) -> Result<Response<ValuesResponse>, Status> {
+        let req = request.into_inner();
+        let op = DbOperation::ListRange { key: req.key, start: req.start, stop: req.stop };
+        values_response(self.namespace(&req.namespace)?.execute(op).await)
+    }
+
+    async fn hash_set(&self, request: Request<HashSetRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let op = DbOperation::HashSet { key: req.key, fields: req.fields.into_iter().collect() };
+        count_response(self.namespace(&req.namespace)?.execute(op).await)
+    }
+
+    async fn hash_get(&self, request: Request<HashGetRequest>) -> Result<Response<GetResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::HashGet { key: req.key.clone(), field: req.field.clone() }).await {
+            DbResponse::Value(value) => Ok(Response::new(GetResponse { value })),
+            DbResponse::NotFound => Err(Status::not_found(format!("Field not found: {}.{}", req.key, req.field))),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn hash_delete(&self, request: Request<HashDeleteRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::HashDelete { key: req.key, fields: req.fields }).await)
+    }
+
+    async fn set_add(&self, request: Request<MembersRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::SetAdd { key: req.key, members: req.members }).await)
+    }
+
+    async fn set_remove(&self, request: Request<MembersRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::SetRemove { key: req.key, members: req.members }).await)
+    }
+
+    async fn set_members(&self, request: Request<CollectionRequest>) -> Result<Response<ValuesResponse>, Status> {
+        let req = request.into_inner();
+        values_response(self.namespace(&req.namespace)?.execute(DbOperation::SetMembers { key: req.key }).await)
+    }
+
+    async fn set_intersection(&self, request: Request<SetIntersectionRequest>) -> Result<Response<ValuesResponse>, Status> {
+        let req = request.into_inner();
+        if req.keys.is_empty() {
+            return Err(Status::invalid_argument("At least one key is required"));
+        }
+        values_response(self.namespace(&req.namespace)?.execute(DbOperation::SetIntersection { keys: req.keys }).await)
+    }
+
+    async fn z_set_add(&self, request: Request<ZSetAddRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let members = req.members.into_iter().map(|m| (m.score, m.member)).collect();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::ZSetAdd { key: req.key, members }).await)
+    }
+
+    async fn z_set_remove(&self, request: Request<MembersRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::ZSetRemove { key: req.key, members: req.members }).await)
+    }
+
+    async fn z_set_range(&self, request: Request<RankRangeRequest>) -> Result<Response<ScoredMembersResponse>, Status> {
+        let req = request.into_inner();
+        let op = DbOperation::ZSetRange { key: req.key, start: req.start, stop: req.stop };
+        scored_response(self.namespace(&req.namespace)?.execute(op).await)
+    }
+
+    async fn z_set_range_by_score(&self, request: Request<ScoreRangeRequest>) -> Result<Response<ScoredMembersResponse>, Status> {
+        let req = request.into_inner();
+        let min = req.min.unwrap_or(f64::NEG_INFINITY);
+        let max = req.max.unwrap_or(f64::INFINITY);
+        scored_response(self.namespace(&req.namespace)?.execute(DbOperation::ZSetRangeByScore { key: req.key, min, max }).await)
+    }
+
+    async fn increment(&self, request: Request<IncrementRequest>) -> Result<Response<IncrementResponse>, Status> {
+        let req = request.into_inner();
+        let delta = req.delta.unwrap_or(1);
+        match self.namespace(&req.namespace)?.execute(DbOperation::Increment { key: req.key, delta }).await {
+            DbResponse::Integer(value) => Ok(Response::new(IncrementResponse { value })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn decrement(&self, request: Request<IncrementRequest>) -> Result<Response<IncrementResponse>, Status> {
+        let req = request.into_inner();
+        let delta = req.delta.unwrap_or(1);
+        match self.namespace(&req.namespace)?.execute(DbOperation::Decrement { key: req.key, delta }).await {
+            DbResponse::Integer(value) => Ok(Response::new(IncrementResponse { value })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn multi_get(&self, request: Request<MultiGetRequest>) -> Result<Response<MultiGetResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::MultiGet { keys: req.keys }).await {
+            DbResponse::OptionalValues(values) => Ok(Response::new(MultiGetResponse {
+                values: values.into_iter().map(|value| OptionalValue { value }).collect(),
+            })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn multi_set(&self, request: Request<MultiSetRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let entries = req.entries.into_iter().map(|entry| (entry.key, entry.value)).collect();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::MultiSet { entries, atomic: req.atomic }).await)
+    }
+
+    async fn multi_delete(&self, request: Request<MultiDeleteRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::MultiDelete { keys: req.keys }).await)
+    }
+
+    async fn increment_float(&self, request: Request<IncrementFloatRequest>) -> Result<Response<IncrementFloatResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::IncrementFloat { key: req.key, delta: req.delta }).await {
+            DbResponse::Float(value) => Ok(Response::new(IncrementFloatResponse { value })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn json_get(&self, request: Request<JsonPathRequest>) -> Result<Response<JsonResponse>, Status> {
+        let req = request.into_inner();
+        json_response(self.namespace(&req.namespace)?.execute(DbOperation::JsonGet { key: req.key, path: req.path }).await)
+    }
+
+    async fn json_set(&self, request: Request<JsonSetRequest>) -> Result<Response<SetResponse>, Status> {
+        let req = request.into_inner();
+        let value = parse_json(&req.value)?;
+        match self.namespace(&req.namespace)?.execute(DbOperation::JsonSet { key: req.key.clone(), path: req.path, value }).await {
+            DbResponse::Ok => Ok(Response::new(SetResponse {})),
+            DbResponse::NotFound => Err(Status::not_found(format!("Key not found: {}", req.key))),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn json_delete(&self, request: Request<JsonPathRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::JsonDelete { key: req.key, path: req.path }).await)
+    }
+
+    async fn json_append(&self, request: Request<JsonAppendRequest>) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let values = req.values.iter().map(|value| parse_json(value)).collect::<Result<_, _>>()?;
+        count_response(self.namespace(&req.namespace)?.execute(DbOperation::JsonAppend { key: req.key, path: req.path, values }).await)
+    }
+
+    async fn json_increment(&self, request: Request<JsonIncrementRequest>) -> Result<Response<JsonResponse>, Status> {
+        let req = request.into_inner();
+        json_response(self.namespace(&req.namespace)?.execute(DbOperation::JsonIncrement { key: req.key, path: req.path, delta: req.delta }).await)
+    }
+
+    async fn create_index(&self, request: Request<CreateIndexRequest>) -> Result<Response<IndexResponse>, Status> {
+        let req = request.into_inner();
+        let index = req.index.ok_or_else(|| Status::invalid_argument("Missing index definition"))?;
+        let op = DbOperation::CreateIndex { name: index.name, prefix: index.prefix, path: index.path };
+        match self.namespace(&req.namespace)?.execute(op).await {
+            DbResponse::Bool(changed) => Ok(Response::new(IndexResponse { changed })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn drop_index(&self, request: Request<DropIndexRequest>) -> Result<Response<IndexResponse>, Status> {
+        let req = request.into_inner();
+        match self.namespace(&req.namespace)?.execute(DbOperation::DropIndex { name: req.name }).await {
+            DbResponse::Bool(changed) => Ok(Response::new(IndexResponse { changed })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn list_indexes(&self, request: Request<ListIndexesRequest>) -> Result<Response<ListIndexesResponse>, Status> {
+        match self.namespace(&request.get_ref().namespace)?.execute(DbOperation::Indexes).await {
+            DbResponse::Indexes(indexes) => Ok(Response::new(ListIndexesResponse {
+                indexes: indexes.into_iter().map(|def| IndexDefinition { name: def.name, prefix: def.prefix, path: def.path }).collect(),
+            })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn query_index(&self, request: Request<QueryIndexRequest>) -> Result<Response<QueryIndexResponse>, Status> {
+        let req = request.into_inner();
+        let bound = |text: Option<String>| text.as_deref().map(parse_json).transpose();
+        let query = match (bound(req.eq)?, bound(req.min)?, bound(req.max)?) {
+            (Some(value), None, None) => IndexQuery::Equals(value),
+            (Some(_), _, _) => return Err(Status::invalid_argument("eq cannot be combined with min or max")),
+            (None, min, max) => IndexQuery::Range { min, max },
+        };
+        let op = DbOperation::QueryIndex { name: req.name, query, cursor: req.cursor, limit: req.limit as usize };
+        match self.namespace(&req.namespace)?.execute(op).await {
+            DbResponse::KeysPage(page) => Ok(Response::new(QueryIndexResponse { keys: page.keys, next_cursor: page.next_cursor })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn eval(&self, request: Request<EvalRequest>) -> Result<Response<JsonResponse>, Status> {
+        let req = request.into_inner();
+        json_response(self.namespace(&req.namespace)?.execute(DbOperation::Eval { script: req.script, keys: req.keys, args: req.args }).await)
+    }
+
+    async fn eval_sha(&self, request: Request<EvalShaRequest>) -> Result<Response<JsonResponse>, Status> {
+        let req = request.into_inner();
+        json_response(self.namespace(&req.namespace)?.execute(DbOperation::EvalSha { sha: req.sha, keys: req.keys, args: req.args }).await)
+    }
+
+    // La cache de scripts es comun a todos los namespaces
+    async fn script_load(&self, request: Request<ScriptLoadRequest>) -> Result<Response<ScriptLoadResponse>, Status> {
+        match self.db.execute(DbOperation::ScriptLoad { script: request.into_inner().script }).await {
+            DbResponse::Value(sha) => Ok(Response::new(ScriptLoadResponse { sha: String::from_utf8_lossy(&sha).to_string() })),
+            other => Err(db_error(other)),
+        }
+    }
+
+    async fn replication_info(&self, _request: Request<ReplicationInfoRequest>) -> Result<Response<ReplicationInfoResponse>, Status> {
+        match self.db.execute(DbOperation::ReplicationInfo).await {
+            DbResponse::Replication(info) => Ok(Response::new(ReplicationInfoResponse {
+                id: info.id,
+                offset: info.offset,
+                leader: info.leader,
+                connected: info.connected,
+                followers: info.followers.into_iter().map(|follower| FollowerInfo { addr: follower.addr, offset: follower.offset }).collect(),
+            })),
+            other => Err(db_error(other)),
+        }
+    }
+}
+
+// Respuestas de colecciones
+fn count_response(response: DbResponse) -> Result<Response<CountResponse>, Status> {
+    match response {
+        DbResponse::Count(count) => Ok(Response::new(CountResponse { count: count as u64 })),
+        other => Err(db_error(other)),
+    }
+}
+
+fn values_response(response: DbResponse) -> Result<Response<ValuesResponse>, Status> {
+    match response {
+        DbResponse::Values(values) => Ok(Response::new(ValuesResponse { values })),
+        other => Err(db_error(other)),
+    }
+}
+
+fn scored_response(response: DbResponse) -> Result<Response<ScoredMembersResponse>, Status> {
+    match response {
+        DbResponse::ScoredMembers(members) => Ok(Response::new(ScoredMembersResponse {
+            members: members.into_iter().map(|(member, score)| ScoredMember { member, score }).collect(),
+        })),
+        other => Err(db_error(other)),
+    }
+}
+
+// Documentos JSON
+fn parse_json(text: &str) -> Result<serde_json::Value, Status> {
+    serde_json::from_str(text).map_err(|e| Status::invalid_argument(format!("Invalid JSON: {}", e)))
+}
+
+fn json_response(response: DbResponse) -> Result<Response<JsonResponse>, Status> {
+    match response {
+        DbResponse::Json(value) => Ok(Response::new(JsonResponse { value: value.to_string() })),
+        other => Err(db_error(other)),
+    }
+}
+
+fn watch_event(change: ChangeEvent) -> WatchEvent {
+    let kind = match change.kind {
+        nanodb_core::ChangeKind::Set => ChangeKind::Set,
+        nanodb_core::ChangeKind::Delete => ChangeKind::Delete,
+        nanodb_core::ChangeKind::Clear => ChangeKind::Clear,
+        nanodb_core::ChangeKind::Expire => ChangeKind::Expire,
+        nanodb_core::ChangeKind::Expired => ChangeKind::Expired,
+    };
+    WatchEvent {
+        kind: kind as i32,
+        key: change.key,
+        value: change.value,
+        version: change.version,
+        expires_at_ms: change.expires_at,
+    }
+}
+
+// Conversiones de mensajes de transaccion
+fn precondition(p: Precondition) -> Result<nanodb_core::Precondition, Status> {
+    match p.check {
+        Some(precondition::Check::Value(value)) => Ok(nanodb_core::Precondition::Value { key: p.key, expected: Some(value) }),
+        Some(precondition::Check::Absent(_)) => Ok(nanodb_core::Precondition::Value { key: p.key, expected: None }),
+        Some(precondition::Check::Version(version)) => Ok(nanodb_core::Precondition::Version { key: p.key, version }),
+        None => Err(Status::invalid_argument(format!("Precondition without check for key {}", p.key))),
+    }
+}
+
+fn txn_operation(op: TxnOp) -> Result<DbOperation, Status> {
+    match op.op {
+        Some(txn_op::Op::Set(req)) => Ok(DbOperation::Set { key: req.key, value: req.value, ttl: req.ttl_ms.map(Duration::from_millis) }),
+        Some(txn_op::Op::Get(req)) => Ok(DbOperation::Get { key: req.key, default: None }),
+        Some(txn_op::Op::Delete(req)) => Ok(DbOperation::Delete { key: req.key }),
+        Some(txn_op::Op::Cas(req)) => Ok(DbOperation::CompareAndSwap { key: req.key, old_value: req.old_value, new_value: req.new_value }),
+        None => Err(Status::invalid_argument("Empty transaction operation")),
+    }
+}
+
+fn txn_result(response: DbResponse) -> Result<TxnOpResult, Status> {
+    let result = match response {
+        DbResponse::Ok => txn_op_result::Result::Ok(true),
+        DbResponse::Value(value) => txn_op_result::Result::Value(value),
+        DbResponse::NotFound => txn_op_result::Result::NotFound(true),
+        DbResponse::Cas(outcome) => txn_op_result::Result::Cas(CasResponse { swapped: outcome.swapped, current: outcome.current }),
+        other => return Err(db_error(other)),
+    };
+    Ok(TxnOpResult { result: Some(result) })
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Iniciando servidor gRPC simple en puerto 9090...");
-    
-    // Crear base de datos compartida
-    let _db = Arc::new(NanoDb::new());
-    
-    // Por ahora, servidor placeholder que demuestra que los structs funcionan
-    let _test_request = SetRequest {
-        key: "test".to_string(),
-        value: b"test_value".to_vec(),
-    };
-    
-    println!("Structs gRPC generados correctamente!");
-    println!("SetRequest, GetResponse, etc. están disponibles");
-    
-    // Mantener el servidor corriendo
-    tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
-    
+    println!("Iniciando servidor gRPC en puerto 9090...");
+
+    // Crear base de datos compartida (persistente si NANODB_AOF_PATH esta definido)
+    let db = Arc::new(NanoDb::with_config(DbConfig::from_env()?)?);
+    // Purga periodica de claves expiradas
+    db.spawn_reaper(Duration::from_secs(1));
+    // Lider o seguidor segun NANODB_REPLICATION_ADDR / NANODB_REPLICA_OF
+    db.start_replication().await?;
+
+    let addr = "127.0.0.1:9090".parse()?;
+    let mut server = Server::builder();
+    // Nodo de un cluster Raft si NANODB_CLUSTER_ID esta definido
+    if let Some(mut cluster) = ClusterConfig::from_env()? {
+        cluster.advertise.get_or_insert_with(|| "127.0.0.1:9090".to_string());
+        let db = RaftNode::start(db, cluster).await?;
+        server.add_service(NanoDbServiceServer::new(NanoDbGrpc { db })).serve(addr).await?;
+        return Ok(());
+    }
+    server.add_service(NanoDbServiceServer::new(NanoDbGrpc { db })).serve(addr).await?;
+
     Ok(())
 }
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cas_conflict_and_unauthorized_status() {
+        let conflict = error_status(DbError::CasConflict { key: "k".to_string() });
+        assert_eq!(conflict.code(), tonic::Code::Aborted);
+        assert_eq!(conflict.metadata().get("nanodb-error-code").unwrap(), "4");
+        let unauthorized = error_status(DbError::Unauthorized);
+        assert_eq!(unauthorized.code(), tonic::Code::Unauthenticated);
+        assert_eq!(unauthorized.metadata().get("nanodb-error-code").unwrap(), "7");
+    }
+}
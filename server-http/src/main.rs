@@ -1,16 +1,20 @@
 // Importaciones externas
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post, delete},
+    extract::{FromRequestParts, Path, Query, State},
+    http::{request::Parts, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Json, Response},
+    routing::{get, post, put, delete},
     Router,
 };
 
 // Importaciones
 use serde::{Deserialize, Serialize};
+use futures::stream::{self, Stream};
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
-use nanodb_core::NanoDb;
+use std::time::Duration;
+use nanodb_core::{ChangeEvent, ClusterConfig, Message, NanoDb, DbConfig, DbError, DbOperation, DbResponse, IndexDef, IndexQuery, MetricsSnapshot, Precondition, RaftNode, ReplicationInfo, StoragePort, TxnOutcome, WatchFilter};
 use base64::{Engine as _, engine::general_purpose};
 use tracing::info;
 
@@ -19,6 +23,8 @@ use tracing::info;
 struct SetRequest {
     key: String,
     value: String,    // Base64
+    #[serde(default)]
+    ttl_ms: Option<u64>,  // Expiracion opcional en milisegundos
 }
 
 #[derive(Serialize)]
@@ -26,14 +32,491 @@ struct GetResponse {
     value: String,    // Base64
 }
 
+#[derive(Serialize)]
+struct ExistsResponse {
+    exists: bool,
+}
+
+#[derive(Serialize)]
+struct CountResponse {
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct EntryResponse {
+    key: String,
+    value: String,    // Base64
+}
+
+#[derive(Serialize)]
+struct KeysPageResponse {
+    keys: Vec<String>,
+    next_cursor: Option<String>,  // null = no hay mas claves
+}
+
+// Parametros de ruta (con nombre: dentro de /ns/{ns} tambien llega el namespace)
+#[derive(Deserialize)]
+struct KeyPath {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct PrefixPath {
+    prefix: String,
+}
+
+// Parametros de consulta
+#[derive(Deserialize)]
+struct GetQuery {
+    default: Option<String>,      // Base64
+}
+
+#[derive(Deserialize)]
+struct PrefixQuery {
+    prefix: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    prefix: Option<String>,
+    cursor: Option<String>,
+    #[serde(default)]
+    limit: usize,                 // 0 = sin limite
+}
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    start: Option<String>,        // Inclusivo
+    end: Option<String>,          // Exclusivo
+    #[serde(default)]
+    reverse: bool,
+    #[serde(default)]
+    limit: usize,                 // 0 = sin limite
+}
+
+// Con `key` se observa una clave exacta; si no, el prefijo (vacio = todas)
+#[derive(Deserialize)]
+struct WatchQuery {
+    key: Option<String>,
+    prefix: Option<String>,
+}
+
+// Listas separadas por comas
+#[derive(Deserialize)]
+struct SubscribeQuery {
+    #[serde(default)]
+    channels: String,
+    #[serde(default)]
+    patterns: String,             // Glob: * y ?
+}
+
+#[derive(Deserialize)]
+struct PublishRequest {
+    channel: String,
+    message: String,              // Base64
+}
+
+#[derive(Serialize)]
+struct PublishResponse {
+    receivers: usize,
+}
+
+#[derive(Serialize)]
+struct MessageJson {
+    channel: String,
+    pattern: Option<String>,
+    message: String,              // Base64
+}
+
+// Colecciones (valores en Base64)
+#[derive(Deserialize)]
+struct PushRequest {
+    values: Vec<String>,
+    #[serde(default)]
+    front: bool,                  // true = al inicio de la lista
+}
+
+#[derive(Deserialize)]
+struct PopRequest {
+    #[serde(default = "one")]
+    count: usize,
+    #[serde(default)]
+    front: bool,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+struct HashSetRequest {
+    fields: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct HashFieldPath {
+    key: String,
+    field: String,
+}
+
+#[derive(Deserialize)]
+struct MembersRequest {
+    members: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ZAddRequest {
+    members: Vec<ScoredMemberJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScoredMemberJson {
+    member: String,               // Base64
+    score: f64,
+}
+
+// Posiciones inclusivas; los negativos cuentan desde el final (por defecto, todo)
+#[derive(Deserialize)]
+struct RankQuery {
+    #[serde(default)]
+    start: i64,
+    #[serde(default = "last_rank")]
+    stop: i64,
+}
+
+fn last_rank() -> i64 {
+    -1
+}
+
+#[derive(Deserialize)]
+struct ScoreQuery {
+    min: Option<f64>,             // Sin limite si falta
+    max: Option<f64>,
+}
+
+// Claves separadas por comas
+#[derive(Deserialize)]
+struct KeysQuery {
+    keys: String,
+}
+
+// Lotes: una sola peticion para muchas claves
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum BatchRequest {
+    Get { keys: Vec<String> },
+    Set { entries: Vec<BatchEntry>, #[serde(default)] atomic: bool },
+    Delete { keys: Vec<String> },
+}
+
+#[derive(Deserialize)]
+struct BatchEntry {
+    key: String,
+    value: String,                // Base64
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchResponse {
+    Values { values: Vec<Option<String>> },   // get: null = clave ausente
+    Count { count: usize },                   // set: escritas, delete: borradas
+}
+
+// Contadores
+#[derive(Deserialize)]
+struct IncrRequest {
+    #[serde(default = "one_step")]
+    delta: i64,
+}
+
+fn one_step() -> i64 {
+    1
+}
+
+#[derive(Deserialize)]
+struct IncrFloatRequest {
+    delta: f64,
+}
+
+#[derive(Serialize)]
+struct CounterResponse<N> {
+    value: N,
+}
+
+// Documentos JSON (los valores viajan como JSON, sin Base64)
+#[derive(Deserialize)]
+struct JsonPathQuery {
+    #[serde(default)]
+    path: String,                 // Vacia = raiz del documento
+}
+
+#[derive(Deserialize)]
+struct JsonAppendRequest {
+    #[serde(default)]
+    path: String,
+    values: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct JsonIncrRequest {
+    #[serde(default)]
+    path: String,
+    delta: f64,
+}
+
+// Indices secundarios
+#[derive(Deserialize)]
+struct IndexPath {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct CreateIndexRequest {
+    #[serde(default)]
+    prefix: String,               // Vacio = todas las claves
+    path: String,                 // Ruta JSON del campo indexado
+}
+
+// Igualdad (eq) o rango inclusivo (min/max); los valores son JSON y null cuenta como valor
+#[derive(Deserialize)]
+struct IndexQueryRequest {
+    #[serde(default, deserialize_with = "present")]
+    eq: Option<serde_json::Value>,
+    #[serde(default, deserialize_with = "present")]
+    min: Option<serde_json::Value>,
+    #[serde(default, deserialize_with = "present")]
+    max: Option<serde_json::Value>,
+    cursor: Option<String>,
+    #[serde(default)]
+    limit: usize,                 // 0 = sin limite
+}
+
+// Scripts (Rhai): el script solo puede tocar las claves de `keys`
+#[derive(Deserialize)]
+struct ShaPath {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct ScriptLoadRequest {
+    script: String,
+}
+
+#[derive(Serialize)]
+struct ScriptLoadResponse {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct EvalRequest {
+    script: String,
+    #[serde(default)]
+    keys: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct EvalShaRequest {
+    #[serde(default)]
+    keys: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+// Un campo presente es Some aunque valga null (ausente = None por el default)
+fn present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<serde_json::Value>, D::Error> {
+    serde_json::Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+struct CasRequest {
+    key: String,
+    #[serde(default)]
+    old_value: Option<String>,    // Base64; null = solo si la clave no existe
+    #[serde(default)]
+    new_value: Option<String>,    // Base64; null = borrar si coincide
+}
+
+#[derive(Serialize)]
+struct CasResponse {
+    swapped: bool,
+    current: Option<String>,      // Base64; valor actual despues de la operacion
+}
+
+#[derive(Serialize)]
+struct VersionResponse {
+    version: u64,
+}
+
+// Transacciones: {"preconditions": [...], "operations": [...]}
+#[derive(Deserialize)]
+struct TxnRequest {
+    #[serde(default)]
+    preconditions: Vec<PreconditionJson>,
+    operations: Vec<TxnOpJson>,
+}
+
+// Con `version` se compara la version; si no, `value` (null = la clave no debe existir)
+#[derive(Deserialize)]
+struct PreconditionJson {
+    key: String,
+    #[serde(default)]
+    value: Option<String>,        // Base64
+    #[serde(default)]
+    version: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum TxnOpJson {
+    Get { key: String },
+    Set { key: String, value: String, #[serde(default)] ttl_ms: Option<u64> },
+    Delete { key: String },
+    Exists { key: String },
+    Expire { key: String, ttl_ms: u64 },
+    Persist { key: String },
+    Ttl { key: String },
+    Cas { key: String, #[serde(default)] old_value: Option<String>, #[serde(default)] new_value: Option<String> },
+}
+
+#[derive(Serialize)]
+struct TxnResponse {
+    committed: bool,
+    failed_precondition: Option<usize>,   // Indice de la precondicion que aborto
+    results: Vec<TxnResultJson>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TxnResultJson {
+    Ok,
+    Value { value: String },              // Base64
+    NotFound,
+    Bool { value: bool },
+    Ttl { ttl_ms: Option<u64> },
+    Cas { swapped: bool, current: Option<String> },
+    Error { message: String },
+}
+
+// Datos de cada evento SSE (el tipo va en el campo `event`)
+#[derive(Serialize)]
+struct WatchEventJson {
+    key: String,
+    value: Option<String>,        // Base64, solo en set
+    version: u64,
+    expires_at_ms: Option<u64>,   // Milisegundos unix
+}
+
+#[derive(Serialize)]
+struct TtlResponse {
+    ttl_ms: Option<u64>,  // null = la clave no expira
+}
+
+#[derive(Serialize)]
+struct MetricsResponse {
+    #[serde(flatten)]
+    operations: MetricsSnapshot,
+    used_memory: u64,
+}
+
 #[derive(Serialize)]
 struct StatusResponse {
     success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<u16>,            // Codigo estable de DbError
     message: Option<String>,
 }
 
-// Estado compartido
-type AppState = Arc<NanoDb>;
+// Error HTTP: status segun el tipo de DbError y cuerpo JSON con su codigo
+struct ApiError {
+    status: StatusCode,
+    code: Option<u16>,
+    message: String,
+    leader: Option<String>,       // Nodo del cluster al que reintentar (cabecera x-nanodb-leader)
+}
+
+impl ApiError {
+    fn bad_request(message: &str) -> Self {
+        ApiError { status: StatusCode::BAD_REQUEST, code: None, message: message.to_string(), leader: None }
+    }
+
+    fn not_found() -> Self {
+        ApiError { status: StatusCode::NOT_FOUND, code: None, message: "Key not found".to_string(), leader: None }
+    }
+
+    fn namespace_not_found(name: &str) -> Self {
+        ApiError { status: StatusCode::NOT_FOUND, code: None, message: format!("Namespace not found: {}", name), leader: None }
+    }
+
+    fn index_not_found(name: &str) -> Self {
+        ApiError { status: StatusCode::NOT_FOUND, code: None, message: format!("Index not found: {}", name), leader: None }
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(err: DbError) -> Self {
+        let status = match err {
+            DbError::KeyTooLarge { .. } | DbError::ValueTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            DbError::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+            DbError::TypeMismatch { .. }
+            | DbError::CasConflict { .. }
+            | DbError::NotANumber { .. }
+            | DbError::Overflow { .. }
+            | DbError::JsonPath { .. } => {
+                StatusCode::CONFLICT
+            }
+            DbError::Script(_) | DbError::ScriptTimeout { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            DbError::NoScript(_) => StatusCode::NOT_FOUND,
+            DbError::OutOfMemory { .. } => StatusCode::INSUFFICIENT_STORAGE,
+            DbError::ReadOnly => StatusCode::FORBIDDEN,
+            DbError::Unauthorized => StatusCode::UNAUTHORIZED,
+            DbError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            DbError::Busy(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DbError::Storage(_) | DbError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            // 421: la peticion va al lider; sin lider conocido el cluster no esta disponible
+            DbError::NotLeader { leader: Some(_) } => StatusCode::MISDIRECTED_REQUEST,
+            DbError::NotLeader { leader: None } => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        let leader = match &err {
+            DbError::NotLeader { leader } => leader.clone(),
+            _ => None,
+        };
+        ApiError { status, code: Some(err.code()), message: err.to_string(), leader }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = StatusResponse { success: false, code: self.code, message: Some(self.message) };
+        let mut response = (self.status, Json(body)).into_response();
+        if let Some(leader) = self.leader.and_then(|leader| leader.parse().ok()) {
+            response.headers_mut().insert("x-nanodb-leader", leader);
+        }
+        response
+    }
+}
+
+// Estado compartido: cualquier motor que implemente el puerto de almacenamiento
+type AppState<S> = Arc<S>;
+
+// Base sobre la que opera un handler: el namespace de /ns/{ns}/... o la base principal
+struct Ns<S>(Arc<S>);
+
+impl<S: StoragePort> FromRequestParts<AppState<S>> for Ns<S> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState<S>) -> Result<Self, Self::Rejection> {
+        let params = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map(|Path(params)| params)
+            .unwrap_or_default();
+        match params.get("ns") {
+            Some(name) => state.namespace(name).map(Ns).ok_or_else(|| ApiError::namespace_not_found(name)),
+            None => Ok(Ns(state.clone())),
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
@@ -42,17 +525,22 @@ async fn main() {
     
     tracing::info!("Iniciando servidor HTTP en puerto 3000...");
 
-    // Crear base de datos compartida
-    let db = Arc::new(NanoDb::new());
+    // Crear base de datos compartida (persistente si NANODB_AOF_PATH esta definido)
+    let db = Arc::new(DbConfig::from_env().and_then(NanoDb::with_config).expect("No se pudo abrir el log append-only"));
+    // Purga periodica de claves expiradas
+    db.spawn_reaper(Duration::from_secs(1));
+    // Lider o seguidor segun NANODB_REPLICATION_ADDR / NANODB_REPLICA_OF
+    db.start_replication().await.expect("No se pudo iniciar la replicacion");
 
-    // Crear router
-    let app = Router::new()
-        .route("/set", post(set_handler))
-        .route("/get/{key}", get(get_handler))
-        .route("/delete/{key}", delete(delete_handler))
-        .route("/flush", get(flush_handler))
-        .route("/keys", get(keys_handler))
-        .with_state(db);
+    // Crear router (sobre un nodo de cluster Raft si NANODB_CLUSTER_ID esta definido)
+    let cluster = ClusterConfig::from_env().expect("Configuracion de cluster invalida");
+    let app = match cluster {
+        Some(mut cluster) => {
+            cluster.advertise.get_or_insert_with(|| "127.0.0.1:3000".to_string());
+            router(RaftNode::start(db, cluster).await.expect("No se pudo iniciar el nodo del cluster"))
+        }
+        None => router(db),
+    };
 
     // Iniciar el servidor
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
@@ -63,93 +551,682 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-// Handlers (implementar despues)
-async fn set_handler(State(db): State<AppState>, Json(req): Json<SetRequest>) -> Result<Json<StatusResponse>, StatusCode> {
+// Router generico sobre el puerto de almacenamiento.
+// Las rutas de datos existen en la raiz (base principal) y bajo /ns/{ns}/ (namespace).
+fn router<S: StoragePort>(db: Arc<S>) -> Router {
+    Router::new()
+        .merge(data_routes::<S>())
+        .nest("/ns/{ns}", data_routes::<S>())
+        .route("/namespaces", get(namespaces_handler::<S>))
+        .route("/namespaces/{ns}", put(create_namespace_handler::<S>).delete(drop_namespace_handler::<S>))
+        .route("/replication", get(replication_handler::<S>))
+        .with_state(db)
+}
+
+fn data_routes<S: StoragePort>() -> Router<AppState<S>> {
+    Router::new()
+        .route("/set", post(set_handler::<S>))
+        .route("/cas", post(cas_handler::<S>))
+        .route("/txn", post(txn_handler::<S>))
+        .route("/version/{key}", get(version_handler::<S>))
+        .route("/get/{key}", get(get_handler::<S>))
+        .route("/ttl/{key}", get(ttl_handler::<S>))
+        .route("/delete/{key}", delete(delete_handler::<S>))
+        .route("/flush", get(flush_handler::<S>))
+        .route("/save", get(save_handler::<S>))
+        .route("/exists/{key}", get(exists_handler::<S>))
+        .route("/size", get(size_handler::<S>))
+        .route("/keys", get(keys_handler::<S>))
+        .route("/keys/page", get(keys_page_handler::<S>))
+        .route("/values", get(values_handler::<S>))
+        .route("/entries", get(entries_handler::<S>))
+        .route("/range", get(range_handler::<S>))
+        .route("/prefix/{prefix}", delete(delete_prefix_handler::<S>))
+        .route("/metrics", get(metrics_handler::<S>))
+        .route("/watch", get(watch_handler::<S>))
+        .route("/publish", post(publish_handler::<S>))
+        .route("/subscribe", get(subscribe_handler::<S>))
+        .route("/lists/{key}", get(list_range_handler::<S>))
+        .route("/lists/{key}/push", post(list_push_handler::<S>))
+        .route("/lists/{key}/pop", post(list_pop_handler::<S>))
+        .route("/hashes/{key}", post(hash_set_handler::<S>))
+        .route("/hashes/{key}/{field}", get(hash_get_handler::<S>).delete(hash_delete_handler::<S>))
+        .route("/sets/{key}", get(set_members_handler::<S>))
+        .route("/sets/{key}/add", post(set_add_handler::<S>))
+        .route("/sets/{key}/remove", post(set_remove_handler::<S>))
+        .route("/intersect", get(set_intersection_handler::<S>))
+        .route("/zsets/{key}", get(zset_range_handler::<S>))
+        .route("/zsets/{key}/score", get(zset_score_handler::<S>))
+        .route("/zsets/{key}/add", post(zset_add_handler::<S>))
+        .route("/zsets/{key}/remove", post(zset_remove_handler::<S>))
+        .route("/batch", post(batch_handler::<S>))
+        .route("/counters/{key}/incr", post(incr_handler::<S>))
+        .route("/counters/{key}/decr", post(decr_handler::<S>))
+        .route("/counters/{key}/incr-float", post(incr_float_handler::<S>))
+        .route("/json/{key}", get(json_get_handler::<S>).put(json_set_handler::<S>).delete(json_delete_handler::<S>))
+        .route("/json/{key}/append", post(json_append_handler::<S>))
+        .route("/json/{key}/incr", post(json_incr_handler::<S>))
+        .route("/indexes", get(indexes_handler::<S>))
+        .route("/indexes/{name}", put(create_index_handler::<S>).delete(drop_index_handler::<S>))
+        .route("/indexes/{name}/query", post(query_index_handler::<S>))
+        .route("/eval", post(eval_handler::<S>))
+        .route("/scripts", post(script_load_handler::<S>))
+        .route("/scripts/{sha}", post(eval_sha_handler::<S>))
+}
+
+// Respuesta de estado para operaciones sin datos
+fn status_response(response: DbResponse) -> Result<Json<StatusResponse>, ApiError> {
+    let (success, message) = match response {
+        DbResponse::Error(err) => return Err(err.into()),
+        DbResponse::NotFound => (false, Some("Key not found".to_string())),
+        DbResponse::Snapshot(info) => (true, Some(format!("Snapshot saved: {} keys, {} bytes", info.keys, info.bytes))),
+        _ => (true, None),
+    };
+    Ok(Json(StatusResponse { success, code: None, message }))
+}
+
+// Errores para handlers que devuelven datos
+fn error_status(response: DbResponse) -> ApiError {
+    match response {
+        DbResponse::NotFound => ApiError::not_found(),
+        DbResponse::Error(err) => err.into(),
+        other => DbError::Internal(format!("Unexpected response: {:?}", other)).into(),
+    }
+}
+
+// Handlers
+async fn set_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<SetRequest>) -> Result<Json<StatusResponse>, ApiError> {
     // 1. Decodificar Base64
     let value_bytes = match general_purpose::STANDARD.decode(&req.value) {
         Ok(bytes) => bytes,
-        Err(_) => return Ok(Json(StatusResponse {
-            success: false,
-            message: Some("Invalid Base64".to_string()),
-        })),
+        Err(_) => return Err(ApiError::bad_request("Invalid Base64")),
     };
 
     // 2. Ejecutar comando
-    match db.set(req.key, value_bytes).await {
-        // 3. Devolver respuesta
-        nanodb_core::DbResult::Ok(_) => Ok(Json(StatusResponse {
-            success: true,
-            message: None,
-        })),
-        // 4. Devolver error
-        nanodb_core::DbResult::Err(msg) => Ok(Json(StatusResponse {
-            success: false,
-            message: Some(msg),
-        })),
-        // 5. Devolver NotFound
-        nanodb_core::DbResult::NotFound => Ok(Json(StatusResponse {
-            success: false,
-            message: Some("Unexpected NotFound".to_string()),
-        })),
+    let ttl = req.ttl_ms.map(Duration::from_millis);
+    let response = db.execute(DbOperation::Set { key: req.key, value: value_bytes, ttl }).await;
+    // 3. Devolver respuesta
+    status_response(response)
+}
+
+// Escritura condicional: 200 si se aplico, 409 con el valor actual si no coincide
+async fn cas_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<CasRequest>) -> Result<(StatusCode, Json<CasResponse>), ApiError> {
+    let op = DbOperation::CompareAndSwap {
+        key: req.key,
+        old_value: decode_optional(req.old_value)?,
+        new_value: decode_optional(req.new_value)?,
+    };
+    match db.execute(op).await {
+        DbResponse::Cas(outcome) => {
+            let status = if outcome.swapped { StatusCode::OK } else { StatusCode::CONFLICT };
+            Ok((status, Json(CasResponse {
+                swapped: outcome.swapped,
+                current: outcome.current.map(|v| general_purpose::STANDARD.encode(v)),
+            })))
+        }
+        other => Err(error_status(other)),
     }
 }
 
-// Handlers
-async fn get_handler(State(db): State<AppState>, Path(key): Path<String>) -> Result<Json<GetResponse>, StatusCode> {
-    match db.get(&key).await {
-        nanodb_core::DbResult::Ok(value_bytes) => {
+// Decodificar Base64 opcional
+fn decode_optional(value: Option<String>) -> Result<Option<Vec<u8>>, ApiError> {
+    match value {
+        Some(encoded) => general_purpose::STANDARD.decode(encoded).map(Some).map_err(|_| ApiError::bad_request("Invalid Base64")),
+        None => Ok(None),
+    }
+}
+
+fn decode_txn_op(op: TxnOpJson) -> Result<DbOperation, ApiError> {
+    Ok(match op {
+        TxnOpJson::Get { key } => DbOperation::Get { key, default: None },
+        TxnOpJson::Set { key, value, ttl_ms } => DbOperation::Set {
+            key,
+            value: general_purpose::STANDARD.decode(value).map_err(|_| ApiError::bad_request("Invalid Base64"))?,
+            ttl: ttl_ms.map(Duration::from_millis),
+        },
+        TxnOpJson::Delete { key } => DbOperation::Delete { key },
+        TxnOpJson::Exists { key } => DbOperation::Exists { key },
+        TxnOpJson::Expire { key, ttl_ms } => DbOperation::Expire { key, ttl: Duration::from_millis(ttl_ms) },
+        TxnOpJson::Persist { key } => DbOperation::Persist { key },
+        TxnOpJson::Ttl { key } => DbOperation::Ttl { key },
+        TxnOpJson::Cas { key, old_value, new_value } => DbOperation::CompareAndSwap {
+            key,
+            old_value: decode_optional(old_value)?,
+            new_value: decode_optional(new_value)?,
+        },
+    })
+}
+
+fn txn_result(response: DbResponse) -> TxnResultJson {
+    match response {
+        DbResponse::Ok => TxnResultJson::Ok,
+        DbResponse::Value(value) => TxnResultJson::Value { value: general_purpose::STANDARD.encode(value) },
+        DbResponse::NotFound => TxnResultJson::NotFound,
+        DbResponse::Bool(value) => TxnResultJson::Bool { value },
+        DbResponse::Ttl(ttl) => TxnResultJson::Ttl { ttl_ms: ttl.map(|t| t.as_millis() as u64) },
+        DbResponse::Cas(outcome) => TxnResultJson::Cas {
+            swapped: outcome.swapped,
+            current: outcome.current.map(|v| general_purpose::STANDARD.encode(v)),
+        },
+        DbResponse::Error(err) => TxnResultJson::Error { message: err.to_string() },
+        other => TxnResultJson::Error { message: format!("Unexpected response: {:?}", other) },
+    }
+}
+
+// Transaccion todo-o-nada: 200 si se aplico, 409 si fallo una precondicion
+async fn txn_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<TxnRequest>) -> Result<(StatusCode, Json<TxnResponse>), ApiError> {
+    let mut preconditions = Vec::with_capacity(req.preconditions.len());
+    for p in req.preconditions {
+        preconditions.push(match p.version {
+            Some(version) => Precondition::Version { key: p.key, version },
+            None => Precondition::Value { key: p.key, expected: decode_optional(p.value)? },
+        });
+    }
+    let operations = req.operations.into_iter().map(decode_txn_op).collect::<Result<Vec<_>, _>>()?;
+    match db.execute(DbOperation::Transaction { preconditions, operations }).await {
+        DbResponse::Txn(TxnOutcome::Committed(results)) => Ok((StatusCode::OK, Json(TxnResponse {
+            committed: true,
+            failed_precondition: None,
+            results: results.into_iter().map(txn_result).collect(),
+        }))),
+        DbResponse::Txn(TxnOutcome::Aborted { precondition }) => Ok((StatusCode::CONFLICT, Json(TxnResponse {
+            committed: false,
+            failed_precondition: Some(precondition),
+            results: vec![],
+        }))),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn version_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>) -> Result<Json<VersionResponse>, ApiError> {
+    match db.execute(DbOperation::Version { key }).await {
+        DbResponse::Version(version) => Ok(Json(VersionResponse { version })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn get_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<GetQuery>) -> Result<Json<GetResponse>, ApiError> {
+    // Valor por defecto opcional en Base64
+    let default = match query.default {
+        Some(encoded) => Some(general_purpose::STANDARD.decode(encoded).map_err(|_| ApiError::bad_request("Invalid Base64"))?),
+        None => None,
+    };
+    match db.execute(DbOperation::Get { key, default }).await {
+        DbResponse::Value(value_bytes) => {
             let encoded_value = general_purpose::STANDARD.encode(value_bytes);
             Ok(Json(GetResponse {
                 value: encoded_value,
             }))
         },
-        nanodb_core::DbResult::NotFound => {
-            Err(StatusCode::NOT_FOUND)
-        },
-        nanodb_core::DbResult::Err(_msg) => {
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        },
+        other => Err(error_status(other)),
     }
 }
 
-async fn delete_handler(State(db): State<AppState>, Path(key): Path<String>) -> Result<Json<StatusResponse>, StatusCode> {
-    match db.delete(&key).await {
-        nanodb_core::DbResult::Ok(_) => Ok(Json(StatusResponse {
-            success: true,
-            message: None,
-        })),
-        nanodb_core::DbResult::NotFound => Ok(Json(StatusResponse {
-            success: false,
-            message: Some("Key not found".to_string()),
-        })),
-        nanodb_core::DbResult::Err(msg) => Ok(Json(StatusResponse {
-            success: false,
-            message: Some(msg),
+async fn ttl_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>) -> Result<Json<TtlResponse>, ApiError> {
+    match db.execute(DbOperation::Ttl { key }).await {
+        DbResponse::Ttl(ttl) => Ok(Json(TtlResponse {
+            ttl_ms: ttl.map(|t| t.as_millis() as u64),
         })),
+        other => Err(error_status(other)),
     }
 }
 
-async fn flush_handler(State(db): State<AppState>) -> Result<Json<StatusResponse>, StatusCode> {
-    match db.clear().await {
-        nanodb_core::DbResult::Ok(_) => Ok(Json(StatusResponse {
-            success: true,
-            message: None,
-        })),
-        nanodb_core::DbResult::NotFound => Ok(Json(StatusResponse {
-            success: false,
-            message: Some("Key not found".to_string()),
-        })),
-        nanodb_core::DbResult::Err(msg) => Ok(Json(StatusResponse {
-            success: false,
-            message: Some(msg),
+async fn exists_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>) -> Result<Json<ExistsResponse>, ApiError> {
+    match db.execute(DbOperation::Exists { key }).await {
+        DbResponse::Bool(exists) => Ok(Json(ExistsResponse { exists })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn delete_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>) -> Result<Json<StatusResponse>, ApiError> {
+    status_response(db.execute(DbOperation::Delete { key }).await)
+}
+
+async fn delete_prefix_handler<S: StoragePort>(Ns(db): Ns<S>, Path(PrefixPath { prefix }): Path<PrefixPath>) -> Result<Json<CountResponse>, ApiError> {
+    match db.execute(DbOperation::DeletePrefix { prefix }).await {
+        DbResponse::Count(count) => Ok(Json(CountResponse { count })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn flush_handler<S: StoragePort>(Ns(db): Ns<S>) -> Result<Json<StatusResponse>, ApiError> {
+    status_response(db.execute(DbOperation::Flush).await)
+}
+
+async fn save_handler<S: StoragePort>(Ns(db): Ns<S>) -> Result<Json<StatusResponse>, ApiError> {
+    status_response(db.execute(DbOperation::Save).await)
+}
+
+async fn size_handler<S: StoragePort>(Ns(db): Ns<S>) -> Result<Json<CountResponse>, ApiError> {
+    match db.execute(DbOperation::Size).await {
+        DbResponse::Count(count) => Ok(Json(CountResponse { count })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn keys_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<PrefixQuery>) -> Result<Json<Vec<String>>, ApiError> {
+    let op = match query.prefix {
+        Some(prefix) => DbOperation::KeysPrefix { prefix },
+        None => DbOperation::Keys,
+    };
+    match db.execute(op).await {
+        DbResponse::Keys(keys) => Ok(Json(keys)),
+        DbResponse::NotFound => Ok(Json(vec![])),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn keys_page_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<PageQuery>) -> Result<Json<KeysPageResponse>, ApiError> {
+    let op = DbOperation::KeysCursor { prefix: query.prefix, cursor: query.cursor, limit: query.limit };
+    match db.execute(op).await {
+        DbResponse::KeysPage(page) => Ok(Json(KeysPageResponse { keys: page.keys, next_cursor: page.next_cursor })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn values_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<PrefixQuery>) -> Result<Json<Vec<String>>, ApiError> {
+    let op = match query.prefix {
+        Some(prefix) => DbOperation::ValuesPrefix { prefix },
+        None => DbOperation::Values,
+    };
+    match db.execute(op).await {
+        DbResponse::Values(values) => Ok(Json(values.into_iter().map(|v| general_purpose::STANDARD.encode(v)).collect())),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn entries_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<PrefixQuery>) -> Result<Json<Vec<EntryResponse>>, ApiError> {
+    let prefix = query.prefix.unwrap_or_default();
+    match db.execute(DbOperation::GetPrefix { prefix }).await {
+        DbResponse::Entries(entries) => Ok(Json(entries
+            .into_iter()
+            .map(|(key, value)| EntryResponse { key, value: general_purpose::STANDARD.encode(value) })
+            .collect())),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn range_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<RangeQuery>) -> Result<Json<Vec<EntryResponse>>, ApiError> {
+    let op = DbOperation::Range { start: query.start, end: query.end, reverse: query.reverse, limit: query.limit };
+    match db.execute(op).await {
+        DbResponse::Entries(entries) => Ok(Json(entries
+            .into_iter()
+            .map(|(key, value)| EntryResponse { key, value: general_purpose::STANDARD.encode(value) })
+            .collect())),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn metrics_handler<S: StoragePort>(Ns(db): Ns<S>) -> Json<MetricsResponse> {
+    Json(MetricsResponse {
+        operations: db.metrics().get_stats(),
+        used_memory: db.used_memory(),
+    })
+}
+
+// Change-feed como Server-Sent Events: un evento por cambio, con el tipo en `event`.
+// Si el suscriptor se atrasa se envia un evento `error` y se cierra el stream.
+async fn watch_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<WatchQuery>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let filter = match query.key {
+        Some(key) => WatchFilter::Key(key),
+        None => WatchFilter::Prefix(query.prefix.unwrap_or_default()),
+    };
+    let events = stream::unfold(Some(db.watch(filter)), |watcher| async move {
+        let mut watcher = watcher?;
+        match watcher.recv().await {
+            Ok(change) => Some((Ok(sse_event(change)), Some(watcher))),
+            Err(err) => Some((Ok(sse_error(err)), None)),
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+// Evento `error` con el mismo cuerpo que ApiError; el stream termina despues
+fn sse_error(err: DbError) -> Event {
+    let body = StatusResponse { success: false, code: Some(err.code()), message: Some(err.to_string()) };
+    Event::default().event("error").json_data(body).unwrap_or_default()
+}
+
+fn sse_event(change: ChangeEvent) -> Event {
+    let data = WatchEventJson {
+        key: change.key,
+        value: change.value.map(|v| general_purpose::STANDARD.encode(v)),
+        version: change.version,
+        expires_at_ms: change.expires_at,
+    };
+    Event::default().event(change.kind.as_str()).json_data(data).unwrap_or_default()
+}
+
+async fn publish_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<PublishRequest>) -> Result<Json<PublishResponse>, ApiError> {
+    let message = general_purpose::STANDARD.decode(req.message).map_err(|_| ApiError::bad_request("Invalid Base64"))?;
+    match db.execute(DbOperation::Publish { channel: req.channel, message }).await {
+        DbResponse::Count(receivers) => Ok(Json(PublishResponse { receivers })),
+        other => Err(error_status(other)),
+    }
+}
+
+// Mensajes de los canales y patrones pedidos como Server-Sent Events (`event: message`)
+async fn subscribe_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<SubscribeQuery>) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let mut subscription = db.subscriber();
+    for channel in query.channels.split(',').filter(|c| !c.is_empty()) {
+        subscription.subscribe(channel);
+    }
+    for pattern in query.patterns.split(',').filter(|p| !p.is_empty()) {
+        subscription.psubscribe(pattern);
+    }
+    if subscription.count() == 0 {
+        return Err(ApiError::bad_request("At least one channel or pattern is required"));
+    }
+    let messages = stream::unfold(Some(subscription), |subscription| async move {
+        let mut subscription = subscription?;
+        match subscription.recv().await {
+            Ok(message) => Some((Ok(sse_message(message)), Some(subscription))),
+            Err(err) => Some((Ok(sse_error(err)), None)),
+        }
+    });
+    Ok(Sse::new(messages).keep_alive(KeepAlive::default()))
+}
+
+fn sse_message(message: Message) -> Event {
+    let data = MessageJson {
+        channel: message.channel,
+        pattern: message.pattern,
+        message: general_purpose::STANDARD.encode(message.payload),
+    };
+    Event::default().event("message").json_data(data).unwrap_or_default()
+}
+
+async fn namespaces_handler<S: StoragePort>(State(db): State<AppState<S>>) -> Result<Json<Vec<String>>, ApiError> {
+    match db.execute(DbOperation::Namespaces).await {
+        DbResponse::Keys(names) => Ok(Json(names)),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn replication_handler<S: StoragePort>(State(db): State<AppState<S>>) -> Result<Json<ReplicationInfo>, ApiError> {
+    match db.execute(DbOperation::ReplicationInfo).await {
+        DbResponse::Replication(info) => Ok(Json(info)),
+        other => Err(error_status(other)),
+    }
+}
+
+// 201 si se creo, 200 si ya existia
+async fn create_namespace_handler<S: StoragePort>(State(db): State<AppState<S>>, Path(name): Path<String>) -> Result<(StatusCode, Json<StatusResponse>), ApiError> {
+    match db.execute(DbOperation::CreateNamespace { name }).await {
+        DbResponse::Bool(created) => {
+            let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+            Ok((status, Json(StatusResponse { success: true, code: None, message: None })))
+        }
+        other => Err(error_status(other)),
+    }
+}
+
+async fn drop_namespace_handler<S: StoragePort>(State(db): State<AppState<S>>, Path(name): Path<String>) -> Result<Json<StatusResponse>, ApiError> {
+    match db.execute(DbOperation::DropNamespace { name: name.clone() }).await {
+        DbResponse::Bool(true) => Ok(Json(StatusResponse { success: true, code: None, message: None })),
+        DbResponse::Bool(false) => Err(ApiError::namespace_not_found(&name)),
+        other => Err(error_status(other)),
+    }
+}
+
+// Colecciones
+fn decode_values(values: Vec<String>) -> Result<Vec<Vec<u8>>, ApiError> {
+    values
+        .into_iter()
+        .map(|v| general_purpose::STANDARD.decode(v).map_err(|_| ApiError::bad_request("Invalid Base64")))
+        .collect()
+}
+
+fn values_response(response: DbResponse) -> Result<Json<Vec<String>>, ApiError> {
+    match response {
+        DbResponse::Values(values) => Ok(Json(values.into_iter().map(|v| general_purpose::STANDARD.encode(v)).collect())),
+        other => Err(error_status(other)),
+    }
+}
+
+fn count_response(response: DbResponse) -> Result<Json<CountResponse>, ApiError> {
+    match response {
+        DbResponse::Count(count) => Ok(Json(CountResponse { count })),
+        other => Err(error_status(other)),
+    }
+}
+
+fn scored_response(response: DbResponse) -> Result<Json<Vec<ScoredMemberJson>>, ApiError> {
+    match response {
+        DbResponse::ScoredMembers(members) => Ok(Json(members
+            .into_iter()
+            .map(|(member, score)| ScoredMemberJson { member: general_purpose::STANDARD.encode(member), score })
+            .collect())),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn list_push_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<PushRequest>) -> Result<Json<CountResponse>, ApiError> {
+    let values = decode_values(req.values)?;
+    count_response(db.execute(DbOperation::ListPush { key, values, front: req.front }).await)
+}
+
+// 404 si la lista no existe
+async fn list_pop_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<PopRequest>) -> Result<Json<Vec<String>>, ApiError> {
+    values_response(db.execute(DbOperation::ListPop { key, count: req.count, front: req.front }).await)
+}
+
+async fn list_range_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<RankQuery>) -> Result<Json<Vec<String>>, ApiError> {
+    values_response(db.execute(DbOperation::ListRange { key, start: query.start, stop: query.stop }).await)
+}
+
+async fn hash_set_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<HashSetRequest>) -> Result<Json<CountResponse>, ApiError> {
+    let fields = req
+        .fields
+        .into_iter()
+        .map(|(field, value)| Ok((field, general_purpose::STANDARD.decode(value).map_err(|_| ApiError::bad_request("Invalid Base64"))?)))
+        .collect::<Result<Vec<_>, ApiError>>()?;
+    count_response(db.execute(DbOperation::HashSet { key, fields }).await)
+}
+
+async fn hash_get_handler<S: StoragePort>(Ns(db): Ns<S>, Path(HashFieldPath { key, field }): Path<HashFieldPath>) -> Result<Json<GetResponse>, ApiError> {
+    match db.execute(DbOperation::HashGet { key, field }).await {
+        DbResponse::Value(value) => Ok(Json(GetResponse { value: general_purpose::STANDARD.encode(value) })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn hash_delete_handler<S: StoragePort>(Ns(db): Ns<S>, Path(HashFieldPath { key, field }): Path<HashFieldPath>) -> Result<Json<CountResponse>, ApiError> {
+    count_response(db.execute(DbOperation::HashDelete { key, fields: vec![field] }).await)
+}
+
+async fn set_add_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<MembersRequest>) -> Result<Json<CountResponse>, ApiError> {
+    let members = decode_values(req.members)?;
+    count_response(db.execute(DbOperation::SetAdd { key, members }).await)
+}
+
+async fn set_remove_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<MembersRequest>) -> Result<Json<CountResponse>, ApiError> {
+    let members = decode_values(req.members)?;
+    count_response(db.execute(DbOperation::SetRemove { key, members }).await)
+}
+
+async fn set_members_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>) -> Result<Json<Vec<String>>, ApiError> {
+    values_response(db.execute(DbOperation::SetMembers { key }).await)
+}
+
+async fn set_intersection_handler<S: StoragePort>(Ns(db): Ns<S>, Query(query): Query<KeysQuery>) -> Result<Json<Vec<String>>, ApiError> {
+    let keys: Vec<String> = query.keys.split(',').filter(|k| !k.is_empty()).map(str::to_string).collect();
+    if keys.is_empty() {
+        return Err(ApiError::bad_request("At least one key is required"));
+    }
+    values_response(db.execute(DbOperation::SetIntersection { keys }).await)
+}
+
+async fn zset_add_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<ZAddRequest>) -> Result<Json<CountResponse>, ApiError> {
+    let members = req
+        .members
+        .into_iter()
+        .map(|m| Ok((m.score, general_purpose::STANDARD.decode(m.member).map_err(|_| ApiError::bad_request("Invalid Base64"))?)))
+        .collect::<Result<Vec<_>, ApiError>>()?;
+    count_response(db.execute(DbOperation::ZSetAdd { key, members }).await)
+}
+
+async fn zset_remove_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<MembersRequest>) -> Result<Json<CountResponse>, ApiError> {
+    let members = decode_values(req.members)?;
+    count_response(db.execute(DbOperation::ZSetRemove { key, members }).await)
+}
+
+async fn zset_range_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<RankQuery>) -> Result<Json<Vec<ScoredMemberJson>>, ApiError> {
+    scored_response(db.execute(DbOperation::ZSetRange { key, start: query.start, stop: query.stop }).await)
+}
+
+async fn zset_score_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<ScoreQuery>) -> Result<Json<Vec<ScoredMemberJson>>, ApiError> {
+    let min = query.min.unwrap_or(f64::NEG_INFINITY);
+    let max = query.max.unwrap_or(f64::INFINITY);
+    scored_response(db.execute(DbOperation::ZSetRangeByScore { key, min, max }).await)
+}
+
+// Contadores (el cuerpo es opcional en incr/decr: delta = 1)
+async fn incr_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, req: Option<Json<IncrRequest>>) -> Result<Json<CounterResponse<i64>>, ApiError> {
+    let delta = req.map_or(1, |Json(req)| req.delta);
+    match db.execute(DbOperation::Increment { key, delta }).await {
+        DbResponse::Integer(value) => Ok(Json(CounterResponse { value })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn decr_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, req: Option<Json<IncrRequest>>) -> Result<Json<CounterResponse<i64>>, ApiError> {
+    let delta = req.map_or(1, |Json(req)| req.delta);
+    match db.execute(DbOperation::Decrement { key, delta }).await {
+        DbResponse::Integer(value) => Ok(Json(CounterResponse { value })),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn incr_float_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<IncrFloatRequest>) -> Result<Json<CounterResponse<f64>>, ApiError> {
+    match db.execute(DbOperation::IncrementFloat { key, delta: req.delta }).await {
+        DbResponse::Float(value) => Ok(Json(CounterResponse { value })),
+        other => Err(error_status(other)),
+    }
+}
+
+// Documentos JSON
+async fn json_get_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<JsonPathQuery>) -> Result<Json<serde_json::Value>, ApiError> {
+    match db.execute(DbOperation::JsonGet { key, path: query.path }).await {
+        DbResponse::Json(value) => Ok(Json(value)),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn json_set_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<JsonPathQuery>, Json(value): Json<serde_json::Value>) -> Result<Json<StatusResponse>, ApiError> {
+    status_response(db.execute(DbOperation::JsonSet { key, path: query.path, value }).await)
+}
+
+async fn json_delete_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Query(query): Query<JsonPathQuery>) -> Result<Json<CountResponse>, ApiError> {
+    count_response(db.execute(DbOperation::JsonDelete { key, path: query.path }).await)
+}
+
+async fn json_append_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<JsonAppendRequest>) -> Result<Json<CountResponse>, ApiError> {
+    count_response(db.execute(DbOperation::JsonAppend { key, path: req.path, values: req.values }).await)
+}
+
+async fn json_incr_handler<S: StoragePort>(Ns(db): Ns<S>, Path(KeyPath { key }): Path<KeyPath>, Json(req): Json<JsonIncrRequest>) -> Result<Json<CounterResponse<serde_json::Value>>, ApiError> {
+    match db.execute(DbOperation::JsonIncrement { key, path: req.path, delta: req.delta }).await {
+        DbResponse::Json(value) => Ok(Json(CounterResponse { value })),
+        other => Err(error_status(other)),
+    }
+}
+
+// Indices secundarios
+async fn indexes_handler<S: StoragePort>(Ns(db): Ns<S>) -> Result<Json<Vec<IndexDef>>, ApiError> {
+    match db.execute(DbOperation::Indexes).await {
+        DbResponse::Indexes(indexes) => Ok(Json(indexes)),
+        other => Err(error_status(other)),
+    }
+}
+
+// 201 si se creo, 200 si ya existia con la misma definicion
+async fn create_index_handler<S: StoragePort>(Ns(db): Ns<S>, Path(IndexPath { name }): Path<IndexPath>, Json(req): Json<CreateIndexRequest>) -> Result<(StatusCode, Json<StatusResponse>), ApiError> {
+    match db.execute(DbOperation::CreateIndex { name, prefix: req.prefix, path: req.path }).await {
+        DbResponse::Bool(created) => {
+            let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+            Ok((status, Json(StatusResponse { success: true, code: None, message: None })))
+        }
+        other => Err(error_status(other)),
+    }
+}
+
+async fn drop_index_handler<S: StoragePort>(Ns(db): Ns<S>, Path(IndexPath { name }): Path<IndexPath>) -> Result<Json<StatusResponse>, ApiError> {
+    match db.execute(DbOperation::DropIndex { name: name.clone() }).await {
+        DbResponse::Bool(true) => Ok(Json(StatusResponse { success: true, code: None, message: None })),
+        DbResponse::Bool(false) => Err(ApiError::index_not_found(&name)),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn query_index_handler<S: StoragePort>(Ns(db): Ns<S>, Path(IndexPath { name }): Path<IndexPath>, Json(req): Json<IndexQueryRequest>) -> Result<Json<KeysPageResponse>, ApiError> {
+    let query = match (req.eq, req.min, req.max) {
+        (Some(value), None, None) => IndexQuery::Equals(value),
+        (Some(_), _, _) => return Err(ApiError::bad_request("eq cannot be combined with min or max")),
+        (None, min, max) => IndexQuery::Range { min, max },
+    };
+    match db.execute(DbOperation::QueryIndex { name, query, cursor: req.cursor, limit: req.limit }).await {
+        DbResponse::KeysPage(page) => Ok(Json(KeysPageResponse { keys: page.keys, next_cursor: page.next_cursor })),
+        other => Err(error_status(other)),
+    }
+}
+
+// Scripts: la respuesta es el valor que devuelve el script, como JSON
+async fn eval_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<EvalRequest>) -> Result<Json<serde_json::Value>, ApiError> {
+    match db.execute(DbOperation::Eval { script: req.script, keys: req.keys, args: req.args }).await {
+        DbResponse::Json(value) => Ok(Json(value)),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn eval_sha_handler<S: StoragePort>(Ns(db): Ns<S>, Path(ShaPath { sha }): Path<ShaPath>, Json(req): Json<EvalShaRequest>) -> Result<Json<serde_json::Value>, ApiError> {
+    match db.execute(DbOperation::EvalSha { sha, keys: req.keys, args: req.args }).await {
+        DbResponse::Json(value) => Ok(Json(value)),
+        other => Err(error_status(other)),
+    }
+}
+
+async fn script_load_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<ScriptLoadRequest>) -> Result<Json<ScriptLoadResponse>, ApiError> {
+    match db.execute(DbOperation::ScriptLoad { script: req.script }).await {
+        DbResponse::Value(sha) => Ok(Json(ScriptLoadResponse { sha: String::from_utf8_lossy(&sha).to_string() })),
+        other => Err(error_status(other)),
+    }
+}
+
+// Lotes
+async fn batch_handler<S: StoragePort>(Ns(db): Ns<S>, Json(req): Json<BatchRequest>) -> Result<Json<BatchResponse>, ApiError> {
+    let op = match req {
+        BatchRequest::Get { keys } => DbOperation::MultiGet { keys },
+        BatchRequest::Delete { keys } => DbOperation::MultiDelete { keys },
+        BatchRequest::Set { entries, atomic } => {
+            let entries = entries
+                .into_iter()
+                .map(|entry| Ok((entry.key, general_purpose::STANDARD.decode(entry.value).map_err(|_| ApiError::bad_request("Invalid Base64"))?)))
+                .collect::<Result<Vec<_>, ApiError>>()?;
+            DbOperation::MultiSet { entries, atomic }
+        }
+    };
+    match db.execute(op).await {
+        DbResponse::OptionalValues(values) => Ok(Json(BatchResponse::Values {
+            values: values.into_iter().map(|v| v.map(|v| general_purpose::STANDARD.encode(v))).collect(),
         })),
+        DbResponse::Count(count) => Ok(Json(BatchResponse::Count { count })),
+        other => Err(error_status(other)),
     }
 }
 
-async fn keys_handler(State(db): State<AppState>) -> Result<Json<Vec<String>>, StatusCode> {
-    match db.keys().await {
-        nanodb_core::DbResult::Ok(keys) => Ok(Json(keys)),
-        nanodb_core::DbResult::NotFound => Ok(Json(vec![])),
-        nanodb_core::DbResult::Err(_msg) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cas_conflict_and_unauthorized_status() {
+        let conflict = ApiError::from(DbError::CasConflict { key: "k".to_string() });
+        assert_eq!(conflict.status, StatusCode::CONFLICT);
+        assert_eq!(conflict.code, Some(4));
+        let unauthorized = ApiError::from(DbError::Unauthorized);
+        assert_eq!(unauthorized.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(unauthorized.code, Some(7));
     }
-}
\ No newline at end of file
+}
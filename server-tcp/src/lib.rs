@@ -2,4 +2,4 @@ pub mod protocol;
 pub mod server;
 
 pub use protocol::ProtocolParser;
-pub use server::run_server;
\ No newline at end of file
+pub use server::{run_server, serve};
\ No newline at end of file
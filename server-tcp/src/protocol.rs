@@ -1,5 +1,97 @@
 // Importaciones
-use nanodb_core::DbOperation;
+use nanodb_core::value::decode_items;
+use nanodb_core::{DbError, DbOperation, IndexQuery, Precondition, WatchFilter};
+use std::time::Duration;
+
+// Opcodes del protocolo
+pub const OP_GET: u8 = 1;
+pub const OP_SET: u8 = 2;
+pub const OP_DELETE: u8 = 3;
+pub const OP_FLUSH: u8 = 4;
+pub const OP_SAVE: u8 = 5;
+pub const OP_SETEX: u8 = 6;     // value = ttl_ms (8 bytes BE) + valor
+pub const OP_EXPIRE: u8 = 7;    // value = ttl_ms (8 bytes BE)
+pub const OP_TTL: u8 = 8;
+pub const OP_PERSIST: u8 = 9;
+pub const OP_EXISTS: u8 = 10;
+pub const OP_KEYS: u8 = 11;         // Solo opcode
+pub const OP_KEYS_PREFIX: u8 = 12;  // key = prefijo
+pub const OP_VALUES: u8 = 13;       // Solo opcode
+pub const OP_VALUES_PREFIX: u8 = 14;
+pub const OP_GET_PREFIX: u8 = 15;
+pub const OP_DELETE_PREFIX: u8 = 16;
+pub const OP_SIZE: u8 = 17;         // Solo opcode
+pub const OP_KEYS_CURSOR: u8 = 18;  // key = prefijo, value = limit (4 bytes BE) + cursor
+pub const OP_RANGE: u8 = 19;        // key = inicio, value = reverse (1) + limit (4 bytes BE) + fin
+pub const OP_CAS: u8 = 20;          // value = flags (1) + old_len (4 bytes BE) + old + new
+pub const OP_TXN: u8 = 21;          // value = precondiciones + frames de operaciones (ver parse_transaction)
+pub const OP_VERSION: u8 = 22;
+pub const OP_WATCH: u8 = 23;        // key = clave o prefijo, value = modo (1): WATCH_KEY / WATCH_PREFIX
+pub const OP_PUBLISH: u8 = 24;      // key = canal, value = mensaje
+pub const OP_SUBSCRIBE: u8 = 25;    // key = canal o patron, value = modo (1): CHANNEL_EXACT / CHANNEL_PATTERN
+pub const OP_UNSUBSCRIBE: u8 = 26;  // igual que OP_SUBSCRIBE
+pub const OP_SELECT: u8 = 27;       // key = namespace (vacia = base principal)
+pub const OP_NS_CREATE: u8 = 28;    // key = namespace
+pub const OP_NS_DROP: u8 = 29;      // key = namespace
+pub const OP_NAMESPACES: u8 = 30;   // Solo opcode
+// Colecciones. "items" = count (4 bytes BE) | [len (4 bytes BE) | bytes]*
+pub const OP_LPUSH: u8 = 31;        // value = items
+pub const OP_RPUSH: u8 = 32;        // value = items
+pub const OP_LPOP: u8 = 33;         // value = count (4 bytes BE)
+pub const OP_RPOP: u8 = 34;         // value = count (4 bytes BE)
+pub const OP_LRANGE: u8 = 35;       // value = start (8 bytes BE, i64) + stop (8 bytes BE, i64)
+pub const OP_HSET: u8 = 36;         // value = items: campo, valor, campo, valor...
+pub const OP_HGET: u8 = 37;         // value = campo
+pub const OP_HDEL: u8 = 38;         // value = items (campos)
+pub const OP_SADD: u8 = 39;         // value = items
+pub const OP_SREM: u8 = 40;         // value = items
+pub const OP_SMEMBERS: u8 = 41;
+pub const OP_SINTER: u8 = 42;       // key vacia, value = items (claves)
+pub const OP_ZADD: u8 = 43;         // value = items: puntaje (8 bytes BE, f64), miembro...
+pub const OP_ZREM: u8 = 44;         // value = items
+pub const OP_ZRANGE: u8 = 45;       // value = start (8 bytes BE, i64) + stop (8 bytes BE, i64)
+pub const OP_ZRANGE_SCORE: u8 = 46; // value = min (8 bytes BE, f64) + max (8 bytes BE, f64)
+pub const OP_INCR: u8 = 47;         // value = delta (8 bytes BE, i64); vacio = 1
+pub const OP_DECR: u8 = 48;         // igual que OP_INCR
+pub const OP_INCR_FLOAT: u8 = 49;   // value = delta (8 bytes BE, f64)
+pub const OP_MGET: u8 = 50;         // key vacia, value = items (claves)
+pub const OP_MSET: u8 = 51;         // key vacia, value = atomic (1) + items: clave, valor, clave, valor...
+pub const OP_MDELETE: u8 = 52;      // key vacia, value = items (claves)
+// Documentos JSON. Las rutas usan la sintaxis de nanodb_core (`$.a.b[0]`; vacia = raiz)
+pub const OP_JSON_GET: u8 = 53;     // value = ruta
+pub const OP_JSON_SET: u8 = 54;     // value = items: ruta, documento JSON
+pub const OP_JSON_DEL: u8 = 55;     // value = ruta
+pub const OP_JSON_APPEND: u8 = 56;  // value = items: ruta, valor JSON, valor JSON...
+pub const OP_JSON_INCR: u8 = 57;    // value = delta (8 bytes BE, f64) + ruta
+// Indices secundarios sobre documentos JSON
+pub const OP_IDX_CREATE: u8 = 58;   // key = nombre, value = items: prefijo, ruta
+pub const OP_IDX_DROP: u8 = 59;     // key = nombre
+pub const OP_INDEXES: u8 = 60;      // Solo opcode
+pub const OP_IDX_QUERY: u8 = 61;    // key = nombre, value = limit (4 bytes BE) + items: cursor, igual, min, max
+                                    // (JSON; vacio = ausente; con "igual" no se usan min y max)
+// Scripts (Rhai). El script solo puede tocar las claves declaradas
+pub const OP_EVAL: u8 = 62;         // key vacia, value = cantidad de claves (4 bytes BE) + items: script, claves..., args...
+pub const OP_EVALSHA: u8 = 63;      // key = hash, value = cantidad de claves (4 bytes BE) + items: claves..., args...
+pub const OP_SCRIPT_LOAD: u8 = 64;  // key vacia, value = script
+// Replicacion
+pub const OP_REPLICATION: u8 = 65;  // Solo opcode
+
+// Flags de OP_CAS: que valores estan presentes
+pub const CAS_HAS_OLD: u8 = 0b01;    // Sin old = solo si la clave no existe
+pub const CAS_HAS_NEW: u8 = 0b10;    // Sin new = borrar si coincide
+
+// Modos de OP_WATCH
+pub const WATCH_KEY: u8 = 0;
+pub const WATCH_PREFIX: u8 = 1;
+
+// Modos de OP_SUBSCRIBE / OP_UNSUBSCRIBE
+pub const CHANNEL_EXACT: u8 = 0;
+pub const CHANNEL_PATTERN: u8 = 1;
+
+// Tipos de precondicion de OP_TXN
+pub const PRE_VALUE: u8 = 1;        // val_len (4 bytes BE) + valor esperado
+pub const PRE_ABSENT: u8 = 2;       // la clave no debe existir
+pub const PRE_VERSION: u8 = 3;      // version (8 bytes BE)
 
 // Estado de parsing para cada conexion
 pub struct ProtocolParser {
@@ -11,6 +103,7 @@ pub struct ProtocolParser {
 
 // Estados de parsing
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum ParseState {
     ReadingOpCode,                      // Necesita 1 byte
     ReadingKeyLength,                   // Necesita 2 bytes
@@ -19,6 +112,12 @@ enum ParseState {
     ReadingValue { expected: u32 },     // Necesita N bytes
 }
 
+impl Default for ProtocolParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // Logica de parsing
 impl ProtocolParser {
     pub fn new() -> Self {
@@ -29,36 +128,30 @@ impl ProtocolParser {
             current_key: None,
         }
     }
-    // Función principal
-    pub fn feed_bytes(&mut self, new_bytes: &[u8]) -> Vec<DbOperation> {
+    // Función principal. Un frame invalido devuelve InvalidArgument en su lugar,
+    // para que el cliente reciba una respuesta por cada frame enviado
+    pub fn feed_bytes(&mut self, new_bytes: &[u8]) -> Vec<Result<DbOperation, DbError>> {
         // 1. Agregar nuevos bytes al buffer
         self.buffer.extend_from_slice(new_bytes);
         // 2. Procesar comandos
         let mut commands = Vec::new();
         
         // 3. Procesar comandos mientras sea posible
-        loop {
-            match self.try_parse_command() {
-                Some(command) => {
-                    commands.push(command);
-                }
-                None => {
-                    break;
-                }
-            }
+        while let Some(command) = self.try_parse_command() {
+            commands.push(command);
         }
         // 4. Devolver comandos
         commands
     }
     
     // Función auxiliar
-    fn try_parse_command(&mut self) -> Option<DbOperation> {
+    fn try_parse_command(&mut self) -> Option<Result<DbOperation, DbError>> {
         loop {
             print!("Parser state: {:?}, buffer len: {}", self.state, self.buffer.len());
         // Aquí implementaremos la máquina de estados
         match self.state{
             ParseState::ReadingOpCode => {
-                if self.buffer.len() < 1 {return None;}
+                if self.buffer.is_empty() {return None;}
                 let opcode = self.buffer[0];
                 print!("Opcode: {}", opcode);
                 self.buffer.remove(0);
@@ -66,30 +159,36 @@ impl ProtocolParser {
                 // Aquí devolveremos el comando
                 match opcode {
 
-                    4=> {
+                    OP_FLUSH=> {
                         self.state = ParseState::ReadingOpCode;
 
-                        return Some(DbOperation::Flush);
+                        return Some(Ok(DbOperation::Flush));
                     }
-                    1=> {
-                        self.state = ParseState::ReadingKeyLength;
-
-                        
-                    }
-                    2=> {
-                        self.state = ParseState::ReadingKeyLength;
+                    OP_SAVE=> {
+                        self.state = ParseState::ReadingOpCode;
 
-                        
+                        return Some(Ok(DbOperation::Save));
                     }
-                    3=> {
+                    OP_KEYS => return Some(Ok(DbOperation::Keys)),
+                    OP_VALUES => return Some(Ok(DbOperation::Values)),
+                    OP_SIZE => return Some(Ok(DbOperation::Size)),
+                    OP_NAMESPACES => return Some(Ok(DbOperation::Namespaces)),
+                    OP_INDEXES => return Some(Ok(DbOperation::Indexes)),
+                    OP_REPLICATION => return Some(Ok(DbOperation::ReplicationInfo)),
+                    OP_GET | OP_SET | OP_DELETE | OP_SETEX | OP_EXPIRE | OP_TTL | OP_PERSIST
+                    | OP_EXISTS | OP_KEYS_PREFIX | OP_VALUES_PREFIX | OP_GET_PREFIX
+                    | OP_DELETE_PREFIX | OP_KEYS_CURSOR | OP_RANGE | OP_CAS | OP_TXN | OP_VERSION
+                    | OP_WATCH | OP_PUBLISH | OP_SUBSCRIBE | OP_UNSUBSCRIBE | OP_SELECT | OP_NS_CREATE
+                    | OP_NS_DROP | OP_LPUSH..=OP_ZRANGE_SCORE | OP_INCR..=OP_INCR_FLOAT | OP_MGET..=OP_MDELETE
+                    | OP_JSON_GET..=OP_JSON_INCR | OP_IDX_CREATE | OP_IDX_DROP | OP_IDX_QUERY | OP_EVAL..=OP_SCRIPT_LOAD => {
                         self.state = ParseState::ReadingKeyLength;
 
                         
                     }
                     _=> {
                         self.state = ParseState::ReadingOpCode;
-                        
-                        return None;
+
+                        return Some(Err(DbError::InvalidArgument(format!("unknown opcode {}", opcode))));
                     }
                 }
             }
@@ -138,39 +237,688 @@ impl ProtocolParser {
                 let key = self.current_key.take().unwrap();
 
                 // Construir el comando segun el opcode
-                let command = match opcode {
-                    1 => DbOperation::Get { key, default: None },
-                    2 => DbOperation::Set { key, value: value_bytes },
-                    3 => DbOperation::Delete { key },
-                    _ => return None, // Otro opcode no soportado
-                };
-
-                // Reset state y retornar
                 self.state = ParseState::ReadingOpCode;
-                return Some(command);
+                // Frame invalido: se responde con error y se sigue con el siguiente
+                return Some(build_command(opcode, key, value_bytes)
+                    .ok_or_else(|| DbError::InvalidArgument(format!("malformed frame for opcode {}", opcode))));
             }
         }
         }
     }
 }
+// Construir el comando de un frame completo (opcode + key + value)
+fn build_command(opcode: u8, key: String, value: Vec<u8>) -> Option<DbOperation> {
+    match opcode {
+        // GET con value no vacio: el value es el valor por defecto
+        OP_GET => Some(DbOperation::Get { key, default: Some(value).filter(|v| !v.is_empty()) }),
+        OP_SET => Some(DbOperation::Set { key, value, ttl: None }),
+        OP_DELETE => Some(DbOperation::Delete { key }),
+        OP_SETEX => {
+            let ttl = read_ttl(&value)?;
+            Some(DbOperation::Set { key, value: value[8..].to_vec(), ttl: Some(ttl) })
+        }
+        OP_EXPIRE => Some(DbOperation::Expire { key, ttl: read_ttl(&value)? }),
+        OP_TTL => Some(DbOperation::Ttl { key }),
+        OP_PERSIST => Some(DbOperation::Persist { key }),
+        OP_EXISTS => Some(DbOperation::Exists { key }),
+        OP_KEYS_PREFIX => Some(DbOperation::KeysPrefix { prefix: key }),
+        OP_VALUES_PREFIX => Some(DbOperation::ValuesPrefix { prefix: key }),
+        OP_GET_PREFIX => Some(DbOperation::GetPrefix { prefix: key }),
+        OP_DELETE_PREFIX => Some(DbOperation::DeletePrefix { prefix: key }),
+        OP_KEYS_CURSOR => {
+            let limit: [u8; 4] = value.get(..4)?.try_into().ok()?;
+            let cursor = String::from_utf8_lossy(&value[4..]).to_string();
+            Some(DbOperation::KeysCursor {
+                prefix: Some(key).filter(|p| !p.is_empty()),
+                cursor: Some(cursor).filter(|c| !c.is_empty()),
+                limit: u32::from_be_bytes(limit) as usize,
+            })
+        }
+        OP_RANGE => {
+            // Inicio o fin vacios = sin limite
+            let reverse = *value.first()? != 0;
+            let limit: [u8; 4] = value.get(1..5)?.try_into().ok()?;
+            let end = String::from_utf8_lossy(&value[5..]).to_string();
+            Some(DbOperation::Range {
+                start: Some(key).filter(|s| !s.is_empty()),
+                end: Some(end).filter(|e| !e.is_empty()),
+                reverse,
+                limit: u32::from_be_bytes(limit) as usize,
+            })
+        }
+        OP_CAS => {
+            let flags = *value.first()?;
+            let old_len: [u8; 4] = value.get(1..5)?.try_into().ok()?;
+            let old_end = 5 + u32::from_be_bytes(old_len) as usize;
+            let old = value.get(5..old_end)?.to_vec();
+            let new = value[old_end..].to_vec();
+            Some(DbOperation::CompareAndSwap {
+                key,
+                old_value: (flags & CAS_HAS_OLD != 0).then_some(old),
+                new_value: (flags & CAS_HAS_NEW != 0).then_some(new),
+            })
+        }
+        OP_VERSION => Some(DbOperation::Version { key }),
+        OP_TXN => parse_transaction(&value),
+        OP_WATCH => match value.first().copied().unwrap_or(WATCH_KEY) {
+            WATCH_KEY => Some(DbOperation::Watch { filter: WatchFilter::Key(key) }),
+            WATCH_PREFIX => Some(DbOperation::Watch { filter: WatchFilter::Prefix(key) }),
+            _ => None,
+        },
+        OP_SELECT => Some(DbOperation::Select { namespace: Some(key).filter(|k| !k.is_empty()) }),
+        OP_NS_CREATE => Some(DbOperation::CreateNamespace { name: key }),
+        OP_NS_DROP => Some(DbOperation::DropNamespace { name: key }),
+        OP_PUBLISH => Some(DbOperation::Publish { channel: key, message: value }),
+        OP_SUBSCRIBE | OP_UNSUBSCRIBE => {
+            let pattern = match value.first().copied().unwrap_or(CHANNEL_EXACT) {
+                CHANNEL_EXACT => false,
+                CHANNEL_PATTERN => true,
+                _ => return None,
+            };
+            if opcode == OP_SUBSCRIBE {
+                Some(DbOperation::Subscribe { channel: key, pattern })
+            } else {
+                Some(DbOperation::Unsubscribe { channel: key, pattern })
+            }
+        }
+        OP_LPUSH | OP_RPUSH => Some(DbOperation::ListPush { key, values: decode_items(&value).ok()?, front: opcode == OP_LPUSH }),
+        OP_LPOP | OP_RPOP => {
+            let count: [u8; 4] = value.as_slice().try_into().ok()?;
+            Some(DbOperation::ListPop { key, count: u32::from_be_bytes(count) as usize, front: opcode == OP_LPOP })
+        }
+        OP_LRANGE => {
+            let (start, stop) = read_pair(&value)?;
+            Some(DbOperation::ListRange { key, start: i64::from_be_bytes(start), stop: i64::from_be_bytes(stop) })
+        }
+        OP_HSET => {
+            let items = decode_items(&value).ok()?;
+            if !items.len().is_multiple_of(2) {
+                return None;
+            }
+            let fields = items
+                .chunks(2)
+                .map(|pair| (String::from_utf8_lossy(&pair[0]).to_string(), pair[1].clone()))
+                .collect();
+            Some(DbOperation::HashSet { key, fields })
+        }
+        OP_HGET => Some(DbOperation::HashGet { key, field: String::from_utf8_lossy(&value).to_string() }),
+        OP_HDEL => {
+            let fields = decode_items(&value).ok()?.iter().map(|f| String::from_utf8_lossy(f).to_string()).collect();
+            Some(DbOperation::HashDelete { key, fields })
+        }
+        OP_SADD => Some(DbOperation::SetAdd { key, members: decode_items(&value).ok()? }),
+        OP_SREM => Some(DbOperation::SetRemove { key, members: decode_items(&value).ok()? }),
+        OP_SMEMBERS => Some(DbOperation::SetMembers { key }),
+        OP_SINTER => Some(DbOperation::SetIntersection { keys: decode_keys(&value)? }),
+        OP_ZADD => {
+            let items = decode_items(&value).ok()?;
+            if !items.len().is_multiple_of(2) {
+                return None;
+            }
+            let mut members = Vec::with_capacity(items.len() / 2);
+            for pair in items.chunks(2) {
+                let score: [u8; 8] = pair[0].as_slice().try_into().ok()?;
+                members.push((f64::from_be_bytes(score), pair[1].clone()));
+            }
+            Some(DbOperation::ZSetAdd { key, members })
+        }
+        OP_INCR | OP_DECR => {
+            let delta = match value.len() {
+                0 => 1,
+                _ => i64::from_be_bytes(value.as_slice().try_into().ok()?),
+            };
+            if opcode == OP_INCR {
+                Some(DbOperation::Increment { key, delta })
+            } else {
+                Some(DbOperation::Decrement { key, delta })
+            }
+        }
+        OP_INCR_FLOAT => {
+            let delta: [u8; 8] = value.as_slice().try_into().ok()?;
+            Some(DbOperation::IncrementFloat { key, delta: f64::from_be_bytes(delta) })
+        }
+        OP_MGET => Some(DbOperation::MultiGet { keys: decode_keys(&value)? }),
+        OP_MDELETE => Some(DbOperation::MultiDelete { keys: decode_keys(&value)? }),
+        OP_MSET => {
+            let atomic = *value.first()? != 0;
+            let items = decode_items(&value[1..]).ok()?;
+            if !items.len().is_multiple_of(2) {
+                return None;
+            }
+            let entries = items
+                .chunks(2)
+                .map(|pair| (String::from_utf8_lossy(&pair[0]).to_string(), pair[1].clone()))
+                .collect();
+            Some(DbOperation::MultiSet { entries, atomic })
+        }
+        OP_ZREM => Some(DbOperation::ZSetRemove { key, members: decode_items(&value).ok()? }),
+        OP_ZRANGE => {
+            let (start, stop) = read_pair(&value)?;
+            Some(DbOperation::ZSetRange { key, start: i64::from_be_bytes(start), stop: i64::from_be_bytes(stop) })
+        }
+        OP_ZRANGE_SCORE => {
+            let (min, max) = read_pair(&value)?;
+            Some(DbOperation::ZSetRangeByScore { key, min: f64::from_be_bytes(min), max: f64::from_be_bytes(max) })
+        }
+        OP_JSON_GET => Some(DbOperation::JsonGet { key, path: String::from_utf8(value).ok()? }),
+        OP_JSON_DEL => Some(DbOperation::JsonDelete { key, path: String::from_utf8(value).ok()? }),
+        OP_JSON_SET => {
+            let items = decode_items(&value).ok()?;
+            let [path, document] = items.as_slice() else { return None };
+            Some(DbOperation::JsonSet { key, path: std::str::from_utf8(path).ok()?.to_string(), value: serde_json::from_slice(document).ok()? })
+        }
+        OP_JSON_APPEND => {
+            let items = decode_items(&value).ok()?;
+            let (path, values) = items.split_first()?;
+            let values = values.iter().map(|v| serde_json::from_slice(v).ok()).collect::<Option<_>>()?;
+            Some(DbOperation::JsonAppend { key, path: std::str::from_utf8(path).ok()?.to_string(), values })
+        }
+        OP_JSON_INCR => {
+            let delta: [u8; 8] = value.get(..8)?.try_into().ok()?;
+            let path = std::str::from_utf8(&value[8..]).ok()?.to_string();
+            Some(DbOperation::JsonIncrement { key, path, delta: f64::from_be_bytes(delta) })
+        }
+        OP_IDX_CREATE => {
+            let items = decode_items(&value).ok()?;
+            let [prefix, path] = items.as_slice() else { return None };
+            let text = |bytes: &[u8]| std::str::from_utf8(bytes).ok().map(str::to_string);
+            Some(DbOperation::CreateIndex { name: key, prefix: text(prefix)?, path: text(path)? })
+        }
+        OP_IDX_DROP => Some(DbOperation::DropIndex { name: key }),
+        OP_IDX_QUERY => {
+            let limit = u32::from_be_bytes(value.get(..4)?.try_into().ok()?) as usize;
+            let items = decode_items(&value[4..]).ok()?;
+            let [cursor, equals, min, max] = items.as_slice() else { return None };
+            // Vacio = ausente
+            let json = |bytes: &[u8]| match bytes.is_empty() {
+                true => Some(None),
+                false => serde_json::from_slice(bytes).ok().map(Some),
+            };
+            let query = match json(equals)? {
+                Some(value) => IndexQuery::Equals(value),
+                None => IndexQuery::Range { min: json(min)?, max: json(max)? },
+            };
+            let cursor = Some(String::from_utf8(cursor.clone()).ok()?).filter(|c| !c.is_empty());
+            Some(DbOperation::QueryIndex { name: key, query, cursor, limit })
+        }
+        OP_EVAL => {
+            let (mut items, key_count) = script_items(&value)?;
+            if items.is_empty() {
+                return None;
+            }
+            let script = items.remove(0);
+            let args = items.split_off(key_count.min(items.len()));
+            (items.len() == key_count).then_some(DbOperation::Eval { script, keys: items, args })
+        }
+        OP_EVALSHA => {
+            let (mut keys, key_count) = script_items(&value)?;
+            let args = keys.split_off(key_count.min(keys.len()));
+            (keys.len() == key_count).then_some(DbOperation::EvalSha { sha: key, keys, args })
+        }
+        OP_SCRIPT_LOAD => Some(DbOperation::ScriptLoad { script: String::from_utf8(value).ok()? }),
+        _ => None, // Otro opcode no soportado
+    }
+}
+
+// Cantidad de claves (4 bytes BE) seguida de items de texto
+fn script_items(value: &[u8]) -> Option<(Vec<String>, usize)> {
+    let key_count = u32::from_be_bytes(value.get(..4)?.try_into().ok()?) as usize;
+    let items = decode_items(&value[4..]).ok()?.into_iter().map(|item| String::from_utf8(item).ok()).collect::<Option<_>>()?;
+    Some((items, key_count))
+}
+
+// Lista de claves codificada con encode_items
+fn decode_keys(value: &[u8]) -> Option<Vec<String>> {
+    Some(decode_items(value).ok()?.iter().map(|k| String::from_utf8_lossy(k).to_string()).collect())
+}
+
+// Dos numeros de 8 bytes (rangos de posiciones o de puntajes)
+fn read_pair(value: &[u8]) -> Option<([u8; 8], [u8; 8])> {
+    if value.len() != 16 {
+        return None;
+    }
+    Some((value[..8].try_into().ok()?, value[8..].try_into().ok()?))
+}
+
+// Cuerpo de OP_TXN:
+//   pre_count (2) | [kind (1) | key_len (2) | key | datos segun kind]*
+//   op_count (2)  | frames normales de cada operacion
+// Si algun frame interno es invalido se descarta la transaccion completa.
+fn parse_transaction(value: &[u8]) -> Option<DbOperation> {
+    let mut pos = 0;
+    let pre_count = u16::from_be_bytes(value.get(pos..pos + 2)?.try_into().ok()?);
+    pos += 2;
+    let mut preconditions = Vec::with_capacity(pre_count as usize);
+    for _ in 0..pre_count {
+        let kind = *value.get(pos)?;
+        let key_len = u16::from_be_bytes(value.get(pos + 1..pos + 3)?.try_into().ok()?) as usize;
+        pos += 3;
+        let key = String::from_utf8_lossy(value.get(pos..pos + key_len)?).to_string();
+        pos += key_len;
+        let precondition = match kind {
+            PRE_VALUE => {
+                let len = u32::from_be_bytes(value.get(pos..pos + 4)?.try_into().ok()?) as usize;
+                let expected = value.get(pos + 4..pos + 4 + len)?.to_vec();
+                pos += 4 + len;
+                Precondition::Value { key, expected: Some(expected) }
+            }
+            PRE_ABSENT => Precondition::Value { key, expected: None },
+            PRE_VERSION => {
+                let version = u64::from_be_bytes(value.get(pos..pos + 8)?.try_into().ok()?);
+                pos += 8;
+                Precondition::Version { key, version }
+            }
+            _ => return None,
+        };
+        preconditions.push(precondition);
+    }
+    let op_count = u16::from_be_bytes(value.get(pos..pos + 2)?.try_into().ok()?) as usize;
+    pos += 2;
+    let mut parser = ProtocolParser::new();
+    let operations = parser.feed_bytes(&value[pos..]).into_iter().collect::<Result<Vec<_>, _>>().ok()?;
+    if operations.len() != op_count || !parser.buffer.is_empty() {
+        return None;
+    }
+    Some(DbOperation::Transaction { preconditions, operations })
+}
+
+// TTL en milisegundos al inicio del value
+fn read_ttl(value: &[u8]) -> Option<Duration> {
+    let bytes: [u8; 8] = value.get(..8)?.try_into().ok()?;
+    Some(Duration::from_millis(u64::from_be_bytes(bytes)))
+}
+
 // Tests
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    // Comandos de frames validos; falla si alguno se rechaza
+    fn parse(parser: &mut ProtocolParser, bytes: &[u8]) -> Vec<DbOperation> {
+        parser.feed_bytes(bytes).into_iter().map(|command| command.expect("valid frame")).collect()
+    }
     #[test]
     fn test_flush_command() {
         let mut parser = ProtocolParser::new();
-        let commands = parser.feed_bytes(&[4]);
+        let commands = parse(&mut parser, &[4]);
         assert_eq!(commands.len(), 1);
         assert_eq!(commands[0], DbOperation::Flush);
     }
+    #[test]
+    fn test_save_command() {
+        let mut parser = ProtocolParser::new();
+        let commands = parse(&mut parser, &[5]);
+        assert_eq!(commands, vec![DbOperation::Save]);
+    }
+    #[test]
+    fn test_setex_command() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_SETEX, 0, 1, b'k'];
+        frame.extend_from_slice(&10u32.to_be_bytes());
+        frame.extend_from_slice(&1500u64.to_be_bytes());
+        frame.extend_from_slice(b"va");
+        let commands = parse(&mut parser, &frame);
+        assert_eq!(commands, vec![DbOperation::Set {
+            key: "k".to_string(),
+            value: b"va".to_vec(),
+            ttl: Some(Duration::from_millis(1500)),
+        }]);
+    }
+    #[test]
+    fn test_ttl_command() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_TTL, 0, 1, b'k'];
+        frame.extend_from_slice(&0u32.to_be_bytes());
+        let commands = parse(&mut parser, &frame);
+        assert_eq!(commands, vec![DbOperation::Ttl { key: "k".to_string() }]);
+    }
+    #[test]
+    fn test_opcode_only_commands() {
+        let mut parser = ProtocolParser::new();
+        let commands = parse(&mut parser, &[OP_KEYS, OP_VALUES, OP_SIZE, OP_REPLICATION]);
+        assert_eq!(commands, vec![DbOperation::Keys, DbOperation::Values, DbOperation::Size, DbOperation::ReplicationInfo]);
+    }
+    #[test]
+    fn test_get_with_default() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_GET, 0, 1, b'k'];
+        frame.extend_from_slice(&3u32.to_be_bytes());
+        frame.extend_from_slice(b"def");
+        let commands = parse(&mut parser, &frame);
+        assert_eq!(commands, vec![DbOperation::Get { key: "k".to_string(), default: Some(b"def".to_vec()) }]);
+    }
+    #[test]
+    fn test_keys_cursor_command() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_KEYS_CURSOR, 0, 2, b'u', b':'];
+        frame.extend_from_slice(&7u32.to_be_bytes());
+        frame.extend_from_slice(&10u32.to_be_bytes());
+        frame.extend_from_slice(b"u:1");
+        let commands = parse(&mut parser, &frame);
+        assert_eq!(commands, vec![DbOperation::KeysCursor {
+            prefix: Some("u:".to_string()),
+            cursor: Some("u:1".to_string()),
+            limit: 10,
+        }]);
+    }
+    #[test]
+    fn test_range_command() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_RANGE, 0, 1, b'a'];
+        frame.extend_from_slice(&6u32.to_be_bytes());
+        frame.push(1);
+        frame.extend_from_slice(&5u32.to_be_bytes());
+        frame.push(b'm');
+        let commands = parse(&mut parser, &frame);
+        assert_eq!(commands, vec![DbOperation::Range {
+            start: Some("a".to_string()),
+            end: Some("m".to_string()),
+            reverse: true,
+            limit: 5,
+        }]);
+    }
+    #[test]
+    fn test_cas_command() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_CAS, 0, 1, b'k'];
+        frame.extend_from_slice(&7u32.to_be_bytes());
+        frame.push(CAS_HAS_OLD | CAS_HAS_NEW);
+        frame.extend_from_slice(&1u32.to_be_bytes());
+        frame.extend_from_slice(b"ab");
+        // Sin valor nuevo: borrar si coincide
+        frame.extend_from_slice(&[OP_CAS, 0, 1, b'k']);
+        frame.extend_from_slice(&6u32.to_be_bytes());
+        frame.push(CAS_HAS_OLD);
+        frame.extend_from_slice(&1u32.to_be_bytes());
+        frame.push(b'a');
+        let commands = parse(&mut parser, &frame);
+        assert_eq!(commands, vec![
+            DbOperation::CompareAndSwap { key: "k".to_string(), old_value: Some(b"a".to_vec()), new_value: Some(b"b".to_vec()) },
+            DbOperation::CompareAndSwap { key: "k".to_string(), old_value: Some(b"a".to_vec()), new_value: None },
+        ]);
+    }
+    #[test]
+    fn test_transaction_command() {
+        let mut body = 2u16.to_be_bytes().to_vec();
+        body.extend_from_slice(&[PRE_ABSENT, 0, 2, b't', b'o']);
+        body.extend_from_slice(&[PRE_VERSION, 0, 1, b'a']);
+        body.extend_from_slice(&7u64.to_be_bytes());
+        body.extend_from_slice(&2u16.to_be_bytes());
+        body.extend_from_slice(&[OP_SET, 0, 2, b't', b'o']);
+        body.extend_from_slice(&1u32.to_be_bytes());
+        body.push(b'x');
+        body.extend_from_slice(&[OP_DELETE, 0, 1, b'a']);
+        body.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut frame = vec![OP_TXN, 0, 0];
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+        let mut parser = ProtocolParser::new();
+        assert_eq!(parse(&mut parser, &frame), vec![DbOperation::Transaction {
+            preconditions: vec![
+                Precondition::Value { key: "to".to_string(), expected: None },
+                Precondition::Version { key: "a".to_string(), version: 7 },
+            ],
+            operations: vec![
+                DbOperation::Set { key: "to".to_string(), value: b"x".to_vec(), ttl: None },
+                DbOperation::Delete { key: "a".to_string() },
+            ],
+        }]);
+
+        // Un conteo de operaciones que no coincide invalida la transaccion
+        body[20] = 3;
+        let mut frame = vec![OP_TXN, 0, 0];
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+        assert!(matches!(parser.feed_bytes(&frame).as_slice(), [Err(DbError::InvalidArgument(_))]));
+    }
+    #[test]
+    fn test_watch_command() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_WATCH, 0, 5];
+        frame.extend_from_slice(b"user:");
+        frame.extend_from_slice(&1u32.to_be_bytes());
+        frame.push(WATCH_PREFIX);
+        assert_eq!(parse(&mut parser, &frame), vec![DbOperation::Watch { filter: WatchFilter::Prefix("user:".to_string()) }]);
+    }
+
+    #[test]
+    fn test_pubsub_commands() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_PUBLISH, 0, 4];
+        frame.extend_from_slice(b"chat");
+        frame.extend_from_slice(&2u32.to_be_bytes());
+        frame.extend_from_slice(b"hi");
+        frame.extend_from_slice(&[OP_SUBSCRIBE, 0, 6]);
+        frame.extend_from_slice(b"chat.*");
+        frame.extend_from_slice(&1u32.to_be_bytes());
+        frame.push(CHANNEL_PATTERN);
+        frame.extend_from_slice(&[OP_UNSUBSCRIBE, 0, 4]);
+        frame.extend_from_slice(b"chat");
+        frame.extend_from_slice(&0u32.to_be_bytes());
+        assert_eq!(parse(&mut parser, &frame), vec![
+            DbOperation::Publish { channel: "chat".to_string(), message: b"hi".to_vec() },
+            DbOperation::Subscribe { channel: "chat.*".to_string(), pattern: true },
+            DbOperation::Unsubscribe { channel: "chat".to_string(), pattern: false },
+        ]);
+    }
+    #[test]
+    fn test_namespace_commands() {
+        let mut parser = ProtocolParser::new();
+        let mut frame = vec![OP_SELECT, 0, 4];
+        frame.extend_from_slice(b"team");
+        frame.extend_from_slice(&0u32.to_be_bytes());
+        frame.extend_from_slice(&[OP_SELECT, 0, 0, 0, 0, 0, 0]);
+        frame.push(OP_NAMESPACES);
+        assert_eq!(parse(&mut parser, &frame), vec![
+            DbOperation::Select { namespace: Some("team".to_string()) },
+            DbOperation::Select { namespace: None },
+            DbOperation::Namespaces,
+        ]);
+    }
+    #[test]
+    fn test_collection_commands() {
+        let frame_with = |opcode: u8, key: &str, value: &[u8]| {
+            let mut frame = vec![opcode];
+            frame.extend_from_slice(&(key.len() as u16).to_be_bytes());
+            frame.extend_from_slice(key.as_bytes());
+            frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            frame.extend_from_slice(value);
+            frame
+        };
+        let mut items = Vec::new();
+        nanodb_core::value::encode_items(&mut items, [&b"a"[..], b"b"].into_iter());
+        let mut scored = Vec::new();
+        nanodb_core::value::encode_items(&mut scored, [&1.5f64.to_be_bytes()[..], b"m"].into_iter());
+        let mut range = 0i64.to_be_bytes().to_vec();
+        range.extend_from_slice(&(-1i64).to_be_bytes());
+
+        let mut bytes = frame_with(OP_LPUSH, "list", &items);
+        bytes.extend(frame_with(OP_RPOP, "list", &2u32.to_be_bytes()));
+        bytes.extend(frame_with(OP_HSET, "hash", &items));
+        bytes.extend(frame_with(OP_SINTER, "", &items));
+        bytes.extend(frame_with(OP_ZADD, "zset", &scored));
+        bytes.extend(frame_with(OP_ZRANGE, "zset", &range));
+        let mut parser = ProtocolParser::new();
+        assert_eq!(parse(&mut parser, &bytes), vec![
+            DbOperation::ListPush { key: "list".to_string(), values: vec![b"a".to_vec(), b"b".to_vec()], front: true },
+            DbOperation::ListPop { key: "list".to_string(), count: 2, front: false },
+            DbOperation::HashSet { key: "hash".to_string(), fields: vec![("a".to_string(), b"b".to_vec())] },
+            DbOperation::SetIntersection { keys: vec!["a".to_string(), "b".to_string()] },
+            DbOperation::ZSetAdd { key: "zset".to_string(), members: vec![(1.5, b"m".to_vec())] },
+            DbOperation::ZSetRange { key: "zset".to_string(), start: 0, stop: -1 },
+        ]);
+    }
+
+    #[test]
+    fn test_batch_commands() {
+        let frame_with = |opcode: u8, value: &[u8]| {
+            let mut frame = vec![opcode, 0, 0];
+            frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            frame.extend_from_slice(value);
+            frame
+        };
+        let mut keys = Vec::new();
+        nanodb_core::value::encode_items(&mut keys, [&b"a"[..], b"b"].into_iter());
+        let mut entries = vec![1];
+        nanodb_core::value::encode_items(&mut entries, [&b"a"[..], b"1", b"b", b"2"].into_iter());
+
+        let mut bytes = frame_with(OP_MSET, &entries);
+        bytes.extend(frame_with(OP_MGET, &keys));
+        bytes.extend(frame_with(OP_MDELETE, &keys));
+        let mut parser = ProtocolParser::new();
+        assert_eq!(parse(&mut parser, &bytes), vec![
+            DbOperation::MultiSet { entries: vec![("a".to_string(), b"1".to_vec()), ("b".to_string(), b"2".to_vec())], atomic: true },
+            DbOperation::MultiGet { keys: vec!["a".to_string(), "b".to_string()] },
+            DbOperation::MultiDelete { keys: vec!["a".to_string(), "b".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn test_counter_commands() {
+        let mut bytes = vec![OP_INCR, 0, 4];
+        bytes.extend_from_slice(b"hits");
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes.extend_from_slice(&[OP_DECR, 0, 4]);
+        bytes.extend_from_slice(b"hits");
+        bytes.extend_from_slice(&8u32.to_be_bytes());
+        bytes.extend_from_slice(&5i64.to_be_bytes());
+        bytes.extend_from_slice(&[OP_INCR_FLOAT, 0, 4]);
+        bytes.extend_from_slice(b"temp");
+        bytes.extend_from_slice(&8u32.to_be_bytes());
+        bytes.extend_from_slice(&0.5f64.to_be_bytes());
+        let mut parser = ProtocolParser::new();
+        assert_eq!(parse(&mut parser, &bytes), vec![
+            DbOperation::Increment { key: "hits".to_string(), delta: 1 },
+            DbOperation::Decrement { key: "hits".to_string(), delta: 5 },
+            DbOperation::IncrementFloat { key: "temp".to_string(), delta: 0.5 },
+        ]);
+    }
+
+    #[test]
+    fn test_json_commands() {
+        let frame_with = |opcode: u8, value: &[u8]| {
+            let mut frame = vec![opcode, 0, 3];
+            frame.extend_from_slice(b"doc");
+            frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            frame.extend_from_slice(value);
+            frame
+        };
+        let mut set = Vec::new();
+        nanodb_core::value::encode_items(&mut set, [&b"$.a"[..], br#"{"b":[1]}"#].into_iter());
+        let mut append = Vec::new();
+        nanodb_core::value::encode_items(&mut append, [&b"$.a.b"[..], b"2", b"\"x\""].into_iter());
+        let mut incr = 1.5f64.to_be_bytes().to_vec();
+        incr.extend_from_slice(b"$.n");
+
+        let mut bytes = frame_with(OP_JSON_SET, &set);
+        bytes.extend(frame_with(OP_JSON_APPEND, &append));
+        bytes.extend(frame_with(OP_JSON_INCR, &incr));
+        bytes.extend(frame_with(OP_JSON_GET, b"$.a"));
+        bytes.extend(frame_with(OP_JSON_DEL, b""));
+        let mut parser = ProtocolParser::new();
+        let key = || "doc".to_string();
+        assert_eq!(parse(&mut parser, &bytes), vec![
+            DbOperation::JsonSet { key: key(), path: "$.a".to_string(), value: serde_json::json!({"b": [1]}) },
+            DbOperation::JsonAppend { key: key(), path: "$.a.b".to_string(), values: vec![serde_json::json!(2), serde_json::json!("x")] },
+            DbOperation::JsonIncrement { key: key(), path: "$.n".to_string(), delta: 1.5 },
+            DbOperation::JsonGet { key: key(), path: "$.a".to_string() },
+            DbOperation::JsonDelete { key: key(), path: String::new() },
+        ]);
+    }
+
+    #[test]
+    fn test_index_commands() {
+        let frame_with = |opcode: u8, key: &str, value: &[u8]| {
+            let mut frame = vec![opcode, 0, key.len() as u8];
+            frame.extend_from_slice(key.as_bytes());
+            frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            frame.extend_from_slice(value);
+            frame
+        };
+        let mut create = Vec::new();
+        nanodb_core::value::encode_items(&mut create, [&b"user:"[..], b"$.age"].into_iter());
+        let mut range = 10u32.to_be_bytes().to_vec();
+        nanodb_core::value::encode_items(&mut range, [&b"c"[..], b"", b"18", b""].into_iter());
+        let mut equals = 0u32.to_be_bytes().to_vec();
+        nanodb_core::value::encode_items(&mut equals, [&b""[..], b"\"ana\"", b"", b""].into_iter());
+
+        let mut bytes = frame_with(OP_IDX_CREATE, "age", &create);
+        bytes.extend(frame_with(OP_IDX_QUERY, "age", &range));
+        bytes.extend(frame_with(OP_IDX_QUERY, "name", &equals));
+        bytes.extend(frame_with(OP_IDX_DROP, "age", &[]));
+        bytes.push(OP_INDEXES);
+        let mut parser = ProtocolParser::new();
+        assert_eq!(parse(&mut parser, &bytes), vec![
+            DbOperation::CreateIndex { name: "age".to_string(), prefix: "user:".to_string(), path: "$.age".to_string() },
+            DbOperation::QueryIndex {
+                name: "age".to_string(),
+                query: IndexQuery::Range { min: Some(serde_json::json!(18)), max: None },
+                cursor: Some("c".to_string()),
+                limit: 10,
+            },
+            DbOperation::QueryIndex { name: "name".to_string(), query: IndexQuery::Equals(serde_json::json!("ana")), cursor: None, limit: 0 },
+            DbOperation::DropIndex { name: "age".to_string() },
+            DbOperation::Indexes,
+        ]);
+    }
+
+    #[test]
+    fn test_script_commands() {
+        let frame_with = |opcode: u8, key: &str, value: &[u8]| {
+            let mut frame = vec![opcode, 0, key.len() as u8];
+            frame.extend_from_slice(key.as_bytes());
+            frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            frame.extend_from_slice(value);
+            frame
+        };
+        let mut eval = 1u32.to_be_bytes().to_vec();
+        nanodb_core::value::encode_items(&mut eval, [&b"get(KEYS[0])"[..], b"a", b"x"].into_iter());
+        let mut eval_sha = 2u32.to_be_bytes().to_vec();
+        nanodb_core::value::encode_items(&mut eval_sha, [&b"a"[..], b"b"].into_iter());
+        let mut missing_keys = 3u32.to_be_bytes().to_vec();
+        nanodb_core::value::encode_items(&mut missing_keys, [&b"a"[..]].into_iter());
+
+        let mut bytes = frame_with(OP_EVAL, "", &eval);
+        bytes.extend(frame_with(OP_EVALSHA, "abc", &eval_sha));
+        bytes.extend(frame_with(OP_EVALSHA, "abc", &missing_keys));
+        bytes.extend(frame_with(OP_SCRIPT_LOAD, "", b"1 + 1"));
+        let mut parser = ProtocolParser::new();
+        let commands = parser.feed_bytes(&bytes);
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands[0], Ok(DbOperation::Eval { script: "get(KEYS[0])".to_string(), keys: vec!["a".to_string()], args: vec!["x".to_string()] }));
+        assert_eq!(commands[1], Ok(DbOperation::EvalSha { sha: "abc".to_string(), keys: vec!["a".to_string(), "b".to_string()], args: vec![] }));
+        // Menos claves que las declaradas
+        assert!(matches!(commands[2], Err(DbError::InvalidArgument(_))));
+        assert_eq!(commands[3], Ok(DbOperation::ScriptLoad { script: "1 + 1".to_string() }));
+    }
     // Comando SET
     #[test]
     fn test_incomplete_command() {
         let mut parser = ProtocolParser::new();
         // Solo enviar opcode SET (1), sin mas datos
-        let commands = parser.feed_bytes(&[1]);
+        let commands = parse(&mut parser, &[1]);
         // No debe retornar comandos (esperando mas datos)
         assert_eq!(commands.len(), 0);
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_malformed_frame_is_answered() {
+        let frame_with = |opcode: u8, key: &str, value: &[u8]| {
+            let mut frame = vec![opcode, 0, key.len() as u8];
+            frame.extend_from_slice(key.as_bytes());
+            frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            frame.extend_from_slice(value);
+            frame
+        };
+        // HSET con un campo sin valor, seguido de un GET valido
+        let mut odd = Vec::new();
+        nanodb_core::value::encode_items(&mut odd, [&b"a"[..], b"b", b"c"].into_iter());
+        let mut bytes = frame_with(OP_HSET, "hash", &odd);
+        bytes.extend(frame_with(OP_GET, "k", b""));
+        let mut parser = ProtocolParser::new();
+        let commands = parser.feed_bytes(&bytes);
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(&commands[0], Err(err) if err.code() == 9));
+        assert_eq!(commands[1], Ok(DbOperation::Get { key: "k".to_string(), default: None }));
+    }
+}
@@ -1,25 +1,32 @@
 // Importaciones necesarias
 use tokio::net::{TcpListener, TcpStream};
 use std::sync::Arc;
+use std::time::Duration;
 use crate::protocol::ProtocolParser;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use nanodb_core::{NanoDb, DbResult, DbOperation};
-
-// Emum para unificar respuestas 
-enum CommandResult {
-    Data(Vec<u8>),
-    Success,
-    NotFound,
-    Err(String),
-}
+use nanodb_core::{ChangeEvent, ClusterConfig, DbConfig, DbError, DbOperation, DbResponse, Message, NanoDb, RaftNode, StoragePort, Subscription, TxnOutcome, Watcher};
 
 // Funcion principal del servidor
 pub async fn run_server()-> Result<(), Box<dyn std::error::Error>> {
-    // Crear base de deatos compartida
-    let db = Arc::new(NanoDb::new());
+    // Crear base de deatos compartida (persistente si NANODB_AOF_PATH esta definido)
+    let db = Arc::new(NanoDb::with_config(DbConfig::from_env()?)?);
+    // Purga periodica de claves expiradas
+    db.spawn_reaper(Duration::from_secs(1));
+    // Lider o seguidor segun NANODB_REPLICATION_ADDR / NANODB_REPLICA_OF
+    db.start_replication().await?;
     // Bind al puerto 6379
     let listener = TcpListener::bind("127.0.0.1:8080").await?;
-    // Loop de aceptar conexiones
+    // Nodo de un cluster Raft si NANODB_CLUSTER_ID esta definido
+    if let Some(mut cluster) = ClusterConfig::from_env()? {
+        cluster.advertise.get_or_insert_with(|| "127.0.0.1:8080".to_string());
+        serve(listener, RaftNode::start(db, cluster).await?).await?;
+        return Ok(());
+    }
+    serve(listener, db).await?;
+    Ok(())
+}
+// Loop de aceptar conexiones sobre cualquier motor que implemente el puerto
+pub async fn serve<S: StoragePort>(listener: TcpListener, db: Arc<S>) -> std::io::Result<()> {
     loop {
         // Aceptar una conexion
         let (socket, _) = listener.accept().await?;
@@ -34,18 +41,40 @@ pub async fn run_server()-> Result<(), Box<dyn std::error::Error>> {
     // Nota: Este código nunca se alcanza debido al loop infinito
 }
 // Funcion para manejar una conexion
-async fn handle_connection(
+async fn handle_connection<S: StoragePort>(
     mut socket: TcpStream,
-    db: Arc<NanoDb>
+    db: Arc<S>
 ) {
     // Crear parser para esta conexion
     let mut parser = ProtocolParser::new();
+    // Namespace seleccionado (al inicio la base principal)
+    let root = db;
+    let mut db = root.clone();
+    // Canales suscritos en esta conexion (None = ninguno)
+    let mut subscription: Option<Subscription> = None;
 
     // Loop de leer datos del socket
     loop {
-        // Leer datos del socket
+        // Leer datos del socket (o reenviar mensajes de los canales suscritos)
         let mut buffer = [0; 1024];
-        let bytes_read = socket.read(&mut buffer).await.unwrap();
+        let bytes_read = tokio::select! {
+            read = socket.read(&mut buffer) => read.unwrap(),
+            message = next_message(&mut subscription) => {
+                let line = match message {
+                    Ok(message) => render_message(&message),
+                    Err(err) => {
+                        // Suscriptor atrasado: se cancelan sus suscripciones
+                        subscription = None;
+                        render_response(DbResponse::Error(err))
+                    }
+                };
+                // Cliente desconectado: al salir se sueltan sus suscripciones
+                if socket.write_all(line.as_bytes()).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
         // Si no hay datos, salir
         if bytes_read == 0 {
             break;
@@ -58,56 +87,371 @@ async fn handle_connection(
 
         // Procesar cada comando
         for comando in comando {
-            // Ejecutar comando contra la base de datos
-            let result = match comando {
-                DbOperation::Get { key, .. } => {
-                    match db.get(&key).await {
-                        DbResult::Ok(data) => CommandResult::Data(data),
-                        DbResult::NotFound => CommandResult::NotFound,
-                        DbResult::Err(msg) => CommandResult::Err(msg),
-                    }
-                },
-                DbOperation::Set { key, value, .. } => {
-                    match db.set(key, value).await {
-                        DbResult::Ok(_) => CommandResult::Success,
-                        DbResult::NotFound => CommandResult::NotFound,
-                        DbResult::Err(msg) => CommandResult::Err(msg),
+            let response = match comando {
+                // Frame invalido: se responde con el error para no desincronizar al cliente
+                Err(err) => render_response(DbResponse::Error(err)),
+                // WATCH: la conexion pasa a recibir solo eventos
+                Ok(DbOperation::Watch { filter }) => {
+                    let watcher = db.watch(filter);
+                    // Cliente desconectado antes de recibir la confirmacion
+                    if let Err(e) = socket.write_all(b"OK\n").await {
+                        println!("Error al confirmar WATCH: {}", e);
+                        return;
                     }
-                },
-                DbOperation::Delete { key, .. } => {
-                    match db.delete(&key).await {
-                        DbResult::Ok(_) => CommandResult::Success,
-                        DbResult::NotFound => CommandResult::NotFound,
-                        DbResult::Err(msg) => CommandResult::Err(msg),
-                
+                    push_events(socket, watcher).await;
+                    return;
+                }
+                Ok(DbOperation::Subscribe { channel, pattern }) => {
+                    let sub = subscription.get_or_insert_with(|| db.subscriber());
+                    let count = if pattern { sub.psubscribe(&channel) } else { sub.subscribe(&channel) };
+                    format!("SUBSCRIBED: {} {}\n", channel, count)
+                }
+                Ok(DbOperation::Unsubscribe { channel, pattern }) => {
+                    let count = match subscription.as_mut() {
+                        Some(sub) if pattern => sub.punsubscribe(&channel),
+                        Some(sub) => sub.unsubscribe(&channel),
+                        None => 0,
+                    };
+                    if count == 0 {
+                        subscription = None;
                     }
-                },
-                DbOperation::Flush => {
-                    match db.clear().await {
-                        DbResult::Ok(_) => CommandResult::Success,
-                        DbResult::NotFound => CommandResult::NotFound,
-                        DbResult::Err(msg) => CommandResult::Err(msg),
+                    format!("UNSUBSCRIBED: {} {}\n", channel, count)
+                }
+                Ok(DbOperation::Select { namespace }) => {
+                    let selected = match &namespace {
+                        Some(name) => root.namespace(name),
+                        None => Some(root.clone()),
+                    };
+                    match selected {
+                        Some(selected) => {
+                            db = selected;
+                            "OK\n".to_string()
+                        }
+                        None => "NOT_FOUND\n".to_string(),
                     }
-                },
-                _ => {
-                    // Otros comandos por implementar
-                    CommandResult::Err("Command not implemented".to_string())
                 }
+                // La administracion de namespaces y la replicacion siempre van a la base principal
+                Ok(comando @ (DbOperation::CreateNamespace { .. } | DbOperation::DropNamespace { .. } | DbOperation::Namespaces | DbOperation::ReplicationInfo)) => {
+                    render_response(root.execute(comando).await)
+                }
+                // Ejecutar comando contra la base de datos
+                Ok(comando) => render_response(db.execute(comando).await),
             };
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+}
+
+// Siguiente mensaje de los canales suscritos; sin suscripcion nunca termina
+async fn next_message(subscription: &mut Option<Subscription>) -> Result<Message, DbError> {
+    match subscription {
+        Some(subscription) => subscription.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// MESSAGE: <canal> <mensaje>  /  PMESSAGE: <patron> <canal> <mensaje>
+fn render_message(message: &Message) -> String {
+    let payload = String::from_utf8_lossy(&message.payload);
+    match &message.pattern {
+        Some(pattern) => format!("PMESSAGE: {} {} {}\n", pattern, message.channel, payload),
+        None => format!("MESSAGE: {} {}\n", message.channel, payload),
+    }
+}
 
-            // Enviar respuesta (por ahora simple)
-            match result {
-                CommandResult::Data(data) => {
-                    let response = format!("DATA: {}\n", String::from_utf8_lossy(&data));
-                    socket.write_all(response.as_bytes()).await.unwrap();
-                },
-                CommandResult::Success => socket.write_all(b"OK\n").await.unwrap(),
-                CommandResult::NotFound => socket.write_all(b"NOT_FOUND\n").await.unwrap(),
-                CommandResult::Err(msg) => {
-                    let response = format!("ERROR: {}\n", msg);
-                    socket.write_all(response.as_bytes()).await.unwrap();
+// Envia los eventos del watcher hasta que el cliente cierre la conexion.
+// Si el suscriptor se atrasa se envia un ERROR y se cierra: el cliente debe volver a suscribirse.
+async fn push_events(mut socket: TcpStream, mut watcher: Watcher) {
+    let mut buffer = [0; 1024];
+    loop {
+        tokio::select! {
+            event = watcher.recv() => {
+                let line = match event {
+                    Ok(event) => render_event(&event),
+                    Err(err) => {
+                        let _ = socket.write_all(render_response(DbResponse::Error(err)).as_bytes()).await;
+                        return;
+                    }
+                };
+                if socket.write_all(line.as_bytes()).await.is_err() {
+                    return;
                 }
             }
+            // Los comandos recibidos en modo suscripcion se ignoran
+            read = socket.read(&mut buffer) => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// EVENT: <tipo> <version> <clave>[=<valor>]
+fn render_event(event: &ChangeEvent) -> String {
+    let mut line = format!("EVENT: {} {}", event.kind.as_str(), event.version);
+    if !event.key.is_empty() {
+        line.push(' ');
+        line.push_str(&event.key);
+    }
+    if let Some(value) = &event.value {
+        line.push('=');
+        line.push_str(&String::from_utf8_lossy(value));
+    }
+    line.push('\n');
+    line
+}
+
+// Respuesta en texto para cada forma de DbResponse
+fn render_response(response: DbResponse) -> String {
+    match response {
+        DbResponse::Ok | DbResponse::Snapshot(_) => "OK\n".to_string(),
+        DbResponse::Value(data) => format!("DATA: {}\n", String::from_utf8_lossy(&data)),
+        DbResponse::NotFound => "NOT_FOUND\n".to_string(),
+        DbResponse::Bool(flag) => format!("INT: {}\n", flag as i64),
+        DbResponse::Count(n) => format!("INT: {}\n", n),
+        DbResponse::Version(version) => format!("INT: {}\n", version),
+        DbResponse::Integer(n) => format!("INT: {}\n", n),
+        DbResponse::Float(n) => format!("FLOAT: {}\n", n),
+        DbResponse::Json(doc) => format!("JSON: {}\n", doc),
+        // -1 = la clave no expira
+        DbResponse::Ttl(Some(ttl)) => format!("INT: {}\n", ttl.as_millis()),
+        DbResponse::Ttl(None) => "INT: -1\n".to_string(),
+        DbResponse::Keys(keys) => render_lines("KEYS", &keys),
+        DbResponse::KeysPage(page) => {
+            let mut out = render_lines("KEYS", &page.keys);
+            out.push_str(&format!("CURSOR: {}\n", page.next_cursor.unwrap_or_default()));
+            out
+        }
+        DbResponse::Values(values) => {
+            let lines: Vec<String> = values.iter().map(|v| String::from_utf8_lossy(v).to_string()).collect();
+            render_lines("VALUES", &lines)
+        }
+        DbResponse::Entries(entries) => {
+            let lines: Vec<String> = entries
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, String::from_utf8_lossy(v)))
+                .collect();
+            render_lines("ENTRIES", &lines)
+        }
+        // Una respuesta por clave, en orden
+        DbResponse::OptionalValues(values) => {
+            let mut out = format!("MGET: {}\n", values.len());
+            for value in values {
+                out.push_str(&render_response(value.map_or(DbResponse::NotFound, DbResponse::Value)));
+            }
+            out
+        }
+        // Una linea "miembro puntaje" por elemento
+        DbResponse::ScoredMembers(members) => {
+            let lines: Vec<String> = members
+                .iter()
+                .map(|(member, score)| format!("{} {}", String::from_utf8_lossy(member), score))
+                .collect();
+            render_lines("MEMBERS", &lines)
+        }
+        DbResponse::Cas(outcome) => match outcome.current {
+            Some(current) if !outcome.swapped => format!("MISMATCH: {}\n", String::from_utf8_lossy(&current)),
+            None if !outcome.swapped => "MISMATCH\n".to_string(),
+            _ => "OK\n".to_string(),
+        },
+        // Una respuesta por operacion, en orden
+        DbResponse::Txn(TxnOutcome::Committed(results)) => {
+            let mut out = format!("TXN: {}\n", results.len());
+            for result in results {
+                out.push_str(&render_response(result));
+            }
+            out
+        }
+        DbResponse::Txn(TxnOutcome::Aborted { precondition }) => format!("ABORTED: {}\n", precondition),
+        // Una linea "nombre prefijo ruta" por indice (el prefijo entre comillas: puede ser vacio)
+        DbResponse::Indexes(indexes) => {
+            let lines: Vec<String> = indexes.iter().map(|def| format!("{} {:?} {}", def.name, def.prefix, def.path)).collect();
+            render_lines("INDEXES", &lines)
+        }
+        // Rol, historia y offset; en un seguidor el lider y si esta conectado; luego "follower=direccion offset"
+        DbResponse::Replication(info) => {
+            let role = if info.leader.is_some() { "follower" } else { "leader" };
+            let mut lines = vec![format!("role={}", role), format!("id={}", info.id), format!("offset={}", info.offset)];
+            if let Some(leader) = &info.leader {
+                lines.push(format!("leader={}", leader));
+                lines.push(format!("connected={}", info.connected as u8));
+            }
+            lines.extend(info.followers.iter().map(|follower| format!("follower={} {}", follower.addr, follower.offset)));
+            render_lines("REPLICATION", &lines)
+        }
+        DbResponse::Error(err) => format!("ERROR {}: {}\n", err.code(), err),
+    }
+}
+
+// Cabecera con el numero de lineas + una linea por elemento
+fn render_lines(label: &str, lines: &[String]) -> String {
+    let mut out = format!("{}: {}\n", label, lines.len());
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn roundtrip(socket: &mut TcpStream, frame: &[u8]) -> String {
+        socket.write_all(frame).await.unwrap();
+        let mut buffer = [0; 1024];
+        let n = socket.read(&mut buffer).await.unwrap();
+        String::from_utf8_lossy(&buffer[..n]).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_serve_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(NanoDb::new())));
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let mut set = vec![2, 0, 3];
+        set.extend_from_slice(b"key");
+        set.extend_from_slice(&5u32.to_be_bytes());
+        set.extend_from_slice(b"value");
+        assert_eq!(roundtrip(&mut socket, &set).await, "OK\n");
+
+        let mut get = vec![1, 0, 3];
+        get.extend_from_slice(b"key");
+        get.extend_from_slice(&0u32.to_be_bytes());
+        assert_eq!(roundtrip(&mut socket, &get).await, "DATA: value\n");
+
+        let mut prefix = vec![12, 0, 1];
+        prefix.extend_from_slice(b"k");
+        prefix.extend_from_slice(&0u32.to_be_bytes());
+        assert_eq!(roundtrip(&mut socket, &prefix).await, "KEYS: 1\nkey\n");
+        assert_eq!(roundtrip(&mut socket, &[17]).await, "INT: 1\n");
+
+        // CAS con valor esperado incorrecto devuelve el valor actual
+        let mut cas = vec![20, 0, 3];
+        cas.extend_from_slice(b"key");
+        cas.extend_from_slice(&9u32.to_be_bytes());
+        cas.push(0b11);
+        cas.extend_from_slice(&2u32.to_be_bytes());
+        cas.extend_from_slice(b"nov2");
+        assert_eq!(roundtrip(&mut socket, &cas).await, "MISMATCH: value\n");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_frame_gets_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(NanoDb::new())));
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        // INCR con un delta de 3 bytes, seguido de un SIZE valido
+        let mut frames = vec![47, 0, 1, b'n'];
+        frames.extend_from_slice(&3u32.to_be_bytes());
+        frames.extend_from_slice(b"abc");
+        frames.push(17);
+        socket.write_all(&frames).await.unwrap();
+        let mut received = String::new();
+        let mut buffer = [0; 1024];
+        while received.lines().count() < 2 {
+            let n = socket.read(&mut buffer).await.unwrap();
+            assert!(n > 0, "connection closed after {:?}", received);
+            received.push_str(&String::from_utf8_lossy(&buffer[..n]));
         }
+        let lines: Vec<&str> = received.lines().collect();
+        assert!(lines[0].starts_with("ERROR 9:"), "{:?}", lines);
+        assert_eq!(lines[1], "INT: 0");
+    }
+
+    #[test]
+    fn test_render_cas_conflict_and_unauthorized() {
+        let conflict = render_response(DbResponse::Error(DbError::CasConflict { key: "k".to_string() }));
+        assert_eq!(conflict, "ERROR 4: compare-and-swap conflict on key k\n");
+        assert_eq!(render_response(DbResponse::Error(DbError::Unauthorized)), "ERROR 7: unauthorized\n");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_pushes_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let db = Arc::new(NanoDb::new());
+        tokio::spawn(serve(listener, db.clone()));
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let mut subscribe = vec![23, 0, 5];
+        subscribe.extend_from_slice(b"user:");
+        subscribe.extend_from_slice(&1u32.to_be_bytes());
+        subscribe.push(1);
+        assert_eq!(roundtrip(&mut socket, &subscribe).await, "OK\n");
+
+        db.set("order:1".to_string(), b"skip".to_vec()).await;
+        db.set("user:1".to_string(), b"alice".to_vec()).await;
+        let mut buffer = [0; 1024];
+        let n = socket.read(&mut buffer).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&buffer[..n]), "EVENT: set 2 user:1=alice\n");
+    }
+
+    #[tokio::test]
+    async fn test_select_namespace() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(NanoDb::new())));
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let frame = |opcode: u8, key: &[u8]| {
+            let mut frame = vec![opcode, 0, key.len() as u8];
+            frame.extend_from_slice(key);
+            frame.extend_from_slice(&0u32.to_be_bytes());
+            frame
+        };
+        assert_eq!(roundtrip(&mut socket, &frame(27, b"team")).await, "NOT_FOUND\n");
+        assert_eq!(roundtrip(&mut socket, &frame(28, b"team")).await, "INT: 1\n");
+        assert_eq!(roundtrip(&mut socket, &frame(27, b"team")).await, "OK\n");
+        let mut set = frame(2, b"key");
+        set.truncate(set.len() - 4);
+        set.extend_from_slice(&1u32.to_be_bytes());
+        set.push(b'v');
+        assert_eq!(roundtrip(&mut socket, &set).await, "OK\n");
+        assert_eq!(roundtrip(&mut socket, &[17]).await, "INT: 1\n");
+        assert_eq!(roundtrip(&mut socket, &[30]).await, "KEYS: 1\nteam\n");
+
+        // De vuelta en la base principal la clave no existe
+        assert_eq!(roundtrip(&mut socket, &frame(27, b"")).await, "OK\n");
+        assert_eq!(roundtrip(&mut socket, &[17]).await, "INT: 0\n");
+    }
+
+    #[tokio::test]
+    async fn test_publish_and_subscribe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(NanoDb::new())));
+
+        let mut subscriber = TcpStream::connect(addr).await.unwrap();
+        let mut subscribe = vec![25, 0, 6];
+        subscribe.extend_from_slice(b"chat.*");
+        subscribe.extend_from_slice(&1u32.to_be_bytes());
+        subscribe.push(1);
+        assert_eq!(roundtrip(&mut subscriber, &subscribe).await, "SUBSCRIBED: chat.* 1\n");
+
+        let mut publisher = TcpStream::connect(addr).await.unwrap();
+        let mut publish = vec![24, 0, 7];
+        publish.extend_from_slice(b"chat.es");
+        publish.extend_from_slice(&4u32.to_be_bytes());
+        publish.extend_from_slice(b"hola");
+        assert_eq!(roundtrip(&mut publisher, &publish).await, "INT: 1\n");
+
+        let mut buffer = [0; 1024];
+        let n = subscriber.read(&mut buffer).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&buffer[..n]), "PMESSAGE: chat.* chat.es hola\n");
+
+        // Sin suscriptores el mensaje no llega a nadie
+        let mut unsubscribe = vec![26, 0, 6];
+        unsubscribe.extend_from_slice(b"chat.*");
+        unsubscribe.extend_from_slice(&1u32.to_be_bytes());
+        unsubscribe.push(1);
+        assert_eq!(roundtrip(&mut subscriber, &unsubscribe).await, "UNSUBSCRIBED: chat.* 0\n");
+        assert_eq!(roundtrip(&mut publisher, &publish).await, "INT: 0\n");
     }
-}
\ No newline at end of file
+}
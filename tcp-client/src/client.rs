@@ -4,6 +4,10 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use nanodb_core::DbOperation;
 use crate::serializer::serialize_command;
 
+// Respuesta de un nodo del cluster que no es el lider (DbError::NotLeader)
+const NOT_LEADER_PREFIX: &str = "ERROR 19: not the cluster leader; leader is at ";
+const MAX_REDIRECTS: usize = 3;
+
 // Definición de la estructura del cliente
 pub struct TcpClient {
     stream: TcpStream,
@@ -15,11 +19,21 @@ impl TcpClient {
         let stream = TcpStream::connect(addr).await?;
         Ok(TcpClient { stream })
     }
-    // Funcion para ejecutar un comando
+    // Funcion para ejecutar un comando. En un cluster, si el nodo no es el lider
+    // se reconecta a la direccion que informa y se reintenta.
     pub async fn execute(&mut self, command: DbOperation) -> Result<String, Box<dyn std::error::Error>> {
-        // Enviar comando y recibir respuesta
+        let mut response = self.send(&command).await?;
+        for _ in 0..MAX_REDIRECTS {
+            let Some(leader) = response.strip_prefix(NOT_LEADER_PREFIX) else { break };
+            self.stream = TcpStream::connect(leader.trim()).await?;
+            response = self.send(&command).await?;
+        }
+        Ok(response)
+    }
+    // Enviar comando y recibir respuesta
+    async fn send(&mut self, command: &DbOperation) -> Result<String, Box<dyn std::error::Error>> {
         // 1. Serializar el comando
-        let bytes = serialize_command(&command);
+        let bytes = serialize_command(command);
         print!("Enviando {} bytes: {:?}", bytes.len(), bytes);
 
         // 2. Enviar al servidor
@@ -18,6 +18,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let set_cmd = DbOperation::Set {
         key: "cross-test".to_string(),
         value: b"hello world".to_vec(),
+        ttl: None,
     };
     // Ejecutar comando
     let response = client.execute(set_cmd).await?;
@@ -1,17 +1,99 @@
-use nanodb_core::DbOperation;
+use nanodb_core::value::encode_items;
+use nanodb_core::{DbOperation, IndexQuery, Precondition, WatchFilter};
 
 // Constantes del protocolo (igual que en el servidor)
 const OP_GET: u8 = 1;
 const OP_SET: u8 = 2;
 const OP_DELETE: u8 = 3;
 const OP_FLUSH: u8 = 4;
+const OP_SAVE: u8 = 5;
+const OP_SETEX: u8 = 6;
+const OP_EXPIRE: u8 = 7;
+const OP_TTL: u8 = 8;
+const OP_PERSIST: u8 = 9;
+const OP_EXISTS: u8 = 10;
+const OP_KEYS: u8 = 11;
+const OP_KEYS_PREFIX: u8 = 12;
+const OP_VALUES: u8 = 13;
+const OP_VALUES_PREFIX: u8 = 14;
+const OP_GET_PREFIX: u8 = 15;
+const OP_DELETE_PREFIX: u8 = 16;
+const OP_SIZE: u8 = 17;
+const OP_KEYS_CURSOR: u8 = 18;
+const OP_RANGE: u8 = 19;
+const OP_CAS: u8 = 20;
+const OP_TXN: u8 = 21;
+const OP_VERSION: u8 = 22;
+const OP_WATCH: u8 = 23;
+const OP_PUBLISH: u8 = 24;
+const OP_SUBSCRIBE: u8 = 25;
+const OP_UNSUBSCRIBE: u8 = 26;
+const OP_SELECT: u8 = 27;
+const OP_NS_CREATE: u8 = 28;
+const OP_NS_DROP: u8 = 29;
+const OP_NAMESPACES: u8 = 30;
+const OP_LPUSH: u8 = 31;
+const OP_RPUSH: u8 = 32;
+const OP_LPOP: u8 = 33;
+const OP_RPOP: u8 = 34;
+const OP_LRANGE: u8 = 35;
+const OP_HSET: u8 = 36;
+const OP_HGET: u8 = 37;
+const OP_HDEL: u8 = 38;
+const OP_SADD: u8 = 39;
+const OP_SREM: u8 = 40;
+const OP_SMEMBERS: u8 = 41;
+const OP_SINTER: u8 = 42;
+const OP_ZADD: u8 = 43;
+const OP_ZREM: u8 = 44;
+const OP_ZRANGE: u8 = 45;
+const OP_ZRANGE_SCORE: u8 = 46;
+const OP_INCR: u8 = 47;
+const OP_DECR: u8 = 48;
+const OP_INCR_FLOAT: u8 = 49;
+const OP_MGET: u8 = 50;
+const OP_MSET: u8 = 51;
+const OP_MDELETE: u8 = 52;
+const OP_JSON_GET: u8 = 53;
+const OP_JSON_SET: u8 = 54;
+const OP_JSON_DEL: u8 = 55;
+const OP_JSON_APPEND: u8 = 56;
+const OP_JSON_INCR: u8 = 57;
+const OP_IDX_CREATE: u8 = 58;
+const OP_IDX_DROP: u8 = 59;
+const OP_INDEXES: u8 = 60;
+const OP_IDX_QUERY: u8 = 61;
+const OP_EVAL: u8 = 62;
+const OP_EVALSHA: u8 = 63;
+const OP_SCRIPT_LOAD: u8 = 64;
+const OP_REPLICATION: u8 = 65;
+
+// Modos de OP_WATCH
+const WATCH_KEY: u8 = 0;
+const WATCH_PREFIX: u8 = 1;
+
+// Modos de OP_SUBSCRIBE / OP_UNSUBSCRIBE
+const CHANNEL_EXACT: u8 = 0;
+const CHANNEL_PATTERN: u8 = 1;
+
+// Tipos de precondicion de OP_TXN
+const PRE_VALUE: u8 = 1;
+const PRE_ABSENT: u8 = 2;
+const PRE_VERSION: u8 = 3;
 
 pub fn serialize_command(op: &DbOperation) -> Vec<u8> {
     // Convertir DbOperation a bytes segun nuestra protocolo
     let mut bytes = Vec::new();
 
     match op {
-        DbOperation::Set { key, value } => {
+        DbOperation::Set { key, value, ttl: Some(ttl) } => {
+            // SETEX: el value lleva el TTL en milisegundos delante
+            let mut payload = (ttl.as_millis() as u64).to_be_bytes().to_vec();
+            payload.extend_from_slice(value);
+            write_frame(&mut bytes, OP_SETEX, key, &payload);
+        },
+
+        DbOperation::Set { key, value, ttl: None } => {
             // 1. Opcode
             bytes.push(OP_SET);
 
@@ -30,7 +112,12 @@ pub fn serialize_command(op: &DbOperation) -> Vec<u8> {
             bytes.extend_from_slice(value);
         },
 
-        DbOperation::Get { key, .. } => {
+        DbOperation::Get { key, default: Some(default) } => {
+            // GET con valor por defecto: va en el value
+            write_frame(&mut bytes, OP_GET, key, default);
+        },
+
+        DbOperation::Get { key, default: None } => {
             // GET tambien nesecita key, pero no value
             bytes.push(OP_GET);
             let key_len = key.len() as u16;
@@ -51,18 +138,218 @@ pub fn serialize_command(op: &DbOperation) -> Vec<u8> {
         },
 
         DbOperation::Flush => {
-            // Solo opcode, sin key ni value
+            // Solo opcode, sin key ni value (el servidor no espera longitudes)
             bytes.push(OP_FLUSH);
-            // Key length = 0
-            bytes.extend_from_slice(&0u16.to_be_bytes());
-            // Value length = 0
-            bytes.extend_from_slice(&0u32.to_be_bytes());
         },
-        _ => {
-            // Otros comandos no implementados
-            panic!("Command not supported for serialization");
-        }
+
+        DbOperation::Save => {
+            // Igual que FLUSH: solo opcode
+            bytes.push(OP_SAVE);
+        },
+        DbOperation::Expire { key, ttl } => {
+            write_frame(&mut bytes, OP_EXPIRE, key, &(ttl.as_millis() as u64).to_be_bytes());
+        },
+
+        DbOperation::Ttl { key } => write_frame(&mut bytes, OP_TTL, key, &[]),
+
+        DbOperation::Persist { key } => write_frame(&mut bytes, OP_PERSIST, key, &[]),
+
+        DbOperation::Exists { key } => write_frame(&mut bytes, OP_EXISTS, key, &[]),
+
+        // Solo opcode
+        DbOperation::Keys => bytes.push(OP_KEYS),
+        DbOperation::Values => bytes.push(OP_VALUES),
+        DbOperation::Size => bytes.push(OP_SIZE),
+
+        // El prefijo va en la key
+        DbOperation::KeysPrefix { prefix } => write_frame(&mut bytes, OP_KEYS_PREFIX, prefix, &[]),
+        DbOperation::ValuesPrefix { prefix } => write_frame(&mut bytes, OP_VALUES_PREFIX, prefix, &[]),
+        DbOperation::GetPrefix { prefix } => write_frame(&mut bytes, OP_GET_PREFIX, prefix, &[]),
+        DbOperation::DeletePrefix { prefix } => write_frame(&mut bytes, OP_DELETE_PREFIX, prefix, &[]),
+
+        DbOperation::KeysCursor { prefix, cursor, limit } => {
+            // value = limit (4 bytes) + cursor
+            let mut payload = (*limit as u32).to_be_bytes().to_vec();
+            payload.extend_from_slice(cursor.as_deref().unwrap_or("").as_bytes());
+            write_frame(&mut bytes, OP_KEYS_CURSOR, prefix.as_deref().unwrap_or(""), &payload);
+        },
+
+        DbOperation::Range { start, end, reverse, limit } => {
+            // value = reverse (1 byte) + limit (4 bytes) + fin
+            let mut payload = vec![*reverse as u8];
+            payload.extend_from_slice(&(*limit as u32).to_be_bytes());
+            payload.extend_from_slice(end.as_deref().unwrap_or("").as_bytes());
+            write_frame(&mut bytes, OP_RANGE, start.as_deref().unwrap_or(""), &payload);
+        },
+
+        DbOperation::CompareAndSwap { key, old_value, new_value } => {
+            // value = flags (bit 0: old, bit 1: new) + old_len (4 bytes) + old + new
+            let flags = old_value.is_some() as u8 | (new_value.is_some() as u8) << 1;
+            let old = old_value.as_deref().unwrap_or(&[]);
+            let mut payload = vec![flags];
+            payload.extend_from_slice(&(old.len() as u32).to_be_bytes());
+            payload.extend_from_slice(old);
+            payload.extend_from_slice(new_value.as_deref().unwrap_or(&[]));
+            write_frame(&mut bytes, OP_CAS, key, &payload);
+        },
+
+        DbOperation::Version { key } => write_frame(&mut bytes, OP_VERSION, key, &[]),
+
+        DbOperation::Transaction { preconditions, operations } => {
+            // value = precondiciones + frames normales de cada operacion
+            let mut payload = (preconditions.len() as u16).to_be_bytes().to_vec();
+            for precondition in preconditions {
+                let (kind, key) = match precondition {
+                    Precondition::Value { key, expected: Some(_) } => (PRE_VALUE, key),
+                    Precondition::Value { key, expected: None } => (PRE_ABSENT, key),
+                    Precondition::Version { key, .. } => (PRE_VERSION, key),
+                };
+                payload.push(kind);
+                payload.extend_from_slice(&(key.len() as u16).to_be_bytes());
+                payload.extend_from_slice(key.as_bytes());
+                match precondition {
+                    Precondition::Value { expected: Some(expected), .. } => {
+                        payload.extend_from_slice(&(expected.len() as u32).to_be_bytes());
+                        payload.extend_from_slice(expected);
+                    }
+                    Precondition::Version { version, .. } => payload.extend_from_slice(&version.to_be_bytes()),
+                    Precondition::Value { expected: None, .. } => {}
+                }
+            }
+            payload.extend_from_slice(&(operations.len() as u16).to_be_bytes());
+            for op in operations {
+                payload.extend_from_slice(&serialize_command(op));
+            }
+            write_frame(&mut bytes, OP_TXN, "", &payload);
+        },
+
+        // Despues de SUBSCRIBE el servidor solo envia eventos por esta conexion
+        DbOperation::Watch { filter: WatchFilter::Key(key) } => write_frame(&mut bytes, OP_WATCH, key, &[WATCH_KEY]),
+        DbOperation::Watch { filter: WatchFilter::Prefix(prefix) } => write_frame(&mut bytes, OP_WATCH, prefix, &[WATCH_PREFIX]),
+
+        DbOperation::Publish { channel, message } => write_frame(&mut bytes, OP_PUBLISH, channel, message),
+        DbOperation::Subscribe { channel, pattern } => {
+            let mode = if *pattern { CHANNEL_PATTERN } else { CHANNEL_EXACT };
+            write_frame(&mut bytes, OP_SUBSCRIBE, channel, &[mode]);
+        },
+        DbOperation::Unsubscribe { channel, pattern } => {
+            let mode = if *pattern { CHANNEL_PATTERN } else { CHANNEL_EXACT };
+            write_frame(&mut bytes, OP_UNSUBSCRIBE, channel, &[mode]);
+        },
+
+        // Clave vacia = volver a la base principal
+        DbOperation::Select { namespace } => write_frame(&mut bytes, OP_SELECT, namespace.as_deref().unwrap_or(""), &[]),
+        DbOperation::CreateNamespace { name } => write_frame(&mut bytes, OP_NS_CREATE, name, &[]),
+        DbOperation::DropNamespace { name } => write_frame(&mut bytes, OP_NS_DROP, name, &[]),
+        DbOperation::Namespaces => bytes.push(OP_NAMESPACES),
+
+        // Colecciones: las listas de elementos van como items (count + [len + bytes]*)
+        DbOperation::ListPush { key, values, front } => {
+            let opcode = if *front { OP_LPUSH } else { OP_RPUSH };
+            write_frame(&mut bytes, opcode, key, &items(values.iter().map(Vec::as_slice)));
+        },
+        DbOperation::ListPop { key, count, front } => {
+            let opcode = if *front { OP_LPOP } else { OP_RPOP };
+            write_frame(&mut bytes, opcode, key, &(*count as u32).to_be_bytes());
+        },
+        DbOperation::ListRange { key, start, stop } => write_frame(&mut bytes, OP_LRANGE, key, &pair(start.to_be_bytes(), stop.to_be_bytes())),
+        DbOperation::HashSet { key, fields } => {
+            let payload = items(fields.iter().flat_map(|(field, value)| [field.as_bytes(), value.as_slice()]));
+            write_frame(&mut bytes, OP_HSET, key, &payload);
+        },
+        DbOperation::HashGet { key, field } => write_frame(&mut bytes, OP_HGET, key, field.as_bytes()),
+        DbOperation::HashDelete { key, fields } => write_frame(&mut bytes, OP_HDEL, key, &items(fields.iter().map(String::as_bytes))),
+        DbOperation::SetAdd { key, members } => write_frame(&mut bytes, OP_SADD, key, &items(members.iter().map(Vec::as_slice))),
+        DbOperation::SetRemove { key, members } => write_frame(&mut bytes, OP_SREM, key, &items(members.iter().map(Vec::as_slice))),
+        DbOperation::SetMembers { key } => write_frame(&mut bytes, OP_SMEMBERS, key, &[]),
+        DbOperation::SetIntersection { keys } => write_frame(&mut bytes, OP_SINTER, "", &items(keys.iter().map(String::as_bytes))),
+        DbOperation::ZSetAdd { key, members } => {
+            // Puntaje (8 bytes, f64) seguido del miembro
+            let scores: Vec<[u8; 8]> = members.iter().map(|(score, _)| score.to_be_bytes()).collect();
+            let payload = items(members.iter().zip(&scores).flat_map(|((_, member), score)| [&score[..], member]));
+            write_frame(&mut bytes, OP_ZADD, key, &payload);
+        },
+        DbOperation::ZSetRemove { key, members } => write_frame(&mut bytes, OP_ZREM, key, &items(members.iter().map(Vec::as_slice))),
+        DbOperation::ZSetRange { key, start, stop } => write_frame(&mut bytes, OP_ZRANGE, key, &pair(start.to_be_bytes(), stop.to_be_bytes())),
+        DbOperation::ZSetRangeByScore { key, min, max } => {
+            write_frame(&mut bytes, OP_ZRANGE_SCORE, key, &pair(min.to_be_bytes(), max.to_be_bytes()));
+        },
+        DbOperation::Increment { key, delta } => write_frame(&mut bytes, OP_INCR, key, &delta.to_be_bytes()),
+        DbOperation::Decrement { key, delta } => write_frame(&mut bytes, OP_DECR, key, &delta.to_be_bytes()),
+        DbOperation::IncrementFloat { key, delta } => write_frame(&mut bytes, OP_INCR_FLOAT, key, &delta.to_be_bytes()),
+        DbOperation::MultiGet { keys } => write_frame(&mut bytes, OP_MGET, "", &items(keys.iter().map(String::as_bytes))),
+        DbOperation::MultiDelete { keys } => write_frame(&mut bytes, OP_MDELETE, "", &items(keys.iter().map(String::as_bytes))),
+        DbOperation::MultiSet { entries, atomic } => {
+            let mut payload = vec![*atomic as u8];
+            encode_items(&mut payload, entries.iter().flat_map(|(key, value)| [key.as_bytes(), value.as_slice()]));
+            write_frame(&mut bytes, OP_MSET, "", &payload);
+        },
+        DbOperation::JsonGet { key, path } => write_frame(&mut bytes, OP_JSON_GET, key, path.as_bytes()),
+        DbOperation::JsonSet { key, path, value } => {
+            write_frame(&mut bytes, OP_JSON_SET, key, &items([path.as_bytes(), value.to_string().as_bytes()].into_iter()));
+        },
+        DbOperation::JsonDelete { key, path } => write_frame(&mut bytes, OP_JSON_DEL, key, path.as_bytes()),
+        DbOperation::JsonAppend { key, path, values } => {
+            let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+            let payload = items(std::iter::once(path.as_bytes()).chain(values.iter().map(String::as_bytes)));
+            write_frame(&mut bytes, OP_JSON_APPEND, key, &payload);
+        },
+        DbOperation::JsonIncrement { key, path, delta } => {
+            let mut payload = delta.to_be_bytes().to_vec();
+            payload.extend_from_slice(path.as_bytes());
+            write_frame(&mut bytes, OP_JSON_INCR, key, &payload);
+        },
+        DbOperation::CreateIndex { name, prefix, path } => {
+            write_frame(&mut bytes, OP_IDX_CREATE, name, &items([prefix.as_bytes(), path.as_bytes()].into_iter()));
+        },
+        DbOperation::DropIndex { name } => write_frame(&mut bytes, OP_IDX_DROP, name, &[]),
+        DbOperation::Indexes => bytes.push(OP_INDEXES),
+        DbOperation::QueryIndex { name, query, cursor, limit } => {
+            // cursor, igual, min, max; vacio = ausente
+            let json = |value: Option<&serde_json::Value>| value.map(ToString::to_string).unwrap_or_default();
+            let fields = match query {
+                IndexQuery::Equals(value) => [json(Some(value)), String::new(), String::new()],
+                IndexQuery::Range { min, max } => [String::new(), json(min.as_ref()), json(max.as_ref())],
+            };
+            let mut payload = (*limit as u32).to_be_bytes().to_vec();
+            let cursor = cursor.as_deref().unwrap_or("");
+            encode_items(&mut payload, std::iter::once(cursor.as_bytes()).chain(fields.iter().map(String::as_bytes)));
+            write_frame(&mut bytes, OP_IDX_QUERY, name, &payload);
+        },
+        DbOperation::Eval { script, keys, args } => {
+            // cantidad de claves + script, claves, args
+            let mut payload = (keys.len() as u32).to_be_bytes().to_vec();
+            let fields = std::iter::once(script).chain(keys).chain(args);
+            encode_items(&mut payload, fields.map(String::as_bytes));
+            write_frame(&mut bytes, OP_EVAL, "", &payload);
+        },
+        DbOperation::EvalSha { sha, keys, args } => {
+            let mut payload = (keys.len() as u32).to_be_bytes().to_vec();
+            encode_items(&mut payload, keys.iter().chain(args).map(String::as_bytes));
+            write_frame(&mut bytes, OP_EVALSHA, sha, &payload);
+        },
+        DbOperation::ScriptLoad { script } => write_frame(&mut bytes, OP_SCRIPT_LOAD, "", script.as_bytes()),
+        DbOperation::ReplicationInfo => bytes.push(OP_REPLICATION),
     }
 
     bytes
-}
\ No newline at end of file
+}
+
+fn items<'a>(values: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut payload = Vec::new();
+    encode_items(&mut payload, values);
+    payload
+}
+
+fn pair(first: [u8; 8], second: [u8; 8]) -> Vec<u8> {
+    [first, second].concat()
+}
+
+// Frame generico: opcode | key_len (2 bytes) | key | value_len (4 bytes) | value
+fn write_frame(bytes: &mut Vec<u8>, opcode: u8, key: &str, value: &[u8]) {
+    bytes.push(opcode);
+    bytes.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(key.as_bytes());
+    bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(value);
+}